@@ -0,0 +1,43 @@
+//! Records what the build is, for `trim::build_info`: its target, compiler and git commit, none
+//! of which are otherwise known to the crate once it is compiled.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // the commit changes with the branch `HEAD` points to, or with `HEAD` itself when detached
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    if let Some(head) = std::fs::read_to_string(".git/HEAD")
+        .ok()
+        .and_then(|head| head.strip_prefix("ref: ").map(|r| r.trim().to_string()))
+        .filter(|head| Path::new(".git").join(head).exists())
+    {
+        println!("cargo:rerun-if-changed=.git/{}", head);
+    }
+
+    let target = env::var("TARGET").unwrap_or_default();
+    println!("cargo:rustc-env=TRIM_BUILD_TARGET={}", target);
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = output(Command::new(rustc).arg("--version")).unwrap_or_default();
+    println!("cargo:rustc-env=TRIM_BUILD_RUSTC={}", rustc_version);
+
+    // empty if not built from a git repository, e.g. from crates.io
+    let git_hash =
+        output(Command::new("git").args(["rev-parse", "--short=12", "HEAD"])).unwrap_or_default();
+    println!("cargo:rustc-env=TRIM_BUILD_GIT_HASH={}", git_hash);
+}
+
+/// # Returns
+///
+/// The first line of what `command` wrote to stdout, if it ran and exited successfully.
+fn output(command: &mut Command) -> Option<String> {
+    let output = command
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    stdout.lines().next().map(str::to_string)
+}
@@ -0,0 +1,223 @@
+//! Audits of a random sample of the files a run modified, checked again once the run is done, as
+//! cheap assurance that fleet-wide runs only ever removed whitespace.
+
+use std::fmt;
+use std::fs::read;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::invariants::check_trimmed;
+use crate::invariants::Violation;
+use crate::options::TrimOptions;
+
+/// A uniformly random sample of the files modified by a run, kept along with their content from
+/// before the run; see `TrimOptions::audit`.
+///
+/// Files are sampled as they are modified, so only the content of sampled files is read again,
+/// and at most `size` of them are held in memory at once.
+#[derive(Debug)]
+pub struct Audit {
+    size: usize,
+    state: Mutex<AuditState>,
+}
+
+#[derive(Debug)]
+struct AuditState {
+    /// number of modified files offered so far
+    offered: u64,
+    /// xorshift64 state, never 0
+    rng: u64,
+    sample: Vec<Sampled>,
+}
+
+/// A sampled file, as it was before the run.
+#[derive(Debug)]
+struct Sampled {
+    path: PathBuf,
+    original: io::Result<Vec<u8>>,
+    bytes_saved: i32,
+}
+
+/// Why an audited file failed its audit.
+#[derive(Debug)]
+pub enum AuditError {
+    /// it, or its content from before the run, could not be read
+    Io(io::Error),
+    /// it is not its content from before the run trimmed, as far as can be told
+    Violation(Violation),
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuditError::Io(err) => write!(f, "cannot be audited: {}", err),
+            AuditError::Violation(violation) => write!(f, "{}", violation),
+        }
+    }
+}
+
+/// The outcome of an audit; see `Audit::verify`.
+#[derive(Debug)]
+pub struct AuditReport {
+    /// number of files the run modified, which the sample was drawn from
+    pub modified: u64,
+    /// each sampled file, sorted by path, with why it failed its audit, if it did
+    pub audited: Vec<(PathBuf, Result<(), AuditError>)>,
+}
+
+impl AuditReport {
+    /// # Returns
+    ///
+    /// Number of audited files that failed their audit.
+    pub fn failures(&self) -> usize {
+        self.audited
+            .iter()
+            .filter(|(_, result)| result.is_err())
+            .count()
+    }
+}
+
+impl Audit {
+    /// # Parameters
+    ///
+    /// 1. `size` -- number of modified files to sample
+    /// 1. `seed` -- seed of the sampling, e.g. `util::random_u64`
+    pub fn new(size: usize, seed: u64) -> Self {
+        Audit {
+            size,
+            state: Mutex::new(AuditState {
+                offered: 0,
+                // spread out seeds that are close together, which xorshift keeps close for a while
+                rng: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1,
+                sample: Vec::with_capacity(size),
+            }),
+        }
+    }
+
+    /// Account for a modified file, and keep it in the sample with the odds that keep the sample
+    /// uniform; reservoir sampling.
+    ///
+    /// # Parameters
+    ///
+    /// 1. `path` -- the file as modified, e.g. its mirror in `TrimOptions::out_dir`
+    /// 1. `bytes_saved` -- see `TrimResult::bytes_saved`
+    /// 1. `original` -- reads the content of the file from before the run; only called if the file
+    ///    is sampled
+    pub fn offer<F>(&self, path: &Path, bytes_saved: i32, original: F)
+    where
+        F: FnOnce() -> io::Result<Vec<u8>>,
+    {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        state.offered += 1;
+        let slot = match state.sample.len() < self.size {
+            true => state.sample.len(),
+            false => {
+                state.rng ^= state.rng << 13;
+                state.rng ^= state.rng >> 7;
+                state.rng ^= state.rng << 17;
+                match (state.rng % state.offered) as usize {
+                    slot if slot < self.size => slot,
+                    _ => return,
+                }
+            }
+        };
+        let sampled = Sampled {
+            path: path.to_path_buf(),
+            original: original(),
+            bytes_saved,
+        };
+        match slot == state.sample.len() {
+            true => state.sample.push(sampled),
+            false => state.sample[slot] = sampled,
+        }
+    }
+
+    /// Read every sampled file again, and check that it is its content from before the run,
+    /// trimmed with `options`, and nothing else; see `invariants::check_trimmed`.
+    ///
+    /// `options` must not have `rules` or a `filter`, which may change more than whitespace by
+    /// design.
+    pub fn verify(&self, options: &TrimOptions) -> AuditReport {
+        let state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut audited: Vec<_> = state
+            .sample
+            .iter()
+            .map(|sampled| {
+                let original = match &sampled.original {
+                    Ok(original) => original,
+                    Err(err) => {
+                        let err = io::Error::new(err.kind(), err.to_string());
+                        return (sampled.path.clone(), Err(AuditError::Io(err)));
+                    }
+                };
+                let result = read(&sampled.path)
+                    .map_err(AuditError::Io)
+                    .and_then(|trimmed| {
+                        check_trimmed(original, &trimmed, sampled.bytes_saved, options)
+                            .map_err(AuditError::Violation)
+                    });
+                (sampled.path.clone(), result)
+            })
+            .collect();
+        audited.sort_by(|(a, _), (b, _)| a.cmp(b));
+        AuditReport {
+            modified: state.offered,
+            audited,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::mktemp;
+    use std::fs::write;
+
+    #[test]
+    fn samples_uniformly() {
+        // every file should be sampled about as often as any other
+        let mut counts = [0; 10];
+        for seed in 0..2000 {
+            let audit = Audit::new(3, seed);
+            for index in 0..counts.len() {
+                audit.offer(Path::new(&index.to_string()), 0, || Ok(Vec::new()));
+            }
+            let report = audit.verify(&TrimOptions::default());
+            assert_eq!(10, report.modified);
+            assert_eq!(3, report.audited.len());
+            for (path, _) in report.audited {
+                counts[path.to_string_lossy().parse::<usize>().unwrap()] += 1;
+            }
+        }
+        for count in counts {
+            assert!((450..750).contains(&count), "{:?}", counts);
+        }
+    }
+
+    #[test]
+    fn verify() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let path = mktemp(&prefix, &"a\nb\n").unwrap();
+        let audit = Audit::new(1, 7);
+        audit.offer(&path, 2, || Ok(b"a \nb \n".to_vec()));
+        let report = audit.verify(&TrimOptions::default());
+        assert_eq!(0, report.failures());
+
+        // changed by more than whitespace since
+        write(&path, "a\nc\n").unwrap();
+        let report = audit.verify(&TrimOptions::default());
+        assert_eq!(1, report.failures());
+        assert!(matches!(
+            report.audited[0].1,
+            Err(AuditError::Violation(Violation::NotWhitespace(_)))
+        ));
+    }
+}
@@ -0,0 +1,224 @@
+//! Findings that are known and accepted, so that only new ones fail a run; for adopting trim in
+//! large repositories without cleaning them up all at once.
+
+use serde_json::json;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::fs::remove_file;
+use std::fs::rename;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use crate::trim::Finding;
+use crate::util::mktemp_sibling;
+
+/// Version of the format written by `Baseline::save`.
+const VERSION: u64 = 1;
+
+/// How a finding relates to the baseline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// recorded in the baseline; reported, but does not fail the run
+    Baselined,
+    /// not in the baseline; fails the run
+    New,
+}
+
+/// Known findings of each file.
+///
+/// Findings are matched by the content of the trimmed line and the number of bytes trimmed, not
+/// by line number, so that they survive edits elsewhere in the file. A file may have the same
+/// finding several times; each occurrence is matched at most once.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Baseline {
+    /// path -> (trimmed line, bytes trimmed) -> number of occurrences
+    files: HashMap<String, HashMap<(String, usize), usize>>,
+}
+
+impl Baseline {
+    /// # Parameters
+    ///
+    /// 1. `findings` -- the path of each file, `-` for stdin, and its findings
+    ///
+    /// # Returns
+    ///
+    /// A baseline that accepts exactly `findings`.
+    pub fn from_findings<'a, I>(findings: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, &'a [Finding])>,
+    {
+        let mut baseline = Baseline::default();
+        for (path, file_findings) in findings {
+            let known = baseline.files.entry(path.to_string()).or_default();
+            for finding in file_findings {
+                *known.entry(key(finding)).or_insert(0) += 1;
+            }
+        }
+        baseline
+    }
+
+    /// # Returns
+    ///
+    /// The baseline saved under `path` by `save`.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let value: Value = serde_json::from_str(&read_to_string(path)?)
+            .map_err(|err| invalid(format!("{:?} is not a baseline: {}", path, err)))?;
+
+        match value.get("version").and_then(Value::as_u64) {
+            Some(VERSION) => {}
+            version => {
+                let message = format!("unsupported baseline version {:?} in {:?}", version, path);
+                return Err(invalid(message));
+            }
+        }
+
+        let mut baseline = Baseline::default();
+        let files = value.get("files").and_then(Value::as_object);
+        for (file, findings) in files.into_iter().flatten() {
+            let known = baseline.files.entry(file.clone()).or_default();
+            for finding in findings.as_array().into_iter().flatten() {
+                let line = finding.get("line").and_then(Value::as_str);
+                let bytes_trimmed = finding.get("bytes_trimmed").and_then(Value::as_u64);
+                let count = finding.get("count").and_then(Value::as_u64).unwrap_or(1);
+                match (line, bytes_trimmed) {
+                    (Some(line), Some(bytes_trimmed)) => {
+                        let key = (line.to_string(), bytes_trimmed as usize);
+                        *known.entry(key).or_insert(0) += count as usize;
+                    }
+                    _ => return Err(invalid(format!("malformed finding in {:?}", path))),
+                }
+            }
+        }
+        Ok(baseline)
+    }
+
+    /// Save this baseline under `path`, atomically, in a format that `load` understands.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        // sorted, so that the file does not change when the findings do not; `Map` is sorted by
+        // key already
+        let files: serde_json::Map<String, Value> = self
+            .files
+            .iter()
+            .map(|(file, known)| {
+                let mut known: Vec<_> = known.iter().collect();
+                known.sort();
+                let findings = known
+                    .into_iter()
+                    .map(|((line, bytes_trimmed), count)| {
+                        json!({ "line": line, "bytes_trimmed": bytes_trimmed, "count": count })
+                    })
+                    .collect();
+                (file.clone(), Value::Array(findings))
+            })
+            .collect();
+        let value = json!({ "version": VERSION, "files": files });
+
+        let (temp_path, mut temp_file) = mktemp_sibling(path)?;
+        let result = writeln!(temp_file, "{:#}", value).and_then(|_| rename(&temp_path, path));
+        if result.is_err() {
+            let _ = remove_file(&temp_path);
+        }
+        result
+    }
+
+    /// # Returns
+    ///
+    /// The severity of each of `findings` of the file under `path`, `-` for stdin, in order.
+    pub fn classify(&self, path: &str, findings: &[Finding]) -> Vec<Severity> {
+        let mut remaining = self.files.get(path).cloned().unwrap_or_default();
+        findings
+            .iter()
+            .map(|finding| match remaining.get_mut(&key(finding)) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    Severity::Baselined
+                }
+                _ => Severity::New,
+            })
+            .collect()
+    }
+
+    /// # Returns
+    ///
+    /// Number of findings accepted by this baseline, across all files.
+    pub fn len(&self) -> usize {
+        self.files.values().flat_map(HashMap::values).sum()
+    }
+
+    /// # Returns
+    ///
+    /// `true` if this baseline accepts no findings at all, `false` otherwise.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// # Returns
+///
+/// What a finding is matched by.
+#[inline]
+fn key(finding: &Finding) -> (String, usize) {
+    (finding.trimmed_line.clone(), finding.bytes_trimmed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::mktemp;
+
+    fn finding(line_number: usize, trimmed_line: &str, bytes_trimmed: usize) -> Finding {
+        Finding {
+            line_number,
+            trimmed_line: trimmed_line.to_string(),
+            bytes_trimmed,
+            width_trimmed: Some(bytes_trimmed),
+        }
+    }
+
+    #[test]
+    fn classify() {
+        let known = [finding(1, "a", 1), finding(5, "b", 2)];
+        let baseline = Baseline::from_findings(vec![("x", &known[..])]);
+
+        // line numbers moved, one occurrence of `b` is new, so is `c`
+        let findings = vec![
+            finding(2, "a", 1),
+            finding(6, "b", 2),
+            finding(7, "b", 2),
+            finding(9, "c", 1),
+        ];
+        let expected = vec![
+            Severity::Baselined,
+            Severity::Baselined,
+            Severity::New,
+            Severity::New,
+        ];
+        assert_eq!(expected, baseline.classify("x", &findings));
+        assert_eq!(vec![Severity::New], baseline.classify("y", &findings[..1]));
+    }
+
+    #[test]
+    fn save_then_load() {
+        let known = [finding(1, "a \"quoted\"", 1), finding(2, "a \"quoted\"", 1)];
+        let baseline = Baseline::from_findings(vec![("x", &known[..]), ("-", &[][..])]);
+        assert_eq!(2, baseline.len());
+
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let path = mktemp(&prefix, &"").unwrap();
+        baseline.save(&path).unwrap();
+
+        assert_eq!(baseline, Baseline::load(&path).unwrap());
+    }
+
+    #[test]
+    fn load_rejects_other_versions() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let path = mktemp(&prefix, &r#"{ "version": 99, "files": {} }"#).unwrap();
+
+        let err = Baseline::load(&path).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+}
@@ -0,0 +1,98 @@
+//! Aborting a run from the outside, e.g. when an editor closes the buffer being trimmed, or on
+//! Ctrl-C.
+
+use std::io;
+use std::io::Read;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// A flag shared by whoever may cancel a run and every reader of it; clones share the flag.
+///
+/// Once cancelled, every read of an input fails, so files being trimmed are abandoned mid-file
+/// and left untouched, and files not started yet are not opened at all.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    /// Cancel the run; cannot be undone.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// # Returns
+    ///
+    /// `true` if `cancel` was called on this token or any of its clones, `false` otherwise.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Reads from `inner` until a `CancellationToken`, if any, is cancelled.
+#[derive(Debug)]
+pub struct Cancellable<'a, R> {
+    inner: R,
+    token: Option<&'a CancellationToken>,
+}
+
+/// # Returns
+///
+/// `reader`, failing with `cancelled` once `token` is cancelled; never failing that way if
+/// `token` is `None`.
+#[inline]
+pub fn cancellable<R>(reader: R, token: Option<&CancellationToken>) -> Cancellable<'_, R>
+where
+    R: Read,
+{
+    Cancellable {
+        inner: reader,
+        token,
+    }
+}
+
+impl<R> Read for Cancellable<'_, R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.token {
+            Some(token) if token.is_cancelled() => Err(cancelled()),
+            _ => self.inner.read(buf),
+        }
+    }
+}
+
+/// # Returns
+///
+/// The error a read fails with once its run is cancelled. Not `io::ErrorKind::Interrupted`, which
+/// readers retry.
+pub fn cancelled() -> io::Error {
+    io::Error::other("cancelled")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_until_cancelled() {
+        let token = CancellationToken::new();
+        let mut reader = cancellable(&b"abc"[..], Some(&token));
+
+        let mut buf = [0; 1];
+        assert_eq!(1, reader.read(&mut buf).unwrap());
+
+        token.clone().cancel();
+        assert!(token.is_cancelled());
+        assert!(reader.read(&mut buf).is_err());
+
+        let mut content = Vec::new();
+        assert!(cancellable(&b"abc"[..], None)
+            .read_to_end(&mut content)
+            .is_ok());
+    }
+}
@@ -1,26 +1,578 @@
 use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
 use structopt::StructOpt;
+use trim::encoding::Encoding;
+use trim::filter::FilterFailure;
+use trim::options::ControlChars;
+use trim::options::EmptyFile;
+use trim::options::Engine;
+use trim::options::MetadataPolicy;
+use trim::options::Schedule;
+use trim::options::Whitespace;
+use trim::owner::Owner;
+use trim::pattern::Pattern;
+use trim::report::Format;
+use trim::report::PathStyle;
+use trim::report::Report;
+use trim::schema::Schema;
+use trim::stats::Threshold;
+use trim::util::Fsync;
 
 #[derive(StructOpt, Debug)]
-#[structopt(name = "trim")]
+#[structopt(
+    name = "trim",
+    raw(setting = "structopt::clap::AppSettings::DisableVersion")
+)]
 pub struct Opt {
     /// trim <files> in-place, overwritting the content of the files atomically
     #[structopt(short = "i", long = "in-place")]
     pub in_place: bool,
 
+    /// trim every regular file under each of <files> that is a directory, recursively; symbolic
+    /// links are not followed, and `.git`, `.hg` and `.svn` are left out
+    #[structopt(short = "r", long = "recursive")]
+    pub recursive: bool,
+
+    /// with `-i`, trim files under `/`, a drive root or the home directory without asking first
+    #[structopt(short = "y", long = "yes")]
+    pub yes: bool,
+
+    /// with `-i`, if more than N files would be modified, show how many and the bytes ish they
+    /// would save, and ask before going ahead unless `--yes`
+    #[structopt(long = "confirm-over", requires = "in_place")]
+    pub confirm_over: Option<usize>,
+
+    /// write trimmed copies of <files> under this directory, by their relative paths, instead of
+    /// trimming them in-place; the files themselves are left untouched
+    #[structopt(long = "out-dir", parse(from_os_str), conflicts_with = "in_place")]
+    pub out_dir: Option<PathBuf>,
+
+    /// with `--out-dir`, write the SHA-256 checksums of the trimmed copies to this file once they
+    /// are written, relative to the directory, as `sha256sum` does, e.g. to sign the tree
+    #[structopt(long = "manifest-out", parse(from_os_str), requires = "out_dir")]
+    pub manifest_out: Option<PathBuf>,
+
+    /// with `--out-dir`, split the trimmed lines from stdin into files `part-00000`, `part-00001`
+    /// and so on under it, starting the next file at each line that matches this regex, e.g.
+    /// `^\d{4}-\d{2}-\d{2}` for a log with sections per day; each file appears once it is whole
+    #[structopt(long = "split-by", requires = "out_dir")]
+    pub split_by: Option<Pattern>,
+
+    /// read lines from this file descriptor, inherited from the parent process, instead of files
+    #[structopt(
+        long = "fd",
+        raw(conflicts_with_all = r#"&["files", "in_place", "out_dir"]"#)
+    )]
+    pub input_fd: Option<i32>,
+
+    /// write trimmed lines to this file descriptor instead of stdout, e.g. 3 for `3>trimmed.txt`
+    #[structopt(
+        long = "output-fd",
+        raw(conflicts_with_all = r#"&["in_place", "out_dir"]"#)
+    )]
+    pub output_fd: Option<i32>,
+
+    /// also write the trimmed lines to this file, which is replaced atomically once they are all
+    /// written, e.g. to keep a copy of what a pipeline consumed
+    #[structopt(
+        long = "tee",
+        parse(from_os_str),
+        raw(conflicts_with_all = r#"&["in_place", "out_dir", "follow"]"#)
+    )]
+    pub tee: Option<PathBuf>,
+
+    /// append the trimmed lines to this file instead of writing them to stdout, creating it if
+    /// need be; it is never replaced, so it may be a log that others append to as well
+    #[structopt(
+        long = "append-to",
+        parse(from_os_str),
+        raw(conflicts_with_all = r#"&["in_place", "out_dir", "output_fd"]"#)
+    )]
+    pub append_to: Option<PathBuf>,
+
+    /// with `--append-to`, when to sync what was appended to disk; `flush` syncs after every
+    /// line with `--line-buffered` or `--follow`; ignored otherwise
+    #[structopt(
+        long = "fsync",
+        default_value = "end",
+        raw(possible_values = "Fsync::VARIANTS")
+    )]
+    pub fsync: Fsync,
+
+    /// output the canonical form, for hashing or diffing: ASCII whitespace trimmed, `\n` endings
+    /// and one final `\n`; stable across versions, and overrides options that change the output
+    #[structopt(long = "canonical")]
+    pub canonical: bool,
+
+    /// write a unified diff of what trimming would change instead of the trimmed lines; for stdin
+    /// or a single file, which is left untouched
+    #[structopt(
+        long = "diff",
+        raw(
+            conflicts_with_all = r#"&["in_place", "out_dir", "input_fd", "line_buffered", "follow"]"#
+        )
+    )]
+    pub show_diff: bool,
+
+    /// with `--diff`, diff from the content of this file instead of the untrimmed input, e.g. from
+    /// the saved version of a buffer that is being edited
+    #[structopt(long = "against", parse(from_os_str), requires = "show_diff")]
+    pub against: Option<PathBuf>,
+
+    /// instead of writing the trimmed lines, write a fixture of each input to this directory, named
+    /// after it: the input, the options, the trimmed content and its stats; see `trim::fixture`
+    #[structopt(
+        long = "emit-fixture",
+        parse(from_os_str),
+        raw(conflicts_with_all = r#"&["in_place", "out_dir", "input_fd", "show_diff", "follow"]"#)
+    )]
+    pub emit_fixture: Option<PathBuf>,
+
+    /// write the trimmed content of only the inputs that trimming changes, each after a
+    /// `==> path <==` header if there are several; unchanged inputs are left out entirely
+    #[structopt(
+        long = "changed-content-only",
+        raw(conflicts_with_all = r#"&["in_place", "out_dir", "show_diff", "emit_fixture"]"#)
+    )]
+    pub changed_content_only: bool,
+
+    /// only report which inputs trimming would change, and the bytes ish it would save in each,
+    /// e.g. in CI; nothing is written, and exits with 1 if any input would change
+    #[structopt(
+        long = "check",
+        raw(
+            conflicts_with_all = r#"&["in_place", "out_dir", "show_diff", "emit_fixture",
+            "changed_content_only", "tee", "follow"]"#
+        )
+    )]
+    pub check: bool,
+
+    /// write each line from stdin as soon as it is read, for streaming pipelines such as
+    /// `tail -f log | trim`; blank lines wait at most 100ms, in case they are trailing
+    #[structopt(
+        long = "line-buffered",
+        raw(conflicts_with_all = r#"&["suppress_newline", "final_newlines"]"#)
+    )]
+    pub line_buffered: bool,
+
+    /// keep reading the file, or stdin, as it grows, like `tail -f`, writing each line as soon as
+    /// it is read; trailing blank lines are not dropped, as there is no end
+    #[structopt(
+        long = "follow",
+        raw(
+            conflicts_with_all = r#"&["suppress_newline", "final_newlines", "in_place", "out_dir"]"#
+        )
+    )]
+    pub follow: bool,
+
     /// suppress outputting the trailing `\n` in the last line
     #[structopt(short = "N", long = "suppress-newline")]
     pub suppress_newline: bool,
 
+    /// end the output with exactly N newlines, e.g. 2 for some changelog conventions, however
+    /// many blank lines the input ends with
+    #[structopt(long = "final-newlines", conflicts_with = "suppress_newline")]
+    pub final_newlines: Option<usize>,
+
+    /// end empty inputs with a `\n` too, as any other input, instead of leaving them empty
+    #[structopt(long = "empty-newline", conflicts_with = "suppress_newline")]
+    pub empty_newline: bool,
+
+    /// what becomes of inputs that hold nothing but whitespace: `keep` trims them to a `\n`, like
+    /// any other, `truncate` to nothing at all, and `delete` deletes such files
+    #[structopt(
+        long = "empty-file",
+        default_value = "keep",
+        raw(possible_values = "EmptyFile::VARIANTS")
+    )]
+    pub empty_file: EmptyFile,
+
     /// suppress summary
     #[structopt(short = "S", long = "suppress-summary")]
     pub suppress_summary: bool,
 
+    /// format of the summary
+    #[structopt(
+        long = "format",
+        default_value = "text",
+        raw(possible_values = "Format::VARIANTS")
+    )]
+    pub format: Format,
+
+    /// with `--format text`, list only the N files that saved the most, then the totals of every
+    /// file; errors are always listed
+    #[structopt(long = "top")]
+    pub top: Option<usize>,
+
+    /// list every file in the summary, even with `--top`
+    #[structopt(long = "all")]
+    pub all: bool,
+
+    /// draw the summary with ASCII only, e.g. the bars of `--format heatmap`, for terminals and
+    /// logs that mangle anything else
+    #[structopt(long = "ascii")]
+    pub ascii: bool,
+
+    /// also report these findings, which fail the run when found; `eof-blank` for files that end
+    /// with blank lines, whether or not any line has trailing whitespace
+    #[structopt(
+        long = "report",
+        use_delimiter = true,
+        raw(number_of_values = "1", possible_values = "Report::VARIANTS")
+    )]
+    pub reports: Vec<Report>,
+
+    /// how to show the path of each file in the summary; `given` as passed, `canonical` as an
+    /// absolute path, `repo` relative to the root of its git repository
+    #[structopt(
+        long = "path-style",
+        default_value = "given",
+        raw(possible_values = "PathStyle::VARIANTS")
+    )]
+    pub path_style: PathStyle,
+
+    /// show the path of each file in the summary relative to this directory instead, e.g. the root
+    /// of a CI checkout, so that paths do not depend on where the checkout is
+    #[structopt(
+        long = "relative-to",
+        parse(from_os_str),
+        conflicts_with = "path_style"
+    )]
+    pub relative_to: Option<PathBuf>,
+
+    /// fail the run if it trims more than this, in total; `<N>bytes` for bytes ish saved,
+    /// `<N>files` for files modified, e.g. `0files` to fail on any trailing whitespace
+    #[structopt(
+        long = "fail-threshold",
+        use_delimiter = true,
+        raw(number_of_values = "1")
+    )]
+    pub fail_thresholds: Vec<Threshold>,
+
+    /// fail the run if any input has warnings, e.g. mixed line endings; warnings are listed in the
+    /// summary either way
+    #[structopt(long = "warnings-as-errors")]
+    pub warnings_as_errors: bool,
+
+    /// same as `--format porcelain`; a stable, tab-separated summary for scripts
+    #[structopt(long = "porcelain")]
+    pub porcelain: bool,
+
+    /// identify this run in the summary by this id instead of a new ULID, e.g. to reproduce a report;
+    /// the text summary only names the run if this is given
+    #[structopt(long = "run-id")]
+    pub run_id: Option<String>,
+
+    /// write the summary to this file descriptor instead of stderr, e.g. 3 for `3>summary.txt`
+    #[structopt(long = "summary-fd")]
+    pub summary_fd: Option<i32>,
+
+    /// write visualizations to this file descriptor instead of stderr
+    #[structopt(long = "visual-fd")]
+    pub visual_fd: Option<i32>,
+
     /// suppress visualizations of the trim
     #[structopt(short = "V", long = "suppress-visual")]
     pub suppress_visual: bool,
 
-    /// files to trim; if '-' exists or none provided, stdin will be used
+    /// which characters are whitespace; `unicode` also trims NBSP, ideographic space, etc.
+    #[structopt(
+        long = "whitespace",
+        default_value = "unicode",
+        raw(possible_values = "Whitespace::VARIANTS")
+    )]
+    pub whitespace: Whitespace,
+
+    /// how trailing whitespace is found: `chars` decodes each line backwards, `bytes` scans its
+    /// bytes, `simd` too, 8 at a time; they trim the same, so each can be timed against, or fall
+    /// back to, another, see `trim bench`
+    #[structopt(
+        long = "engine",
+        default_value = "chars",
+        raw(possible_values = "Engine::VARIANTS")
+    )]
+    pub engine: Engine,
+
+    /// keep a trailing form feed (`\x0C`) on each line that has one, as a page break
+    #[structopt(long = "keep-form-feed")]
+    pub keep_form_feed: bool,
+
+    /// keep `\r\n` line endings as they are, instead of converting them to `\n`
+    #[structopt(long = "keep-crlf")]
+    pub keep_crlf: bool,
+
+    /// strip terminal escape sequences, e.g. colors, from each line as it is trimmed, so that a
+    /// captured console log comes out paste-ready
+    #[structopt(long = "strip-ansi")]
+    pub strip_ansi: bool,
+
+    /// what becomes of control characters such as bell or backspace within each line: `keep`
+    /// them, `strip` them, or `escape` them so that they are visible, e.g. as `\u{7}`; tabs and
+    /// other whitespace, as defined by `--whitespace`, are only trimmed
+    #[structopt(
+        long = "control-chars",
+        default_value = "keep",
+        raw(possible_values = "ControlChars::VARIANTS")
+    )]
+    pub control_chars: ControlChars,
+
+    /// split the input into records ending with this ASCII character, e.g. ';' or '\0', instead
+    /// of lines, and trim whitespace at the end of each record
+    #[structopt(
+        long = "record-sep",
+        parse(try_from_str = "trim::options::parse_record_sep")
+    )]
+    pub record_sep: Option<u8>,
+
+    /// only trim trailing runs of at least this many whitespace characters
+    #[structopt(long = "min-run", default_value = "1")]
+    pub min_run: usize,
+
+    /// trim only the first N lines and stop output there, to preview what a full run would do
+    #[structopt(
+        long = "max-lines",
+        raw(conflicts_with_all = r#"&["in_place", "out_dir"]"#)
+    )]
+    pub max_lines: Option<usize>,
+
+    /// additional per-line cleanup to apply after trimming; may be repeated
+    #[structopt(long = "rule", raw(possible_values = "trim::rules::BUILTIN_NAMES"))]
+    pub rules: Vec<String>,
+
+    /// read each file as a whole and trim chunks of it in parallel; for huge files
+    #[structopt(long = "intra-file-parallel")]
+    pub intra_file_parallel: bool,
+
+    /// bound the memory spent on buffers, e.g. 64M; shrinks buffers and applies backpressure
+    #[structopt(long = "max-memory", parse(try_from_str = "trim::options::parse_size"))]
+    pub max_memory: Option<usize>,
+
+    /// cap the rate at which files are read, in MB/s across all workers, e.g. 20 or 0.5
+    #[structopt(long = "throttle", parse(try_from_str = "trim::throttle::parse_rate"))]
+    pub throttle: Option<u64>,
+
+    /// skip files that other processes have open for writing; best-effort, Linux only
+    #[structopt(long = "skip-open-for-writing", requires = "in_place")]
+    pub skip_open_for_writing: bool,
+
+    /// with `-i` or `--out-dir`, show how many files are done, and which file is being trimmed
+    #[structopt(long = "progress")]
+    pub progress: bool,
+
+    /// with `-i` or `--out-dir`, print the totals so far every this many seconds; for long runs
+    #[structopt(long = "stats-interval")]
+    pub stats_interval: Option<u64>,
+
+    /// order in which files are trimmed; `size` starts with the largest, `input` keeps the order
+    #[structopt(
+        long = "schedule",
+        default_value = "size",
+        raw(possible_values = "Schedule::VARIANTS")
+    )]
+    pub schedule: Schedule,
+
+    /// for network filesystems; fsync, verify after rename, and retry transient errors
+    #[structopt(long = "net-safe")]
+    pub net_safe: bool,
+
+    /// leave lines ending with this marker untouched, and lines between trim:off and trim:on
+    #[structopt(long = "ignore-marker", default_value = "trim:ignore")]
+    pub ignore_marker: String,
+
+    /// leave files with this marker in their first `--skip-file-lines` lines untouched as a whole
+    #[structopt(long = "skip-file-marker", default_value = "trim: skip-file")]
+    pub skip_file_marker: String,
+
+    /// number of lines at the start of a file searched for `--skip-file-marker`
+    #[structopt(long = "skip-file-lines", default_value = "5")]
+    pub skip_file_lines: usize,
+
+    /// don't honor any marker, trim every line
+    #[structopt(long = "no-ignore-markers")]
+    pub no_ignore_markers: bool,
+
+    /// only trim files with one of these extensions, e.g. `rs,toml,md`, or scripts without one
+    /// whose shebang runs the interpreter of one, e.g. `py` for `#!/usr/bin/env python3`; others
+    /// are left out
+    #[structopt(long = "ext", use_delimiter = true)]
+    pub extensions: Vec<String>,
+
+    /// leave out files matching this glob pattern, e.g. "*.min.js", whether they are listed or
+    /// found with `-r`, and with `-r`, every file under a directory matching it, e.g. "vendor". As
+    /// in `.gitignore`, a pattern with a `/`, e.g. "vendor/**", matches the path relative to the
+    /// directory walked, and one without matches any name. May be given more than once
+    #[structopt(long = "exclude", number_of_values = 1)]
+    pub exclude: Vec<glob::Pattern>,
+
+    /// with `-i` or `--out-dir`, skip files modified less than this long ago, e.g. `7d`, `12h` or
+    /// `30m`; for logs that may still be written to
+    #[structopt(
+        long = "older-than",
+        parse(try_from_str = "trim::options::parse_duration")
+    )]
+    pub older_than: Option<Duration>,
+
+    /// with `-i` or `--out-dir`, skip files last modified before this, either a duration ago,
+    /// e.g. `1d`, or a time in UTC, e.g. `2024-01-31T12:00:00Z` or `@1706702400`; for
+    /// incremental runs
+    #[structopt(
+        long = "newer-than",
+        parse(try_from_str = "trim::options::parse_since")
+    )]
+    pub newer_than: Option<SystemTime>,
+
+    /// with `-i` or `--out-dir`, skip files not owned by this user, user and group, or group,
+    /// e.g. `alice`, `alice:staff`, `:staff` or `1000`; Unix only
+    #[structopt(long = "owned-by")]
+    pub owned_by: Option<Owner>,
+
+    /// with `-i` or `--out-dir`, skip and warn of files that trimming would shrink by more than
+    /// this percentage, e.g. 50; they are likely not line-oriented text
+    #[structopt(long = "max-shrink-percent")]
+    pub max_shrink_percent: Option<f64>,
+
+    /// with `-i` or `--out-dir`, what becomes of files whose owner, group or permissions cannot
+    /// be kept, e.g. files of other users when not root: `best-effort` trims them and warns,
+    /// `strict` fails them
+    #[structopt(
+        long = "metadata",
+        default_value = "best-effort",
+        raw(possible_values = "MetadataPolicy::VARIANTS")
+    )]
+    pub metadata: MetadataPolicy,
+
+    /// how to decode stdin: `utf-8` refuses content that looks like UTF-16 or binary, and `auto`
+    /// transcodes UTF-16, told by its BOM or its NUL bytes, to UTF-8
+    #[structopt(
+        long = "encoding",
+        default_value = "utf-8",
+        raw(possible_values = "Encoding::VARIANTS")
+    )]
+    pub encoding: Encoding,
+
+    /// with `-i` or `--out-dir`, pipe the trimmed content of each file through this shell
+    /// command, e.g. `sed 's/\t/    /g'`, and write what it outputs instead; the path of the file
+    /// is in `$TRIM_PATH`
+    #[structopt(long = "filter-cmd")]
+    pub filter_cmd: Option<String>,
+
+    /// with `--filter-cmd`, kill the command once it runs this long on a file, e.g. `30s`, which
+    /// then counts as failing on it
+    #[structopt(
+        long = "filter-timeout",
+        parse(try_from_str = "trim::options::parse_duration")
+    )]
+    pub filter_timeout: Option<Duration>,
+
+    /// with `--filter-cmd`, what becomes of a file the command fails on: `error` fails it,
+    /// `skip` leaves it untouched, and `unfiltered` writes it trimmed but unfiltered; the latter
+    /// two warn of it
+    #[structopt(
+        long = "filter-fail",
+        default_value = "error",
+        raw(possible_values = "FilterFailure::VARIANTS")
+    )]
+    pub filter_fail: FilterFailure,
+
+    /// with `-i` or `--out-dir`, check that each file only lost whitespace before it is written,
+    /// and leave it as is otherwise, as a safeguard against bugs; costs reading it again
+    #[structopt(
+        long = "paranoid",
+        raw(conflicts_with_all = r#"&["rules", "filter_cmd", "strip_ansi", "control_chars"]"#)
+    )]
+    pub paranoid: bool,
+
+    /// with `-i` or `--out-dir`, check N random modified files again once the run is done, that
+    /// they only lost whitespace; the audit is in the summary, and a failed one fails the run
+    #[structopt(
+        long = "audit-sample",
+        raw(conflicts_with_all = r#"&["rules", "filter_cmd", "strip_ansi", "control_chars"]"#)
+    )]
+    pub audit_sample: Option<usize>,
+
+    /// fail only on findings that are not in this baseline file; records it if it doesn't exist
+    #[structopt(long = "baseline", parse(from_os_str))]
+    pub baseline: Option<PathBuf>,
+
+    /// record trimmed files in this state file, and skip those already recorded, so that an
+    /// interrupted `-i` run can be restarted; removed once a run completes without errors
+    #[structopt(long = "resume", parse(from_os_str), requires = "in_place")]
+    pub resume: Option<PathBuf>,
+
+    /// print the version and exit
+    #[structopt(long = "version")]
+    pub version: bool,
+
+    /// with `--version`, also print the git commit, target, compiler and cargo features of the
+    /// build, e.g. for bug reports
+    #[structopt(long = "verbose", requires = "version")]
+    pub verbose: bool,
+
+    /// files to trim; if '-' exists or none provided, stdin will be used. Glob patterns, e.g.
+    /// "**/*.md", are expanded even if the shell leaves them as they are, as on Windows
     #[structopt(parse(from_os_str))]
     pub files: Vec<PathBuf>,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// report whether two files, or two trees, differ only by what trimming changes; exits with 1
+    /// if they differ by more
+    #[structopt(name = "diff")]
+    Diff {
+        /// the first file or directory
+        #[structopt(parse(from_os_str))]
+        a: PathBuf,
+        /// the second file or directory
+        #[structopt(parse(from_os_str))]
+        b: PathBuf,
+    },
+    /// merge JSON reports of several runs, e.g. one per CI shard, into one with the totals of all
+    /// their files, written to stdout; see `--format json`
+    #[structopt(name = "merge-reports")]
+    MergeReports {
+        /// the JSON reports to merge; a file in several of them is kept as in the first
+        #[structopt(parse(from_os_str), required = true)]
+        reports: Vec<PathBuf>,
+    },
+    /// apply a patch written by `--format patch`, relative to the current directory; refuses the
+    /// whole patch, writing nothing, unless it only changes what trimming changes
+    #[structopt(name = "apply")]
+    Apply {
+        /// the patch to apply
+        #[structopt(parse(from_os_str))]
+        patch: PathBuf,
+    },
+    /// trim random inputs, heavy on whitespace, and check that every trim is idempotent, removes
+    /// nothing but whitespace, and accounts for the bytes it saves; exits with 1 on the first that
+    /// does not, e.g. to validate a build on an unusual platform
+    #[structopt(name = "self-test")]
+    SelfTest {
+        /// number of random inputs to trim
+        #[structopt(long = "iterations", default_value = "1000")]
+        iterations: u64,
+        /// seed of the random inputs, to repeat a failed self-test; random by default
+        #[structopt(long = "seed")]
+        seed: Option<u64>,
+    },
+    /// print the JSON Schema of a JSON document that trim writes, e.g. to validate reports, or to
+    /// generate types for them
+    #[structopt(name = "schema")]
+    Schema {
+        /// the document: `report` for `--format json`, `merged-report` for `merge-reports`
+        #[structopt(default_value = "report", raw(possible_values = "Schema::VARIANTS"))]
+        schema: Schema,
+    },
+    /// time every `--engine` trimming lines with trailing whitespace, in memory, and print how
+    /// many MiB/s each trims; with the `simd` feature, also how fast clean lines are scanned
+    #[structopt(name = "bench")]
+    Bench {
+        /// MiB of lines to trim, and to scan
+        #[structopt(long = "size", default_value = "64")]
+        size: usize,
+    },
 }
@@ -0,0 +1,158 @@
+//! Whether two files, or two trees of files, differ by more than what trimming changes; for
+//! verifying that a cleanup made no semantic changes.
+
+use std::collections::BTreeSet;
+use std::fs::read;
+use std::fs::read_dir;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::options::TrimOptions;
+use crate::trim::trim_chunked;
+use crate::util::long_path;
+
+/// How two files compare.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    /// byte for byte the same
+    Identical,
+    /// the same once trimmed; they differ only by trailing whitespace, line endings or trailing
+    /// newlines
+    WhitespaceOnly,
+    /// different even once trimmed, first at this 1-based line of the trimmed content
+    Differs(usize),
+    /// only the first tree has the file
+    OnlyInA,
+    /// only the second tree has the file
+    OnlyInB,
+}
+
+impl Comparison {
+    /// # Returns
+    ///
+    /// `true` if the two sides differ by more than what trimming changes, `false` otherwise.
+    #[inline]
+    pub fn is_semantic(self) -> bool {
+        !matches!(self, Comparison::Identical | Comparison::WhitespaceOnly)
+    }
+}
+
+/// # Returns
+///
+/// How the content `a` compares to the content `b`; both are brought to canonical form, see
+/// `TrimOptions::into_canonical`, before they are compared.
+pub fn compare_content(a: &[u8], b: &[u8]) -> io::Result<Comparison> {
+    if a == b {
+        return Ok(Comparison::Identical);
+    }
+
+    let options = TrimOptions::canonical();
+    let (mut trimmed_a, mut trimmed_b) = (Vec::new(), Vec::new());
+    trim_chunked(a, &mut trimmed_a, &mut None::<File>, &options)?;
+    trim_chunked(b, &mut trimmed_b, &mut None::<File>, &options)?;
+    if trimmed_a == trimmed_b {
+        return Ok(Comparison::WhitespaceOnly);
+    }
+
+    let first_difference = trimmed_a
+        .split(|&b| b == b'\n')
+        .zip(trimmed_b.split(|&b| b == b'\n'))
+        .position(|(line_a, line_b)| line_a != line_b)
+        // one is a prefix of the other, line-wise
+        .unwrap_or_else(|| {
+            let lines = |trimmed: &[u8]| trimmed.split(|&b| b == b'\n').count();
+            lines(&trimmed_a).min(lines(&trimmed_b))
+        });
+    Ok(Comparison::Differs(first_difference + 1))
+}
+
+/// # Returns
+///
+/// How the files under `a` and `b` compare, if both are files; otherwise, if both are
+/// directories, how each file in either tree compares to the one at the same relative path in
+/// the other, sorted by that path. Files are returned with an empty relative path.
+pub fn compare_paths(a: &Path, b: &Path) -> io::Result<Vec<(PathBuf, Comparison)>> {
+    match (a.is_dir(), b.is_dir()) {
+        (false, false) => Ok(vec![(
+            PathBuf::new(),
+            compare_content(&read(a)?, &read(b)?)?,
+        )]),
+        (true, true) => {
+            let (mut files_a, mut files_b) = (BTreeSet::new(), BTreeSet::new());
+            walk(a, Path::new(""), &mut files_a)?;
+            walk(b, Path::new(""), &mut files_b)?;
+
+            files_a
+                .union(&files_b)
+                .map(|relative| {
+                    let comparison = match (files_a.contains(relative), files_b.contains(relative))
+                    {
+                        (true, false) => Comparison::OnlyInA,
+                        (false, true) => Comparison::OnlyInB,
+                        _ => {
+                            let a = read(long_path(&a.join(relative)))?;
+                            compare_content(&a, &read(long_path(&b.join(relative)))?)?
+                        }
+                    };
+                    Ok((relative.clone(), comparison))
+                })
+                .collect()
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cannot compare {:?} to {:?}; one is a directory", a, b),
+        )),
+    }
+}
+
+/// Collect the path of every file under `root`, relative to `root`, into `files`.
+///
+/// # Parameters
+///
+/// 1. `root` -- the tree being walked
+/// 1. `relative` -- the directory being walked, relative to `root`
+/// 1. `files` -- where the paths are collected
+fn walk(root: &Path, relative: &Path, files: &mut BTreeSet<PathBuf>) -> io::Result<()> {
+    for entry in read_dir(long_path(&root.join(relative)))? {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+        match entry.file_type()?.is_dir() {
+            true => walk(root, &path, files)?,
+            false => {
+                files.insert(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parametrized_compare_content() {
+        let test_data = vec![
+            ("abc\n", "abc\n", Comparison::Identical),
+            ("abc  \n", "abc\n", Comparison::WhitespaceOnly),
+            ("abc\r\ndef\r\n", "abc\ndef", Comparison::WhitespaceOnly),
+            ("abc\n\n\n", "abc", Comparison::WhitespaceOnly),
+            ("abc\ndef\n", "abc\nxyz\n", Comparison::Differs(2)),
+            ("abc \n", " abc\n", Comparison::Differs(1)),
+            // markers are not honored, so they cannot hide a change
+            (
+                "abc # trim:ignore \n",
+                "abc # trim:ignore\n",
+                Comparison::WhitespaceOnly,
+            ),
+        ];
+        for (a, b, expected) in test_data {
+            assert_eq!(
+                expected,
+                compare_content(a.as_bytes(), b.as_bytes()).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! A `TrimWrite` backed by `copy_file_range(2)`, for in-place trims on Linux.
+//!
+//! Spans of the output that are identical to the input are copied from file to file within the
+//! kernel, without ever passing through userspace; only modified lines are written, in batches.
+
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::RawFd;
+use std::ptr;
+
+use crate::trim::TrimWrite;
+
+/// Number of bytes of modified lines to buffer before writing them out.
+const BATCH_SIZE: usize = 1 << 16;
+
+/// Writes the trimmed content of `source` to the current position of `dest`.
+pub struct CopyRangeWriter<'a> {
+    source: &'a File,
+    dest: File,
+    /// `(offset, length)` of the span of `source` that is yet to be copied to `dest`
+    span: Option<(u64, u64)>,
+    /// modified bytes that are yet to be written to `dest`
+    batch: Vec<u8>,
+    /// number of modified bytes to buffer before writing them out
+    batch_size: usize,
+}
+
+impl<'a> CopyRangeWriter<'a> {
+    /// # Parameters
+    ///
+    /// 1. `source` -- the file being trimmed, which offsets in `TrimWrite::write_unchanged` refer
+    ///    to; its position is never used nor modified, so it can be read from meanwhile
+    /// 1. `dest` -- where the trimmed content is written to, from its current position
+    #[inline]
+    pub fn new(source: &'a File, dest: File) -> Self {
+        CopyRangeWriter::with_batch_size(source, dest, BATCH_SIZE)
+    }
+
+    /// Like `new`, but buffers up to `batch_size` bytes of modified lines instead of
+    /// `BATCH_SIZE`.
+    pub fn with_batch_size(source: &'a File, dest: File, batch_size: usize) -> Self {
+        CopyRangeWriter {
+            source,
+            dest,
+            span: None,
+            batch: Vec::with_capacity(batch_size),
+            batch_size,
+        }
+    }
+
+    /// Copy the pending span of `source` to `dest`, if any.
+    fn flush_span(&mut self) -> io::Result<()> {
+        if let Some((offset, length)) = self.span.take() {
+            copy_file_range(self.source, offset, &self.dest, length)?;
+        }
+        Ok(())
+    }
+
+    /// Write the pending modified bytes to `dest`, if any.
+    fn flush_batch(&mut self) -> io::Result<()> {
+        if !self.batch.is_empty() {
+            self.dest.write_all(&self.batch)?;
+            self.batch.clear();
+        }
+        Ok(())
+    }
+}
+
+impl<'a> TrimWrite for CopyRangeWriter<'a> {
+    fn write_trimmed(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.flush_span()?;
+        self.batch.extend_from_slice(bytes);
+        if self.batch.len() >= self.batch_size {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    fn write_unchanged(&mut self, bytes: &[u8], offset: u64) -> io::Result<()> {
+        let length = bytes.len() as u64;
+        match self.span {
+            // contiguous with the pending span; just extend it
+            Some((span_offset, span_length)) if span_offset + span_length == offset => {
+                self.span = Some((span_offset, span_length + length));
+            }
+            _ => {
+                self.flush_batch()?;
+                self.flush_span()?;
+                self.span = Some((offset, length));
+            }
+        }
+        Ok(())
+    }
+
+    fn flush_trimmed(&mut self) -> io::Result<()> {
+        // only one of the two is ever non-empty
+        self.flush_batch()?;
+        self.flush_span()?;
+        self.dest.flush()
+    }
+}
+
+/// Copy `length` bytes of `source` from `offset` to the current position of `dest`, falling back
+/// to reading and writing in userspace where `copy_file_range(2)` is not supported, e.g. across
+/// filesystems on older kernels.
+fn copy_file_range(source: &File, mut offset: u64, mut dest: &File, length: u64) -> io::Result<()> {
+    let mut remaining = length;
+    while remaining > 0 {
+        let mut off_in = offset as libc::loff_t;
+        let copied = unsafe {
+            sys_copy_file_range(
+                source.as_raw_fd(),
+                &mut off_in,
+                dest.as_raw_fd(),
+                remaining as usize,
+            )
+        };
+        match copied {
+            -1 => {
+                let err = io::Error::last_os_error();
+                match err.raw_os_error() {
+                    Some(libc::EXDEV)
+                    | Some(libc::ENOSYS)
+                    | Some(libc::EOPNOTSUPP)
+                    | Some(libc::EINVAL) => {
+                        let mut buf = vec![0; remaining as usize];
+                        source.read_exact_at(&mut buf, offset)?;
+                        return dest.write_all(&buf);
+                    }
+                    Some(libc::EINTR) => continue,
+                    _ => return Err(err),
+                }
+            }
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            _ => {
+                offset += copied as u64;
+                remaining -= copied as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `copy_file_range(2)` from `off_in` of `fd_in` to the current position of `fd_out`.
+#[cfg(not(target_env = "musl"))]
+unsafe fn sys_copy_file_range(
+    fd_in: RawFd,
+    off_in: &mut libc::loff_t,
+    fd_out: RawFd,
+    len: usize,
+) -> isize {
+    libc::copy_file_range(fd_in, off_in, fd_out, ptr::null_mut(), len, 0)
+}
+
+/// `copy_file_range(2)` from `off_in` of `fd_in` to the current position of `fd_out`, as a raw
+/// system call; musl only wraps it since 1.1.24, and static builds against an older one would not
+/// link otherwise.
+#[cfg(target_env = "musl")]
+unsafe fn sys_copy_file_range(
+    fd_in: RawFd,
+    off_in: &mut libc::loff_t,
+    fd_out: RawFd,
+    len: usize,
+) -> isize {
+    let null: *mut libc::loff_t = ptr::null_mut();
+    let flags: libc::c_uint = 0;
+    libc::syscall(
+        libc::SYS_copy_file_range,
+        fd_in,
+        off_in as *mut libc::loff_t,
+        fd_out,
+        null,
+        len,
+        flags,
+    ) as isize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::TrimOptions;
+    use crate::trim::trim_custom;
+    use crate::util::*;
+    use std::fs::read_to_string;
+    use std::fs::OpenOptions;
+
+    /// same output as trimming into memory
+    #[test]
+    fn matches_in_memory() {
+        let inputs = vec![
+            "",
+            "abc",
+            "abc\n",
+            "abc\ndef \nghi\n\n\njkl\n",
+            "abc\n\n\n",
+            "abc\r\n\r\ndef\r\n",
+            "\n\n \nabc  \n\ndef\n",
+        ];
+        for (index, input) in inputs.into_iter().enumerate() {
+            for keep_crlf in [false, true] {
+                let options = TrimOptions {
+                    keep_crlf,
+                    ..TrimOptions::default()
+                };
+                let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), index, keep_crlf);
+                let source_path = mktemp(&prefix, &input).unwrap();
+                let dest_path = mktemp(&format!("{}_dest", prefix), &"").unwrap();
+
+                let mut expected = Vec::new();
+                let expected_tr = trim_custom(
+                    split_lines(input.as_bytes()),
+                    &mut expected,
+                    &mut None::<File>,
+                    &options,
+                )
+                .unwrap();
+
+                let source = File::open(&source_path).unwrap();
+                let dest = OpenOptions::new().write(true).open(&dest_path).unwrap();
+                let tr = trim_custom(
+                    readlines(&source_path).unwrap(),
+                    &mut CopyRangeWriter::new(&source, dest),
+                    &mut None::<File>,
+                    &options,
+                )
+                .unwrap();
+
+                assert_eq!(expected_tr, tr);
+                assert_eq!(
+                    String::from_utf8(expected).unwrap(),
+                    read_to_string(&dest_path).unwrap()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Registry of the stable, machine-readable codes of everything reported about an input, so that
+//! wrapper scripts can branch on them without matching messages: `E_` for errors, see
+//! `error_code`, `W_` for warnings, see `warning_code`, and `S_` for skips, see `skip_code`.
+//!
+//! Codes are never renamed or reused once released; new ones may be added.
+
+use std::io;
+
+use crate::trim::SkipReason;
+use crate::trim::Warning;
+
+/// # Returns
+///
+/// The code of the error an input could not be trimmed with; `E_IO` for errors without a code
+/// of their own.
+pub fn error_code(err: &io::Error) -> &'static str {
+    match err.kind() {
+        io::ErrorKind::ReadOnlyFilesystem => "E_READ_ONLY",
+        io::ErrorKind::NotFound => "E_NOT_FOUND",
+        io::ErrorKind::PermissionDenied => "E_PERM",
+        io::ErrorKind::IsADirectory => "E_IS_DIR",
+        io::ErrorKind::InvalidData => "E_INVALID_DATA",
+        io::ErrorKind::StorageFull => "E_STORAGE_FULL",
+        io::ErrorKind::StaleNetworkFileHandle => "E_STALE_HANDLE",
+        _ => "E_IO",
+    }
+}
+
+/// # Returns
+///
+/// The code of `warning`.
+pub fn warning_code(warning: Warning) -> &'static str {
+    match warning {
+        Warning::MixedLineEndings => "W_MIXED_EOL",
+        Warning::Symlink => "W_SYMLINK",
+        Warning::Shrinks => "W_SHRINKS",
+        Warning::FilterFailed => "W_FILTER_FAILED",
+        Warning::MetadataNotKept => "W_METADATA_NOT_KEPT",
+        Warning::BomBeforeShebang => "W_BOM_SHEBANG",
+    }
+}
+
+/// # Returns
+///
+/// The code of why an input was skipped.
+pub fn skip_code(reason: SkipReason) -> &'static str {
+    match reason {
+        SkipReason::Marker => "S_MARKER",
+        SkipReason::OpenForWriting => "S_OPEN_FOR_WRITING",
+        SkipReason::Cancelled => "S_CANCELLED",
+        SkipReason::Duplicate => "S_DUPLICATE",
+        SkipReason::Recent => "S_RECENT",
+        SkipReason::Old => "S_OLD",
+        SkipReason::NotOwned => "S_NOT_OWNED",
+        SkipReason::Shrinks => "S_SHRINKS",
+        SkipReason::FilterFailed => "S_FILTER_FAILED",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::KNOWN_KINDS;
+    use std::collections::HashSet;
+    use std::iter;
+
+    #[test]
+    fn codes_are_unique_and_prefixed() {
+        let errors: Vec<io::ErrorKind> = KNOWN_KINDS
+            .iter()
+            .copied()
+            .chain(iter::once(io::ErrorKind::Other))
+            .collect();
+        let warnings = Warning::ALL;
+        let skips = SkipReason::ALL;
+        let codes: Vec<(&str, &str)> = errors
+            .iter()
+            .map(|&kind| ("E_", error_code(&io::Error::from(kind))))
+            .chain(
+                warnings
+                    .iter()
+                    .map(|&warning| ("W_", warning_code(warning))),
+            )
+            .chain(skips.iter().map(|&reason| ("S_", skip_code(reason))))
+            .collect();
+
+        let mut seen = HashSet::new();
+        for (prefix, code) in codes {
+            assert!(code.starts_with(prefix), "{}", code);
+            assert!(code.bytes().all(|b| b.is_ascii_uppercase() || b == b'_'));
+            assert!(seen.insert(code), "{} is not unique", code);
+        }
+    }
+}
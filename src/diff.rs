@@ -0,0 +1,326 @@
+//! Unified diffs between two versions of a content, e.g. to preview what trimming would change
+//! without writing anything.
+
+use std::io;
+use std::io::Write;
+
+/// Lines of unchanged content shown around each change.
+pub const CONTEXT: usize = 3;
+
+/// What happens to a line on the way from the old content to the new one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Edit {
+    /// the line at this 0-based index of the old content is kept, as the line at this index of
+    /// the new content
+    Keep(usize, usize),
+    /// the line at this 0-based index of the old content is removed
+    Remove(usize),
+    /// the line at this 0-based index of the new content is added
+    Add(usize),
+}
+
+/// # Returns
+///
+/// The lines of `content`, each with its `\n`, if any.
+fn lines(content: &[u8]) -> Vec<&[u8]> {
+    content.split_inclusive(|&b| b == b'\n').collect()
+}
+
+/// # Returns
+///
+/// A shortest edit script from `a` to `b`, in order; see Myers, "An O(ND) Difference Algorithm
+/// and Its Variations".
+fn edits(a: &[&[u8]], b: &[&[u8]]) -> Vec<Edit> {
+    // a common prefix and suffix are kept as is, which is most of the content when trimming
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (middle_a, middle_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut script: Vec<Edit> = (0..prefix).map(|i| Edit::Keep(i, i)).collect();
+    script.extend(
+        middle_edits(middle_a, middle_b)
+            .into_iter()
+            .map(|edit| match edit {
+                Edit::Keep(i, j) => Edit::Keep(prefix + i, prefix + j),
+                Edit::Remove(i) => Edit::Remove(prefix + i),
+                Edit::Add(j) => Edit::Add(prefix + j),
+            }),
+    );
+    script.extend((0..suffix).map(|i| Edit::Keep(a.len() - suffix + i, b.len() - suffix + i)));
+    script
+}
+
+/// Like `edits`, without looking for a common prefix or suffix first.
+fn middle_edits(a: &[&[u8]], b: &[&[u8]]) -> Vec<Edit> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    // furthest x on each diagonal k = x - y, offset by `max` so that it can be indexed
+    let mut v = vec![0isize; 2 * max as usize + 2];
+    let index = |k: isize| (k + max) as usize;
+    let mut trace = Vec::new();
+
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = match k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+                true => v[index(k + 1)],
+                false => v[index(k - 1)] + 1,
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // walk back from the end, through the furthest points of each round
+    let mut script = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = match k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+            true => k + 1,
+            false => k - 1,
+        };
+        let prev_x = v[index(prev_k)];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            script.push(Edit::Keep(x as usize, y as usize));
+        }
+        if d > 0 {
+            match x == prev_x {
+                true => script.push(Edit::Add(prev_y as usize)),
+                false => script.push(Edit::Remove(prev_x as usize)),
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    script.reverse();
+    script
+}
+
+/// Write a unified diff from `old` to `new` to `out`, with `CONTEXT` lines around each change.
+///
+/// # Parameters
+///
+/// 1. `old` -- content before the change
+/// 1. `new` -- content after the change
+/// 1. `old_label` -- name of `old` in the `---` header, e.g. its path
+/// 1. `new_label` -- name of `new` in the `+++` header
+/// 1. `out` -- where the diff is written
+///
+/// # Returns
+///
+/// `true` if the two differ, `false` otherwise.
+///
+/// # Side Effects
+///
+/// Nothing is written if the two are the same. A line without a `\n` at the end of the content is
+/// followed by `\ No newline at end of file`, like `diff -u` does.
+pub fn unified_diff<W>(
+    old: &[u8],
+    new: &[u8],
+    old_label: &str,
+    new_label: &str,
+    out: &mut W,
+) -> io::Result<bool>
+where
+    W: Write + ?Sized,
+{
+    if old == new {
+        return Ok(false);
+    }
+    let (a, b) = (lines(old), lines(new));
+    let script = edits(&a, &b);
+
+    // changes, grouped into hunks wherever fewer than 2 * CONTEXT lines are kept between them
+    let changed: Vec<usize> = (0..script.len())
+        .filter(|&i| !matches!(script[i], Edit::Keep(..)))
+        .collect();
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &i in &changed {
+        let start = i.saturating_sub(CONTEXT);
+        let end = (i + 1 + CONTEXT).min(script.len());
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    writeln!(out, "--- {}", old_label)?;
+    writeln!(out, "+++ {}", new_label)?;
+    for (start, end) in hunks {
+        let edits = &script[start..end];
+        // 0-based line of each side where the hunk starts
+        let (old_start, new_start) = position(&script[..start]);
+        let old_count = edits.iter().filter(|e| !matches!(e, Edit::Add(_))).count();
+        let new_count = edits
+            .iter()
+            .filter(|e| !matches!(e, Edit::Remove(_)))
+            .count();
+        writeln!(
+            out,
+            "@@ -{} +{} @@",
+            range(old_start, old_count),
+            range(new_start, new_count)
+        )?;
+        for edit in edits {
+            let (prefix, line) = match *edit {
+                Edit::Keep(i, _) => (b' ', a[i]),
+                Edit::Remove(i) => (b'-', a[i]),
+                Edit::Add(j) => (b'+', b[j]),
+            };
+            out.write_all(&[prefix])?;
+            out.write_all(line)?;
+            if !line.ends_with(b"\n") {
+                out.write_all(b"\n\\ No newline at end of file\n")?;
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Like `unified_diff`, but in the format of `git diff`, so that `git apply` accepts it: with a
+/// `diff --git` header, and `path` prefixed with `a/` and `b/` on either side.
+///
+/// # Parameters
+///
+/// 1. `old` -- content of the file under `path`
+/// 1. `new` -- content to change it to
+/// 1. `path` -- path of the file, relative to where the patch is applied
+/// 1. `out` -- where the diff is written
+///
+/// # Returns
+///
+/// `true` if the two differ, `false` otherwise.
+pub fn git_diff<W>(old: &[u8], new: &[u8], path: &str, out: &mut W) -> io::Result<bool>
+where
+    W: Write + ?Sized,
+{
+    if old == new {
+        return Ok(false);
+    }
+    let path = path.strip_prefix("./").unwrap_or(path);
+    writeln!(out, "diff --git a/{} b/{}", path, path)?;
+    let (old_label, new_label) = (format!("a/{}", path), format!("b/{}", path));
+    unified_diff(old, new, &old_label, &new_label, out)
+}
+
+/// # Returns
+///
+/// 0-based lines of the old and new content that come after `done`, the edits so far.
+fn position(done: &[Edit]) -> (usize, usize) {
+    let old = done.iter().filter(|e| !matches!(e, Edit::Add(_))).count();
+    let new = done
+        .iter()
+        .filter(|e| !matches!(e, Edit::Remove(_)))
+        .count();
+    (old, new)
+}
+
+/// # Returns
+///
+/// The range of a hunk header for `count` lines from the 0-based line `start`; an empty range is
+/// named by the line before it, as `diff -u` does.
+fn range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(old: &str, new: &str) -> String {
+        let mut out = Vec::new();
+        unified_diff(old.as_bytes(), new.as_bytes(), "a", "b", &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn parametrized_unified_diff() {
+        let test_data = vec![
+            ("a\nb\n", "a\nb\n", ""),
+            (
+                "a \nb\n",
+                "a\nb\n",
+                "--- a\n+++ b\n@@ -1,2 +1,2 @@\n-a \n+a\n b\n",
+            ),
+            (
+                "a\nb\n\n\n",
+                "a\nb\n",
+                "--- a\n+++ b\n@@ -1,4 +1,2 @@\n a\n b\n-\n-\n",
+            ),
+            (
+                "a\nb",
+                "a\nb\n",
+                "--- a\n+++ b\n@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b\n",
+            ),
+            ("", "a\n", "--- a\n+++ b\n@@ -0,0 +1 @@\n+a\n"),
+        ];
+        for (old, new, expected) in test_data {
+            assert_eq!(expected, diff(old, new), "{:?} -> {:?}", old, new);
+        }
+    }
+
+    #[test]
+    fn git_diff_prefixes() {
+        let mut out = Vec::new();
+        assert!(git_diff(b"a \n", b"a\n", "./src/x.rs", &mut out).unwrap());
+        let expected = concat!(
+            "diff --git a/src/x.rs b/src/x.rs\n",
+            "--- a/src/x.rs\n",
+            "+++ b/src/x.rs\n",
+            "@@ -1 +1 @@\n",
+            "-a \n",
+            "+a\n",
+        );
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+
+        let mut out = Vec::new();
+        assert!(!git_diff(b"a\n", b"a\n", "x", &mut out).unwrap());
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn hunks_are_separate_only_if_far_apart() {
+        let old: String = (1..=20).map(|i| format!("{} \n", i)).collect();
+        let new = old.replacen("1 \n", "1\n", 1).replacen("20 \n", "20\n", 1);
+        let far = diff(&old, &new);
+        assert_eq!(2, far.matches("@@ -").count());
+        assert!(far.contains("@@ -1,4 +1,4 @@\n"));
+        assert!(far.contains("@@ -17,4 +17,4 @@\n"));
+
+        let new = old.replacen("1 \n", "1\n", 1).replacen("7 \n", "7\n", 1);
+        assert_eq!(1, diff(&old, &new).matches("@@ -").count());
+    }
+
+    #[test]
+    fn edits_are_shortest() {
+        let a = lines(b"x\na\nb\nc\ny\n");
+        let b = lines(b"a\nz\nc\n");
+        let script = edits(&a, &b);
+        let changes = script
+            .iter()
+            .filter(|e| !matches!(e, Edit::Keep(..)))
+            .count();
+        assert_eq!(4, changes);
+    }
+}
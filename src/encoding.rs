@@ -0,0 +1,330 @@
+//! Sniffing the encoding of piped content, which has no file extension or name to go by, so that
+//! UTF-16 is transcoded or refused rather than trimmed into mojibake.
+
+use std::fmt;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Cursor;
+use std::io::Read;
+use std::str::FromStr;
+
+/// Number of bytes at the start of an input that `sniff` looks at.
+pub const SNIFF_LEN: usize = 4096;
+
+/// Percentage of NUL bytes above which content is not taken for UTF-8 text.
+const MAX_NUL_PERCENT: usize = 10;
+
+/// Percentage of the NUL bytes that must be at odd or even offsets for content to be taken for
+/// UTF-16 without a BOM, rather than binary.
+const MIN_ALIGNED_NUL_PERCENT: usize = 90;
+
+/// How piped content is decoded before it is trimmed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8; content that looks like anything else is refused with an error saying what it looks
+    /// like
+    #[default]
+    Utf8,
+    /// UTF-8, or UTF-16 as told by its BOM or by the offsets of its NUL bytes, transcoded to UTF-8
+    Auto,
+}
+
+impl Encoding {
+    /// Values accepted by `FromStr`, in the order they should be listed to the user.
+    pub const VARIANTS: &'static [&'static str] = &["utf-8", "auto"];
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "utf-8" => Ok(Encoding::Utf8),
+            "auto" => Ok(Encoding::Auto),
+            _ => Err(format!(
+                "expected one of {:?}, got {:?}",
+                Encoding::VARIANTS,
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Encoding::Utf8 => write!(f, "utf-8"),
+            Encoding::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+/// What the start of some content looks like; see `sniff`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sniffed {
+    /// UTF-8, or at least not anything else
+    Utf8,
+    /// UTF-16, little-endian; `bom` if it starts with one
+    Utf16Le { bom: bool },
+    /// UTF-16, big-endian; `bom` if it starts with one
+    Utf16Be { bom: bool },
+    /// neither text nor UTF-16, going by its many NUL bytes
+    Binary,
+}
+
+impl fmt::Display for Sniffed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sniffed::Utf8 => write!(f, "UTF-8"),
+            Sniffed::Utf16Le { .. } => write!(f, "UTF-16LE"),
+            Sniffed::Utf16Be { .. } => write!(f, "UTF-16BE"),
+            Sniffed::Binary => write!(f, "binary"),
+        }
+    }
+}
+
+/// # Parameters
+///
+/// 1. `head` -- the start of the content, e.g. its first `SNIFF_LEN` bytes
+///
+/// # Returns
+///
+/// What the content looks like: UTF-16 if it starts with a UTF-16 BOM, or if more than
+/// `MAX_NUL_PERCENT` of `head` are NUL bytes nearly all at odd offsets, for little-endian, or at
+/// even offsets, for big-endian; binary if they are not; UTF-8 otherwise.
+pub fn sniff(head: &[u8]) -> Sniffed {
+    match head {
+        [0xFF, 0xFE, ..] => return Sniffed::Utf16Le { bom: true },
+        [0xFE, 0xFF, ..] => return Sniffed::Utf16Be { bom: true },
+        _ => (),
+    }
+    let head = &head[..head.len().min(SNIFF_LEN)];
+    let nuls = head.iter().filter(|&&byte| byte == 0).count();
+    if nuls * 100 <= head.len() * MAX_NUL_PERCENT {
+        return Sniffed::Utf8;
+    }
+    let odd = head
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(|&&byte| byte == 0)
+        .count();
+    match (odd * 100, (nuls - odd) * 100) {
+        (odd, _) if odd >= nuls * MIN_ALIGNED_NUL_PERCENT => Sniffed::Utf16Le { bom: false },
+        (_, even) if even >= nuls * MIN_ALIGNED_NUL_PERCENT => Sniffed::Utf16Be { bom: false },
+        _ => Sniffed::Binary,
+    }
+}
+
+/// # Parameters
+///
+/// 1. `reader` -- the content, e.g. stdin
+/// 1. `encoding` -- how to decode it
+///
+/// # Returns
+///
+/// `reader`, as UTF-8: as it is if the start of it, as far as it can be read without waiting,
+/// looks like UTF-8, see `sniff`, or transcoded from UTF-16,
+/// without its BOM, with `Encoding::Auto`. An `io::ErrorKind::InvalidData` error saying what the
+/// content looks like otherwise, rather than trimming it into mojibake.
+pub fn decoded<'a, R>(mut reader: R, encoding: Encoding) -> io::Result<Box<dyn BufRead + Send + 'a>>
+where
+    R: BufRead + Send + 'a,
+{
+    // whatever is there already, as waiting for more would hold up streams; enough for a BOM
+    let mut head = Vec::new();
+    while head.len() < 2 {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        head.extend_from_slice(buf);
+        let len = buf.len();
+        reader.consume(len);
+    }
+    let sniffed = sniff(&head);
+    let mut reader = Cursor::new(head).chain(reader);
+    let (big_endian, bom) = match (sniffed, encoding) {
+        (Sniffed::Utf8, _) => return Ok(Box::new(reader)),
+        (Sniffed::Binary, _) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "looks like binary rather than text, with many NUL bytes",
+            ))
+        }
+        (_, Encoding::Utf8) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "looks like {}, not UTF-8; pass `--encoding auto` to transcode it",
+                    sniffed
+                ),
+            ))
+        }
+        (Sniffed::Utf16Le { bom }, Encoding::Auto) => (false, bom),
+        (Sniffed::Utf16Be { bom }, Encoding::Auto) => (true, bom),
+    };
+    if bom {
+        reader.consume(2);
+    }
+    Ok(Box::new(BufReader::new(Utf16Reader {
+        inner: reader,
+        big_endian,
+        carried: Vec::new(),
+        encoded: Vec::new(),
+        offset: 0,
+    })))
+}
+
+/// Transcodes UTF-16 read from `inner` to UTF-8.
+struct Utf16Reader<R> {
+    inner: R,
+    big_endian: bool,
+    /// bytes read but not transcoded yet: half a code unit, or a high surrogate waiting for its
+    /// low surrogate, or both
+    carried: Vec<u8>,
+    /// UTF-8 transcoded but not read yet, from `offset` on
+    encoded: Vec<u8>,
+    offset: usize,
+}
+
+impl<R> Utf16Reader<R>
+where
+    R: BufRead,
+{
+    /// Transcode the next buffer of `inner` into `encoded`.
+    ///
+    /// # Returns
+    ///
+    /// `false` at the end of `inner`, `true` otherwise. An `io::ErrorKind::InvalidData` error if
+    /// it is not valid UTF-16.
+    fn fill(&mut self) -> io::Result<bool> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+        let buf = self.inner.fill_buf()?;
+        let eof = buf.is_empty();
+        if eof && self.carried.is_empty() {
+            return Ok(false);
+        }
+        let mut bytes = std::mem::take(&mut self.carried);
+        bytes.extend_from_slice(buf);
+        let len = buf.len();
+        self.inner.consume(len);
+
+        let mut units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| match self.big_endian {
+                true => u16::from_be_bytes([pair[0], pair[1]]),
+                false => u16::from_le_bytes([pair[0], pair[1]]),
+            })
+            .collect();
+        let mut kept = bytes.len() - bytes.len() % 2;
+        // a high surrogate waits for its low surrogate, likely in the next buffer
+        if !eof
+            && units
+                .last()
+                .is_some_and(|unit| (0xD800..0xDC00).contains(unit))
+        {
+            units.pop();
+            kept -= 2;
+        }
+        self.carried = bytes.split_off(kept);
+        if eof && !self.carried.is_empty() {
+            return Err(invalid("UTF-16 ends in the middle of a code unit"));
+        }
+
+        self.encoded.clear();
+        self.offset = 0;
+        for decoded in char::decode_utf16(units) {
+            let c = decoded.map_err(|_| invalid("not valid UTF-16, with an unpaired surrogate"))?;
+            let mut utf8 = [0; 4];
+            self.encoded
+                .extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+        }
+        Ok(true)
+    }
+}
+
+impl<R> Read for Utf16Reader<R>
+where
+    R: BufRead,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.offset == self.encoded.len() {
+            if !self.fill()? {
+                return Ok(0);
+            }
+        }
+        let encoded = &self.encoded[self.offset..];
+        let len = encoded.len().min(buf.len());
+        buf[..len].copy_from_slice(&encoded[..len]);
+        self.offset += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(s: &str, big_endian: bool, bom: bool) -> Vec<u8> {
+        let units = bom.then_some(0xFEFF).into_iter().chain(s.encode_utf16());
+        units
+            .flat_map(|unit| match big_endian {
+                true => unit.to_be_bytes(),
+                false => unit.to_le_bytes(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parametrized_sniff() {
+        let test_data = [
+            (b"abc  \n".to_vec(), Sniffed::Utf8),
+            (Vec::new(), Sniffed::Utf8),
+            (b"\xEF\xBB\xBFabc\n".to_vec(), Sniffed::Utf8),
+            (utf16("abc \n", false, true), Sniffed::Utf16Le { bom: true }),
+            (utf16("abc \n", true, true), Sniffed::Utf16Be { bom: true }),
+            (
+                utf16("abc \n", false, false),
+                Sniffed::Utf16Le { bom: false },
+            ),
+            (
+                utf16("abc \n", true, false),
+                Sniffed::Utf16Be { bom: false },
+            ),
+            (b"\0\0\0\0abcd\0\0".to_vec(), Sniffed::Binary),
+            (b"a\0bcdefghijklmnopqrstuvwxyz".to_vec(), Sniffed::Utf8),
+        ];
+        for (input, expected) in test_data {
+            assert_eq!(expected, sniff(&input), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn parametrized_decoded() {
+        let text = "abc  \n\u{1F600} d\u{e9}f\t\n";
+        let test_data = [
+            (text.as_bytes().to_vec(), Encoding::Utf8, Some(text)),
+            (utf16(text, false, true), Encoding::Utf8, None),
+            (utf16(text, false, true), Encoding::Auto, Some(text)),
+            (utf16(text, true, true), Encoding::Auto, Some(text)),
+            (utf16(text, false, false), Encoding::Auto, Some(text)),
+            (utf16(text, true, false), Encoding::Auto, Some(text)),
+            (b"\0\0\0\0abcd\0\0".to_vec(), Encoding::Auto, None),
+            // cut in the middle of a code unit
+            (utf16(text, false, true)[..7].to_vec(), Encoding::Auto, None),
+        ];
+        for (input, encoding, expected) in test_data {
+            // buffers small enough to cut code units and surrogate pairs in half
+            for capacity in [1, 3, 4, 4096] {
+                let reader = BufReader::with_capacity(capacity, &input[..]);
+                let output = decoded(reader, encoding).and_then(|mut reader| {
+                    let mut output = String::new();
+                    reader.read_to_string(&mut output).map(|_| output)
+                });
+                assert_eq!(expected, output.ok().as_deref(), "{:?} {}", input, capacity);
+            }
+        }
+    }
+}
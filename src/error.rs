@@ -0,0 +1,98 @@
+//! Classification of the errors that can happen while trimming, for reports and remediation.
+
+use std::io;
+
+/// Message of the error reported for a file that cannot be trimmed in-place, because it is on a
+/// read-only filesystem.
+pub const READ_ONLY_MESSAGE: &str = "read-only filesystem; use --out-dir or remount";
+
+/// Kinds of errors that `error_kind` tells apart; errors of any other kind are all `io`.
+pub const KNOWN_KINDS: &[io::ErrorKind] = &[
+    io::ErrorKind::ReadOnlyFilesystem,
+    io::ErrorKind::NotFound,
+    io::ErrorKind::PermissionDenied,
+    io::ErrorKind::IsADirectory,
+    io::ErrorKind::InvalidData,
+    io::ErrorKind::StorageFull,
+    io::ErrorKind::StaleNetworkFileHandle,
+];
+
+/// `EIO`, which has no `io::ErrorKind` of its own.
+#[cfg(unix)]
+const EIO: i32 = 5;
+
+/// # Returns
+///
+/// A stable, machine-readable name for the kind of `err`, e.g. for JSON reports.
+pub fn error_kind(err: &io::Error) -> &'static str {
+    match err.kind() {
+        io::ErrorKind::ReadOnlyFilesystem => "read-only-filesystem",
+        io::ErrorKind::NotFound => "not-found",
+        io::ErrorKind::PermissionDenied => "permission-denied",
+        io::ErrorKind::IsADirectory => "is-a-directory",
+        io::ErrorKind::InvalidData => "invalid-data",
+        io::ErrorKind::StorageFull => "storage-full",
+        io::ErrorKind::StaleNetworkFileHandle => "stale-file-handle",
+        _ => "io",
+    }
+}
+
+/// # Returns
+///
+/// `true` if `err` may go away by itself on a network filesystem, e.g. a stale file handle after
+/// the server restarted, `false` otherwise.
+pub fn is_transient(err: &io::Error) -> bool {
+    #[cfg(unix)]
+    let eio = err.raw_os_error() == Some(EIO);
+    #[cfg(not(unix))]
+    let eio = false;
+
+    err.kind() == io::ErrorKind::StaleNetworkFileHandle || eio
+}
+
+/// # Returns
+///
+/// `err`, with a message that says how to work around it if it is due to a read-only filesystem,
+/// rather than whatever the failing operation reported.
+pub fn with_remediation(err: io::Error) -> io::Error {
+    match err.kind() {
+        io::ErrorKind::ReadOnlyFilesystem => {
+            io::Error::new(io::ErrorKind::ReadOnlyFilesystem, READ_ONLY_MESSAGE)
+        }
+        _ => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_only_filesystem() {
+        #[cfg(unix)]
+        let err = io::Error::from_raw_os_error(30); // EROFS
+        #[cfg(not(unix))]
+        let err = io::Error::from(io::ErrorKind::ReadOnlyFilesystem);
+
+        let err = with_remediation(err);
+        assert_eq!("read-only-filesystem", error_kind(&err));
+        assert_eq!(READ_ONLY_MESSAGE, err.to_string());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn transient() {
+        assert!(is_transient(&io::Error::from_raw_os_error(EIO)));
+        assert!(is_transient(&io::Error::from(
+            io::ErrorKind::StaleNetworkFileHandle
+        )));
+        assert!(!is_transient(&io::Error::from(io::ErrorKind::NotFound)));
+    }
+
+    #[test]
+    fn other_errors_untouched() {
+        let err = with_remediation(io::Error::new(io::ErrorKind::NotFound, "gone"));
+        assert_eq!("not-found", error_kind(&err));
+        assert_eq!("gone", err.to_string());
+    }
+}
@@ -0,0 +1,223 @@
+//! External commands that trimmed content is piped through before it is written, like git's
+//! clean filters, so that custom normalizations can be bolted on without forking trim.
+
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use std::process::Stdio;
+use std::str::FromStr;
+use std::thread::sleep;
+use std::thread::spawn;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How often a running filter is checked on, for its timeout.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A command that the trimmed content of each file is piped through, as a whole, before it is
+/// written; see `TrimOptions::filter`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Filter {
+    /// run by the shell, `sh -c` or `cmd /C`, with the trimmed content on its stdin, and the path
+    /// of the file in `TRIM_PATH`; what it writes to stdout is written instead
+    pub command: String,
+    /// the command is killed, and counts as failed, once it runs this long; it may run for as long
+    /// as it takes if `None`
+    pub timeout: Option<Duration>,
+    /// what becomes of a file the command fails on
+    pub on_failure: FilterFailure,
+}
+
+/// What becomes of a file that `Filter::command` fails on, by exiting with an error, or running
+/// past its timeout.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FilterFailure {
+    /// the file fails, and is left untouched
+    #[default]
+    Error,
+    /// the file is skipped, left untouched, and warned of; see `SkipReason::FilterFailed`
+    Skip,
+    /// the file is written as trimmed, without the filter, and warned of; see
+    /// `Warning::FilterFailed`
+    Unfiltered,
+}
+
+impl FilterFailure {
+    /// Values accepted by `FromStr`, in the order they should be listed to the user.
+    pub const VARIANTS: &'static [&'static str] = &["error", "skip", "unfiltered"];
+}
+
+impl FromStr for FilterFailure {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(FilterFailure::Error),
+            "skip" => Ok(FilterFailure::Skip),
+            "unfiltered" => Ok(FilterFailure::Unfiltered),
+            _ => Err(format!(
+                "expected one of {:?}, got {:?}",
+                FilterFailure::VARIANTS,
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FilterFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FilterFailure::Error => write!(f, "error"),
+            FilterFailure::Skip => write!(f, "skip"),
+            FilterFailure::Unfiltered => write!(f, "unfiltered"),
+        }
+    }
+}
+
+impl Filter {
+    /// # Parameters
+    ///
+    /// 1. `path` -- the file that `content` is the trimmed content of, for the command to see
+    /// 1. `content` -- what to pipe through the command
+    ///
+    /// # Returns
+    ///
+    /// What the command wrote to stdout, or an error if it could not be run, exited with an
+    /// error, or ran past `timeout`; `TimedOut` for the latter.
+    pub fn run(&self, path: &Path, content: &[u8]) -> io::Result<Vec<u8>> {
+        let mut child = shell(&self.command)
+            .env("TRIM_PATH", path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // written and read on their own threads, so that neither pipe fills up and blocks
+        let mut stdin = child.stdin.take();
+        let content = content.to_vec();
+        let writer = spawn(move || match &mut stdin {
+            // a command that stops reading early is free to do so
+            Some(stdin) => match stdin.write_all(&content) {
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+                result => result,
+            },
+            None => Ok(()),
+        });
+        let drain = |pipe: Option<Box<dyn Read + Send>>| {
+            spawn(move || {
+                let mut out = Vec::new();
+                if let Some(mut pipe) = pipe {
+                    pipe.read_to_end(&mut out)?;
+                }
+                Ok::<_, io::Error>(out)
+            })
+        };
+        let stdout = drain(child.stdout.take().map(|pipe| Box::new(pipe) as _));
+        let stderr = drain(child.stderr.take().map(|pipe| Box::new(pipe) as _));
+
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if self
+                .timeout
+                .is_some_and(|timeout| started.elapsed() >= timeout)
+            {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("`{}` timed out", self.command),
+                ));
+            }
+            sleep(POLL_INTERVAL);
+        };
+        joined(writer)?;
+        let stdout = joined(stdout)?;
+        let stderr = joined(stderr)?;
+
+        match status.success() {
+            true => Ok(stdout),
+            false => Err(io::Error::other(format!(
+                "`{}` failed with {}: {}",
+                self.command,
+                status,
+                String::from_utf8_lossy(&stderr).trim_end()
+            ))),
+        }
+    }
+}
+
+/// # Returns
+///
+/// What the thread of `handle` returned, once it is done.
+fn joined<T>(handle: JoinHandle<io::Result<T>>) -> io::Result<T> {
+    handle
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("a thread piping to the filter panicked")))
+}
+
+/// # Returns
+///
+/// A command that runs `command` with the shell of the platform.
+fn shell(command: &str) -> Command {
+    let (program, flag) = match cfg!(windows) {
+        true => ("cmd", "/C"),
+        false => ("sh", "-c"),
+    };
+    let mut shell = Command::new(program);
+    shell.arg(flag).arg(command);
+    shell
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parametrized_run() {
+        let test_data = [
+            ("tr a-z A-Z", "abc\ndef\n", Some("ABC\nDEF\n")),
+            ("cat", "", Some("")),
+            ("printf %s \"$TRIM_PATH\"", "abc\n", Some("dir/file.txt")),
+            ("head -c 1", "abc\n", Some("a")),
+            ("exit 3", "abc\n", None),
+            ("no-such-command-of-trim", "abc\n", None),
+        ];
+        for (command, input, expected) in test_data {
+            let filter = Filter {
+                command: command.to_string(),
+                timeout: Some(Duration::from_secs(60)),
+                on_failure: FilterFailure::Error,
+            };
+            let output = filter.run(Path::new("dir/file.txt"), input.as_bytes()).ok();
+            assert_eq!(
+                expected.map(str::as_bytes),
+                output.as_deref(),
+                "{}",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn timeout() {
+        let filter = Filter {
+            command: "sleep 10".to_string(),
+            timeout: Some(Duration::from_millis(100)),
+            on_failure: FilterFailure::Error,
+        };
+        let started = Instant::now();
+        let err = filter.run(Path::new("file.txt"), b"abc\n").unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
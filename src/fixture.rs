@@ -0,0 +1,194 @@
+//! Fixtures of the trimmer: an input, the options it is trimmed with, and what that should give;
+//! for the crate's own tests, and for packagers checking that a build behaves on their platform.
+//!
+//! A fixture is a directory of its own, named after it, holding:
+//!
+//! 1. `input` -- the content before trimming, byte for byte
+//! 1. `options.json` -- the `TrimOptions` it is trimmed with, as far as they differ from
+//!    `TrimOptions::default()`; fields left out are the default
+//! 1. `expected` -- the trimmed content, byte for byte
+//! 1. `stats.json` -- `bytes_saved` and `eof_blank_lines` of the `TrimResult`
+
+use serde_json::json;
+use std::fs::create_dir_all;
+use std::fs::read;
+use std::fs::read_dir;
+use std::fs::write;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use crate::options::TrimOptions;
+use crate::trim::trim_slice_to;
+use crate::trim::TrimResult;
+
+/// A fixture, as read by `read_fixtures`.
+#[derive(Clone, Debug)]
+pub struct Fixture {
+    /// name of the directory of the fixture
+    pub name: String,
+    pub input: Vec<u8>,
+    pub options: TrimOptions,
+    /// `input`, trimmed with `options`
+    pub expected: Vec<u8>,
+    /// see `TrimResult::bytes_saved`
+    pub bytes_saved: i32,
+    /// see `TrimResult::eof_blank_lines`
+    pub eof_blank_lines: usize,
+}
+
+/// Trim `input` with `options`, and write the fixture of it to `dir/name`, replacing any fixture
+/// of the same name.
+///
+/// # Returns
+///
+/// The result of trimming `input`.
+pub fn write_fixture(
+    dir: &Path,
+    name: &str,
+    input: &[u8],
+    options: &TrimOptions,
+) -> io::Result<TrimResult> {
+    let mut expected = Vec::new();
+    let trim_result = trim_slice_to(input, &mut expected, &mut None::<File>, options)?;
+
+    let fixture_dir = dir.join(name);
+    create_dir_all(&fixture_dir)?;
+    let stats = json!({
+        "bytes_saved": trim_result.bytes_saved,
+        "eof_blank_lines": trim_result.eof_blank_lines,
+    });
+    write(fixture_dir.join("input"), input)?;
+    write(fixture_dir.join("options.json"), pretty(&changed(options))?)?;
+    write(fixture_dir.join("expected"), &expected)?;
+    write(fixture_dir.join("stats.json"), pretty(&stats)?)?;
+    Ok(trim_result)
+}
+
+/// # Returns
+///
+/// Every fixture under `dir`, sorted by name; an error if any of them is incomplete or invalid.
+pub fn read_fixtures(dir: &Path) -> io::Result<Vec<Fixture>> {
+    let mut fixtures = Vec::new();
+    for entry in read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let fixture_dir = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let invalid = |message: String| {
+            let message = format!("invalid fixture {:?}: {}", name, message);
+            io::Error::new(io::ErrorKind::InvalidData, message)
+        };
+
+        let options = serde_json::from_slice(&read(fixture_dir.join("options.json"))?)
+            .map_err(|err| invalid(err.to_string()))?;
+        let stats: serde_json::Value =
+            serde_json::from_slice(&read(fixture_dir.join("stats.json"))?)
+                .map_err(|err| invalid(err.to_string()))?;
+        let stat = |key: &str| stats.get(key).and_then(serde_json::Value::as_i64);
+        let (bytes_saved, eof_blank_lines) = match (stat("bytes_saved"), stat("eof_blank_lines")) {
+            (Some(bytes_saved), Some(eof_blank_lines)) => (bytes_saved, eof_blank_lines),
+            _ => {
+                let message = "`stats.json` needs `bytes_saved` and `eof_blank_lines`";
+                return Err(invalid(message.to_string()));
+            }
+        };
+        fixtures.push(Fixture {
+            input: read(fixture_dir.join("input"))?,
+            options,
+            expected: read(fixture_dir.join("expected"))?,
+            bytes_saved: bytes_saved as i32,
+            eof_blank_lines: eof_blank_lines as usize,
+            name,
+        });
+    }
+    fixtures.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(fixtures)
+}
+
+/// # Returns
+///
+/// The fields of `options` that differ from `TrimOptions::default()`, as JSON; the others are
+/// left out, so that a fixture is not rewritten each time an option is added.
+fn changed(options: &TrimOptions) -> serde_json::Value {
+    let defaults = json!(TrimOptions::default());
+    match json!(options) {
+        serde_json::Value::Object(fields) => fields
+            .into_iter()
+            .filter(|(key, value)| defaults.get(key) != Some(value))
+            .collect(),
+        value => value,
+    }
+}
+
+/// # Returns
+///
+/// `value` as pretty-printed JSON, ending with a newline, so that fixtures diff well.
+fn pretty(value: &serde_json::Value) -> io::Result<String> {
+    let mut pretty = serde_json::to_string_pretty(value)?;
+    pretty.push('\n');
+    Ok(pretty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    #[test]
+    fn round_trip() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let dir = temp_dir().join(prefix);
+        let options = TrimOptions {
+            min_run: 2,
+            ..TrimOptions::default()
+        };
+        let trim_result = write_fixture(&dir, "b", b"a \nb  \n\n", &options).unwrap();
+        write_fixture(&dir, "a", b"", &TrimOptions::default()).unwrap();
+
+        let fixtures = read_fixtures(&dir).unwrap();
+        let names: Vec<_> = fixtures.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(vec!["a", "b"], names);
+        let fixture = &fixtures[1];
+        assert_eq!(b"a \nb  \n\n", &fixture.input[..]);
+        assert_eq!(b"a \nb\n", &fixture.expected[..]);
+        assert_eq!(2, fixture.options.min_run);
+        let options = read(dir.join("b/options.json")).unwrap();
+        assert_eq!(
+            "{\n  \"min_run\": 2\n}\n",
+            String::from_utf8(options).unwrap()
+        );
+        assert_eq!(trim_result.bytes_saved, fixture.bytes_saved);
+        assert_eq!(1, fixture.eof_blank_lines);
+    }
+
+    #[test]
+    fn fixtures_hold() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let fixtures = read_fixtures(&dir).unwrap();
+        assert!(!fixtures.is_empty());
+        for fixture in fixtures {
+            let mut result = Vec::new();
+            let trim_result = trim_slice_to(
+                &fixture.input,
+                &mut result,
+                &mut None::<File>,
+                &fixture.options,
+            )
+            .unwrap();
+            assert_eq!(fixture.expected, result, "{}", fixture.name);
+            assert_eq!(
+                fixture.bytes_saved, trim_result.bytes_saved,
+                "{}",
+                fixture.name
+            );
+            assert_eq!(
+                fixture.eof_blank_lines, trim_result.eof_blank_lines,
+                "{}",
+                fixture.name
+            );
+        }
+    }
+}
@@ -0,0 +1,437 @@
+//! Properties every trim must have, whatever the input; for self-tests of an installed build, and
+//! for fuzzers.
+
+use std::fmt;
+use std::fs::File;
+use std::io::Cursor;
+use std::str;
+
+use crate::compare::compare_content;
+use crate::compare::Comparison;
+use crate::diff::git_diff;
+use crate::options::Engine;
+use crate::options::TrimOptions;
+use crate::options::Whitespace;
+use crate::patch::parse_patch;
+use crate::trim::trim_reader_to;
+use crate::trim::trim_slice_to;
+
+/// Most that `TrimResult::bytes_saved` may be off by, as it counts bytes saved ish: as if the final
+/// `\r\n` were suppressed, plus the `\n` that it imagines an unterminated input to end with.
+const BYTES_SAVED_SLACK: i64 = 3;
+
+/// Pieces that random inputs are made of; mostly whitespace and linebreaks of all kinds.
+const PIECES: &[&str] = &[
+    "a",
+    "b",
+    "é",
+    "\u{1f600}",
+    " ",
+    " ",
+    "\t",
+    "\r",
+    "\n",
+    "\n",
+    "\r\n",
+    "\u{a0}",
+    "\u{3000}",
+    "\x0b",
+    "\x0c",
+];
+
+/// A property that a trim did not have.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// trimming the trimmed content again changed it
+    NotIdempotent,
+    /// something other than whitespace was removed or added, first at this byte of the input
+    NotWhitespace(usize),
+    /// `TrimResult::bytes_saved` is off by more than it may be from the actual difference in
+    /// length
+    BytesSaved { reported: i32, actual: i64 },
+    /// trimming with this engine, instead of that of the options, gives a different content
+    EnginesDiffer(Engine),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::NotIdempotent => write!(f, "trimming again changes the content"),
+            Violation::NotWhitespace(offset) => {
+                write!(f, "changes more than whitespace at byte {}", offset)
+            }
+            Violation::BytesSaved { reported, actual } => {
+                write!(f, "reports {} bytes saved, but saves {}", reported, actual)
+            }
+            Violation::EnginesDiffer(engine) => {
+                write!(f, "trims differently with `--engine {}`", engine)
+            }
+        }
+    }
+}
+
+/// Trim `input` with `options`, and check that the trim:
+///
+/// 1. is idempotent; trimming the trimmed content again changes nothing
+/// 1. removes nothing but whitespace and linebreaks, and adds nothing but linebreaks at the end
+/// 1. accounts for what it saved; see `TrimResult::bytes_saved`
+/// 1. gives the same content with every other `Engine`
+///
+/// `options` must not have `rules`, `strip_ansi` or `control_chars`, which may change more than
+/// whitespace by design.
+///
+/// # Returns
+///
+/// The first property the trim does not have, if any. An input that cannot be trimmed at all,
+/// e.g. one that is not UTF-8, has them all.
+pub fn check_invariants(input: &[u8], options: &TrimOptions) -> Result<(), Violation> {
+    let mut trimmed = Vec::new();
+    let trim_result = match trim_slice_to(input, &mut trimmed, &mut None::<File>, options) {
+        Ok(trim_result) => trim_result,
+        Err(_) => return Ok(()),
+    };
+    check_trimmed(input, &trimmed, trim_result.bytes_saved, options)?;
+
+    for &engine in Engine::ALL
+        .iter()
+        .filter(|&&engine| engine != options.engine)
+    {
+        let options = TrimOptions {
+            engine,
+            ..options.clone()
+        };
+        let mut other = Vec::new();
+        let same = trim_slice_to(input, &mut other, &mut None::<File>, &options).is_ok();
+        if !same || other != trimmed {
+            return Err(Violation::EnginesDiffer(engine));
+        }
+    }
+    Ok(())
+}
+
+/// Like `check_invariants`, for a trim that is already done, e.g. of a file trimmed in-place.
+///
+/// # Parameters
+///
+/// 1. `input` -- the content before trimming
+/// 1. `trimmed` -- `input`, trimmed
+/// 1. `bytes_saved` -- what trimming `input` reported, see `TrimResult::bytes_saved`
+/// 1. `options` -- what `input` was trimmed with
+///
+/// # Returns
+///
+/// The first property the trim does not have, if any.
+pub fn check_trimmed(
+    input: &[u8],
+    trimmed: &[u8],
+    bytes_saved: i32,
+    options: &TrimOptions,
+) -> Result<(), Violation> {
+    let mut again = Vec::new();
+    if trim_slice_to(trimmed, &mut again, &mut None::<File>, options).is_err() {
+        return Err(Violation::NotIdempotent);
+    }
+    check_trim(input, trimmed, &again, bytes_saved, options)
+}
+
+/// Like `check_invariants`, but panics with the first property the trim does not have; for
+/// fuzzers, which report panics.
+pub fn assert_invariants(input: &[u8], options: &TrimOptions) {
+    if let Err(violation) = check_invariants(input, options) {
+        panic!(
+            "{}, trimming {:?}",
+            violation,
+            String::from_utf8_lossy(input)
+        );
+    }
+}
+
+/// Entry point for fuzzers, e.g. `cargo fuzz` targets; trims `data` with `options` in memory, the
+/// way files and pipes are trimmed, and panics unless:
+///
+/// 1. trimming it whole and streaming it from a reader, with findings reported, agree
+/// 1. the trim has the properties of `check_invariants`
+/// 1. the trim only changes ASCII whitespace, see `compare_content`, if it only trims ASCII
+///    whitespace
+/// 1. the patch of the trim applies to `data`, giving the trimmed content
+///
+/// Deterministic, and touches no file. `options` must not have `rules`, `strip_ansi` or
+/// `control_chars`; see `check_invariants`.
+#[doc(hidden)]
+pub fn fuzz_trim_bytes(data: &[u8], options: &TrimOptions) {
+    let mut trimmed = Vec::new();
+    let trim_result = match trim_slice_to(data, &mut trimmed, &mut None::<File>, options) {
+        Ok(trim_result) => trim_result,
+        // e.g. not UTF-8, which the reader must refuse just the same
+        Err(_) => {
+            let streamed =
+                trim_reader_to(Cursor::new(data), Vec::new(), &mut None::<File>, options);
+            assert!(streamed.is_err(), "only streaming trims {:?}", data);
+            return;
+        }
+    };
+
+    let mut streamed = Vec::new();
+    let mut findings = Some(Vec::new());
+    let streamed_result = trim_reader_to(Cursor::new(data), &mut streamed, &mut findings, options)
+        .expect("only trimming whole succeeds");
+    assert_eq!(trimmed, streamed, "streaming trims differently");
+    assert_eq!(trim_result.bytes_saved, streamed_result.bytes_saved);
+
+    assert_invariants(data, options);
+    // what `compare_content` considers whitespace is fixed to ASCII
+    if options.whitespace == Whitespace::Ascii {
+        match compare_content(data, &trimmed) {
+            Ok(Comparison::Differs(line)) => {
+                panic!("changes more than whitespace at line {}", line)
+            }
+            Ok(_) => (),
+            Err(err) => panic!("cannot compare to the trimmed content: {}", err),
+        }
+    }
+
+    let mut patch = Vec::new();
+    git_diff(data, &trimmed, "fuzz", &mut patch).expect("writing to memory fails");
+    let patched = match parse_patch(&patch).expect("the patch of the trim is invalid") {
+        file_patches if file_patches.is_empty() => data.to_vec(),
+        file_patches => file_patches[0]
+            .apply(data)
+            .expect("the patch of the trim fails"),
+    };
+    assert_eq!(
+        trimmed, patched,
+        "the patch of the trim applies differently"
+    );
+}
+
+/// Like `check_invariants`, for a trim that is already done.
+///
+/// # Parameters
+///
+/// 1. `input` -- the content before trimming
+/// 1. `trimmed` -- `input`, trimmed
+/// 1. `again` -- `trimmed`, trimmed again
+/// 1. `bytes_saved` -- what trimming `input` reported, see `TrimResult::bytes_saved`
+/// 1. `options` -- what `input` was trimmed with
+fn check_trim(
+    input: &[u8],
+    trimmed: &[u8],
+    again: &[u8],
+    bytes_saved: i32,
+    options: &TrimOptions,
+) -> Result<(), Violation> {
+    if again != trimmed {
+        return Err(Violation::NotIdempotent);
+    }
+    check_deletions(input, trimmed, options)?;
+
+    // linebreaks that are rewritten from `\r\n` to `\n` are not counted as saved
+    let (input_text, trimmed_text) = match (str::from_utf8(input), str::from_utf8(trimmed)) {
+        (Ok(input_text), Ok(trimmed_text)) => (input_text, trimmed_text),
+        _ => return Err(Violation::NotWhitespace(0)),
+    };
+    let len = |text: &str| match options.keep_crlf {
+        true => text.len() as i64,
+        false => text.len() as i64 - text.matches("\r\n").count() as i64,
+    };
+    let actual = len(input_text) - len(trimmed_text);
+    match (bytes_saved as i64 - actual).abs() <= BYTES_SAVED_SLACK {
+        true => Ok(()),
+        false => Err(Violation::BytesSaved {
+            reported: bytes_saved,
+            actual,
+        }),
+    }
+}
+
+/// Check that `trimmed` is `input` with nothing but whitespace and linebreaks deleted from the
+/// ends of lines, or records, and nothing but linebreaks added at the end; the cheapest of the
+/// properties of `check_invariants`, e.g. to check every trim before it is written.
+///
+/// # Returns
+///
+/// `Violation::NotWhitespace` at the first byte of `input` that is not accounted for, if any.
+pub fn check_deletions(
+    input: &[u8],
+    trimmed: &[u8],
+    options: &TrimOptions,
+) -> Result<(), Violation> {
+    let (input_text, trimmed_text) = match (str::from_utf8(input), str::from_utf8(trimmed)) {
+        (Ok(input_text), Ok(trimmed_text)) => (input_text, trimmed_text),
+        _ => return Err(Violation::NotWhitespace(0)),
+    };
+    // records end with the separator, if there is one, rather than `\n`
+    let sep = options.separator() as char;
+    let removable = |c: char| c == sep || options.whitespace.is_whitespace(c);
+    let trailing = |rest: &str| {
+        let line = rest.split(sep).next().unwrap_or("");
+        line.chars()
+            .all(|c| c == '\r' || options.whitespace.is_whitespace(c))
+    };
+    let mut kept = trimmed_text.chars().peekable();
+    for (offset, c) in input_text.char_indices() {
+        match kept.peek() {
+            Some(&k) if k == c => {
+                kept.next();
+            }
+            // whitespace may only go at the end of a line, not between words
+            _ if removable(c) && trailing(&input_text[offset..]) => {}
+            _ => return Err(Violation::NotWhitespace(offset)),
+        }
+    }
+    match kept.all(|c| c == sep || c == '\r') {
+        true => Ok(()),
+        false => Err(Violation::NotWhitespace(input.len())),
+    }
+}
+
+/// # Returns
+///
+/// A random input of `pieces` pieces, most of them whitespace and linebreaks of all kinds, e.g.
+/// for `check_invariants`; the same for the same `seed`.
+pub fn random_input(seed: u64, pieces: usize) -> Vec<u8> {
+    // xorshift64*, which is plenty for test inputs; its state must not be 0
+    let mut state = seed | 1;
+    let mut next = || {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    };
+    (0..pieces)
+        .flat_map(|_| PIECES[(next() % PIECES.len() as u64) as usize].bytes())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::builtin;
+
+    #[test]
+    fn random_inputs_hold() {
+        let options = [
+            TrimOptions::default(),
+            TrimOptions {
+                suppress_newline: true,
+                whitespace: Whitespace::Unicode,
+                ..TrimOptions::default()
+            },
+            TrimOptions {
+                keep_crlf: true,
+                keep_form_feed: true,
+                final_newlines: Some(2),
+                ..TrimOptions::default()
+            },
+        ];
+        for seed in 0..500 {
+            let input = random_input(seed, seed as usize % 40);
+            for options in &options {
+                let result = check_invariants(&input, options);
+                assert_eq!(Ok(()), result, "{:?}", String::from_utf8_lossy(&input));
+            }
+        }
+        assert_eq!(random_input(7, 20), random_input(7, 20));
+    }
+
+    #[test]
+    fn fuzz_trim_bytes_holds() {
+        let options = [
+            TrimOptions::default(),
+            TrimOptions {
+                keep_crlf: true,
+                suppress_newline: true,
+                whitespace: Whitespace::Ascii,
+                ..TrimOptions::default()
+            },
+        ];
+        for seed in 0..200 {
+            let input = random_input(seed, seed as usize % 40);
+            for options in &options {
+                fuzz_trim_bytes(&input, options);
+            }
+        }
+        fuzz_trim_bytes(b"a \xff\n", &TrimOptions::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "changes more than whitespace")]
+    fn assert_invariants_panics() {
+        let options = TrimOptions {
+            rules: vec![builtin("collapse-comment-space").unwrap()],
+            ..TrimOptions::default()
+        };
+        assert_invariants(b"abc  // def\n", &options);
+    }
+
+    #[test]
+    fn parametrized_check_deletions() {
+        let record_sep = TrimOptions {
+            record_sep: Some(b';'),
+            ..TrimOptions::default()
+        };
+        let test_data = [
+            ("a \r\nb\t\n", "a\nb\n", TrimOptions::default(), Ok(())),
+            ("", "\n", TrimOptions::default(), Ok(())),
+            (
+                "a b\n",
+                "ab\n",
+                TrimOptions::default(),
+                Err(Violation::NotWhitespace(1)),
+            ),
+            (
+                "a\n",
+                "b\n",
+                TrimOptions::default(),
+                Err(Violation::NotWhitespace(0)),
+            ),
+            (
+                "a\n",
+                "a\nb",
+                TrimOptions::default(),
+                Err(Violation::NotWhitespace(2)),
+            ),
+            ("a ;b ;", "a;b;", record_sep.clone(), Ok(())),
+            (
+                "a \nb;",
+                "a\nb;",
+                record_sep,
+                Err(Violation::NotWhitespace(1)),
+            ),
+        ];
+        for (input, trimmed, options, expected) in test_data {
+            let result = check_deletions(input.as_bytes(), trimmed.as_bytes(), &options);
+            assert_eq!(expected, result, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn parametrized_broken_trims_are_caught() {
+        let test_data = vec![
+            ("a \n", "a \n", "a\n", 1, Violation::NotIdempotent),
+            ("a b\n", "ab\n", "ab\n", 1, Violation::NotWhitespace(1)),
+            ("ab \n", "a\n", "a\n", 2, Violation::NotWhitespace(1)),
+            ("a\n", "a\nb\n", "a\nb\n", 0, Violation::NotWhitespace(2)),
+            (
+                "a    \n",
+                "a\n",
+                "a\n",
+                0,
+                Violation::BytesSaved {
+                    reported: 0,
+                    actual: 4,
+                },
+            ),
+        ];
+        for (input, trimmed, again, bytes_saved, expected) in test_data {
+            let (input, trimmed, again) = (input.as_bytes(), trimmed.as_bytes(), again.as_bytes());
+            let result = check_trim(input, trimmed, again, bytes_saved, &TrimOptions::default());
+            assert_eq!(Err(expected), result);
+        }
+        // a correct trim
+        assert_eq!(
+            Ok(()),
+            check_trim(b"a \n", b"a\n", b"a\n", 1, &TrimOptions::default())
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//! Telling what language a script without an extension is in, e.g. `bin/deploy`, from the
+//! interpreter in its shebang; so that what is selected by extension, such as `--ext py`,
+//! takes in the scripts of that language too. Languages are named by their usual extension.
+
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::path::Path;
+
+/// Most bytes read from the start of a file to find its shebang; Linux reads no more than this
+/// either, see `BINPRM_BUF_SIZE`.
+const SHEBANG_MAX_LEN: u64 = 256;
+
+/// Interpreters, without any version, and the language of their scripts.
+const INTERPRETERS: &[(&str, &str)] = &[
+    ("ash", "sh"),
+    ("awk", "awk"),
+    ("bash", "sh"),
+    ("dash", "sh"),
+    ("deno", "ts"),
+    ("fish", "fish"),
+    ("gawk", "awk"),
+    ("ksh", "sh"),
+    ("lua", "lua"),
+    ("node", "js"),
+    ("perl", "pl"),
+    ("php", "php"),
+    ("pwsh", "ps1"),
+    ("python", "py"),
+    ("ruby", "rb"),
+    ("sh", "sh"),
+    ("tclsh", "tcl"),
+    ("zsh", "zsh"),
+];
+
+/// # Returns
+///
+/// The language of a script whose first line is `line`, from the interpreter of its shebang,
+/// e.g. `py` for `#!/usr/bin/env python3`; `None` without a shebang, or for an interpreter that
+/// is not in `INTERPRETERS`.
+pub fn shebang_language(line: &str) -> Option<&'static str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    // `env` runs the interpreter after its own flags, e.g. `-S`, and variables
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+        program = program.rsplit('/').next()?;
+    }
+    // e.g. `python3.12` or `perl5`
+    let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    INTERPRETERS
+        .iter()
+        .find(|(interpreter, _)| *interpreter == program)
+        .map(|(_, language)| *language)
+}
+
+/// # Returns
+///
+/// The language of the script at `path`, from its shebang; see `shebang_language`. `None` if it
+/// cannot be read, or is not a script.
+pub fn script_language(path: &Path) -> Option<&'static str> {
+    let file = File::open(path).ok()?;
+    let mut line = Vec::new();
+    BufReader::new(file.take(SHEBANG_MAX_LEN))
+        .read_until(b'\n', &mut line)
+        .ok()?;
+    shebang_language(String::from_utf8_lossy(&line).trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::remove_file;
+    use std::fs::write;
+
+    #[test]
+    fn parametrized_shebang_language() {
+        let test_data = [
+            ("#!/bin/sh", Some("sh")),
+            ("#! /bin/bash -e", Some("sh")),
+            ("#!/usr/bin/env python3", Some("py")),
+            ("#!/usr/bin/python3.12 -u", Some("py")),
+            ("#!/usr/bin/env -S PYTHONUTF8=1 python -u", Some("py")),
+            ("#!/usr/local/bin/perl5", Some("pl")),
+            ("#!/usr/bin/env", None),
+            ("#!/usr/bin/make -f", None),
+            ("# python", None),
+            ("", None),
+        ];
+        for (line, expected) in test_data {
+            assert_eq!(expected, shebang_language(line), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn script_language_of_file() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let path = temp_dir().join(prefix);
+        write(&path, "#!/usr/bin/env ruby  \r\nputs 1\n").unwrap();
+        assert_eq!(Some("rb"), script_language(&path));
+        remove_file(&path).unwrap();
+        assert_eq!(None, script_language(&path));
+    }
+}
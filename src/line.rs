@@ -0,0 +1,614 @@
+//! Pure per-line trimming and its accounting, without any IO; only `core` and `alloc` are
+//! needed, so that it can be reused where `std` is not available, e.g. in log sanitizers on
+//! firmware. The streaming and file layers in `trim` sit on top of it, behind the `std` feature.
+
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+use core::str::FromStr;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Definition of whitespace used when trimming the end of a line.
+///
+/// The two differ on characters such as NBSP (`U+00A0`) and the ideographic space (`U+3000`),
+/// which are whitespace under `Unicode` but not under `Ascii`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Whitespace {
+    /// `' '`, `\t`, `\n`, `\x0C` and `\r`; see `char::is_ascii_whitespace`
+    Ascii,
+    /// anything with the Unicode `White_Space` property; see `char::is_whitespace`
+    ///
+    /// This is the default, to match `str::trim_end`.
+    #[default]
+    Unicode,
+}
+
+impl Whitespace {
+    /// Values accepted by `FromStr`, in the order they should be listed to the user.
+    pub const VARIANTS: &'static [&'static str] = &["ascii", "unicode"];
+
+    /// # Returns
+    ///
+    /// `true` if `c` is whitespace under this definition, `false` otherwise.
+    #[inline]
+    pub fn is_whitespace(self, c: char) -> bool {
+        match self {
+            Whitespace::Ascii => c.is_ascii_whitespace(),
+            Whitespace::Unicode => c.is_whitespace(),
+        }
+    }
+}
+
+impl FromStr for Whitespace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ascii" => Ok(Whitespace::Ascii),
+            "unicode" => Ok(Whitespace::Unicode),
+            _ => Err(format!(
+                "expected one of {:?}, got {:?}",
+                Whitespace::VARIANTS,
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Whitespace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Whitespace::Ascii => write!(f, "ascii"),
+            Whitespace::Unicode => write!(f, "unicode"),
+        }
+    }
+}
+
+/// How the trailing whitespace of a line is found; every engine finds the same, and only differs
+/// in speed, so that another can be tried, or fallen back to, on unusual data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Engine {
+    /// decodes the line backwards one character at a time; see `str::trim_end_matches`
+    #[default]
+    Chars,
+    /// scans the bytes of the line backwards, only decoding characters beyond ASCII
+    Bytes,
+    /// like `Bytes`, but skips runs of spaces and tabs 8 bytes at a time, within a `u64`
+    #[cfg(feature = "simd")]
+    Simd,
+}
+
+impl Engine {
+    /// Values accepted by `FromStr`, in the order they should be listed to the user.
+    #[cfg(not(feature = "simd"))]
+    pub const VARIANTS: &'static [&'static str] = &["chars", "bytes"];
+    /// Values accepted by `FromStr`, in the order they should be listed to the user.
+    #[cfg(feature = "simd")]
+    pub const VARIANTS: &'static [&'static str] = &["chars", "bytes", "simd"];
+
+    /// Every engine, in the order they are declared.
+    #[cfg(not(feature = "simd"))]
+    pub const ALL: &'static [Engine] = &[Engine::Chars, Engine::Bytes];
+    /// Every engine, in the order they are declared.
+    #[cfg(feature = "simd")]
+    pub const ALL: &'static [Engine] = &[Engine::Chars, Engine::Bytes, Engine::Simd];
+
+    /// # Returns
+    ///
+    /// `line` without any of its trailing `whitespace`.
+    pub fn trim_trailing(self, line: &str, whitespace: Whitespace) -> &str {
+        match self {
+            Engine::Chars => line.trim_end_matches(|c| whitespace.is_whitespace(c)),
+            Engine::Bytes => {
+                let bytes = line.as_bytes();
+                let mut end = bytes.len();
+                while end > 0 {
+                    let len = match bytes[end - 1] {
+                        byte if byte.is_ascii() => {
+                            (whitespace.is_whitespace(byte as char)).then_some(1)
+                        }
+                        // the last byte of a character beyond ASCII
+                        _ => line[..end]
+                            .chars()
+                            .next_back()
+                            .filter(|&c| whitespace.is_whitespace(c))
+                            .map(char::len_utf8),
+                    };
+                    match len {
+                        Some(len) => end -= len,
+                        None => break,
+                    }
+                }
+                &line[..end]
+            }
+            // spaces and tabs are whitespace under every definition, and ASCII, so that the rest
+            // of the run is found from a character boundary
+            #[cfg(feature = "simd")]
+            Engine::Simd => {
+                Engine::Bytes.trim_trailing(&line[..skip_blanks(line.as_bytes())], whitespace)
+            }
+        }
+    }
+}
+
+/// # Returns
+///
+/// Length of `bytes` without the trailing spaces and tabs that fill whole 8-byte words; those of
+/// the last, partial word are left in.
+#[cfg(feature = "simd")]
+fn skip_blanks(bytes: &[u8]) -> usize {
+    const SPACES: u64 = u64::from_ne_bytes([b' '; 8]);
+    const TABS: u64 = u64::from_ne_bytes([b'\t'; 8]);
+    const LOW: u64 = u64::from_ne_bytes([0x7f; 8]);
+    // the high bit of each byte that is 0, and only of those; no borrow crosses bytes
+    let zero_bytes = |word: u64| !(((word & LOW) + LOW) | word) & !LOW;
+
+    let mut end = bytes.len();
+    while end >= 8 {
+        let mut word = [0; 8];
+        word.copy_from_slice(&bytes[end - 8..end]);
+        let word = u64::from_ne_bytes(word);
+        match zero_bytes(word ^ SPACES) | zero_bytes(word ^ TABS) {
+            blanks if blanks == !LOW => end -= 8,
+            _ => break,
+        }
+    }
+    end
+}
+
+/// Find the first line of `buffer` that ends with `whitespace`, before its `\n` or `\r\n`,
+/// without trimming anything; to tell quickly whether a large buffer needs trimming at all. Line
+/// ends are found with `memchr`, which compares as many bytes at a time as the CPU can.
+///
+/// Only whether a line has trailing whitespace is considered, as with the default `TrimOptions`;
+/// e.g. `min_run` and `keep_form_feed` are not.
+///
+/// # Returns
+///
+/// Offset of the start of that line, if any. A line whose last character is not UTF-8 does not
+/// end with whitespace.
+#[cfg(feature = "simd")]
+pub fn find_trailing_whitespace(buffer: &[u8], whitespace: Whitespace) -> Option<usize> {
+    let ends_with_whitespace = |line: &[u8]| match line.last() {
+        None => false,
+        Some(&byte) if byte.is_ascii() => whitespace.is_whitespace(byte as char),
+        // the last byte of a character beyond ASCII, 4 bytes at most
+        Some(_) => {
+            let tail = &line[line.len().saturating_sub(4)..];
+            (0..tail.len())
+                .find_map(|start| core::str::from_utf8(&tail[start..]).ok())
+                .and_then(|tail| tail.chars().next_back())
+                .is_some_and(|c| whitespace.is_whitespace(c))
+        }
+    };
+
+    let mut start = 0;
+    let ends = memchr::memchr_iter(b'\n', buffer).chain(core::iter::once(buffer.len()));
+    for end in ends {
+        let line = match &buffer[start..end] {
+            [line @ .., b'\r'] if end < buffer.len() => line,
+            line => line,
+        };
+        if ends_with_whitespace(line) {
+            return Some(start);
+        }
+        start = end + 1;
+    }
+    None
+}
+
+impl FromStr for Engine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chars" => Ok(Engine::Chars),
+            "bytes" => Ok(Engine::Bytes),
+            #[cfg(feature = "simd")]
+            "simd" => Ok(Engine::Simd),
+            _ => Err(format!(
+                "expected one of {:?}, got {:?}",
+                Engine::VARIANTS,
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Engine::Chars => write!(f, "chars"),
+            Engine::Bytes => write!(f, "bytes"),
+            #[cfg(feature = "simd")]
+            Engine::Simd => write!(f, "simd"),
+        }
+    }
+}
+
+/// # Parameters
+///
+/// 1. `line` -- the line to trim, without its terminator
+/// 1. `whitespace` -- which characters are trimmed
+/// 1. `min_run` -- trailing runs of whitespace shorter than this many characters are left as is
+/// 1. `keep_form_feed` -- keep a single `\x0C` if the trailing whitespace had one
+///
+/// # Returns
+///
+/// `line` without its trailing whitespace; borrowed unless a `\x0C` had to be kept.
+pub fn trim_end(
+    line: &str,
+    whitespace: Whitespace,
+    min_run: usize,
+    keep_form_feed: bool,
+) -> Cow<'_, str> {
+    trim_end_with(line, whitespace, min_run, keep_form_feed, Engine::default())
+}
+
+/// Like `trim_end`, but finds the trailing whitespace with `engine`.
+pub fn trim_end_with(
+    line: &str,
+    whitespace: Whitespace,
+    min_run: usize,
+    keep_form_feed: bool,
+    engine: Engine,
+) -> Cow<'_, str> {
+    let trimmed = engine.trim_trailing(line, whitespace);
+    let removed = &line[trimmed.len()..];
+
+    // tolerated; pass the line through as is
+    if removed.chars().count() < min_run {
+        return Cow::Borrowed(line);
+    }
+
+    match keep_form_feed && removed.contains('\x0C') {
+        // already a lone page break
+        true if removed == "\x0C" => Cow::Borrowed(line),
+        // a page break; keep exactly one `\x0C`, but drop the whitespace around it
+        true => Cow::Owned(format!("{}\x0C", trimmed)),
+        false => Cow::Borrowed(trimmed),
+    }
+}
+
+/// # Returns
+///
+/// `line` without its terminal escape sequences, e.g. the colors of a captured console log:
+/// control sequences like `\x1b[31m`, strings like the `\x1b]0;title\x07` that sets a window
+/// title, and any other `\x1b` sequence; borrowed if there were none. The 8-bit `\u{9b}` is
+/// taken for `\x1b[`. A sequence cut short by the end of the line is dropped as far as it goes.
+pub fn strip_ansi(line: &str) -> Cow<'_, str> {
+    let is_escape = |c: char| c == '\x1b' || c == '\u{9b}';
+    if !line.contains(is_escape) {
+        return Cow::Borrowed(line);
+    }
+
+    let mut stripped = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find(is_escape) {
+        stripped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        rest = &rest[escape_len(rest.as_bytes())..];
+    }
+    stripped.push_str(rest);
+    Cow::Owned(stripped)
+}
+
+/// # Returns
+///
+/// Number of bytes in the escape sequence that `escape` starts with, which is at least its
+/// `\x1b`; never ends within a character, since every byte a sequence ends with is ASCII.
+fn escape_len(escape: &[u8]) -> usize {
+    let (introducer, rest) = match escape {
+        [0xC2, 0x9B, rest @ ..] => (b'[', rest),
+        [0x1B, introducer, rest @ ..] => (*introducer, rest),
+        _ => return 1,
+    };
+    let body = match introducer {
+        // parameters and intermediates, then a final byte
+        b'[' => {
+            let params = rest
+                .iter()
+                .take_while(|&&b| (0x20..=0x3F).contains(&b))
+                .count();
+            params + matches!(rest.get(params), Some(0x40..=0x7E)) as usize
+        }
+        // a string, up to `\x07` or the string terminator, 7-bit or 8-bit
+        b']' | b'P' | b'X' | b'^' | b'_' => (0..rest.len())
+            .find_map(|i| match &rest[i..] {
+                [0x07, ..] => Some(i + 1),
+                [0x1B, b'\\', ..] | [0xC2, 0x9C, ..] => Some(i + 2),
+                _ => None,
+            })
+            .unwrap_or(rest.len()),
+        // intermediates, then a final byte; `introducer` is the first of them
+        _ => {
+            let tail = &escape[1..];
+            let intermediates = tail
+                .iter()
+                .take_while(|&&b| (0x20..=0x2F).contains(&b))
+                .count();
+            let len = intermediates + matches!(tail.get(intermediates), Some(0x30..=0x7E)) as usize;
+            return 1 + len;
+        }
+    };
+    escape.len() - rest.len() + body
+}
+
+/// What becomes of control characters within a line, e.g. the `\x07` (bell) and `\x08`
+/// (backspace) that captured logs and copy-pastes carry. Control characters that are whitespace,
+/// e.g. tabs and `\r`, or `\u{85}` under `Whitespace::Unicode`, are left to trimming, whatever the
+/// policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ControlChars {
+    /// left as they are
+    #[default]
+    Keep,
+    /// dropped
+    Strip,
+    /// replaced by their escapes, e.g. `\u{7}`, so that they are visible; see
+    /// `char::escape_default`
+    Escape,
+}
+
+impl ControlChars {
+    /// Values accepted by `FromStr`, in the order they should be listed to the user.
+    pub const VARIANTS: &'static [&'static str] = &["keep", "strip", "escape"];
+
+    /// # Returns
+    ///
+    /// `line` with its control characters, other than `whitespace`, dealt with by this policy;
+    /// borrowed if there were none, or they are kept.
+    pub fn apply(self, line: &str, whitespace: Whitespace) -> Cow<'_, str> {
+        let is_junk = |c: char| c.is_control() && !whitespace.is_whitespace(c);
+        if self == ControlChars::Keep || !line.contains(is_junk) {
+            return Cow::Borrowed(line);
+        }
+
+        let mut cleaned = String::with_capacity(line.len());
+        for c in line.chars() {
+            match is_junk(c) {
+                false => cleaned.push(c),
+                true if self == ControlChars::Escape => cleaned.extend(c.escape_default()),
+                true => {}
+            }
+        }
+        Cow::Owned(cleaned)
+    }
+}
+
+impl FromStr for ControlChars {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(ControlChars::Keep),
+            "strip" => Ok(ControlChars::Strip),
+            "escape" => Ok(ControlChars::Escape),
+            _ => Err(format!(
+                "expected one of {:?}, got {:?}",
+                ControlChars::VARIANTS,
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ControlChars {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ControlChars::Keep => write!(f, "keep"),
+            ControlChars::Strip => write!(f, "strip"),
+            ControlChars::Escape => write!(f, "escape"),
+        }
+    }
+}
+
+/// Linebreaks held back at the end of an input, in case they are trailing; what is left of the
+/// input to account for once it ends.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Tail {
+    /// number of linebreaks held back, including the terminator of the last non-empty line
+    pub linebreaks: usize,
+    /// number of bytes in those linebreaks
+    pub len: usize,
+    /// number of bytes in the terminator of the last non-empty line, which is what each final
+    /// linebreak is written as
+    pub eol_len: usize,
+    /// whether the terminator of the last non-empty line is among `linebreaks`; `false` if there
+    /// was no non-empty line
+    pub eol_held: bool,
+}
+
+impl Tail {
+    /// # Returns
+    ///
+    /// Number of blank lines the input ended with.
+    #[inline]
+    pub fn blank_lines(self) -> usize {
+        match self.eol_held {
+            true => self.linebreaks.saturating_sub(1),
+            false => self.linebreaks,
+        }
+    }
+
+    /// # Parameters
+    ///
+    /// 1. `trimmed` -- number of bytes trimmed from the lines themselves
+    /// 1. `final_newlines` -- number of linebreaks the output ends with
+    ///
+    /// # Returns
+    ///
+    /// Number of bytes saved, ish; `abc\n` and `abc` are treated the same, as if the input
+    /// always ended with a linebreak.
+    pub fn bytes_saved(self, trimmed: usize, final_newlines: usize) -> i32 {
+        let eol_len = self.eol_len as i32;
+        (trimmed + self.len) as i32
+            // `len` includes an imaginary `eol` that may or may not exist
+            + match self.linebreaks {
+                // this means that the last line is nonempty and may or may not end with `eol`
+                // as mentioned in the tests, `abc\n` and `abc` are treated the same, so just
+                // subtract to act like the newline doesn't exist
+                1 => -eol_len,
+                // this only happens if file is empty, just ignore
+                0 => 0,
+                // `abc\n\n` is trimmed to `abc`
+                _ => 0,
+            }
+            // compensate for the `eol`s that are written, other than the one accounted for
+            + (1 - final_newlines as i32) * eol_len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn parametrized_trim_end() {
+        let test_data = [
+            ("abc", Whitespace::Unicode, 1, false, "abc"),
+            ("abc \t", Whitespace::Unicode, 1, false, "abc"),
+            ("abc\u{A0}", Whitespace::Ascii, 1, false, "abc\u{A0}"),
+            ("abc\u{A0}", Whitespace::Unicode, 1, false, "abc"),
+            ("abc ", Whitespace::Unicode, 2, false, "abc "),
+            ("abc  ", Whitespace::Unicode, 2, false, "abc"),
+            ("abc \x0C ", Whitespace::Unicode, 1, true, "abc\x0C"),
+            ("abc\x0C", Whitespace::Unicode, 1, true, "abc\x0C"),
+            ("é\t  \t  \t  \t  ", Whitespace::Unicode, 1, false, "é"),
+            (
+                "abc\u{3000}\t         ",
+                Whitespace::Unicode,
+                1,
+                false,
+                "abc",
+            ),
+            (
+                "abc\u{3000}\t         ",
+                Whitespace::Ascii,
+                1,
+                false,
+                "abc\u{3000}",
+            ),
+        ];
+        for (line, whitespace, min_run, keep_form_feed, expected) in test_data {
+            for &engine in Engine::ALL {
+                assert_eq!(
+                    expected,
+                    trim_end_with(line, whitespace, min_run, keep_form_feed, engine),
+                    "{}",
+                    engine
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "simd")]
+    #[test]
+    fn parametrized_find_trailing_whitespace() {
+        let test_data = [
+            ("", Whitespace::Unicode, None),
+            ("abc\ndef\r\n", Whitespace::Unicode, None),
+            ("abc\ndef \r\n", Whitespace::Unicode, Some(4)),
+            ("abc\ndef\r", Whitespace::Unicode, Some(4)),
+            ("abc\n\u{A0}\n", Whitespace::Ascii, None),
+            ("abc\n\u{A0}\n", Whitespace::Unicode, Some(4)),
+            ("abc\t\n", Whitespace::Ascii, Some(0)),
+        ];
+        for (buffer, whitespace, expected) in test_data {
+            assert_eq!(
+                expected,
+                find_trailing_whitespace(buffer.as_bytes(), whitespace),
+                "{:?}",
+                buffer
+            );
+        }
+        assert_eq!(
+            None,
+            find_trailing_whitespace(b"abc\xA0\n", Whitespace::Unicode)
+        );
+    }
+
+    #[test]
+    fn parametrized_control_chars() {
+        let test_data = [
+            ("abc", Whitespace::Unicode, "abc", "abc"),
+            ("a\x07b\x08c", Whitespace::Unicode, "abc", "a\\u{7}b\\u{8}c"),
+            ("\x7f", Whitespace::Unicode, "", "\\u{7f}"),
+            // whitespace, left to trimming
+            ("a\tb\x0C\r", Whitespace::Ascii, "a\tb\x0C\r", "a\tb\x0C\r"),
+            (
+                "a\x0B\u{85}",
+                Whitespace::Unicode,
+                "a\x0B\u{85}",
+                "a\x0B\u{85}",
+            ),
+            ("a\x0B\u{85}", Whitespace::Ascii, "a", "a\\u{b}\\u{85}"),
+        ];
+        for (line, whitespace, stripped, escaped) in test_data {
+            assert_eq!(line, ControlChars::Keep.apply(line, whitespace));
+            let control_chars = ControlChars::Strip;
+            assert_eq!(
+                stripped,
+                control_chars.apply(line, whitespace),
+                "{:?}",
+                line
+            );
+            let control_chars = ControlChars::Escape;
+            assert_eq!(escaped, control_chars.apply(line, whitespace), "{:?}", line);
+        }
+        for variant in ControlChars::VARIANTS {
+            let control_chars: ControlChars = variant.parse().unwrap();
+            assert_eq!(*variant, control_chars.to_string());
+        }
+    }
+
+    #[test]
+    fn parametrized_strip_ansi() {
+        let test_data = [
+            ("abc", "abc"),
+            ("\x1b[1;31merror\x1b[0m: abc", "error: abc"),
+            ("abc  \x1b[0m", "abc  "),
+            ("\u{9b}32mok\u{9b}m", "ok"),
+            ("\x1b]0;title\x07abc", "abc"),
+            ("\x1b]8;;https://a.b\x1b\\link\x1b]8;;\x1b\\", "link"),
+            ("\x1bPq#0\u{9c}abc", "abc"),
+            ("\x1b(Babc\x1b7\x1b8", "abc"),
+            ("abc\x1b", "abc"),
+            ("abc\x1b[3", "abc"),
+            ("\x1b]0;title", ""),
+            ("\x1bé", "é"),
+        ];
+        for (line, expected) in test_data {
+            assert_eq!(expected, strip_ansi(line), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn tail() {
+        // `abc \n\n`, trimmed to `abc\n`
+        let tail = Tail {
+            linebreaks: 2,
+            len: 2,
+            eol_len: 1,
+            eol_held: true,
+        };
+        assert_eq!(1, tail.blank_lines());
+        assert_eq!(3, tail.bytes_saved(1, 1));
+
+        // `abc \n`, trimmed to `abc\n`
+        let tail = Tail {
+            linebreaks: 1,
+            len: 1,
+            ..tail
+        };
+        assert_eq!(0, tail.blank_lines());
+        assert_eq!(1, tail.bytes_saved(1, 1));
+        assert_eq!(2, tail.bytes_saved(1, 0));
+    }
+}
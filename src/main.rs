@@ -1,120 +1,1547 @@
-use colmac::*;
+use serde_json::json;
+use serde_json::Value;
 use std::cmp::min;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fs::read;
+use std::fs::File;
+use std::hint::black_box;
 use std::io;
+use std::io::stderr;
 use std::io::stdin;
-use std::io::BufRead;
+use std::io::stdout;
+use std::io::BufReader;
+use std::io::LineWriter;
+use std::io::Read;
+use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::exit;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread::spawn;
+use std::time::Duration;
+use std::time::Instant;
 use structopt::StructOpt;
+use trim::audit::Audit;
+use trim::audit::AuditReport;
+use trim::baseline::Baseline;
+use trim::baseline::Severity;
+use trim::build_info;
+use trim::compare::compare_content;
+use trim::compare::compare_paths;
+use trim::compare::Comparison;
+use trim::diff::git_diff;
+use trim::diff::unified_diff;
+use trim::encoding::decoded;
+use trim::filter::Filter;
+use trim::fixture::write_fixture;
+use trim::invariants::check_invariants;
+use trim::invariants::random_input;
+#[cfg(feature = "simd")]
+use trim::line::find_trailing_whitespace;
+use trim::manifest::write_manifest;
+use trim::messages;
+use trim::messages::Misuse;
+use trim::options::EmptyFile;
+use trim::options::Engine;
+use trim::options::TrimOptions;
+use trim::options::Whitespace;
+use trim::parallel::*;
+use trim::patch::parse_patch;
+use trim::report::heatmap_report;
+use trim::report::html_report;
+use trim::report::json_report;
+use trim::report::merge_json_reports;
+use trim::report::porcelain_report;
+use trim::report::Format;
+use trim::report::Report;
+use trim::resume::ResumeState;
+use trim::rules::builtin;
+use trim::scan::estimated_savings;
+#[cfg(unix)]
+use trim::signals::status_requests;
+use trim::split::SplitWriter;
+use trim::stats::RunStats;
+use trim::stats::StatsSnapshot;
+use trim::stats::Threshold;
+use trim::throttle::throttled;
+use trim::throttle::Throttle;
+use trim::trim::*;
+use trim::util::*;
+use trim::writers::Writers;
 
 mod clargs;
-mod trim;
-mod util;
 
+use crate::clargs::Command;
 use crate::clargs::Opt;
-use crate::trim::*;
-use crate::util::*;
 
 fn main() {
     // cli args
     let Opt {
         files,
         in_place,
+        recursive,
+        yes,
+        confirm_over,
+        out_dir,
+        manifest_out,
+        split_by,
+        canonical,
+        show_diff,
+        against,
+        emit_fixture,
+        changed_content_only,
+        check,
+        input_fd,
+        output_fd,
+        tee,
+        append_to,
+        fsync,
+        line_buffered,
+        follow,
         suppress_newline,
+        final_newlines,
+        empty_newline,
+        empty_file,
         suppress_summary,
+        format,
+        top,
+        all,
+        ascii,
+        reports,
+        fail_thresholds,
+        warnings_as_errors,
+        path_style,
+        relative_to,
+        porcelain,
+        run_id,
+        summary_fd,
+        visual_fd,
         suppress_visual,
+        whitespace,
+        engine,
+        keep_form_feed,
+        keep_crlf,
+        strip_ansi,
+        control_chars,
+        record_sep,
+        min_run,
+        max_lines,
+        rules,
+        intra_file_parallel,
+        max_memory,
+        schedule,
+        throttle,
+        skip_open_for_writing,
+        progress,
+        stats_interval,
+        net_safe,
+        ignore_marker,
+        skip_file_marker,
+        skip_file_lines,
+        no_ignore_markers,
+        extensions,
+        exclude,
+        older_than,
+        newer_than,
+        owned_by,
+        max_shrink_percent,
+        metadata,
+        encoding,
+        filter_cmd,
+        filter_timeout,
+        filter_fail,
+        paranoid,
+        audit_sample,
+        baseline,
+        resume,
+        version,
+        verbose,
+        command,
     } = Opt::from_args();
+    if version {
+        match verbose {
+            true => println!("{}", build_info()),
+            false => println!("trim {}", build_info().version),
+        }
+        exit(0);
+    }
+    match command {
+        Some(Command::Diff { a, b }) => exit(diff(&a, &b)),
+        Some(Command::MergeReports { reports }) => exit(merge_reports(&reports)),
+        Some(Command::Apply { patch }) => exit(apply(&patch)),
+        Some(Command::SelfTest { iterations, seed }) => exit(self_test(iterations, seed)),
+        Some(Command::Schema { schema }) => {
+            println!("{:#}", schema.to_json());
+            exit(0)
+        }
+        Some(Command::Bench { size }) => {
+            bench(size);
+            exit(0)
+        }
+        None => (),
+    }
+    let options = TrimOptions {
+        suppress_newline,
+        final_newlines,
+        empty_newline,
+        empty_file,
+        whitespace,
+        engine,
+        keep_form_feed,
+        keep_crlf,
+        strip_ansi,
+        control_chars,
+        record_sep,
+        min_run,
+        max_lines,
+        // names are validated by `structopt`, so every lookup succeeds
+        rules: rules.iter().filter_map(|name| builtin(name)).collect(),
+        intra_file_parallel,
+        max_memory,
+        net_safe,
+        collect_findings: false,
+        ignore_marker: match no_ignore_markers {
+            true => None,
+            false => Some(ignore_marker),
+        },
+        skip_file_marker: match no_ignore_markers {
+            true => None,
+            false => Some(skip_file_marker),
+        },
+        skip_file_lines,
+        extensions,
+        exclude,
+        older_than,
+        newer_than,
+        owned_by,
+        max_shrink_percent,
+        metadata,
+        paranoid,
+        filter: filter_cmd.map(|command| Filter {
+            command,
+            timeout: filter_timeout,
+            on_failure: filter_fail,
+        }),
+        schedule,
+        throttle: throttle.map(|bytes_per_sec| Arc::new(Throttle::new(bytes_per_sec))),
+        out_dir: out_dir.clone(),
+        writers: match skip_open_for_writing {
+            true => match Writers::scan() {
+                Ok(writers) => Some(Arc::new(writers)),
+                Err(err) => {
+                    eprintln!("ERROR with `--skip-open-for-writing`: {}", err);
+                    exit(1)
+                }
+            },
+            false => None,
+        },
+        cancel: None,
+        // on unix, also for the status requested by `SIGINFO` or `SIGUSR1`
+        stats: match progress || stats_interval.is_some() || cfg!(unix) {
+            true => Some(Arc::new(RunStats::new())),
+            false => None,
+        },
+        audit: audit_sample.map(|size| Arc::new(Audit::new(size, random_u64()))),
+    };
+
+    let format = match porcelain {
+        true => Format::Porcelain,
+        false => format,
+    };
+    let options = TrimOptions {
+        collect_findings: format.needs_findings() || baseline.is_some(),
+        ..options
+    };
+    let options = match canonical {
+        true => options.into_canonical(),
+        false => options,
+    };
 
-    let no_files_provided = files.len() == 0;
+    // named in the text summary only if given, as it is there for reports to be correlated
+    let run_id_given = run_id.is_some();
+    let run_id = run_id.unwrap_or_else(new_run_id);
+    // otherwise every path would silently be shown as given
+    if let Some(base) = relative_to.as_ref().filter(|base| !base.is_dir()) {
+        let base = red(&format!("{:?}", base));
+        eprintln!("ERROR with {}: {}", base, messages::not_a_directory());
+        exit(1)
+    }
+
+    // where the summary and visuals go; stderr unless routed elsewhere
+    let mut summary_out = open_stream(summary_fd, "--summary-fd");
+    let mut visual_opt = match suppress_visual {
+        true => None,
+        false => Some(open_stream(visual_fd, "--visual-fd")),
+    };
+
+    // where trimmed lines go, unless they go back to files; stdout unless routed elsewhere
+    let output: Box<dyn Write> = match output_fd {
+        Some(fd) => match open_fd(fd) {
+            Ok(file) => Box::new(file),
+            Err(err) => {
+                eprintln!("ERROR with --output-fd {}: {}", fd, err);
+                exit(1)
+            }
+        },
+        None => match append_to {
+            Some(path) => match Append::open(&path, fsync) {
+                Ok(append) => Box::new(append),
+                Err(err) => {
+                    eprintln!("ERROR with {}: {}", red(&format!("{:?}", path)), err);
+                    exit(1)
+                }
+            },
+            None => Box::new(stdout()),
+        },
+    };
+    // and a copy of them, if asked for
+    let mut output = match Tee::new(output, tee.as_deref()) {
+        Ok(output) => output,
+        Err(err) => {
+            let path = tee.unwrap_or_default();
+            eprintln!("ERROR with {}: {}", red(&format!("{:?}", path)), err);
+            exit(1)
+        }
+    };
+
+    // the shells of Windows leave patterns such as `**/*.md` to the program
+    let files = expand_globs(&files);
+    let no_files_provided = files.is_empty();
     let dash_provided = files
         .iter()
         .map(PathBuf::as_path)
         .map(&Path::to_str)
         .any(|x| x == Some("-"));
     let use_stdin = no_files_provided || dash_provided;
+    // `-r` walks directories; what is dangerous to start from is still judged by what was given
+    let roots = files.clone();
+    let files = match recursive {
+        true => walk_files(&files, &|path| options.excludes(path)),
+        false => files,
+    };
+
+    // trimmed content goes back to files, either in-place or mirrored to `--out-dir`
+    let to_files = in_place || out_dir.is_some();
 
     // switch on some of the cli options
     // if key is `None`, this implies that stdin was used
-    let summaries: HashMap<Option<PathBuf>, io::Result<TrimResult>> = match in_place {
+    let summaries: Vec<(Option<PathBuf>, FileOutcome)> = match to_files {
+        // ERROR: a patch is instead of writing to files
+        true if format == Format::Patch => {
+            panic!("{}", Misuse::PatchToFiles)
+        }
+        // split trimmed stdin into files under `--out-dir`
+        true if use_stdin && split_by.is_some() => {
+            let dir = out_dir.clone().unwrap_or_default();
+            let boundary = split_by.expect("checked by the guard");
+            eprintln!("{}", messages::splitting(&dir));
+            let result = SplitWriter::new(&dir, boundary).and_then(|mut split| {
+                let reader = decoded(BufReader::new(stdin()), encoding)?;
+                let trim_result = trim_reader_to(reader, &mut split, &mut visual_opt, &options)?;
+                let parts = split.finish()?;
+                eprintln!("{}", messages::split_into(parts, &dir));
+                Ok(trim_result)
+            });
+            vec![(None, result.into())]
+        }
+        // ERROR: only stdin is split
+        true if split_by.is_some() => panic!("{}", Misuse::SplitFiles),
         // ERROR: cannot do in-place edit using stdin
-        true if use_stdin => panic!("Cannot read from stdin if `-i` is specified"),
+        true if use_stdin && out_dir.is_some() => {
+            panic!("{}", Misuse::StdinToOutDir)
+        }
+        true if use_stdin => panic!("{}", Misuse::StdinInPlace),
         // in-place trim every file
         true => {
-            eprintln!("Trimming {} files in-place...\n", files.len());
-            trim_files(&files, suppress_newline)
+            // a typo such as `trim -i / *` would trim everything in reach
+            if let Some(path) = dangerous_path(&roots).filter(|_| in_place && !yes) {
+                if !confirm(&messages::trim_in_place_from(path)) {
+                    eprintln!(
+                        "ERROR with {}: {}",
+                        red(&format!("{:?}", path)),
+                        messages::too_close_to_root(),
+                    );
+                    exit(1)
+                }
+            }
+            let state_opt = resume.map(|state_path| match ResumeState::open(&state_path) {
+                Ok(state) => state,
+                Err(err) => {
+                    eprintln!("ERROR with {}: {}", red(&format!("{:?}", state_path)), err);
+                    exit(1)
+                }
+            });
+            // files completed by an interrupted run are left out
+            let files: Vec<PathBuf> = match &state_opt {
+                Some(state) => files
+                    .into_iter()
+                    .filter(|path| !state.is_completed(path))
+                    .collect(),
+                None => files,
+            };
+
+            let included = files.iter().filter(|path| options.includes(path)).count();
+            // a quick scan, to give second thoughts a chance before a large run
+            if let Some(max_files) = confirm_over.filter(|_| !yes) {
+                let savings: Vec<usize> = files
+                    .par_iter()
+                    .filter(|path| options.includes(path))
+                    .filter_map(|path| estimated_savings(path, &options).ok())
+                    .filter(|&saved| saved > 0)
+                    .collect();
+                if savings.len() > max_files {
+                    eprintln!(
+                        "{}",
+                        messages::about_to_modify(savings.len(), savings.iter().sum())
+                    );
+                    if !confirm(messages::go_ahead()) {
+                        let flag = format!("`--confirm-over {}`", max_files);
+                        eprintln!(
+                            "ERROR with {}: {}",
+                            flag,
+                            messages::not_confirmed(savings.len())
+                        );
+                        exit(1)
+                    }
+                }
+            }
+            eprintln!("{}\n", messages::trimming(included, out_dir.as_deref()));
+            // a status line every `--stats-interval`, until the files are done
+            let (done_tx, done_rx) = channel::<()>();
+            let status_opt = match (stats_interval, &options.stats) {
+                (Some(secs), Some(stats)) => {
+                    let stats = Arc::clone(stats);
+                    let interval = Duration::from_secs(secs.max(1));
+                    Some(spawn(move || {
+                        while let Err(RecvTimeoutError::Timeout) = done_rx.recv_timeout(interval) {
+                            eprintln!("\r\x1b[K{}", stats.snapshot());
+                        }
+                    }))
+                }
+                _ => None,
+            };
+            // the file started last, for the status requested by signal
+            let current: Arc<Mutex<Option<PathBuf>>> = Arc::default();
+            #[cfg(unix)]
+            if let (Ok(mut requests), Some(stats)) = (status_requests(), &options.stats) {
+                let (stats, current) = (Arc::clone(stats), Arc::clone(&current));
+                // blocks until the process exits
+                spawn(move || {
+                    while requests.wait() {
+                        let current = current.lock().ok().and_then(|current| current.clone());
+                        let status = messages::status(&stats.snapshot(), current.as_deref());
+                        eprintln!("\r\x1b[K{}", status);
+                    }
+                });
+            }
+            let summaries: Vec<_> = trim_files_with(&files, &options, |update| {
+                if let (true, Some(stats)) = (progress, &options.stats) {
+                    draw_progress(&update, stats);
+                }
+                if let (None, Ok(mut current)) = (update.outcome, current.lock()) {
+                    *current = Some(update.path.to_path_buf());
+                }
+                match (&state_opt, update.outcome) {
+                    (Some(state), Some(outcome)) if outcome.is_completed() => {
+                        // saved again below; a lost update only means trimming the file again
+                        let _ = state.complete(update.path);
+                    }
+                    _ => {}
+                }
+            })
+            .into_iter()
+            .map(|(path_buf, outcome)| (Some(path_buf), outcome))
+            .collect();
+            drop(done_tx);
+            if let Some(status) = status_opt {
+                let _ = status.join();
+            }
+            if progress {
+                // clear the progress line for the summary
+                eprint!("\r\x1b[K");
+            }
+
+            // done; a later run starts over, unless some files have to be retried
+            let saved = match state_opt {
+                Some(state) if summaries.iter().all(|(_, outcome)| outcome.is_completed()) => {
+                    state.remove()
+                }
+                Some(state) => state.save(),
+                None => Ok(()),
+            };
+            if let Err(err) = saved {
+                eprintln!("ERROR with `--resume`: {}", err);
+            }
+            summaries
+        }
+        // ERROR: a filter only runs on files being written
+        false if options.filter.is_some() => {
+            panic!("{}", Misuse::FilterToStdout)
+        }
+        // fixtures of stdin or files, rather than the trimmed lines
+        false if emit_fixture.is_some() => {
+            let dir = emit_fixture.unwrap_or_default();
+            let filenames: Vec<Option<PathBuf>> = match use_stdin {
+                true => vec![None],
+                false => files.into_iter().map(Some).collect(),
+            };
+            filenames
+                .into_iter()
+                .map(|filename| {
+                    let result = fixture_of(filename.as_deref(), &dir, &options);
+                    (filename, result.into())
+                })
+                .collect()
+        }
+        // what trimming stdin or files would change, rather than the trimmed lines
+        false if show_diff || format == Format::Patch => {
+            let patch = format == Format::Patch;
+            let filenames: Vec<Option<PathBuf>> = match use_stdin {
+                true => vec![None],
+                // a patch may cover any number of files
+                false if files.len() == 1 || (patch && against.is_none()) => {
+                    files.into_iter().map(Some).collect()
+                }
+                false if patch => panic!("{}", Misuse::SeveralAgainst),
+                false => panic!("{}", Misuse::SeveralToStdout),
+            };
+            filenames
                 .into_iter()
-                .map(|(path_buf, trim_result)| (Some(path_buf), trim_result))
+                .map(|filename| {
+                    let result = diff_trimmed(
+                        filename.as_deref(),
+                        against.as_deref(),
+                        patch,
+                        &mut output,
+                        &mut visual_opt,
+                        &options,
+                    );
+                    (filename, result.into())
+                })
                 .collect()
         }
+        // what trimming would change, with nothing written
+        false if check => {
+            let filenames: Vec<Option<PathBuf>> = match use_stdin {
+                true if files.len() > 1 => panic!("{}", Misuse::DashWithFiles),
+                true => vec![None],
+                false => files
+                    .into_iter()
+                    .filter(|path| options.includes(path))
+                    .map(Some)
+                    .collect(),
+            };
+            match use_stdin {
+                true => filenames
+                    .into_iter()
+                    .map(|filename| {
+                        let outcome = changed_content(
+                            filename.as_deref(),
+                            false,
+                            &mut io::sink(),
+                            &mut visual_opt,
+                            &options,
+                        );
+                        (filename, outcome)
+                    })
+                    .collect(),
+                false => {
+                    let visual = visual_opt.is_some();
+                    let headers = filenames.len() > 1;
+                    let checks: Vec<_> = filenames
+                        .par_iter()
+                        .flatten()
+                        .map(|path| {
+                            // held back, and written in the order of the files
+                            let mut visuals = visual.then(Vec::new);
+                            let outcome = checked(path, &mut visuals, &options);
+                            (path, outcome, visuals)
+                        })
+                        .collect();
+                    checks
+                        .into_iter()
+                        .map(|(path, outcome, visuals)| {
+                            match (&mut visual_opt, visuals) {
+                                (Some(visual), Some(visuals)) if !visuals.is_empty() => {
+                                    // which file the findings are in, as for `--changed-only`
+                                    if headers {
+                                        let _ = writeln!(visual, "==> {} <==", path.display());
+                                    }
+                                    let _ = visual.write_all(&visuals);
+                                }
+                                _ => {}
+                            }
+                            (Some(path.clone()), outcome)
+                        })
+                        .collect()
+                }
+            }
+        }
+        // the trimmed content of only the inputs it differs from
+        false if changed_content_only => {
+            if follow || line_buffered || input_fd.is_some() {
+                panic!("{}", Misuse::HoldBackStream)
+            }
+            let filenames: Vec<Option<PathBuf>> = match use_stdin {
+                true if files.len() > 1 => panic!("{}", Misuse::DashWithFiles),
+                true => vec![None],
+                false => files
+                    .into_iter()
+                    .filter(|path| options.includes(path))
+                    .map(Some)
+                    .collect(),
+            };
+            let headers = filenames.len() > 1;
+            filenames
+                .into_iter()
+                .map(|filename| {
+                    let outcome = changed_content(
+                        filename.as_deref(),
+                        headers,
+                        &mut output,
+                        &mut visual_opt,
+                        &options,
+                    );
+                    (filename, outcome)
+                })
+                .collect()
+        }
+        // trim lines from a file descriptor inherited from the parent process
+        false if input_fd.is_some() => {
+            let fd = input_fd.unwrap_or_default();
+            eprintln!("{}", messages::reading_fd(fd));
+            let filename = Some(PathBuf::from(format!("/dev/fd/{}", fd)));
+            let result = open_fd(fd).and_then(|file| {
+                let reader = BufReader::new(throttled(file, options.throttle.as_deref()));
+                trim_reader_to(reader, &mut output, &mut visual_opt, &options)
+            });
+            vec![(filename, result.into())]
+        }
         // trim lines from stdin
         false if use_stdin => {
             // nonessential; just report what's happening
-            eprintln!(
-                "{}; reading lines from stdin...",
-                match no_files_provided {
-                    // okay if no files are provided; just read from stdin
-                    true => "No files provided",
-                    // okay if `-` is the only arg provided
-                    false if dash_provided && files.len() == 1 => "`-` provided",
-                    // not okay if `-` is provided along with other file names
-                    false if dash_provided => panic!("Can't mix `-` with other files"),
-                    false => unreachable!(),
-                }
-            );
+            // okay if no files are provided, or `-` is the only one; not along with other files
+            if dash_provided && files.len() > 1 {
+                panic!("{}", Misuse::DashWithFiles)
+            }
+            eprintln!("{}", messages::reading_stdin(no_files_provided));
 
-            hashmap![
-                None => trim_iter(stdin().lock().lines(), suppress_visual, suppress_newline)
-            ]
+            // piped content has no name to tell its encoding by
+            let result =
+                decoded(BufReader::new(stdin()), encoding).and_then(|reader| match line_buffered {
+                    _ if follow => {
+                        trim_reader_follow(reader, &mut output, &mut visual_opt, &options)
+                    }
+                    true => {
+                        trim_reader_line_buffered(reader, &mut output, &mut visual_opt, &options)
+                    }
+                    false => trim_reader_to(reader, &mut output, &mut visual_opt, &options),
+                });
+            vec![(None, result.into())]
         }
         // trim lines from a file to stdout; ensuring that only one file is provided
-        false => match files.get(0) {
+        false => match files.first() {
+            // left out by `--ext` or `--exclude`; pass it through as is
+            Some(path) if files.len() == 1 && !options.includes(path) => {
+                eprintln!("{}", messages::passing_through(path));
+                if let Err(err) = File::open(path)
+                    .and_then(|mut file| io::copy(&mut file, &mut output).map(|_| ()))
+                {
+                    eprintln!("ERROR with {}: {}", red(&format!("{:?}", path)), err);
+                    exit(1)
+                }
+                Vec::new()
+            }
+            Some(path) if files.len() == 1 && follow => {
+                eprintln!("{}", messages::following(path));
+                let result = File::open(path).and_then(Follow::new).and_then(|follow| {
+                    let reader = BufReader::new(follow);
+                    trim_reader_follow(reader, &mut output, &mut visual_opt, &options)
+                });
+                vec![(Some(PathBuf::from(path)), result.into())]
+            }
             Some(path) if files.len() == 1 => {
-                eprintln!("Reading lines from {:?}...", path);
+                eprintln!("{}", messages::reading(path));
                 let filename = Some(PathBuf::from(path));
-                let result = match readlines(&path) {
-                    Ok(lines) => trim_iter(lines, suppress_visual, suppress_newline),
-                    Err(err) => Err(err),
+                // pipes, e.g. `<(generator)`, have no length up front, so they are streamed
+                let fits_in_memory = path.metadata().map_or(true, |metadata| {
+                    metadata.is_file() && options.fits_in_memory(metadata.len())
+                });
+                let throttle = options.throttle.as_deref();
+                let result = match intra_file_parallel && fits_in_memory {
+                    true => File::open(path)
+                        .and_then(|file| {
+                            let mut content = Vec::new();
+                            throttled(file, throttle).read_to_end(&mut content)?;
+                            Ok(content)
+                        })
+                        .and_then(|content| {
+                            trim_slice_to(&content, &mut output, &mut visual_opt, &options)
+                        }),
+                    false => File::open(path)
+                        .map(|file| BufReader::new(throttled(file, throttle)))
+                        .and_then(|reader| {
+                            trim_reader_to(reader, &mut output, &mut visual_opt, &options)
+                        }),
                 };
-                hashmap![ filename => result ]
+                vec![(filename, result.into())]
             }
-            _ => panic!("Cannot handle multiple files without `-i`"),
+            _ => panic!("{}", Misuse::SeveralToStdout),
         },
     };
+    // everything is written; only now does the copy replace whatever was there
+    let tee_failed = match output.commit() {
+        Ok(()) => false,
+        Err(err) => {
+            eprintln!("ERROR with `--tee`: {}", err);
+            true
+        }
+    };
+    // checksums of the trimmed copies, for a release pipeline to sign
+    let manifest_failed = match (&manifest_out, &out_dir) {
+        (Some(manifest_path), Some(out_dir)) => {
+            let paths = summaries
+                .iter()
+                .filter(|(_, outcome)| !outcome.is_failed())
+                .filter_map(|(path_opt, _)| path_opt.as_deref());
+            let written = Tee::new(io::sink(), Some(manifest_path)).and_then(|mut manifest| {
+                write_manifest(out_dir, paths, &mut manifest)?;
+                manifest.commit()
+            });
+            match written {
+                Ok(()) => false,
+                Err(err) => {
+                    eprintln!("ERROR with `--manifest-out`: {}", err);
+                    true
+                }
+            }
+        }
+        _ => false,
+    };
 
+    // flush the visuals before the summary, in case both go to the same place
+    if let Some(visual) = &mut visual_opt {
+        let _ = visual.flush();
+    }
+    // shown the same way whatever way they were given in
+    let displayed: Vec<(Option<PathBuf>, &FileOutcome)> = summaries
+        .iter()
+        .map(|(file_opt, outcome)| {
+            let file_opt = file_opt.as_deref().map(|file| match &relative_to {
+                Some(base) => relative_path(file, base),
+                None => path_style.display(file),
+            });
+            (file_opt, outcome)
+        })
+        .collect();
+    // the sampled files, checked again now that every file is done
+    let audit_report = options.audit.as_ref().map(|audit| audit.verify(&options));
     // newline to separate summary from visual
-    if !suppress_summary {
-        eprint!("\n");
+    if !suppress_summary && format == Format::Text {
+        let _ = writeln!(summary_out);
+        if run_id_given {
+            let _ = writeln!(summary_out, "{}", messages::run(&run_id));
+        }
+    }
+    if !suppress_summary && format == Format::Json {
+        let mut report = json_report(
+            &run_id,
+            displayed
+                .iter()
+                .map(|(file_opt, outcome)| (file_opt.as_deref(), *outcome)),
+        );
+        if let Some(audit_report) = &audit_report {
+            report["audit"] = audit_json(audit_report);
+        }
+        let _ = writeln!(summary_out, "{}", report);
+    }
+    if !suppress_summary && format == Format::Porcelain {
+        let report = porcelain_report(
+            displayed
+                .iter()
+                .map(|(file_opt, outcome)| (file_opt.as_deref(), *outcome)),
+        );
+        let _ = write!(summary_out, "{}", report);
+    }
+    if !suppress_summary && format == Format::Html {
+        let report = html_report(
+            &run_id,
+            displayed
+                .iter()
+                .map(|(file_opt, outcome)| (file_opt.as_deref(), *outcome)),
+        );
+        let _ = write!(summary_out, "{}", report);
     }
+    if !suppress_summary && format == Format::Heatmap {
+        let report = heatmap_report(
+            displayed
+                .iter()
+                .map(|(file_opt, outcome)| (file_opt.as_deref(), *outcome)),
+            ascii,
+        );
+        let _ = write!(summary_out, "{}", report);
+    }
+    // the files that saved the most; the rest are left out of the text summary with `--top`, but
+    // errors never are
+    let saved =
+        |outcome: &FileOutcome| outcome.trim_result().map_or(0, |result| result.bytes_saved);
+    let hidden: HashSet<usize> = match top.filter(|_| !all) {
+        Some(top) => {
+            let mut by_savings: Vec<usize> = (0..displayed.len())
+                .filter(|&index| !displayed[index].1.is_failed())
+                .collect();
+            by_savings.sort_by_key(|&index| Reverse(saved(displayed[index].1)));
+            by_savings.into_iter().skip(top).collect()
+        }
+        None => HashSet::new(),
+    };
     // sum up all the exit codes, so if it's > 0, at least one error occurred
-    let exit_code_sum: i32 = summaries
-        .into_iter()
-        .map(|(file_opt, summary_res)| {
+    let exit_code_sum: i32 = displayed
+        .iter()
+        .enumerate()
+        .map(|(index, (file_opt, outcome))| {
             let filename = match file_opt {
-                Some(file) => format!("{:?}", file),
-                None => format!("stdin"),
+                Some(file) => file.display().to_string(),
+                None => "stdin".to_string(),
             };
-            (filename, summary_res)
+            (index, filename, outcome)
         })
-        .map(|(filename, summary_res)| match summary_res {
-            Ok(TrimResult { bytes_saved }) if !suppress_summary => {
+        .map(|(index, filename, outcome)| match outcome {
+            // already reported in the JSON, porcelain or HTML
+            FileOutcome::Failed(_) if format.reports_errors() => 1,
+            FileOutcome::Failed(err) => {
+                let _ = writeln!(summary_out, "ERROR with {}: {}", red(&filename), err);
+                1
+            }
+            _ if suppress_summary || format != Format::Text => 0,
+            _ if hidden.contains(&index) => 0,
+            FileOutcome::Skipped(reason) => {
+                let saved = messages::saved_from(0, &filename);
+                let _ = writeln!(summary_out, "{} ({})", saved, reason);
+                0
+            }
+            FileOutcome::Unchanged(_) => {
+                let _ = writeln!(summary_out, "{}", messages::saved_from(0, &filename));
+                0
+            }
+            FileOutcome::Modified(TrimResult {
+                bytes_saved,
+                retries,
+                whitespace_only,
+                ..
+            }) => {
                 // color the filename green if bytes were saved, don't otherwise
                 let filename_colored = match bytes_saved {
-                    0 => format!("{}", &filename),
+                    0 => filename.to_string(),
                     _ => format!("{}", green(&filename)),
                 };
-                eprintln!("{:>6} bytes ish from {}", bytes_saved, filename_colored);
+                let how = messages::trimmed_how(
+                    *retries,
+                    options.max_lines,
+                    whitespace_only.map(EmptyFile::done),
+                );
+                let saved = messages::saved_from(*bytes_saved as i64, &filename_colored);
+                let _ = writeln!(summary_out, "{}{}", saved, how);
                 0
             }
-            Err(err) => {
-                eprintln!("ERROR with {}: {}", red(&filename), err);
-                1
-            }
-            _ => 0,
         })
         .sum();
+    if !suppress_summary && format == Format::Text && top.is_some() && !all {
+        let totals: StatsSnapshot = displayed
+            .iter()
+            .map(|(_, outcome)| StatsSnapshot::from(*outcome))
+            .sum();
+        let _ = writeln!(
+            summary_out,
+            "{}",
+            messages::totals_with_hidden(totals.bytes_saved, totals.files_total, hidden.len())
+        );
+    }
+
+    // a failed audit fails the run, like errors; it is already in the JSON
+    let audit_failures = match &audit_report {
+        Some(audit_report) if suppress_summary || format != Format::Text => audit_report.failures(),
+        Some(audit_report) => {
+            for (path, result) in &audit_report.audited {
+                if let Err(err) = result {
+                    let path = red(&path.display().to_string());
+                    let _ = writeln!(summary_out, "AUDIT FAILED for {}: {}", path, err);
+                }
+            }
+            let _ = writeln!(
+                summary_out,
+                "{}",
+                messages::audited(
+                    audit_report.audited.len(),
+                    audit_report.modified,
+                    audit_report.failures()
+                )
+            );
+            audit_report.failures()
+        }
+        None => 0,
+    };
+
+    // findings that are not in the baseline fail the run, like errors
+    let new_findings = match &baseline {
+        Some(baseline_path) if suppress_summary => {
+            check_baseline(baseline_path, &summaries, &mut io::sink())
+        }
+        Some(baseline_path) => check_baseline(baseline_path, &summaries, &mut summary_out),
+        None => 0,
+    };
+    // requested findings fail the run too; they are already in the JSON
+    let eof_blank_files = match reports.contains(&Report::EofBlank) {
+        true if suppress_summary || format != Format::Text => {
+            check_eof_blank(&displayed, &mut io::sink())
+        }
+        true => check_eof_blank(&displayed, &mut summary_out),
+        false => 0,
+    };
+    // trimming more than tolerated fails the run too
+    let exceeded = match fail_thresholds.is_empty() {
+        true => 0,
+        false if suppress_summary || format != Format::Text => {
+            check_thresholds(&summaries, &fail_thresholds, &mut io::sink())
+        }
+        false => check_thresholds(&summaries, &fail_thresholds, &mut summary_out),
+    };
+    // warnings only fail the run if asked to; they are already in the JSON
+    let warned_files = match suppress_summary || format != Format::Text {
+        true => check_warnings(&displayed, &mut io::sink()),
+        false => check_warnings(&displayed, &mut summary_out),
+    };
+    let warned_files = match warnings_as_errors {
+        true => warned_files,
+        false => 0,
+    };
+    // with `--check`, anything to trim fails the run
+    let would_trim = match check {
+        true => summaries
+            .iter()
+            .filter(|(_, outcome)| outcome.trim_result().is_some())
+            .count(),
+        false => 0,
+    };
+    if would_trim > 0 && !suppress_summary && format == Format::Text {
+        let _ = writeln!(summary_out, "{}", messages::would_trim(would_trim));
+    }
+    let _ = summary_out.flush();
 
     // truncate for consistency
-    let exit_code = min(1, exit_code_sum);
+    let exit_code = min(
+        1,
+        exit_code_sum
+            + new_findings as i32
+            + eof_blank_files as i32
+            + exceeded as i32
+            + warned_files as i32
+            + audit_failures as i32
+            + would_trim as i32
+            + tee_failed as i32
+            + manifest_failed as i32,
+    );
     exit(exit_code);
 }
+
+/// Compare `a` to `b`, printing how each file compares to stdout.
+///
+/// # Returns
+///
+/// The exit code; 1 if the two differ by more than what trimming changes, or cannot be compared,
+/// 0 otherwise.
+fn diff(a: &Path, b: &Path) -> i32 {
+    let comparisons = match compare_paths(a, b) {
+        Ok(comparisons) => comparisons,
+        Err(err) => {
+            eprintln!("ERROR with `diff`: {}", err);
+            return 1;
+        }
+    };
+
+    let mut semantic = false;
+    for (relative, comparison) in comparisons {
+        // files being compared have no relative path
+        let (path_a, path_b) = match relative.as_os_str().is_empty() {
+            true => (a.to_path_buf(), b.to_path_buf()),
+            false => (a.join(&relative), b.join(&relative)),
+        };
+        semantic |= comparison.is_semantic();
+        match comparison {
+            Comparison::Identical => println!("identical {:?} {:?}", path_a, path_b),
+            Comparison::WhitespaceOnly => println!("whitespace-only {:?} {:?}", path_a, path_b),
+            Comparison::Differs(line) => {
+                println!("differs {:?} {:?} at line {}", path_a, path_b, line)
+            }
+            Comparison::OnlyInA => println!("only-in-a {:?}", path_a),
+            Comparison::OnlyInB => println!("only-in-b {:?}", path_b),
+        }
+    }
+    semantic as i32
+}
+
+/// Merge the JSON reports under `paths` into one, written to stdout; see `merge_json_reports`.
+///
+/// # Returns
+///
+/// The exit code; 1 if a report cannot be read or is not a JSON report, 0 otherwise.
+fn merge_reports(paths: &[PathBuf]) -> i32 {
+    let mut reports = Vec::with_capacity(paths.len());
+    for path in paths {
+        let report = read(path).and_then(|content| {
+            serde_json::from_slice(&content)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        });
+        match report {
+            Ok(report) => reports.push(report),
+            Err(err) => {
+                eprintln!("ERROR with {}: {}", red(&format!("{:?}", path)), err);
+                return 1;
+            }
+        }
+    }
+    match merge_json_reports(&new_run_id(), reports) {
+        Ok(merged) => {
+            println!("{}", merged);
+            0
+        }
+        Err(err) => {
+            eprintln!("ERROR with `merge-reports`: {}", err);
+            1
+        }
+    }
+}
+
+/// Apply the patch under `path`, as written by `--format patch`, to the files it names. Every file
+/// is checked before any is written, so that a patch that does not apply, or that makes changes
+/// beyond what trimming changes, is refused as a whole.
+///
+/// # Returns
+///
+/// The exit code; 1 if the patch is refused or cannot be applied, 0 otherwise.
+///
+/// # Side Effects
+///
+/// Each file is replaced atomically, see `replace_atomically`; a file that fails to be written
+/// leaves the files before it patched, and the ones after it untouched.
+fn apply(path: &Path) -> i32 {
+    let patched = read(path)
+        .and_then(|patch| parse_patch(&patch))
+        .and_then(|file_patches| {
+            file_patches
+                .into_iter()
+                .map(|file_patch| {
+                    let old = read(&file_patch.path)?;
+                    let new = file_patch.apply(&old)?;
+                    Ok((file_patch.path, old, new))
+                })
+                .collect::<io::Result<Vec<_>>>()
+        });
+    let patched = match patched {
+        Ok(patched) => patched,
+        Err(err) => {
+            eprintln!("ERROR with `apply`: {}", err);
+            return 1;
+        }
+    };
+
+    for (file, old, new) in &patched {
+        match compare_content(old, new) {
+            Ok(Comparison::Differs(line)) => {
+                let message = messages::more_than_whitespace(line);
+                eprintln!("ERROR with `apply`: {:?} {}", file, message);
+                return 1;
+            }
+            Ok(_) => (),
+            Err(err) => {
+                eprintln!("ERROR with `apply`: {:?}: {}", file, err);
+                return 1;
+            }
+        }
+    }
+    for (file, old, new) in &patched {
+        if old == new {
+            continue;
+        }
+        if let Err(err) = replace_atomically(file, new) {
+            eprintln!("ERROR with {:?}: {}", file, err);
+            return 1;
+        }
+    }
+    0
+}
+
+/// Trim `iterations` random inputs, each with a few sets of options, and check the invariants of
+/// every trim; see `check_invariants`.
+///
+/// # Parameters
+///
+/// 1. `iterations` -- number of random inputs
+/// 1. `seed_opt` -- seed of the first input, each next input has the next seed; random if `None`
+///
+/// # Returns
+///
+/// The exit code; 1 if any trim breaks an invariant, 0 otherwise.
+fn self_test(iterations: u64, seed_opt: Option<u64>) -> i32 {
+    let seed = seed_opt.unwrap_or_else(random_u64);
+    let options = [
+        TrimOptions::default(),
+        TrimOptions {
+            suppress_newline: true,
+            whitespace: Whitespace::Ascii,
+            ..TrimOptions::default()
+        },
+        TrimOptions {
+            keep_crlf: true,
+            keep_form_feed: true,
+            final_newlines: Some(2),
+            ..TrimOptions::default()
+        },
+    ];
+    for i in 0..iterations {
+        let input = random_input(seed.wrapping_add(i), (i % 64) as usize);
+        for options in &options {
+            if let Err(violation) = check_invariants(&input, options) {
+                let input = String::from_utf8_lossy(&input);
+                let message = messages::self_test_failed(&violation, &input, options, seed);
+                eprintln!("ERROR with `self-test`: {}", message);
+                return 1;
+            }
+        }
+    }
+    println!("{}", messages::self_test_passed(iterations, seed));
+    0
+}
+
+/// Time every `Engine` trimming `size_mib` MiB of lines with trailing whitespace, and, with the
+/// `simd` feature, `find_trailing_whitespace` scanning as many lines without; print how many MiB/s
+/// each gets through.
+fn bench(size_mib: usize) {
+    const MIB: f64 = (1 << 20) as f64;
+    const LINE: &str = "    let trimmed = engine.trim_trailing(line, whitespace);";
+    let lines = size_mib << 20 >> 6;
+    let text = format!("{}\t  \u{3000}  \n", LINE).repeat(lines);
+
+    for &engine in Engine::ALL {
+        let started = Instant::now();
+        let kept: usize = text
+            .lines()
+            .map(|line| engine.trim_trailing(line, Whitespace::Unicode).len())
+            .sum();
+        black_box(kept);
+        let rate = text.len() as f64 / MIB / started.elapsed().as_secs_f64();
+        println!("{:<6} {:>10.1} MiB/s", engine.to_string(), rate);
+    }
+
+    #[cfg(feature = "simd")]
+    {
+        let clean = format!("{}\n", LINE).repeat(lines);
+        let started = Instant::now();
+        let found = find_trailing_whitespace(black_box(clean.as_bytes()), Whitespace::Unicode);
+        assert_eq!(None, found);
+        let rate = clean.len() as f64 / MIB / started.elapsed().as_secs_f64();
+        println!("{:<6} {:>10.1} MiB/s", "scan", rate);
+    }
+}
+
+/// Write a unified diff of what trimming would change to `output`, leaving the input untouched.
+///
+/// # Parameters
+///
+/// 1. `path_opt` -- the file to trim, `None` for stdin
+/// 1. `against_opt` -- file to diff from instead of the untrimmed input, if any
+/// 1. `patch` -- write the diff so that `git apply` accepts it; see `git_diff`
+/// 1. `output` -- where the diff is written
+/// 1. `visual_opt` -- where the visuals are written, if anywhere
+/// 1. `options` -- how to trim each line
+///
+/// # Returns
+///
+/// The result of trimming the input.
+fn diff_trimmed(
+    path_opt: Option<&Path>,
+    against_opt: Option<&Path>,
+    patch: bool,
+    output: &mut dyn Write,
+    visual_opt: &mut Option<Box<dyn Write>>,
+    options: &TrimOptions,
+) -> io::Result<TrimResult> {
+    let mut content = Vec::new();
+    match path_opt {
+        Some(path) => {
+            throttled(File::open(path)?, options.throttle.as_deref()).read_to_end(&mut content)?
+        }
+        None => stdin().lock().read_to_end(&mut content)?,
+    };
+    let mut trimmed = Vec::new();
+    let trim_result = trim_slice_to(&content, &mut trimmed, visual_opt, options)?;
+
+    let label = match path_opt {
+        Some(path) => path.to_string_lossy().into_owned(),
+        None => "stdin".to_string(),
+    };
+    let old = match against_opt {
+        Some(against) => read(against)?,
+        None => content,
+    };
+    match (patch, against_opt) {
+        (true, _) => git_diff(&old, &trimmed, &label, output)?,
+        (false, Some(against)) => {
+            unified_diff(&old, &trimmed, &against.to_string_lossy(), &label, output)?
+        }
+        (false, None) => unified_diff(&old, &trimmed, &label, &label, output)?,
+    };
+    Ok(trim_result)
+}
+
+/// Write the trimmed content of the input to `output`, if it differs from the input.
+///
+/// # Parameters
+///
+/// 1. `path_opt` -- the file to trim, `None` for stdin
+/// 1. `header` -- whether to write a `==> path <==` header before the trimmed content, like
+///    `head` does for several files
+/// 1. `output` -- where the trimmed content is written
+/// 1. `visual_opt` -- where the visuals are written, if anywhere
+/// 1. `options` -- how to trim each line
+///
+/// # Returns
+///
+/// What became of the input; `FileOutcome::Unchanged` if nothing was written for it.
+fn changed_content(
+    path_opt: Option<&Path>,
+    header: bool,
+    output: &mut dyn Write,
+    visual_opt: &mut Option<Box<dyn Write>>,
+    options: &TrimOptions,
+) -> FileOutcome {
+    let result = (|| {
+        let mut content = Vec::new();
+        match path_opt {
+            Some(path) => throttled(File::open(path)?, options.throttle.as_deref())
+                .read_to_end(&mut content)?,
+            None => stdin().lock().read_to_end(&mut content)?,
+        };
+        let mut trimmed = Vec::new();
+        let trim_result = trim_slice_to(&content, &mut trimmed, visual_opt, options)?;
+        if let Some(reason) = trim_result.skipped {
+            return Ok(FileOutcome::Skipped(reason));
+        }
+        if trimmed == content {
+            return Ok(FileOutcome::Unchanged(trim_result.warnings));
+        }
+        if header {
+            let name = match path_opt {
+                Some(path) => path.display().to_string(),
+                None => "stdin".to_string(),
+            };
+            writeln!(output, "==> {} <==", name)?;
+        }
+        output.write_all(&trimmed)?;
+        Ok(FileOutcome::Modified(trim_result))
+    })();
+    match result {
+        Ok(outcome) => outcome,
+        Err(err) => FileOutcome::Failed(err),
+    }
+}
+
+/// Check what trimming the file under `path` would change, with nothing written; content that is
+/// already trimmed is told apart without trimming it in full.
+///
+/// # Parameters
+///
+/// 1. `path` -- the file to check
+/// 1. `visual_opt` -- where the visuals are written, if anywhere
+/// 1. `options` -- how to trim each line
+///
+/// # Returns
+///
+/// What would become of the file.
+fn checked(path: &Path, visual_opt: &mut Option<Vec<u8>>, options: &TrimOptions) -> FileOutcome {
+    let result = (|| {
+        let mut content = Vec::new();
+        throttled(File::open(long_path(path))?, options.throttle.as_deref())
+            .read_to_end(&mut content)?;
+        // trailing whitespace, found many bytes at a time, is all but sure to be trimmed
+        #[cfg(feature = "simd")]
+        let clean = find_trailing_whitespace(&content, options.whitespace).is_none();
+        #[cfg(not(feature = "simd"))]
+        let clean = true;
+        if clean {
+            match already_trimmed_reader(&content[..], options)? {
+                Some(TrimResult {
+                    skipped: Some(reason),
+                    ..
+                }) => return Ok(FileOutcome::Skipped(reason)),
+                Some(trim_result) => return Ok(FileOutcome::Unchanged(trim_result.warnings)),
+                None => {}
+            }
+        }
+        let mut trimmed = Vec::new();
+        let trim_result = trim_slice_to(&content, &mut trimmed, visual_opt, options)?;
+        Ok(match (trim_result.skipped, trimmed == content) {
+            (Some(reason), _) => FileOutcome::Skipped(reason),
+            (None, true) => FileOutcome::Unchanged(trim_result.warnings),
+            (None, false) => FileOutcome::Modified(trim_result),
+        })
+    })();
+    match result {
+        Ok(outcome) => outcome,
+        Err(err) => FileOutcome::Failed(err),
+    }
+}
+
+/// Write a fixture of the input to `dir`, named after its file, or `stdin`; see `write_fixture`.
+///
+/// # Parameters
+///
+/// 1. `path_opt` -- the file to trim, `None` for stdin
+/// 1. `dir` -- where the fixture is written
+/// 1. `options` -- how to trim each line
+///
+/// # Returns
+///
+/// The result of trimming the input.
+fn fixture_of(
+    path_opt: Option<&Path>,
+    dir: &Path,
+    options: &TrimOptions,
+) -> io::Result<TrimResult> {
+    let mut content = Vec::new();
+    let name = match path_opt {
+        Some(path) => {
+            throttled(File::open(path)?, options.throttle.as_deref()).read_to_end(&mut content)?;
+            path.file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .into_owned()
+        }
+        None => {
+            stdin().lock().read_to_end(&mut content)?;
+            "stdin".to_string()
+        }
+    };
+    write_fixture(dir, &name, &content, options)
+}
+
+/// # Returns
+///
+/// `audit_report` for the JSON report: how many files were audited out of how many modified, and
+/// each audited file that failed, with why.
+fn audit_json(audit_report: &AuditReport) -> Value {
+    let failures: Vec<Value> = audit_report
+        .audited
+        .iter()
+        .filter_map(|(path, result)| {
+            let err = result.as_ref().err()?;
+            Some(json!({ "path": path.to_string_lossy(), "message": err.to_string() }))
+        })
+        .collect();
+    json!({
+        "audited": audit_report.audited.len(),
+        "modified": audit_report.modified,
+        "failures": failures,
+    })
+}
+
+/// # Returns
+///
+/// A stream that writes to `fd_opt` if given, to stderr otherwise. Exits with an error message
+/// naming `flag` if `fd_opt` cannot be opened.
+fn open_stream(fd_opt: Option<i32>, flag: &str) -> Box<dyn Write> {
+    match fd_opt {
+        Some(fd) => match open_fd(fd) {
+            Ok(file) => Box::new(LineWriter::new(file)),
+            Err(err) => {
+                eprintln!("ERROR with {} {}: {}", flag, fd, err);
+                exit(1)
+            }
+        },
+        None => Box::new(stderr()),
+    }
+}
+
+/// Draw `progress` over the previous progress line on stderr, with the bytes saved so far.
+fn draw_progress(progress: &Progress, stats: &RunStats) {
+    let bytes_saved = stats.snapshot().bytes_saved;
+    eprint!(
+        "\r\x1b[K{}",
+        messages::progress(
+            progress.files_done,
+            progress.files_total,
+            bytes_saved,
+            progress.path
+        )
+    );
+}
+
+/// Report each of `thresholds` that the run, with the outcomes in `summaries`, trimmed more than,
+/// to `summary_out`.
+///
+/// # Returns
+///
+/// Number of such thresholds.
+fn check_thresholds(
+    summaries: &[(Option<PathBuf>, FileOutcome)],
+    thresholds: &[Threshold],
+    summary_out: &mut dyn Write,
+) -> usize {
+    let totals: StatsSnapshot = summaries
+        .iter()
+        .map(|(_, outcome)| StatsSnapshot::from(outcome))
+        .sum();
+
+    thresholds
+        .iter()
+        .filter(|&&threshold| totals.exceeds(threshold))
+        .inspect(|threshold| {
+            let threshold = red(&threshold.to_string());
+            let _ = writeln!(
+                summary_out,
+                "{}",
+                messages::over_threshold(totals.bytes_saved, totals.files_modified, &threshold)
+            );
+        })
+        .count()
+}
+
+/// Report the inputs in `summaries` that ended with blank lines to `summary_out`, sorted by path.
+///
+/// # Returns
+///
+/// Number of such inputs.
+fn check_eof_blank(
+    summaries: &[(Option<PathBuf>, &FileOutcome)],
+    summary_out: &mut dyn Write,
+) -> usize {
+    let mut found: Vec<_> = summaries
+        .iter()
+        .filter_map(|(file_opt, outcome)| match outcome.trim_result() {
+            Some(TrimResult {
+                eof_blank_lines, ..
+            }) if *eof_blank_lines > 0 => Some((file_opt, *eof_blank_lines)),
+            _ => None,
+        })
+        .collect();
+    found.sort();
+
+    for (file_opt, eof_blank_lines) in &found {
+        let filename = match file_opt {
+            Some(file) => file.display().to_string(),
+            None => "stdin".to_string(),
+        };
+        let _ = writeln!(
+            summary_out,
+            "{}",
+            messages::eof_blank(*eof_blank_lines, &red(&filename))
+        );
+    }
+    found.len()
+}
+
+/// Report the warnings of the inputs in `summaries` to `summary_out`, sorted by path.
+///
+/// # Returns
+///
+/// Number of inputs with warnings.
+fn check_warnings(
+    summaries: &[(Option<PathBuf>, &FileOutcome)],
+    summary_out: &mut dyn Write,
+) -> usize {
+    let mut found: Vec<_> = summaries
+        .iter()
+        .filter(|(_, outcome)| !outcome.warnings().is_empty())
+        .map(|(file_opt, outcome)| (file_opt, outcome.warnings()))
+        .collect();
+    found.sort();
+
+    for (file_opt, warnings) in &found {
+        let filename = match file_opt {
+            Some(file) => file.display().to_string(),
+            None => "stdin".to_string(),
+        };
+        for warning in warnings.iter() {
+            let _ = writeln!(summary_out, "WARNING with {}: {}", red(&filename), warning);
+        }
+    }
+    found.len()
+}
+
+/// Compare the findings in `summaries` against the baseline under `baseline_path`, reporting the
+/// new ones to `summary_out`. If there is no baseline yet, record one from `summaries` instead.
+///
+/// # Returns
+///
+/// Number of findings that are not in the baseline. Exits with an error message if the baseline
+/// cannot be read or written.
+fn check_baseline(
+    baseline_path: &Path,
+    summaries: &[(Option<PathBuf>, FileOutcome)],
+    summary_out: &mut dyn Write,
+) -> usize {
+    let mut findings: Vec<(String, &[Finding])> = summaries
+        .iter()
+        .filter_map(|(file_opt, outcome)| {
+            let path = match file_opt {
+                Some(file) => file.to_string_lossy().into_owned(),
+                None => "-".to_string(),
+            };
+            outcome
+                .trim_result()
+                .map(|trim_result| (path, &trim_result.findings[..]))
+        })
+        .collect();
+    findings.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    // no baseline yet; everything found now is accepted from now on
+    if !baseline_path.exists() {
+        let baseline =
+            Baseline::from_findings(findings.iter().map(|(path, found)| (path.as_str(), *found)));
+        if let Err(err) = baseline.save(baseline_path) {
+            eprintln!(
+                "ERROR with {}: {}",
+                red(&format!("{:?}", baseline_path)),
+                err
+            );
+            exit(1)
+        }
+        let _ = writeln!(
+            summary_out,
+            "{}",
+            messages::recorded_findings(baseline.len(), baseline_path)
+        );
+        return 0;
+    }
+
+    let baseline = match Baseline::load(baseline_path) {
+        Ok(baseline) => baseline,
+        Err(err) => {
+            eprintln!(
+                "ERROR with {}: {}",
+                red(&format!("{:?}", baseline_path)),
+                err
+            );
+            exit(1)
+        }
+    };
+    findings
+        .iter()
+        .map(|(path, found)| {
+            let severities = baseline.classify(path, found);
+            found
+                .iter()
+                .zip(severities)
+                .filter(|(_, severity)| *severity == Severity::New)
+                .map(|(finding, _)| {
+                    let message = messages::new_finding(&red(path), finding.line_number);
+                    let _ = writeln!(summary_out, "{}", message);
+                })
+                .count()
+        })
+        .sum()
+}
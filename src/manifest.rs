@@ -0,0 +1,148 @@
+//! Checksum manifests of trimmed trees, in the format of `sha256sum`, so that a release pipeline
+//! can sign the manifest of an `--out-dir` tree, and anyone can verify the tree with
+//! `sha256sum -c` from within it.
+
+use sha2::Digest;
+use sha2::Sha256;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+
+use crate::util::long_path;
+use crate::util::mirror_path;
+
+/// # Returns
+///
+/// The SHA-256 digest of everything `reader` reads.
+pub fn sha256(mut reader: impl Read) -> io::Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+/// # Returns
+///
+/// The line of `path` in a manifest, with its `digest`, as `sha256sum` writes it: the digest in
+/// hex, two spaces and the path, separated by `/` on every platform. A path with a `\` or a
+/// linebreak is escaped, and its line starts with a `\`.
+pub fn manifest_line(digest: &[u8; 32], path: &Path) -> String {
+    let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let path = path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    match path.contains(['\\', '\n', '\r']) {
+        true => {
+            let escaped = path
+                .replace('\\', "\\\\")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r");
+            format!("\\{}  {}\n", hex, escaped)
+        }
+        false => format!("{}  {}\n", hex, path),
+    }
+}
+
+/// Write the manifest of the files that `paths` are mirrored to under `out_dir` to `out`, sorted
+/// by path, relative to `out_dir`; paths that are not mirrored to a file, e.g. those that failed,
+/// are left out.
+///
+/// # Returns
+///
+/// Number of files in the manifest.
+pub fn write_manifest<'a>(
+    out_dir: &Path,
+    paths: impl IntoIterator<Item = &'a Path>,
+    out: &mut dyn Write,
+) -> io::Result<usize> {
+    let mut relative: Vec<_> = paths
+        .into_iter()
+        .filter_map(|path| mirror_path(Path::new(""), path).ok())
+        .filter(|relative| long_path(&out_dir.join(relative)).is_file())
+        .collect();
+    relative.sort();
+    relative.dedup();
+    for path in &relative {
+        let digest = sha256(File::open(long_path(&out_dir.join(path)))?)?;
+        out.write_all(manifest_line(&digest, path).as_bytes())?;
+    }
+    Ok(relative.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::create_dir_all;
+    use std::fs::write;
+    use std::path::PathBuf;
+
+    #[test]
+    fn parametrized_sha256() {
+        let million_a = "a".repeat(1_000_000);
+        let test_data = [
+            (
+                "",
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            ),
+            (
+                "abc",
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (
+                "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            ),
+            (
+                &million_a,
+                "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0",
+            ),
+        ];
+        for (input, expected) in test_data {
+            let digest = sha256(input.as_bytes()).unwrap();
+            let line = manifest_line(&digest, Path::new("a"));
+            assert_eq!(format!("{}  a\n", expected), line);
+        }
+    }
+
+    #[test]
+    fn parametrized_manifest_line() {
+        let digest = [0xab; 32];
+        let hex = "ab".repeat(32);
+        let test_data = [
+            ("a b.txt", format!("{}  a b.txt\n", hex)),
+            ("a\\b", format!("\\{}  a\\\\b\n", hex)),
+            ("a\nb", format!("\\{}  a\\nb\n", hex)),
+        ];
+        for (path, expected) in test_data {
+            assert_eq!(expected, manifest_line(&digest, Path::new(path)));
+        }
+    }
+
+    #[test]
+    fn manifest_of_mirrored_files() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let out_dir = temp_dir().join(format!("{}.out", prefix));
+        create_dir_all(out_dir.join("src")).unwrap();
+        write(out_dir.join("src/lib.rs"), "abc\n").unwrap();
+        write(out_dir.join("README"), "").unwrap();
+
+        let paths = [
+            PathBuf::from("src/lib.rs"),
+            PathBuf::from("./README"),
+            PathBuf::from("failed.txt"),
+        ];
+        let mut manifest = Vec::new();
+        let count = write_manifest(&out_dir, paths.iter().map(PathBuf::as_path), &mut manifest);
+        assert_eq!(2, count.unwrap());
+        let expected = [
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  README\n",
+            "edeaaff3f1774ad2888673770c6d64097e391bc362d7d6fb34982ddf0efd18cb  src/lib.rs\n",
+        ]
+        .concat();
+        assert_eq!(expected, String::from_utf8(manifest).unwrap());
+    }
+}
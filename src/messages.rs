@@ -0,0 +1,442 @@
+//! Sentences shown to the user, kept apart from the logic that decides to show them, so that they
+//! can be reworded, or translated, in one place; every count in them is pluralized.
+//!
+//! Machine-readable output, e.g. `porcelain_report` or the `ERROR with` prefix that scripts grep
+//! for, is not in here, as it must not change with the wording.
+
+use std::fmt;
+use std::path::Path;
+
+use crate::stats::StatsSnapshot;
+
+/// # Returns
+///
+/// `n` followed by `one` if `n` is 1, by `many` otherwise, e.g. `1 file` or `3 files`.
+pub fn count(n: usize, one: &str, many: &str) -> String {
+    match n {
+        1 => format!("1 {}", one),
+        _ => format!("{} {}", n, many),
+    }
+}
+
+/// # Returns
+///
+/// `n` files, e.g. `1 file` or `3 files`.
+pub fn files(n: usize) -> String {
+    count(n, "file", "files")
+}
+
+/// # Returns
+///
+/// `n` bytes ish, e.g. `1 byte ish` or `-3 bytes ish`; see `TrimResult::bytes_saved`.
+pub fn bytes(n: i64) -> String {
+    match n {
+        1 => "1 byte ish".to_string(),
+        _ => format!("{} bytes ish", n),
+    }
+}
+
+/// # Returns
+///
+/// That `n` bytes ish were saved from `file`, with `n` right-aligned so that the lines of a summary
+/// line up, e.g. `     1 byte ish from a.txt`.
+pub fn saved_from(n: i64, file: &dyn fmt::Display) -> String {
+    let unit = match n {
+        1 => "byte",
+        _ => "bytes",
+    };
+    format!("{:>6} {} ish from {}", n, unit, file)
+}
+
+/// # Returns
+///
+/// The progress of a run, drawn over itself as each file is done: files done out of the total,
+/// the bytes ish saved so far, and the file just done.
+pub fn progress(files_done: usize, files_total: usize, bytes_saved: i64, path: &Path) -> String {
+    format!(
+        "{}/{} files, {}, {}",
+        files_done,
+        files_total,
+        bytes(bytes_saved),
+        path.display()
+    )
+}
+
+/// # Returns
+///
+/// That lines are being read from `path`.
+pub fn reading(path: &Path) -> String {
+    format!("Reading lines from {:?}...", path)
+}
+
+/// # Returns
+///
+/// That lines are being read from the inherited file descriptor `fd`.
+pub fn reading_fd(fd: i32) -> String {
+    format!("Reading lines from file descriptor {}...", fd)
+}
+
+/// # Returns
+///
+/// That lines are being read from stdin, as no files were provided, or only `-`.
+pub fn reading_stdin(no_files_provided: bool) -> String {
+    let why = match no_files_provided {
+        true => "No files provided",
+        false => "`-` provided",
+    };
+    format!("{}; reading lines from stdin...", why)
+}
+
+/// # Returns
+///
+/// That lines are being followed from `path`, as it grows.
+pub fn following(path: &Path) -> String {
+    format!("Following lines from {:?}...", path)
+}
+
+/// # Returns
+///
+/// That lines from stdin are being split into files under `dir`.
+pub fn splitting(dir: &Path) -> String {
+    format!("Splitting lines from stdin into {:?}...", dir)
+}
+
+/// # Returns
+///
+/// That `path` is written out as it is, as `--ext` or `--exclude` leave it out.
+pub fn passing_through(path: &Path) -> String {
+    format!(
+        "Passing {:?} through; it is left out by `--ext` or `--exclude`",
+        path
+    )
+}
+
+/// # Returns
+///
+/// The question whether to trim in-place from `path`, which is close to a root or the home
+/// directory.
+pub fn trim_in_place_from(path: &Path) -> String {
+    format!("Trim in-place starting from {:?}?", path)
+}
+
+/// # Returns
+///
+/// Why trimming in-place from a path close to a root or the home directory was refused.
+pub fn too_close_to_root() -> &'static str {
+    "too close to a root or the home directory to trim in-place; pass `--yes` if this is intended"
+}
+
+/// # Returns
+///
+/// That `files` are about to be trimmed into `out_dir`, or in-place without one.
+pub fn trimming(files_count: usize, out_dir: Option<&Path>) -> String {
+    match out_dir {
+        Some(out_dir) => format!("Trimming {} into {:?}...", files(files_count), out_dir),
+        None => format!("Trimming {} in-place...", files(files_count)),
+    }
+}
+
+/// # Returns
+///
+/// That `files_count` files are about to be modified in-place, saving `bytes_saved`, before asking
+/// whether to go ahead.
+pub fn about_to_modify(files_count: usize, bytes_saved: usize) -> String {
+    format!(
+        "About to modify {} in-place, saving about {}",
+        files(files_count),
+        bytes(bytes_saved as i64)
+    )
+}
+
+/// # Returns
+///
+/// The question whether to go ahead, after `about_to_modify`.
+pub fn go_ahead() -> &'static str {
+    "Go ahead?"
+}
+
+/// # Returns
+///
+/// Why a run that was not confirmed stops: `files_count` files would have been modified.
+pub fn not_confirmed(files_count: usize) -> String {
+    format!(
+        "{} would be modified; pass `--yes` to go ahead",
+        files(files_count)
+    )
+}
+
+/// # Returns
+///
+/// How a file was trimmed, after its savings: after `retries`, of its first `max_lines` only, and
+/// `whitespace_only` with what became of it; empty if none of that applies.
+pub fn trimmed_how(
+    retries: u32,
+    max_lines: Option<usize>,
+    whitespace_only: Option<&str>,
+) -> String {
+    let mut how = String::new();
+    if retries > 0 {
+        how.push_str(&format!(
+            " after {}",
+            count(retries as usize, "retry", "retries")
+        ));
+    }
+    if let Some(max_lines) = max_lines {
+        match max_lines {
+            1 => how.push_str(", first line only"),
+            _ => how.push_str(&format!(", first {} lines only", max_lines)),
+        }
+    }
+    if let Some(done) = whitespace_only {
+        how.push_str(&format!(", whitespace only, {}", done));
+    }
+    how
+}
+
+/// # Returns
+///
+/// The totals of a run with `--top`, of which `hidden` files were not listed.
+pub fn totals_with_hidden(bytes_saved: i64, files_count: usize, hidden: usize) -> String {
+    format!(
+        "{:>6} bytes ish from {} in total; {} not listed, see `--all`",
+        bytes_saved,
+        files(files_count),
+        hidden
+    )
+}
+
+/// # Returns
+///
+/// That a run which modified `files_count` files, saving `bytes_saved`, trimmed more than
+/// `threshold` allows.
+pub fn over_threshold(
+    bytes_saved: i64,
+    files_count: usize,
+    threshold: &dyn fmt::Display,
+) -> String {
+    format!(
+        "{} from {}, over `--fail-threshold {}`",
+        bytes(bytes_saved),
+        files(files_count),
+        threshold
+    )
+}
+
+/// # Returns
+///
+/// That `file` ends with `lines` blank lines.
+pub fn eof_blank(lines: usize, file: &dyn fmt::Display) -> String {
+    format!(
+        "{} at EOF of {}",
+        count(lines, "blank line", "blank lines"),
+        file
+    )
+}
+
+/// # Returns
+///
+/// That `findings` findings were recorded to the baseline under `path`.
+pub fn recorded_findings(findings: usize, path: &Path) -> String {
+    format!(
+        "Recorded {} to {:?}",
+        count(findings, "finding", "findings"),
+        path
+    )
+}
+
+/// # Returns
+///
+/// The outcome of an audit of `audited` out of `modified` files, of which `failed` failed.
+pub fn audited(audited: usize, modified: u64, failed: usize) -> String {
+    format!(
+        "Audited {} of {} modified {}, {} failed",
+        audited,
+        modified,
+        match modified {
+            1 => "file",
+            _ => "files",
+        },
+        failed
+    )
+}
+
+/// # Returns
+///
+/// That `files_count` files had nothing to trim, after the bars of `heatmap_report`.
+pub fn nothing_to_trim(files_count: usize) -> String {
+    format!("{} with nothing to trim", files(files_count))
+}
+
+/// # Returns
+///
+/// That stdin was split into `parts` files under `dir`.
+pub fn split_into(parts: usize, dir: &Path) -> String {
+    format!("Split into {} under {:?}", files(parts), dir)
+}
+
+/// # Returns
+///
+/// That `--check` found `files_count` files that trimming would change.
+pub fn would_trim(files_count: usize) -> String {
+    format!(
+        "{} would be trimmed; run without `--check` to trim them",
+        files(files_count)
+    )
+}
+
+/// # Returns
+///
+/// The status of a run as of `stats`, requested by signal, with the file it started last.
+pub fn status(stats: &StatsSnapshot, current: Option<&Path>) -> String {
+    match current {
+        Some(current) => format!("{}; now on {:?}", stats, current),
+        None => stats.to_string(),
+    }
+}
+
+/// # Returns
+///
+/// The id of the run, e.g. as given with `--run-id`, heading the text summary.
+pub fn run(run_id: &str) -> String {
+    format!("Run {}", run_id)
+}
+
+/// # Returns
+///
+/// That a finding at `line` of `path` is not in the baseline.
+pub fn new_finding(path: &dyn fmt::Display, line: usize) -> String {
+    format!("new finding at {}:{}", path, line)
+}
+
+/// # Returns
+///
+/// Why `--relative-to` was refused: it is not a directory, so that paths cannot be shown
+/// relative to it.
+pub fn not_a_directory() -> &'static str {
+    "`--relative-to` needs a directory"
+}
+
+/// # Returns
+///
+/// Why a patch was not applied: it changes more than whitespace, first at `line`.
+pub fn more_than_whitespace(line: usize) -> String {
+    format!("changes more than whitespace at line {}", line)
+}
+
+/// # Returns
+///
+/// Why the self-test failed: trimming `input` with `options` broke an invariant, with how to
+/// repeat it from `seed`.
+pub fn self_test_failed(
+    violation: &dyn fmt::Display,
+    input: &str,
+    options: &dyn fmt::Debug,
+    seed: u64,
+) -> String {
+    format!(
+        "{}, trimming {:?} with {:?}; repeat with `--seed {}`",
+        violation, input, options, seed
+    )
+}
+
+/// # Returns
+///
+/// That the self-test passed `inputs` random inputs, starting from `seed`.
+pub fn self_test_passed(inputs: u64, seed: u64) -> String {
+    let inputs = count(inputs as usize, "input", "inputs");
+    format!("self-test passed {}, with `--seed {}`", inputs, seed)
+}
+
+/// Flags that do not go together, or with the input, which stop a run before anything is read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Misuse {
+    /// `--format patch` with `-i` or `--out-dir`
+    PatchToFiles,
+    /// `--split-by` with files
+    SplitFiles,
+    /// stdin with `--out-dir`
+    StdinToOutDir,
+    /// stdin with `-i`
+    StdinInPlace,
+    /// `--filter-cmd` without `-i` or `--out-dir`
+    FilterToStdout,
+    /// several files with `--against`
+    SeveralAgainst,
+    /// several files without `-i`
+    SeveralToStdout,
+    /// `-` along with files
+    DashWithFiles,
+    /// `--changed-only` with a stream
+    HoldBackStream,
+}
+
+impl fmt::Display for Misuse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            Misuse::PatchToFiles => "Cannot write a patch if `-i` or `--out-dir` is specified",
+            Misuse::SplitFiles => "Cannot split files, only stdin, with `--split-by`",
+            Misuse::StdinToOutDir => "Cannot read from stdin if `--out-dir` is specified",
+            Misuse::StdinInPlace => "Cannot read from stdin if `-i` is specified",
+            Misuse::FilterToStdout => {
+                "Cannot run `--filter-cmd` unless `-i` or `--out-dir` is specified"
+            }
+            Misuse::SeveralAgainst => "Cannot diff multiple files against `--against`",
+            Misuse::SeveralToStdout => "Cannot handle multiple files without `-i`",
+            Misuse::DashWithFiles => "Can't mix `-` with other files",
+            Misuse::HoldBackStream => {
+                "Cannot hold back the content of a stream until it is known to change"
+            }
+        };
+        write!(f, "{}", message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parametrized_pluralized() {
+        let test_data = [
+            (files(0), "0 files"),
+            (files(1), "1 file"),
+            (files(2), "2 files"),
+            (bytes(1), "1 byte ish"),
+            (bytes(-1), "-1 bytes ish"),
+            (saved_from(1, &"a"), "     1 byte ish from a"),
+            (saved_from(-12, &"a"), "   -12 bytes ish from a"),
+            (
+                progress(1, 2, 1, Path::new("a")),
+                "1/2 files, 1 byte ish, a",
+            ),
+            (trimming(1, None), "Trimming 1 file in-place..."),
+            (
+                trimming(3, Some(Path::new("out"))),
+                "Trimming 3 files into \"out\"...",
+            ),
+            (
+                trimmed_how(1, Some(1), None),
+                " after 1 retry, first line only",
+            ),
+            (
+                trimmed_how(2, None, Some("kept")),
+                " after 2 retries, whitespace only, kept",
+            ),
+            (trimmed_how(0, None, None), ""),
+            (eof_blank(1, &"a"), "1 blank line at EOF of a"),
+            (audited(1, 1, 0), "Audited 1 of 1 modified file, 0 failed"),
+            (nothing_to_trim(1), "1 file with nothing to trim"),
+            (
+                self_test_passed(1, 7),
+                "self-test passed 1 input, with `--seed 7`",
+            ),
+            (
+                would_trim(1),
+                "1 file would be trimmed; run without `--check` to trim them",
+            ),
+        ];
+        for (message, expected) in test_data {
+            assert_eq!(expected, message);
+        }
+    }
+}
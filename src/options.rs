@@ -0,0 +1,879 @@
+use glob::MatchOptions;
+use glob::Pattern;
+use serde::de;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::File;
+use std::fs::Metadata;
+use std::io;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use crate::audit::Audit;
+use crate::cancel::CancellationToken;
+use crate::filter::Filter;
+use crate::language::script_language;
+pub use crate::line::ControlChars;
+pub use crate::line::Engine;
+pub use crate::line::Whitespace;
+use crate::owner::Owner;
+use crate::rules::builtin;
+use crate::rules::LineRule;
+use crate::stats::RunStats;
+use crate::throttle::Throttle;
+use crate::util::copy_permissions;
+use crate::writers::Writers;
+
+/// Options that control how lines are trimmed, independent of where they are read from or
+/// written to.
+///
+/// Serializable, e.g. to ship a trimming policy in a config file, with every field optional.
+/// `rules` are kept by name, see `LineRule::name`; `throttle`, `writers`, `cancel`, `stats` and
+/// `audit` are state of a run rather than policy, so they are left out.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrimOptions {
+    /// omit `\n` at the end of the last line if true, put it otherwise
+    pub suppress_newline: bool,
+    /// end the output with exactly this many linebreaks, e.g. 2 for some changelog conventions,
+    /// however many blank lines the input ends with; overrides `suppress_newline`. A single one,
+    /// or none with `suppress_newline`, if `None`
+    pub final_newlines: Option<usize>,
+    /// end an empty input with the final linebreaks too, as any other input, rather than leaving
+    /// it empty
+    pub empty_newline: bool,
+    /// what becomes of inputs that hold nothing but whitespace; see `EmptyFile`
+    pub empty_file: EmptyFile,
+    /// which characters are considered whitespace when trimming the end of a line
+    pub whitespace: Whitespace,
+    /// how the trailing whitespace of each line is found; engines only differ in speed
+    pub engine: Engine,
+    /// keep a single `\x0C` (form feed) at the end of a line if its trailing whitespace had one,
+    /// so that intentional page breaks survive the trim
+    pub keep_form_feed: bool,
+    /// end lines that were read with `\r\n` with `\r\n`, instead of converting them to `\n`
+    pub keep_crlf: bool,
+    /// drop terminal escape sequences from each line before trimming it, e.g. the colors of a
+    /// captured console log, which also uncovers whitespace they hid; see `line::strip_ansi` and
+    /// `TrimResult::ansi_bytes_stripped`
+    pub strip_ansi: bool,
+    /// what becomes of control characters within each line, other than those that are
+    /// `whitespace`, before it is trimmed; see `ControlChars`
+    pub control_chars: ControlChars,
+    /// split the input into records ending with this ASCII byte, e.g. `;`, instead of lines; each
+    /// record is trimmed like a line, and `\r` before it is not special. Lines, ending with `\n` or
+    /// `\r\n`, if `None`
+    pub record_sep: Option<u8>,
+    /// leave trailing runs of whitespace shorter than this many characters untouched
+    pub min_run: usize,
+    /// trim only the first this many lines of an input and stop there, e.g. to preview what a
+    /// full run would do; the rest of the input is not written. Files cannot be trimmed in-place
+    /// with a limit, as the rest would be lost. Inputs are not limited if `None`
+    pub max_lines: Option<usize>,
+    /// additional cleanups to apply to each line after trimming its trailing whitespace, in order
+    #[serde(
+        serialize_with = "serialize_rules",
+        deserialize_with = "deserialize_rules"
+    )]
+    pub rules: Vec<Arc<dyn LineRule>>,
+    /// when trimming a file, read it as a whole and trim line-aligned chunks of it in parallel;
+    /// worthwhile for huge files only
+    pub intra_file_parallel: bool,
+    /// upper bound, in bytes, on the memory spent on buffers across all workers; buffers are
+    /// shrunk and fewer chunks are kept in flight to stay within it, unbounded if `None`
+    ///
+    /// A single line is always held in memory as a whole, however long it is.
+    pub max_memory: Option<usize>,
+    /// for network filesystems; flush trimmed files to disk before replacing the originals,
+    /// check their size after the rename, and retry transient errors with backoff
+    pub net_safe: bool,
+    /// keep every trimmed line in `TrimResult::findings`, e.g. for reports; costs memory
+    /// proportional to the number of lines trimmed
+    pub collect_findings: bool,
+    /// lines ending with this marker, e.g. `# trim:ignore`, are left untouched, as are lines from
+    /// one containing `trim:off` to one containing `trim:on`; no markers are honored if `None`
+    pub ignore_marker: Option<String>,
+    /// inputs with this marker, e.g. `trim: skip-file`, in their first `skip_file_lines` lines are
+    /// left untouched as a whole; no input is skipped if `None`
+    pub skip_file_marker: Option<String>,
+    /// number of lines at the start of an input that are searched for `skip_file_marker`
+    pub skip_file_lines: usize,
+    /// only files with one of these extensions, e.g. `rs` or `md`, or scripts in the language of
+    /// one, are trimmed when trimming a list of files; every file is if empty. See `includes`
+    pub extensions: Vec<String>,
+    /// files matching any of these glob patterns, e.g. `*.min.js`, are left out when trimming a
+    /// list of files, whatever their extension, as are the files under directories matching any
+    /// when walking them; see `excludes`
+    #[serde(
+        serialize_with = "serialize_patterns",
+        deserialize_with = "deserialize_patterns"
+    )]
+    pub exclude: Vec<Pattern>,
+    /// files modified less than this long ago, e.g. logs still being written, are skipped when
+    /// trimming a list of files; see `SkipReason::Recent`. No file is skipped for its age if `None`
+    pub older_than: Option<Duration>,
+    /// files last modified before this time are skipped when trimming a list of files, e.g. to
+    /// only trim what changed since the last run; see `SkipReason::Old`. No file is skipped for
+    /// its age if `None`
+    pub newer_than: Option<SystemTime>,
+    /// files not owned by this user or group are skipped when trimming a list of files, e.g. to
+    /// leave the files of other users alone; see `SkipReason::NotOwned`. No file is skipped for
+    /// its owner if `None`
+    pub owned_by: Option<Owner>,
+    /// files that trimming would shrink by more than this percentage of their size are skipped
+    /// when trimming a list of files, as they are likely not line-oriented text; see
+    /// `SkipReason::Shrinks`. Inputs that hold nothing but whitespace are exempt, see
+    /// `empty_file`. Files may shrink by any amount if `None`
+    pub max_shrink_percent: Option<f64>,
+    /// what becomes of a file whose owner, group or permissions cannot be kept when it is
+    /// replaced by its trimmed copy, e.g. when not running as root over files of other users
+    pub metadata: MetadataPolicy,
+    /// when trimming a list of files, pipe the trimmed content of each file through this command
+    /// before writing it, e.g. for a custom normalization; such files are held in memory as a
+    /// whole. `TrimResult::bytes_saved` only counts what trimming saved. Nothing is piped if
+    /// `None`
+    pub filter: Option<Filter>,
+    /// when trimming a list of files, check that each trimmed file is the file with nothing but
+    /// whitespace and linebreaks deleted, before it is written, and fail it otherwise; see
+    /// `invariants::check_deletions`. Costs reading each modified file and its trim again, and is
+    /// skipped with `rules`, a `filter`, `strip_ansi` or `control_chars`, which may change more
+    /// than whitespace by design
+    pub paranoid: bool,
+    /// order in which files are handed to workers when trimming a list of files
+    pub schedule: Schedule,
+    /// budget shared by every read of an input, to cap the IO of a run; reads are not limited if
+    /// `None`
+    #[serde(skip)]
+    pub throttle: Option<Arc<Throttle>>,
+    /// files that other processes have open for writing are skipped when trimming in-place, so
+    /// that their writes are not lost; no file is checked if `None`
+    #[serde(skip)]
+    pub writers: Option<Arc<Writers>>,
+    /// when trimming a list of files, abandon every file not trimmed yet once cancelled, leaving
+    /// it untouched; see `SkipReason::Cancelled`. The run cannot be cancelled if `None`
+    #[serde(skip)]
+    pub cancel: Option<CancellationToken>,
+    /// when trimming a list of files, totals updated as each file is done, e.g. to report on a
+    /// long run while it goes; nothing is counted if `None`
+    #[serde(skip)]
+    pub stats: Option<Arc<RunStats>>,
+    /// when trimming a list of files, a random sample of the modified files, kept to be checked
+    /// again once the run is done; see `Audit::verify`. Nothing is sampled if `None`
+    #[serde(skip)]
+    pub audit: Option<Arc<Audit>>,
+    /// when trimming a list of files, write the trimmed copies to this directory, under the same
+    /// relative paths, instead of replacing the files; see `util::mirror_path`
+    pub out_dir: Option<PathBuf>,
+}
+
+impl TrimOptions {
+    /// # Returns
+    ///
+    /// The options of the canonical form of content; see `into_canonical`.
+    #[inline]
+    pub fn canonical() -> Self {
+        TrimOptions::default().into_canonical()
+    }
+
+    /// Override every option that affects the trimmed content, so that it is in canonical form,
+    /// e.g. for hashing or diffing: ASCII whitespace trimmed from the end of every line, `\n` line
+    /// endings, and a single `\n` at the end, even of empty content. No markers are honored and no
+    /// rules are applied.
+    ///
+    /// The canonical form is guaranteed to stay the same across versions. This is why it trims
+    /// `Whitespace::Ascii`, whose definition is fixed, rather than `Whitespace::Unicode`.
+    ///
+    /// # Returns
+    ///
+    /// These options, with those that only affect how the content is read or written, such as
+    /// `max_memory`, left as they are.
+    pub fn into_canonical(self) -> Self {
+        TrimOptions {
+            suppress_newline: false,
+            final_newlines: None,
+            empty_newline: true,
+            empty_file: EmptyFile::Keep,
+            whitespace: Whitespace::Ascii,
+            keep_form_feed: false,
+            keep_crlf: false,
+            strip_ansi: false,
+            control_chars: ControlChars::Keep,
+            record_sep: None,
+            min_run: 1,
+            max_lines: None,
+            rules: Vec::new(),
+            ignore_marker: None,
+            skip_file_marker: None,
+            ..self
+        }
+    }
+
+    /// # Returns
+    ///
+    /// Number of linebreaks to end the output with; see `final_newlines`.
+    #[inline]
+    pub fn final_newline_count(&self) -> usize {
+        match (self.final_newlines, self.suppress_newline) {
+            (Some(count), _) => count,
+            (None, true) => 0,
+            (None, false) => 1,
+        }
+    }
+
+    /// # Returns
+    ///
+    /// The byte that ends a record; `\n` unless `record_sep` is set.
+    #[inline]
+    pub fn separator(&self) -> u8 {
+        self.record_sep.unwrap_or(b'\n')
+    }
+
+    /// # Parameters
+    ///
+    /// 1. `preferred` -- size of the buffer if memory is not bounded
+    /// 1. `workers` -- number of such buffers that may be alive at the same time
+    ///
+    /// # Returns
+    ///
+    /// `preferred`, or less if that many buffers would not fit in `max_memory`; at least 1.
+    #[inline]
+    pub fn buffer_size(&self, preferred: usize, workers: usize) -> usize {
+        match self.max_memory {
+            Some(max_memory) => preferred.min(max_memory / workers.max(1)).max(1),
+            None => preferred,
+        }
+    }
+
+    /// # Returns
+    ///
+    /// `true` if `path` has one of `extensions`, or is a script without an extension in the
+    /// language of one, e.g. `bin/deploy` starting with `#!/usr/bin/env python3` for `py`, see
+    /// `language::script_language`; or if `extensions` is empty. `false` otherwise, or if
+    /// `excludes` its name; the directories it is under are left to `util::walk_files`.
+    pub fn includes(&self, path: &Path) -> bool {
+        if path
+            .file_name()
+            .is_some_and(|name| self.excludes(Path::new(name)))
+        {
+            return false;
+        }
+        let allowed = |ext: &OsStr| {
+            self.extensions
+                .iter()
+                .any(|allowed| ext == allowed.trim_start_matches('.'))
+        };
+        self.extensions.is_empty()
+            || match path.extension() {
+                Some(ext) => allowed(ext),
+                None => script_language(path).is_some_and(|language| allowed(language.as_ref())),
+            }
+    }
+
+    /// # Returns
+    ///
+    /// `true` if a pattern of `exclude` matches `relative`, a file or directory relative to the
+    /// directory being walked, as `.gitignore` patterns do: a pattern without a `/`, e.g.
+    /// `*.min.js` or `vendor`, matches its name, and one with a `/`, e.g. `vendor/**`, matches all
+    /// of `relative`. `*` does not match across a `/`. `false` otherwise.
+    ///
+    /// The directories `relative` is under are not matched again; a walk leaves out everything
+    /// under the directories it excludes, see `util::walk_files`.
+    pub fn excludes(&self, relative: &Path) -> bool {
+        let name = match relative.file_name() {
+            Some(name) if !self.exclude.is_empty() => name.to_string_lossy(),
+            _ => return false,
+        };
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        let relative = relative
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        self.exclude
+            .iter()
+            .any(|pattern| match pattern.as_str().contains('/') {
+                true => pattern.matches_with(&relative, options),
+                false => pattern.matches_with(&name, options),
+            })
+    }
+
+    /// # Returns
+    ///
+    /// `true` if `len` bytes of content can be held in memory along with their trimmed copy,
+    /// `false` otherwise.
+    #[inline]
+    pub fn fits_in_memory(&self, len: u64) -> bool {
+        match self.max_memory {
+            Some(max_memory) => len.saturating_mul(2) <= max_memory as u64,
+            None => true,
+        }
+    }
+}
+
+/// Serialize `rules` by their names.
+fn serialize_rules<S>(rules: &[Arc<dyn LineRule>], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(rules.iter().map(|rule| rule.name()))
+}
+
+/// Deserialize rules from their names; see `rules::builtin`.
+fn deserialize_rules<'de, D>(deserializer: D) -> Result<Vec<Arc<dyn LineRule>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|name| {
+            builtin(name).ok_or_else(|| de::Error::custom(format!("unknown rule {:?}", name)))
+        })
+        .collect()
+}
+
+/// Serialize glob patterns as the strings they were compiled from.
+fn serialize_patterns<S>(patterns: &[Pattern], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(patterns.iter().map(Pattern::as_str))
+}
+
+/// Deserialize glob patterns from strings, compiling each.
+fn deserialize_patterns<'de, D>(deserializer: D) -> Result<Vec<Pattern>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|pattern| {
+            Pattern::new(pattern)
+                .map_err(|err| de::Error::custom(format!("invalid pattern {:?}: {}", pattern, err)))
+        })
+        .collect()
+}
+
+/// Parse a size in bytes, e.g. for `TrimOptions::max_memory`, with an optional binary suffix: `K`,
+/// `M` or `G`.
+///
+/// # Returns
+///
+/// The number of bytes, or a description of why `s` is not a size.
+pub fn parse_size(s: &str) -> Result<usize, String> {
+    let upper = s.trim().to_ascii_uppercase();
+    let (digits, shift) = match upper.as_bytes().last() {
+        Some(b'K') => (&upper[..upper.len() - 1], 10),
+        Some(b'M') => (&upper[..upper.len() - 1], 20),
+        Some(b'G') => (&upper[..upper.len() - 1], 30),
+        _ => (&upper[..], 0),
+    };
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(1 << shift))
+        .ok_or_else(|| format!("expected a size such as 4096, 64K or 1G, got {:?}", s))
+}
+
+/// Parse a duration, e.g. for `TrimOptions::older_than`: a whole number with an optional unit
+/// suffix, `s`, `m`, `h`, `d` or `w`, seconds if there is none.
+///
+/// # Returns
+///
+/// The duration, or a description of why `s` is not a duration.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let trimmed = s.trim();
+    let (digits, secs) = match trimmed.as_bytes().last() {
+        Some(b's') => (&trimmed[..trimmed.len() - 1], 1),
+        Some(b'm') => (&trimmed[..trimmed.len() - 1], 60),
+        Some(b'h') => (&trimmed[..trimmed.len() - 1], 60 * 60),
+        Some(b'd') => (&trimmed[..trimmed.len() - 1], 24 * 60 * 60),
+        Some(b'w') => (&trimmed[..trimmed.len() - 1], 7 * 24 * 60 * 60),
+        _ => (trimmed, 1),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(secs))
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("expected a duration such as 30s, 90m or 7d, got {:?}", s))
+}
+
+/// Parse a point in time, e.g. for `TrimOptions::newer_than`: either a duration before now, see
+/// `parse_duration`, a date and time in UTC such as `2024-01-31`, `2024-01-31T12:00:00` or
+/// `2024-01-31T12:00:00Z`, or seconds since the Unix epoch such as `@1706702400`.
+///
+/// # Returns
+///
+/// The point in time, or a description of why `s` is not one.
+pub fn parse_since(s: &str) -> Result<SystemTime, String> {
+    let trimmed = s.trim();
+    let secs = match trimmed.strip_prefix('@') {
+        Some(digits) => digits.parse::<u64>().ok(),
+        None if trimmed.contains('-') => parse_utc(trimmed),
+        None => {
+            return parse_duration(trimmed).and_then(|duration| {
+                SystemTime::now()
+                    .checked_sub(duration)
+                    .ok_or_else(|| format!("{:?} reaches too far back", s))
+            })
+        }
+    };
+    secs.map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+        .ok_or_else(|| {
+            format!(
+                "expected a duration such as 7d, a time such as {} or {}, got {:?}",
+                "2024-01-31T12:00:00Z", "@1706702400", s
+            )
+        })
+}
+
+/// # Returns
+///
+/// Seconds since the Unix epoch of `s`, a date with an optional time of day, in UTC, such as
+/// `2024-01-31T12:00:00Z`; `None` if it is not one, or is before the epoch.
+fn parse_utc(s: &str) -> Option<u64> {
+    let s = s.strip_suffix('Z').unwrap_or(s);
+    let (date, time) = match s.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (s, None),
+    };
+    let fields = |s: &str, sep| -> Option<Vec<u64>> {
+        s.split(sep)
+            .map(|field| field.parse::<u64>().ok())
+            .collect()
+    };
+    let (year, month, day) = match fields(date, '-')?[..] {
+        [year, month, day] if (1..=12).contains(&month) && (1..=31).contains(&day) => {
+            (year, month, day)
+        }
+        _ => return None,
+    };
+    let secs_of_day = match time.map(|time| fields(time, ':')) {
+        None => 0,
+        Some(Some(hms)) => match hms[..] {
+            [h, m] if h < 24 && m < 60 => (h * 60 + m) * 60,
+            [h, m, sec] if h < 24 && m < 60 && sec < 60 => (h * 60 + m) * 60 + sec,
+            _ => return None,
+        },
+        Some(None) => return None,
+    };
+    // days since the epoch of a date in the proleptic Gregorian calendar, from Howard Hinnant's
+    // `days_from_civil`, with years starting in March so that leap days come last
+    let (year, month) = match month {
+        1 | 2 => (year.checked_sub(1)?, month + 9),
+        _ => (year, month - 3),
+    };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146_097 + day_of_era).checked_sub(719_468)?;
+    Some(days * 24 * 60 * 60 + secs_of_day)
+}
+
+/// Parse a record separator, e.g. for `TrimOptions::record_sep`: a single ASCII character, or
+/// one of the escapes `\0`, `\t`, `\n` and `\\`.
+///
+/// # Returns
+///
+/// The separator, or a description of why `s` is not one.
+pub fn parse_record_sep(s: &str) -> Result<u8, String> {
+    match s.as_bytes() {
+        [b'\\', b'0'] => Ok(b'\0'),
+        [b'\\', b't'] => Ok(b'\t'),
+        [b'\\', b'n'] => Ok(b'\n'),
+        [b'\\', b'\\'] => Ok(b'\\'),
+        [byte] if byte.is_ascii() => Ok(*byte),
+        _ => Err(format!(
+            "expected a single ASCII character such as ; or \\0, got {:?}",
+            s
+        )),
+    }
+}
+
+/// Order in which files are handed to workers when trimming a list of files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Schedule {
+    /// largest files first, so that a huge file does not hold up the end of a run on its own
+    #[default]
+    Size,
+    /// in the order they were given, for runs that must be reproducible
+    Input,
+}
+
+impl Schedule {
+    /// Values accepted by `FromStr`, in the order they should be listed to the user.
+    pub const VARIANTS: &'static [&'static str] = &["size", "input"];
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "size" => Ok(Schedule::Size),
+            "input" => Ok(Schedule::Input),
+            _ => Err(format!(
+                "expected one of {:?}, got {:?}",
+                Schedule::VARIANTS,
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Schedule::Size => write!(f, "size"),
+            Schedule::Input => write!(f, "input"),
+        }
+    }
+}
+
+/// What becomes of an input that holds nothing but whitespace, e.g. a junk placeholder file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmptyFile {
+    /// trim it like any other input, down to the final linebreaks
+    #[default]
+    Keep,
+    /// trim it down to nothing at all, final linebreaks included
+    Truncate,
+    /// delete it, when trimming a list of files; truncate it otherwise
+    Delete,
+}
+
+impl EmptyFile {
+    /// Values accepted by `FromStr`, in the order they should be listed to the user.
+    pub const VARIANTS: &'static [&'static str] = &["keep", "truncate", "delete"];
+
+    /// # Returns
+    ///
+    /// What was done to such an input, for summaries, e.g. `deleted`.
+    pub fn done(self) -> &'static str {
+        match self {
+            EmptyFile::Keep => "kept",
+            EmptyFile::Truncate => "truncated",
+            EmptyFile::Delete => "deleted",
+        }
+    }
+}
+
+impl FromStr for EmptyFile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(EmptyFile::Keep),
+            "truncate" => Ok(EmptyFile::Truncate),
+            "delete" => Ok(EmptyFile::Delete),
+            _ => Err(format!(
+                "expected one of {:?}, got {:?}",
+                EmptyFile::VARIANTS,
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for EmptyFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmptyFile::Keep => write!(f, "keep"),
+            EmptyFile::Truncate => write!(f, "truncate"),
+            EmptyFile::Delete => write!(f, "delete"),
+        }
+    }
+}
+
+/// What becomes of a file whose owner, group or permissions cannot be given to its trimmed copy;
+/// see `MetadataPolicy::copy`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MetadataPolicy {
+    /// it is trimmed anyway, and warned of; see `Warning::MetadataNotKept`
+    #[default]
+    BestEffort,
+    /// it fails, and is left untouched
+    Strict,
+}
+
+impl MetadataPolicy {
+    /// Values accepted by `FromStr`, in the order they should be listed to the user.
+    pub const VARIANTS: &'static [&'static str] = &["best-effort", "strict"];
+
+    /// Give `file` the permissions, owner and group of `metadata`, as far as this policy
+    /// requires; see `util::copy_permissions`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if they were all given, `false` if some could not be with `BestEffort`. An error if
+    /// some could not be with `Strict`.
+    pub fn copy(self, metadata: &Metadata, file: &File) -> io::Result<bool> {
+        match (copy_permissions(metadata, file), self) {
+            (Ok(()), _) => Ok(true),
+            (Err(_), MetadataPolicy::BestEffort) => Ok(false),
+            (Err(err), MetadataPolicy::Strict) => Err(io::Error::new(
+                err.kind(),
+                format!("cannot keep its owner, group or permissions: {}", err),
+            )),
+        }
+    }
+}
+
+impl FromStr for MetadataPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "best-effort" => Ok(MetadataPolicy::BestEffort),
+            "strict" => Ok(MetadataPolicy::Strict),
+            _ => Err(format!(
+                "expected one of {:?}, got {:?}",
+                MetadataPolicy::VARIANTS,
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MetadataPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MetadataPolicy::BestEffort => write!(f, "best-effort"),
+            MetadataPolicy::Strict => write!(f, "strict"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod parse_size {
+        use super::*;
+
+        #[test]
+        fn parametrized_parse_size() {
+            let test_data = vec![
+                ("0", Some(0)),
+                ("4096", Some(4096)),
+                ("64K", Some(64 << 10)),
+                ("64k", Some(64 << 10)),
+                ("3M", Some(3 << 20)),
+                ("1G", Some(1 << 30)),
+                ("", None),
+                ("K", None),
+                ("-1", None),
+                ("1T", None),
+            ];
+            for (input, expected) in test_data {
+                assert_eq!(expected, parse_size(input).ok());
+            }
+        }
+    }
+
+    #[test]
+    fn parametrized_parse_duration() {
+        let test_data = vec![
+            ("0", Some(0)),
+            ("30", Some(30)),
+            ("30s", Some(30)),
+            ("90m", Some(90 * 60)),
+            ("12h", Some(12 * 60 * 60)),
+            ("7d", Some(7 * 24 * 60 * 60)),
+            ("2w", Some(14 * 24 * 60 * 60)),
+            ("", None),
+            ("d", None),
+            ("-1d", None),
+            ("1y", None),
+            ("1.5h", None),
+        ];
+        for (input, expected) in test_data {
+            assert_eq!(
+                expected.map(Duration::from_secs),
+                parse_duration(input).ok(),
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn parametrized_parse_since() {
+        let test_data = vec![
+            ("@0", Some(0)),
+            ("@1706702400", Some(1_706_702_400)),
+            ("1970-01-01", Some(0)),
+            ("2000-03-01", Some(951_868_800)),
+            ("2024-01-31T12:00:00Z", Some(1_706_702_400)),
+            ("2024-01-31T12:00", Some(1_706_702_400)),
+            ("2024-02-29 23:59:59", Some(1_709_251_199)),
+            ("1969-12-31", None),
+            ("2024-13-01", None),
+            ("2024-01-31T24:00", None),
+            ("2024-01", None),
+            ("@-1", None),
+            ("yesterday", None),
+        ];
+        for (input, expected) in test_data {
+            assert_eq!(
+                expected.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+                parse_since(input).ok(),
+                "{:?}",
+                input
+            );
+        }
+
+        // relative to now
+        let since = parse_since("1h").unwrap();
+        let ago = since.elapsed().unwrap();
+        assert!(ago >= Duration::from_secs(60 * 60) && ago < Duration::from_secs(2 * 60 * 60));
+    }
+
+    #[test]
+    fn parametrized_parse_record_sep() {
+        let test_data = vec![
+            (";", Some(b';')),
+            ("\\0", Some(b'\0')),
+            ("\\t", Some(b'\t')),
+            ("\\\\", Some(b'\\')),
+            ("\\", Some(b'\\')),
+            ("", None),
+            (";;", None),
+            ("é", None),
+        ];
+        for (input, expected) in test_data {
+            assert_eq!(expected, parse_record_sep(input).ok());
+        }
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let options = TrimOptions {
+            whitespace: Whitespace::Ascii,
+            record_sep: Some(b';'),
+            rules: vec![builtin("collapse-comment-space").unwrap()],
+            ignore_marker: Some("trim:ignore".to_string()),
+            schedule: Schedule::Input,
+            throttle: Some(Arc::new(Throttle::new(1))),
+            ..TrimOptions::default()
+        };
+        let value = serde_json::to_value(&options).unwrap();
+        assert_eq!("ascii", value["whitespace"]);
+        assert_eq!(
+            serde_json::json!(["collapse-comment-space"]),
+            value["rules"]
+        );
+        assert!(value.get("throttle").is_none());
+
+        let parsed: TrimOptions = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(value, serde_json::to_value(&parsed).unwrap());
+        assert!(parsed.throttle.is_none());
+    }
+
+    #[test]
+    fn serde_partial_and_invalid() {
+        let parsed: TrimOptions = serde_json::from_str(r#"{ "min_run": 2 }"#).unwrap();
+        assert_eq!(2, parsed.min_run);
+        assert_eq!(Whitespace::Unicode, parsed.whitespace);
+
+        let invalid = [
+            r#"{ "rules": ["trailing-whitespace"] }"#,
+            r#"{ "whitespace": "tabs" }"#,
+            r#"{ "min_runs": 2 }"#,
+        ];
+        for json in invalid {
+            assert!(serde_json::from_str::<TrimOptions>(json).is_err());
+        }
+    }
+
+    #[test]
+    fn buffer_size() {
+        let unbounded = TrimOptions::default();
+        assert_eq!(1 << 13, unbounded.buffer_size(1 << 13, 4));
+
+        let bounded = TrimOptions {
+            max_memory: Some(1 << 12),
+            ..TrimOptions::default()
+        };
+        assert_eq!(1 << 10, bounded.buffer_size(1 << 13, 4));
+        assert_eq!(1 << 8, bounded.buffer_size(1 << 8, 4));
+        assert_eq!(1, bounded.buffer_size(1 << 13, 1 << 20));
+        assert!(bounded.fits_in_memory(1 << 11));
+        assert!(!bounded.fits_in_memory((1 << 11) + 1));
+    }
+
+    #[test]
+    fn includes() {
+        assert!(TrimOptions::default().includes(Path::new("Makefile")));
+
+        let options = TrimOptions {
+            extensions: vec!["rs".to_string(), ".md".to_string()],
+            ..TrimOptions::default()
+        };
+        assert!(options.includes(Path::new("src/lib.rs")));
+        assert!(options.includes(Path::new("README.md")));
+        assert!(!options.includes(Path::new("Cargo.toml")));
+        assert!(!options.includes(Path::new("Makefile")));
+        assert!(!options.includes(Path::new("rs")));
+
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let script = std::env::temp_dir().join(prefix);
+        std::fs::write(&script, "#!/usr/bin/env python3\n").unwrap();
+        let options = TrimOptions {
+            extensions: vec!["py".to_string()],
+            ..TrimOptions::default()
+        };
+        assert!(options.includes(&script));
+        std::fs::remove_file(&script).unwrap();
+
+        let options = TrimOptions {
+            exclude: vec![
+                Pattern::new("*.min.js").unwrap(),
+                Pattern::new("vendor/**").unwrap(),
+            ],
+            ..TrimOptions::default()
+        };
+        assert!(options.includes(Path::new("web/app.js")));
+        assert!(!options.includes(Path::new("web/app.min.js")));
+        assert!(options.excludes(Path::new("vendor/a/b.rs")));
+        assert!(!options.excludes(Path::new("src/vendor/b.rs")));
+        assert!(!options.excludes(Path::new("vendored/b.rs")));
+        // the directories a listed file is under are not matched
+        assert!(options.includes(Path::new("vendor/a/b.rs")));
+        let directory = TrimOptions {
+            exclude: vec![
+                Pattern::new("vendor").unwrap(),
+                Pattern::new("a/b").unwrap(),
+            ],
+            ..TrimOptions::default()
+        };
+        assert!(directory.excludes(Path::new("src/vendor")));
+        assert!(directory.excludes(Path::new("a/b")));
+        assert!(!directory.excludes(Path::new("src/a/b")));
+        assert!(!directory.excludes(Path::new("vendored")));
+        // `extensions` do not bring excluded files back
+        let options = TrimOptions {
+            extensions: vec!["js".to_string()],
+            ..options
+        };
+        assert!(!options.includes(Path::new("app.min.js")));
+    }
+}
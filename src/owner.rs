@@ -0,0 +1,140 @@
+//! Ownership of files, for admins trimming shared directories, who must leave the files of other
+//! users alone.
+
+use serde::Deserialize;
+use serde::Serialize;
+use std::fs::read_to_string;
+use std::fs::Metadata;
+use std::str::FromStr;
+
+/// A user, a group, or both, that a file must be owned by to be trimmed; see
+/// `TrimOptions::owned_by`.
+///
+/// Only Unix has owners; elsewhere, `FromStr` always fails.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Owner {
+    /// id of the user that must own the file, any user if `None`
+    pub uid: Option<u32>,
+    /// id of the group that must own the file, any group if `None`
+    pub gid: Option<u32>,
+}
+
+impl Owner {
+    /// # Returns
+    ///
+    /// `true` if the file with `metadata` is owned by this user and group, `false` otherwise.
+    #[cfg(unix)]
+    pub fn owns(&self, metadata: &Metadata) -> bool {
+        use std::os::unix::fs::MetadataExt;
+
+        self.uid.is_none_or(|uid| uid == metadata.uid())
+            && self.gid.is_none_or(|gid| gid == metadata.gid())
+    }
+
+    /// # Returns
+    ///
+    /// `true`; files have no owners on this platform.
+    #[cfg(not(unix))]
+    pub fn owns(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+}
+
+/// Parse `USER`, `USER:GROUP` or `:GROUP`, each either a name or a numeric id. Names are looked
+/// up in `/etc/passwd` and `/etc/group`; users and groups only known to a directory service must
+/// be given by id.
+///
+/// The files are read directly rather than through `getpwnam(3)`, which cannot load NSS modules
+/// in static builds, and has none to load with musl; so every build resolves names the same.
+impl FromStr for Owner {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if cfg!(not(unix)) {
+            return Err("files have no owners on this platform".to_string());
+        }
+        let (user, group) = match s.split_once(':') {
+            Some((user, group)) => (user, group),
+            None => (s, ""),
+        };
+        let lookup = |database, name: &str| match name {
+            "" => Ok(None),
+            _ => lookup_id(database, name)
+                .map(Some)
+                .ok_or_else(|| format!("no {:?} in {}", name, database)),
+        };
+        match (lookup("/etc/passwd", user)?, lookup("/etc/group", group)?) {
+            (None, None) => Err(format!("expected USER, USER:GROUP or :GROUP, got {:?}", s)),
+            (uid, gid) => Ok(Owner { uid, gid }),
+        }
+    }
+}
+
+/// # Parameters
+///
+/// 1. `database` -- path to a database such as `/etc/passwd`, with an entry per line whose first
+///    field is the name, and whose third field is the id
+/// 1. `name` -- the name to look up, or a numeric id
+///
+/// # Returns
+///
+/// The id of `name`, `None` if it is not found.
+fn lookup_id(database: &str, name: &str) -> Option<u32> {
+    if let Ok(id) = name.parse() {
+        return Some(id);
+    }
+    read_to_string(database).ok()?.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        match fields.next() == Some(name) {
+            true => fields.nth(1)?.parse().ok(),
+            false => None,
+        }
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::util::mktemp;
+    use std::fs::metadata;
+
+    #[test]
+    fn parametrized_from_str() {
+        let test_data = [
+            ("0", Some((Some(0), None))),
+            ("root", Some((Some(0), None))),
+            ("root:0", Some((Some(0), Some(0)))),
+            (":0", Some((None, Some(0)))),
+            ("1000:", Some((Some(1000), None))),
+            ("", None),
+            (":", None),
+            ("no-such-user-of-trim", None),
+            ("0:no-such-group-of-trim", None),
+        ];
+        for (input, expected) in test_data {
+            let expected = expected.map(|(uid, gid)| Owner { uid, gid });
+            assert_eq!(expected, input.parse::<Owner>().ok(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn owns() {
+        use std::os::unix::fs::MetadataExt;
+
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let path = mktemp(&prefix, &"abc  \n").unwrap();
+        let path_metadata = metadata(&path).unwrap();
+        let (uid, gid) = (path_metadata.uid(), path_metadata.gid());
+
+        let test_data = [
+            (Some(uid), None, true),
+            (None, Some(gid), true),
+            (Some(uid), Some(gid), true),
+            (Some(uid + 1), None, false),
+            (Some(uid), Some(gid + 1), false),
+        ];
+        for (uid, gid, expected) in test_data {
+            assert_eq!(expected, Owner { uid, gid }.owns(&path_metadata));
+        }
+    }
+}
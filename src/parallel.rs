@@ -0,0 +1,75 @@
+//! Data parallelism with `rayon`, if the `parallel` feature is on; otherwise the same methods,
+//! e.g. `par_iter`, on plain sequential iterators, so that callers read the same either way and
+//! a minimal build does not link a thread pool.
+
+#[cfg(feature = "parallel")]
+pub use rayon::current_num_threads;
+#[cfg(feature = "parallel")]
+pub use rayon::prelude::*;
+
+#[cfg(not(feature = "parallel"))]
+pub use self::sequential::*;
+
+#[cfg(not(feature = "parallel"))]
+mod sequential {
+    /// # Returns
+    ///
+    /// 1, the only thread that does any work.
+    pub fn current_num_threads() -> usize {
+        1
+    }
+
+    /// `rayon::iter::IntoParallelIterator`, in sequence.
+    pub trait IntoParallelIterator: IntoIterator + Sized {
+        fn into_par_iter(self) -> Self::IntoIter {
+            self.into_iter()
+        }
+    }
+
+    impl<I: IntoIterator> IntoParallelIterator for I {}
+
+    /// `rayon::iter::IntoParallelRefIterator`, in sequence.
+    pub trait IntoParallelRefIterator<'a> {
+        type Iter: Iterator;
+
+        fn par_iter(&'a self) -> Self::Iter;
+    }
+
+    impl<'a, I: 'a + ?Sized> IntoParallelRefIterator<'a> for I
+    where
+        &'a I: IntoIterator,
+    {
+        type Iter = <&'a I as IntoIterator>::IntoIter;
+
+        fn par_iter(&'a self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+
+    /// `rayon::iter::IntoParallelRefMutIterator`, in sequence.
+    pub trait IntoParallelRefMutIterator<'a> {
+        type Iter: Iterator;
+
+        fn par_iter_mut(&'a mut self) -> Self::Iter;
+    }
+
+    impl<'a, I: 'a + ?Sized> IntoParallelRefMutIterator<'a> for I
+    where
+        &'a mut I: IntoIterator,
+    {
+        type Iter = <&'a mut I as IntoIterator>::IntoIter;
+
+        fn par_iter_mut(&'a mut self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+
+    /// `rayon::iter::IndexedParallelIterator`, in sequence, where splitting the work is moot.
+    pub trait IndexedParallelIterator: Iterator + Sized {
+        fn with_max_len(self, _: usize) -> Self {
+            self
+        }
+    }
+
+    impl<I: Iterator> IndexedParallelIterator for I {}
+}
@@ -0,0 +1,284 @@
+//! Patches written by `diff::git_diff`, read back and applied; for reviewing a trim before it is
+//! applied to the tree.
+
+use std::io;
+use std::path::Component;
+use std::path::PathBuf;
+
+/// A line of a hunk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum HunkLine {
+    /// kept as is; must be in the file
+    Context(Vec<u8>),
+    /// removed; must be in the file
+    Remove(Vec<u8>),
+    /// added
+    Add(Vec<u8>),
+}
+
+impl HunkLine {
+    fn bytes_mut(&mut self) -> &mut Vec<u8> {
+        match self {
+            HunkLine::Context(line) | HunkLine::Remove(line) | HunkLine::Add(line) => line,
+        }
+    }
+}
+
+/// A hunk of a file patch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Hunk {
+    /// 0-based line of the file where the hunk starts
+    old_start: usize,
+    lines: Vec<HunkLine>,
+}
+
+/// The changes a patch makes to a single file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FilePatch {
+    /// path of the file, relative to where the patch is applied
+    pub path: PathBuf,
+    hunks: Vec<Hunk>,
+}
+
+/// # Returns
+///
+/// An `io::ErrorKind::InvalidData` error with `message`.
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Parse a patch in the format of `git diff`, as written by `diff::git_diff`.
+///
+/// # Returns
+///
+/// The changes to each file, in order. Patches that create, delete or rename files are an error,
+/// and so are paths that are absolute or climb out with `..`, so that applying a patch never
+/// touches anything outside the directory it is applied in.
+pub fn parse_patch(patch: &[u8]) -> io::Result<Vec<FilePatch>> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut lines = patch
+        .split_inclusive(|&b| b == b'\n')
+        .enumerate()
+        .peekable();
+    // lines of the current hunk still to be read, from the old and the new side
+    let mut remaining = (0, 0);
+
+    while let Some((index, line)) = lines.next() {
+        let at = |message: &str| invalid(format!("{} at line {} of the patch", message, index + 1));
+        let text = line.strip_suffix(b"\n").unwrap_or(line);
+
+        if remaining != (0, 0) {
+            let hunk = files
+                .last_mut()
+                .and_then(|file| file.hunks.last_mut())
+                .ok_or_else(|| at("hunk without a header"))?;
+            let (kind, rest) = match line.split_first() {
+                Some(split) => split,
+                None => return Err(at("empty hunk line")),
+            };
+            let (line, counts) = match kind {
+                b' ' => (HunkLine::Context(rest.to_vec()), (1, 1)),
+                b'-' => (HunkLine::Remove(rest.to_vec()), (1, 0)),
+                b'+' => (HunkLine::Add(rest.to_vec()), (0, 1)),
+                _ => return Err(at("unexpected hunk line")),
+            };
+            if remaining.0 < counts.0 || remaining.1 < counts.1 {
+                return Err(at("hunk longer than its header says"));
+            }
+            remaining = (remaining.0 - counts.0, remaining.1 - counts.1);
+            hunk.lines.push(line);
+            // the line just read has no `\n` at the end of the file
+            if let Some((_, next)) = lines.peek() {
+                if next.starts_with(b"\\") {
+                    if let Some(line) = hunk.lines.last_mut().map(HunkLine::bytes_mut) {
+                        if line.last() == Some(&b'\n') {
+                            line.pop();
+                        }
+                    }
+                    lines.next();
+                }
+            }
+        } else if text.starts_with(b"@@") {
+            let file = files.last_mut().ok_or_else(|| at("hunk before any file"))?;
+            let (old_start, old_count, new_count) =
+                parse_hunk_header(text).ok_or_else(|| at("malformed hunk header"))?;
+            file.hunks.push(Hunk {
+                old_start,
+                lines: Vec::new(),
+            });
+            remaining = (old_count, new_count);
+        } else if let Some(old) = text.strip_prefix(b"--- ") {
+            let new = match lines.next() {
+                Some((_, new)) => new.strip_suffix(b"\n").unwrap_or(new),
+                None => return Err(at("`---` without `+++`")),
+            };
+            let new = new
+                .strip_prefix(b"+++ ")
+                .ok_or_else(|| at("`---` without `+++`"))?;
+            let (old, new) = (String::from_utf8_lossy(old), String::from_utf8_lossy(new));
+            let path = match (old.strip_prefix("a/"), new.strip_prefix("b/")) {
+                (Some(old), Some(new)) if old == new => safe_path(new),
+                _ => None,
+            };
+            let path = path.ok_or_else(|| {
+                let message = format!("only changes to files in place are supported, not {}", new);
+                at(&message)
+            })?;
+            files.push(FilePatch {
+                path,
+                hunks: Vec::new(),
+            });
+        }
+        // anything else, e.g. `diff --git` or `index` lines, says nothing that is needed
+    }
+    if remaining != (0, 0) {
+        return Err(invalid(
+            "the patch ends in the middle of a hunk".to_string(),
+        ));
+    }
+    Ok(files)
+}
+
+/// # Returns
+///
+/// The 0-based line where a hunk starts in the old content, and the number of lines it spans in
+/// the old and the new content, from a hunk header; `None` if it is malformed.
+fn parse_hunk_header(header: &[u8]) -> Option<(usize, usize, usize)> {
+    let header = std::str::from_utf8(header).ok()?.strip_prefix("@@ -")?;
+    let (ranges, _) = header.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let range = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let ((old_start, old_count), (_, new_count)) = (range(old)?, range(new)?);
+    // an empty range is named by the line before it
+    let old_start = match old_count {
+        0 => old_start,
+        _ => old_start.checked_sub(1)?,
+    };
+    Some((old_start, old_count, new_count))
+}
+
+/// # Returns
+///
+/// `path` if it is relative and stays within the directory it is relative to, `None` otherwise.
+fn safe_path(path: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(path);
+    let safe = path.components().all(|c| matches!(c, Component::Normal(_)));
+    match safe && !path.as_os_str().is_empty() {
+        true => Some(path),
+        false => None,
+    }
+}
+
+impl FilePatch {
+    /// # Returns
+    ///
+    /// `old`, the content of the file, with this patch applied; an error if the lines the patch
+    /// keeps or removes are not exactly where it says.
+    pub fn apply(&self, old: &[u8]) -> io::Result<Vec<u8>> {
+        let old_lines: Vec<&[u8]> = old.split_inclusive(|&b| b == b'\n').collect();
+        let mut new = Vec::with_capacity(old.len());
+        let mut next = 0;
+
+        for hunk in &self.hunks {
+            let does_not_apply = || {
+                invalid(format!(
+                    "the patch does not apply to {:?} at line {}",
+                    self.path,
+                    hunk.old_start + 1
+                ))
+            };
+            if hunk.old_start < next || hunk.old_start > old_lines.len() {
+                return Err(does_not_apply());
+            }
+            old_lines[next..hunk.old_start]
+                .iter()
+                .for_each(|line| new.extend_from_slice(line));
+            next = hunk.old_start;
+
+            for line in &hunk.lines {
+                match line {
+                    HunkLine::Context(expected) | HunkLine::Remove(expected) => {
+                        if old_lines.get(next) != Some(&&expected[..]) {
+                            return Err(does_not_apply());
+                        }
+                        if let HunkLine::Context(_) = line {
+                            new.extend_from_slice(expected);
+                        }
+                        next += 1;
+                    }
+                    HunkLine::Add(added) => new.extend_from_slice(added),
+                }
+            }
+        }
+        old_lines[next..]
+            .iter()
+            .for_each(|line| new.extend_from_slice(line));
+        Ok(new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::git_diff;
+    use std::path::Path;
+
+    /// # Returns
+    ///
+    /// A patch from `old` to `new` for the file under `path`, as `git_diff` writes it.
+    fn patch(old: &str, new: &str, path: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        git_diff(old.as_bytes(), new.as_bytes(), path, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn parametrized_round_trip() {
+        let far: String = (1..=20).map(|i| format!("{} \n", i)).collect();
+        let test_data = vec![
+            ("a \nb\n".to_string(), "a\nb\n".to_string()),
+            ("a\nb  \n\n\n".to_string(), "a\nb\n".to_string()),
+            ("a\nb ".to_string(), "a\nb\n".to_string()),
+            ("a\nb\n".to_string(), "a\nb".to_string()),
+            ("".to_string(), "a\n".to_string()),
+            (far.clone(), far.replace(" \n", "\n")),
+            (
+                far.clone(),
+                far.replacen("1 \n", "1\n", 1).replacen("20 \n", "20\n", 1),
+            ),
+        ];
+        for (old, new) in test_data {
+            let files = parse_patch(&patch(&old, &new, "src/x.rs")).unwrap();
+            assert_eq!(1, files.len());
+            assert_eq!(Path::new("src/x.rs"), files[0].path);
+            assert_eq!(new.as_bytes(), &files[0].apply(old.as_bytes()).unwrap()[..]);
+        }
+    }
+
+    #[test]
+    fn does_not_apply_to_other_content() {
+        let files = parse_patch(&patch("a \nb\n", "a\nb\n", "x")).unwrap();
+        let err = files[0].apply(b"c \nb\n").unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+
+    #[test]
+    fn rejects_unsafe_and_malformed_patches() {
+        let test_data = vec![
+            patch("a \n", "a\n", "../x"),
+            patch("a \n", "a\n", "/etc/x"),
+            b"--- a/x\n+++ /dev/null\n@@ -1 +0,0 @@\n-a\n".to_vec(),
+            b"--- a/x\n+++ b/x\n@@ -1 +1 @@\n-a \n".to_vec(),
+            b"--- a/x\n+++ b/x\n@@ nonsense @@\n".to_vec(),
+        ];
+        for patch in test_data {
+            let err = parse_patch(&patch).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Regular expressions, for matching lines such as the boundaries of `--split-by`, with the
+//! syntax of the `regex` crate; matching takes time linear in the length of the line, whatever the
+//! pattern.
+
+use regex::Regex;
+use std::fmt;
+use std::str::FromStr;
+
+/// A compiled regular expression; see the module for its syntax.
+#[derive(Clone, Debug)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    /// # Returns
+    ///
+    /// `true` if the pattern matches anywhere in `text`, `false` otherwise.
+    pub fn is_match(&self, text: &str) -> bool {
+        self.0.is_match(text)
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for Pattern {}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Regex::new(s)
+            .map(Pattern)
+            .map_err(|err| format!("invalid pattern {:?}: {}", s, err))
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parametrized_is_match() {
+        let test_data = [
+            ("abc", "xabcx", true),
+            ("abc", "ab", false),
+            ("^abc", "xabc", false),
+            ("abc$", "abcx", false),
+            ("^$", "", true),
+            ("a.c", "abc", true),
+            ("^a*$", "aaa", true),
+            ("^a+$", "", false),
+            ("^ab?c$", "ac", true),
+            ("^(ab|cd)+$", "abcdab", true),
+            ("^(ab|cd)+$", "abc", false),
+            ("^(?:a*)+$", "", true),
+            ("^[a-c_]+$", "ab_c", true),
+            ("^[^0-9]+$", "ab1", false),
+            ("^[]a]+$", "]a", true),
+            ("^[a-]+$", "a-", true),
+            (r"^=== \d{4}-\d{2}-\d{2} ===$", "=== 2024-01-02 ===", true),
+            (r"^\d{2,}$", "1", false),
+            (r"^\d{2,3}$", "1234", false),
+            (r"^\w+\s\W$", "a_1 !", true),
+            (r"^\S+$", "a b", false),
+            (r"\.", "a.b", true),
+            (r"\.", "ab", false),
+            ("é+", "ééé", true),
+        ];
+        for (pattern, text, expected) in test_data {
+            let pattern: Pattern = pattern.parse().unwrap();
+            assert_eq!(expected, pattern.is_match(text), "{} {:?}", pattern, text);
+        }
+
+        // in linear time, however the pattern nests and however long the line
+        let long = "a".repeat(200_000);
+        for (pattern, expected) in [("(a*)*b", false), ("^(a|aa)+$", true)] {
+            let pattern: Pattern = pattern.parse().unwrap();
+            assert_eq!(expected, pattern.is_match(&long), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn parametrized_invalid() {
+        let test_data = [
+            "(", "(a", ")", "a)", "[a", "[z-a]", "*", "a{", "a{2,1}", r"\q",
+        ];
+        for pattern in test_data {
+            assert!(pattern.parse::<Pattern>().is_err(), "{:?}", pattern);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! The supported public API, for embedders; `use trim::prelude::*;` brings in what is needed to
+//! configure a trim, run it over readers, slices or files, and consume its results.
+//!
+//! Everything else is public for the binary and for tests, and may change between versions.
+
+pub use crate::cancel::CancellationToken;
+pub use crate::encoding::decoded;
+pub use crate::encoding::Encoding;
+pub use crate::line::ControlChars;
+pub use crate::line::Engine;
+pub use crate::line::Whitespace;
+pub use crate::options::EmptyFile;
+pub use crate::options::MetadataPolicy;
+pub use crate::options::Schedule;
+pub use crate::options::TrimOptions;
+pub use crate::rules::LineRule;
+pub use crate::stats::RunStats;
+pub use crate::stats::StatsSnapshot;
+pub use crate::trim::already_trimmed_reader;
+pub use crate::trim::is_trimmed;
+pub use crate::trim::is_trimmed_reader;
+pub use crate::trim::trim_files;
+pub use crate::trim::trim_files_with;
+pub use crate::trim::trim_reader_to;
+pub use crate::trim::trim_reader_with;
+pub use crate::trim::trim_slice_to;
+pub use crate::trim::FileOutcome;
+pub use crate::trim::Finding;
+pub use crate::trim::FindingSink;
+pub use crate::trim::LineFinding;
+pub use crate::trim::OnFinding;
+pub use crate::trim::Progress;
+pub use crate::trim::SkipReason;
+pub use crate::trim::TrimResult;
+pub use crate::trim::TrimWrite;
+pub use crate::trim::Warning;
+pub use crate::version::build_info;
+pub use crate::version::BuildInfo;
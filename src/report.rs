@@ -0,0 +1,919 @@
+//! Summaries of a run, in the formats the user can pick from.
+
+use serde_json::json;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::iter;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::diagnostics::error_code;
+use crate::diagnostics::skip_code;
+use crate::diagnostics::warning_code;
+use crate::error::error_kind;
+use crate::messages::bytes;
+use crate::messages::nothing_to_trim;
+use crate::options::EmptyFile;
+use crate::stats::StatsSnapshot;
+use crate::trim::FileOutcome;
+use crate::trim::TrimResult;
+use crate::trim::Warning;
+
+/// Format of the summary printed at the end of a run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// one line per file, for humans
+    #[default]
+    Text,
+    /// a single JSON document, for machines
+    Json,
+    /// tab-separated lines that are guaranteed not to change between versions; see
+    /// `porcelain_report`
+    Porcelain,
+    /// a standalone HTML page with the trimmed lines of each file; see `html_report`
+    Html,
+    /// a bar per file, as wide as its savings, for humans to spot the worst offenders at a
+    /// glance; see `heatmap_report`
+    Heatmap,
+    /// no summary; instead of the trimmed lines, a patch that `git apply` accepts, to apply the
+    /// trim later; see `diff::git_diff`
+    Patch,
+}
+
+impl Format {
+    /// Values accepted by `FromStr`, in the order they should be listed to the user.
+    pub const VARIANTS: &'static [&'static str] =
+        &["text", "json", "porcelain", "html", "heatmap", "patch"];
+
+    /// # Returns
+    ///
+    /// `true` if the report lists the trimmed lines, so `TrimOptions::collect_findings` is
+    /// needed, `false` otherwise.
+    #[inline]
+    pub fn needs_findings(self) -> bool {
+        self == Format::Html
+    }
+
+    /// # Returns
+    ///
+    /// `true` if the report lists the files that could not be trimmed, with their errors, `false`
+    /// otherwise.
+    #[inline]
+    pub fn reports_errors(self) -> bool {
+        matches!(self, Format::Json | Format::Porcelain | Format::Html)
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "porcelain" => Ok(Format::Porcelain),
+            "html" => Ok(Format::Html),
+            "heatmap" => Ok(Format::Heatmap),
+            "patch" => Ok(Format::Patch),
+            _ => Err(format!(
+                "expected one of {:?}, got {:?}",
+                Format::VARIANTS,
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::Text => write!(f, "text"),
+            Format::Json => write!(f, "json"),
+            Format::Porcelain => write!(f, "porcelain"),
+            Format::Html => write!(f, "html"),
+            Format::Heatmap => write!(f, "heatmap"),
+            Format::Patch => write!(f, "patch"),
+        }
+    }
+}
+
+/// Finding reported on request, on top of the trimmed lines, so that a policy about it can be
+/// enforced on its own; found inputs fail the run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Report {
+    /// blank lines at the end of an input, see `TrimResult::eof_blank_lines`
+    EofBlank,
+}
+
+impl Report {
+    /// Values accepted by `FromStr`, in the order they should be listed to the user.
+    pub const VARIANTS: &'static [&'static str] = &["eof-blank"];
+}
+
+impl FromStr for Report {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "eof-blank" => Ok(Report::EofBlank),
+            _ => Err(format!(
+                "expected one of {:?}, got {:?}",
+                Report::VARIANTS,
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Report::EofBlank => write!(f, "eof-blank"),
+        }
+    }
+}
+
+/// How the paths of files are shown in the summary, whatever way they were given in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PathStyle {
+    /// as given, without redundant separators, e.g. `./a/b.txt` for `./a//b.txt`
+    #[default]
+    Given,
+    /// absolute, with symlinks, `.` and `..` resolved
+    Canonical,
+    /// relative to the root of the git repository the file is in; canonical outside of one
+    Repo,
+}
+
+impl PathStyle {
+    /// Values accepted by `FromStr`, in the order they should be listed to the user.
+    pub const VARIANTS: &'static [&'static str] = &["given", "canonical", "repo"];
+
+    /// # Returns
+    ///
+    /// `path` in this style; as given if it cannot be resolved, e.g. if the file is not there.
+    pub fn display(self, path: &Path) -> PathBuf {
+        let given: PathBuf = path.components().collect();
+        let canonical = match self {
+            PathStyle::Given => return given,
+            PathStyle::Canonical | PathStyle::Repo => match path.canonicalize() {
+                Ok(canonical) => canonical,
+                Err(_) => return given,
+            },
+        };
+        if self == PathStyle::Repo {
+            let root = canonical
+                .ancestors()
+                .skip(1)
+                .find(|dir| dir.join(".git").exists());
+            if let Some(relative) = root.and_then(|root| canonical.strip_prefix(root).ok()) {
+                return relative.to_path_buf();
+            }
+        }
+        canonical
+    }
+}
+
+impl FromStr for PathStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "given" => Ok(PathStyle::Given),
+            "canonical" => Ok(PathStyle::Canonical),
+            "repo" => Ok(PathStyle::Repo),
+            _ => Err(format!(
+                "expected one of {:?}, got {:?}",
+                PathStyle::VARIANTS,
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for PathStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathStyle::Given => write!(f, "given"),
+            PathStyle::Canonical => write!(f, "canonical"),
+            PathStyle::Repo => write!(f, "repo"),
+        }
+    }
+}
+
+/// # Parameters
+///
+/// 1. `run_id` -- identifies the run, to correlate the report with other output of the same run
+/// 1. `summaries` -- the path to each input, `None` for stdin, and what became of it
+///
+/// # Returns
+///
+/// The JSON report of a run, with files sorted by path. Each file carries its `status`, one of
+/// `modified`, `unchanged`, `skipped` or `failed`; each trimmed file also carries its `warnings`,
+/// see `Warning::as_str`, and what became of it if it held nothing but whitespace, see
+/// `EmptyFile::done`, and each failed file the machine-readable kind of its error, see
+/// `error_kind`. Every warning, skip and error also carries its stable code, for scripts to
+/// branch on; see `diagnostics`.
+pub fn json_report<'a, I>(run_id: &str, summaries: I) -> Value
+where
+    I: IntoIterator<Item = (Option<&'a Path>, &'a FileOutcome)>,
+{
+    let mut summaries: Vec<_> = summaries.into_iter().collect();
+    summaries.sort_by_key(|(path_opt, _)| *path_opt);
+
+    let files: Vec<Value> = summaries
+        .into_iter()
+        .map(|(path_opt, outcome)| {
+            let path = path_opt.map(|path| path.to_string_lossy());
+            match outcome {
+                FileOutcome::Modified(TrimResult {
+                    bytes_saved,
+                    retries,
+                    eof_blank_lines,
+                    warnings,
+                    whitespace_only,
+                    ansi_bytes_stripped,
+                    ..
+                }) => json!({
+                    "path": path,
+                    "status": "modified",
+                    "bytes_saved": bytes_saved,
+                    "retries": retries,
+                    "eof_blank_lines": eof_blank_lines,
+                    "ansi_bytes_stripped": ansi_bytes_stripped,
+                    "warnings": warning_names(warnings),
+                    "warning_codes": warning_codes(warnings),
+                    "whitespace_only": whitespace_only.map(EmptyFile::done),
+                }),
+                FileOutcome::Unchanged(warnings) => json!({
+                    "path": path,
+                    "status": "unchanged",
+                    "bytes_saved": 0,
+                    "retries": 0,
+                    "eof_blank_lines": 0,
+                    "ansi_bytes_stripped": 0,
+                    "warnings": warning_names(warnings),
+                    "warning_codes": warning_codes(warnings),
+                    "whitespace_only": null,
+                }),
+                FileOutcome::Skipped(reason) => json!({
+                    "path": path,
+                    "status": "skipped",
+                    "skipped": reason.as_str(),
+                    "code": skip_code(*reason),
+                    "retries": 0,
+                }),
+                FileOutcome::Failed(err) => json!({
+                    "path": path,
+                    "status": "failed",
+                    "error": {
+                        "kind": error_kind(err),
+                        "code": error_code(err),
+                        "message": err.to_string(),
+                    },
+                }),
+            }
+        })
+        .collect();
+
+    json!({ "run_id": run_id, "files": files })
+}
+
+/// # Returns
+///
+/// The machine-readable names of `warnings`; see `Warning::as_str`.
+fn warning_names(warnings: &[Warning]) -> Vec<&'static str> {
+    warnings.iter().map(|warning| warning.as_str()).collect()
+}
+
+/// # Returns
+///
+/// The stable codes of `warnings`; see `warning_code`.
+fn warning_codes(warnings: &[Warning]) -> Vec<&'static str> {
+    warnings
+        .iter()
+        .map(|&warning| warning_code(warning))
+        .collect()
+}
+
+/// # Parameters
+///
+/// 1. `run_id` -- identifies the merged report
+/// 1. `reports` -- JSON reports of runs, see `json_report`, e.g. one per shard of a CI matrix
+///
+/// # Returns
+///
+/// A single JSON report with the files of all `reports`, sorted by path, along with the ids of
+/// the runs merged into it, and `stats`, totals over its files; see `StatsSnapshot`. A file in
+/// several reports is kept as it is in the first of them. An error if any of `reports` is not a
+/// JSON report.
+pub fn merge_json_reports<I>(run_id: &str, reports: I) -> io::Result<Value>
+where
+    I: IntoIterator<Item = Value>,
+{
+    let mut merged_run_ids = Vec::new();
+    let mut files: Vec<Value> = Vec::new();
+    let mut paths = HashSet::new();
+    for (index, report) in reports.into_iter().enumerate() {
+        let invalid = |message: &str| {
+            let message = format!("report {} {}", index + 1, message);
+            io::Error::new(io::ErrorKind::InvalidData, message)
+        };
+        let report_files = report
+            .get("files")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid("has no `files`"))?;
+        for file in report_files {
+            let status = file.get("status").and_then(Value::as_str);
+            if !matches!(
+                status,
+                Some("modified" | "unchanged" | "skipped" | "failed")
+            ) {
+                return Err(invalid("has a file without a known `status`"));
+            }
+            // stdin has no path, and is never the same input as another
+            match file.get("path").and_then(Value::as_str) {
+                Some(path) if !paths.insert(path.to_string()) => {}
+                _ => files.push(file.clone()),
+            }
+        }
+        merged_run_ids.push(report.get("run_id").cloned().unwrap_or(Value::Null));
+    }
+    files.sort_by_key(|file| file.get("path").and_then(Value::as_str).map(PathBuf::from));
+
+    let stats: StatsSnapshot = files
+        .iter()
+        .map(|file| {
+            let mut stats = StatsSnapshot {
+                files_total: 1,
+                ..StatsSnapshot::default()
+            };
+            match file.get("status").and_then(Value::as_str) {
+                Some("modified") => {
+                    stats.files_modified = 1;
+                    stats.bytes_saved =
+                        file.get("bytes_saved").and_then(Value::as_i64).unwrap_or(0);
+                    // missing from reports of versions before `--strip-ansi`
+                    stats.ansi_bytes_stripped = file
+                        .get("ansi_bytes_stripped")
+                        .and_then(Value::as_u64)
+                        .unwrap_or(0) as usize;
+                }
+                Some("unchanged") => stats.files_unchanged = 1,
+                Some("skipped") => stats.files_skipped = 1,
+                _ => stats.files_failed = 1,
+            }
+            stats
+        })
+        .sum();
+
+    Ok(json!({
+        "run_id": run_id,
+        "merged_run_ids": merged_run_ids,
+        "stats": stats,
+        "files": files,
+    }))
+}
+
+/// # Parameters
+///
+/// 1. `summaries` -- the path to each input, `None` for stdin, and what became of it
+///
+/// # Returns
+///
+/// The porcelain report of a run; one line per file, sorted by path, with these tab-separated
+/// columns in this order:
+///
+/// 1. `ok` if the file was modified or unchanged, `skipped` if it was left untouched as a whole,
+///    or `error`
+/// 1. bytes saved; `0` on error
+/// 1. number of retries; `0` on error
+/// 1. kind of the error, see `error_kind`, or why the file was skipped, see `SkipReason::as_str`;
+///    `-` if none
+/// 1. path of the file, `-` for stdin; backslashes, tabs and newlines are escaped as `\\`, `\t`
+///    and `\n`
+///
+/// There are no colors, and nothing depends on the locale. Columns may only ever be added after
+/// the path, so scripts should split on at most the first four tabs.
+pub fn porcelain_report<'a, I>(summaries: I) -> String
+where
+    I: IntoIterator<Item = (Option<&'a Path>, &'a FileOutcome)>,
+{
+    let mut summaries: Vec<_> = summaries.into_iter().collect();
+    summaries.sort_by_key(|(path_opt, _)| *path_opt);
+
+    summaries
+        .into_iter()
+        .map(|(path_opt, outcome)| {
+            let path = match path_opt {
+                Some(path) => path
+                    .to_string_lossy()
+                    .replace('\\', "\\\\")
+                    .replace('\t', "\\t")
+                    .replace('\n', "\\n"),
+                None => "-".to_string(),
+            };
+            match outcome {
+                FileOutcome::Modified(TrimResult {
+                    bytes_saved,
+                    retries,
+                    ..
+                }) => format!("ok\t{}\t{}\t-\t{}\n", bytes_saved, retries, path),
+                FileOutcome::Unchanged(_) => format!("ok\t0\t0\t-\t{}\n", path),
+                FileOutcome::Skipped(reason) => {
+                    format!("skipped\t0\t0\t{}\t{}\n", reason.as_str(), path)
+                }
+                FileOutcome::Failed(err) => format!("error\t0\t0\t{}\t{}\n", error_kind(err), path),
+            }
+        })
+        .collect()
+}
+
+/// Width, in characters, of the widest bar of the heatmap report.
+pub const HEATMAP_WIDTH: usize = 40;
+
+/// Eighths of a character cell, for bars of the heatmap report that do not end on a whole cell.
+const EIGHTHS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+/// # Parameters
+///
+/// 1. `summaries` -- the path to each input, `None` for stdin, and what became of it
+///
+/// # Returns
+///
+/// The heatmap report of a run; a line per file that trimming saved bytes of, from the most
+/// saved, with a bar as wide as its savings, `HEATMAP_WIDTH` characters for the most, then the
+/// bytes saved and the path. A last line counts the files with nothing saved, if any.
+pub fn heatmap_report<'a, I>(summaries: I, ascii: bool) -> String
+where
+    I: IntoIterator<Item = (Option<&'a Path>, &'a FileOutcome)>,
+{
+    let mut saved: Vec<(usize, Option<&Path>)> = Vec::new();
+    let mut unsaved = 0;
+    for (path_opt, outcome) in summaries {
+        match outcome
+            .trim_result()
+            .map(|trim_result| trim_result.bytes_saved)
+        {
+            Some(bytes_saved) if bytes_saved > 0 => saved.push((bytes_saved as usize, path_opt)),
+            _ => unsaved += 1,
+        }
+    }
+    saved.sort_by(|(a_saved, a_path), (b_saved, b_path)| {
+        b_saved.cmp(a_saved).then(a_path.cmp(b_path))
+    });
+
+    let most = saved.first().map_or(1, |(bytes_saved, _)| *bytes_saved);
+    let mut report: String = saved
+        .into_iter()
+        .map(|(bytes_saved, path_opt)| {
+            let path = match path_opt {
+                Some(path) => path.to_string_lossy(),
+                None => "stdin".into(),
+            };
+            format!(
+                "{} {:>6} {}
+",
+                bar(bytes_saved, most, ascii),
+                bytes_saved,
+                path
+            )
+        })
+        .collect();
+    if unsaved > 0 {
+        report.push_str(&format!("{}\n", nothing_to_trim(unsaved)));
+    }
+    report
+}
+
+/// # Returns
+///
+/// A bar `HEATMAP_WIDTH` characters wide, filled in proportion to `value` out of `most`, to an
+/// eighth of a character, or to the nearest character with `ascii`; at least an eighth, or a
+/// character, for any `value` above 0.
+fn bar(value: usize, most: usize, ascii: bool) -> String {
+    let eighths = (value as u128 * HEATMAP_WIDTH as u128 * 8 / most.max(1) as u128) as usize;
+    let eighths = eighths.clamp(value.min(1), HEATMAP_WIDTH * 8);
+    let mut bar = match ascii {
+        true => "#".repeat(((eighths + 4) / 8).max(1)),
+        false => "█".repeat(eighths / 8),
+    };
+    match eighths % 8 {
+        0 => (),
+        _ if ascii => (),
+        partial => bar.push(EIGHTHS[partial]),
+    }
+    let len = bar.chars().count();
+    bar.extend(iter::repeat_n(' ', HEATMAP_WIDTH - len));
+    bar
+}
+
+/// Styles of the HTML report; trimmed whitespace is shown like the red padding of the visuals.
+const HTML_STYLE: &str = "body { font-family: sans-serif; }
+pre { background: #f6f8fa; padding: 0.5em; }
+.line-number { color: #888; user-select: none; }
+.trimmed { background: #d73a49; color: #fff; }
+.error { color: #d73a49; }
+.run-id { color: #888; }";
+
+/// # Parameters
+///
+/// 1. `run_id` -- identifies the run, to correlate the report with other output of the same run
+/// 1. `summaries` -- the path to each input, `None` for stdin, and what became of it, with
+///    `TrimResult::findings` collected
+///
+/// # Returns
+///
+/// A standalone HTML page with a section per file, sorted by path, listing its trimmed lines
+/// with the trimmed whitespace highlighted.
+pub fn html_report<'a, I>(run_id: &str, summaries: I) -> String
+where
+    I: IntoIterator<Item = (Option<&'a Path>, &'a FileOutcome)>,
+{
+    let mut summaries: Vec<_> = summaries.into_iter().collect();
+    summaries.sort_by_key(|(path_opt, _)| *path_opt);
+
+    let sections: String = summaries
+        .into_iter()
+        .map(|(path_opt, outcome)| {
+            let path = match path_opt {
+                Some(path) => escape_html(&path.to_string_lossy()),
+                None => "stdin".to_string(),
+            };
+            let body = match outcome {
+                FileOutcome::Unchanged(_) => "<p>unchanged</p>\n".to_string(),
+                FileOutcome::Skipped(reason) => format!("<p>{}</p>\n", reason),
+                FileOutcome::Modified(TrimResult {
+                    bytes_saved,
+                    findings,
+                    ..
+                }) => {
+                    let lines: String = findings
+                        .iter()
+                        .map(|finding| {
+                            let (padding_len, exact) = finding.padding_len();
+                            format!(
+                                concat!(
+                                    "<span class=\"line-number\">{:>6}|</span>",
+                                    "{}<span class=\"trimmed\">{}</span>{}\n",
+                                ),
+                                finding.line_number,
+                                escape_html(&finding.trimmed_line),
+                                "_".repeat(padding_len),
+                                match exact {
+                                    true => String::new(),
+                                    false => format!(" ({} bytes)", padding_len),
+                                }
+                            )
+                        })
+                        .collect();
+                    match lines.is_empty() {
+                        true => format!("<p>{}</p>\n", bytes(*bytes_saved as i64)),
+                        false => {
+                            let bytes_saved = bytes(*bytes_saved as i64);
+                            format!("<p>{}</p>\n<pre>{}</pre>\n", bytes_saved, lines)
+                        }
+                    }
+                }
+                FileOutcome::Failed(err) => format!(
+                    "<p class=\"error\">ERROR: {}</p>\n",
+                    escape_html(&err.to_string())
+                ),
+            };
+            format!("<section>\n<h2>{}</h2>\n{}</section>\n", path, body)
+        })
+        .collect();
+
+    format!(
+        concat!(
+            "<!DOCTYPE html>\n<html>\n<head>\n",
+            "<meta charset=\"utf-8\">\n<title>trim report</title>\n",
+            "<style>\n{}\n</style>\n",
+            "</head>\n<body>\n<h1>trim report</h1>\n",
+            "<p class=\"run-id\">run {}</p>\n{}</body>\n</html>\n",
+        ),
+        HTML_STYLE,
+        escape_html(run_id),
+        sections
+    )
+}
+
+/// # Returns
+///
+/// `s` with the characters that are special in HTML escaped.
+fn escape_html(s: &str) -> String {
+    s.chars()
+        .fold(String::with_capacity(s.len()), |mut escaped, c| {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                _ => escaped.push(c),
+            }
+            escaped
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trim::Finding;
+    use crate::trim::SkipReason;
+    use std::path::PathBuf;
+
+    #[test]
+    fn parametrized_path_style() {
+        let test_data = vec![
+            (PathStyle::Given, "./src//report.rs", "./src/report.rs"),
+            (PathStyle::Given, "src/./report.rs", "src/report.rs"),
+            // not there, so not resolved
+            (PathStyle::Canonical, "./nope//x", "./nope/x"),
+            (PathStyle::Repo, "./nope//x", "./nope/x"),
+        ];
+        for (style, path, expected) in test_data {
+            assert_eq!(Path::new(expected), style.display(Path::new(path)));
+        }
+
+        let canonical = PathStyle::Canonical.display(Path::new("./src//report.rs"));
+        assert!(canonical.is_absolute());
+        assert!(canonical.ends_with("src/report.rs"));
+
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let root = std::env::temp_dir().join(prefix);
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src").join("x.rs"), "").unwrap();
+        let path = root.join("src").join("..").join("src").join("x.rs");
+        assert_eq!(Path::new("src/x.rs"), PathStyle::Repo.display(&path));
+    }
+
+    #[test]
+    fn parse_report() {
+        for &variant in Report::VARIANTS {
+            assert_eq!(variant, variant.parse::<Report>().unwrap().to_string());
+        }
+        assert!("eof".parse::<Report>().is_err());
+    }
+
+    #[test]
+    fn json_report_sorted_with_error_kinds() {
+        let ok = FileOutcome::Modified(TrimResult {
+            bytes_saved: 3,
+            retries: 1,
+            findings: Vec::new(),
+            skipped: None,
+            eof_blank_lines: 2,
+            warnings: vec![Warning::MixedLineEndings],
+            whitespace_only: Some(EmptyFile::Truncate),
+            ansi_bytes_stripped: 1,
+        });
+        let err = FileOutcome::Failed(io::Error::new(io::ErrorKind::PermissionDenied, "nope"));
+        let (a, b) = (PathBuf::from("a"), PathBuf::from("b"));
+
+        let report = json_report(
+            "01ARZ3NDEKTSV4RRFFQ69G5FAV",
+            vec![(Some(b.as_path()), &err), (Some(a.as_path()), &ok)],
+        );
+        let expected = json!({
+            "run_id": "01ARZ3NDEKTSV4RRFFQ69G5FAV",
+            "files": [
+                {
+                    "path": "a",
+                    "status": "modified",
+                    "bytes_saved": 3,
+                    "retries": 1,
+                    "eof_blank_lines": 2,
+                    "ansi_bytes_stripped": 1,
+                    "warnings": ["mixed-line-endings"],
+                    "warning_codes": ["W_MIXED_EOL"],
+                    "whitespace_only": "truncated",
+                },
+                {
+                    "path": "b",
+                    "status": "failed",
+                    "error": { "kind": "permission-denied", "code": "E_PERM", "message": "nope" },
+                },
+            ]
+        });
+        assert_eq!(expected, report);
+    }
+
+    #[test]
+    fn merge_json_reports_dedupes_and_sums() {
+        let shard_1 = json!({
+            "run_id": "1",
+            "files": [
+                {
+                    "path": "b",
+                    "status": "modified",
+                    "bytes_saved": 3,
+                    "ansi_bytes_stripped": 2,
+                },
+                { "path": null, "status": "modified", "bytes_saved": 1 },
+            ]
+        });
+        let shard_2 = json!({
+            "run_id": "2",
+            "files": [
+                { "path": "a", "status": "failed", "error": { "kind": "not-found" } },
+                { "path": "b", "status": "unchanged", "bytes_saved": 0 },
+                { "path": "c", "status": "skipped", "skipped": "marker" },
+            ]
+        });
+
+        let merged = merge_json_reports("3", vec![shard_1, shard_2]).unwrap();
+        assert_eq!(json!("3"), merged["run_id"]);
+        assert_eq!(json!(["1", "2"]), merged["merged_run_ids"]);
+        let paths: Vec<&Value> = merged["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| &file["path"])
+            .collect();
+        assert_eq!(
+            vec![&json!(null), &json!("a"), &json!("b"), &json!("c")],
+            paths
+        );
+        // `b` as it is in the first report
+        assert_eq!(json!("modified"), merged["files"][2]["status"]);
+        let expected = json!({
+            "files_total": 4,
+            "files_modified": 2,
+            "files_unchanged": 0,
+            "files_skipped": 1,
+            "files_failed": 1,
+            "bytes_saved": 4,
+            "ansi_bytes_stripped": 2,
+        });
+        assert_eq!(expected, merged["stats"]);
+
+        let invalid = vec![json!({ "files": [{ "path": "a" }] })];
+        let err = merge_json_reports("4", invalid).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        assert!(merge_json_reports("5", vec![json!([])]).is_err());
+    }
+
+    #[test]
+    fn porcelain_report_columns() {
+        let ok = FileOutcome::Modified(TrimResult {
+            bytes_saved: 3,
+            retries: 0,
+            findings: Vec::new(),
+            skipped: None,
+            eof_blank_lines: 0,
+            warnings: Vec::new(),
+            whitespace_only: None,
+            ansi_bytes_stripped: 0,
+        });
+        let err = FileOutcome::Failed(io::Error::new(io::ErrorKind::NotFound, "gone"));
+        let (a, b) = (PathBuf::from("a\tb"), PathBuf::from("c"));
+
+        let report = porcelain_report(vec![
+            (Some(b.as_path()), &err),
+            (None, &ok),
+            (Some(a.as_path()), &ok),
+        ]);
+        let expected = "ok\t3\t0\t-\t-\nok\t3\t0\t-\ta\\tb\nerror\t0\t0\tnot-found\tc\n";
+        assert_eq!(expected, report);
+    }
+
+    #[test]
+    fn heatmap_report_by_savings() {
+        let modified = |bytes_saved| {
+            FileOutcome::Modified(TrimResult {
+                bytes_saved,
+                retries: 0,
+                findings: Vec::new(),
+                skipped: None,
+                eof_blank_lines: 0,
+                warnings: Vec::new(),
+                whitespace_only: None,
+                ansi_bytes_stripped: 0,
+            })
+        };
+        let (most, half, least, none) = (modified(80), modified(40), modified(1), modified(0));
+        let unchanged = FileOutcome::Unchanged(Vec::new());
+        let (a, b, c, d, e) = (
+            PathBuf::from("a"),
+            PathBuf::from("b"),
+            PathBuf::from("c"),
+            PathBuf::from("d"),
+            PathBuf::from("e"),
+        );
+
+        let summaries = vec![
+            (Some(a.as_path()), &least),
+            (Some(b.as_path()), &most),
+            (Some(c.as_path()), &unchanged),
+            (Some(d.as_path()), &half),
+            (Some(e.as_path()), &none),
+        ];
+        let expected = format!(
+            "{}     80 b\n{}{}     40 d\n▌{}      1 a\n2 files with nothing to trim\n",
+            "█".repeat(40),
+            "█".repeat(20),
+            " ".repeat(20),
+            " ".repeat(39),
+        );
+        assert_eq!(expected, heatmap_report(summaries.clone(), false));
+        let expected = format!(
+            "{}     80 b\n{}{}     40 d\n#{}      1 a\n2 files with nothing to trim\n",
+            "#".repeat(40),
+            "#".repeat(20),
+            " ".repeat(20),
+            " ".repeat(39),
+        );
+        assert_eq!(expected, heatmap_report(summaries, true));
+    }
+
+    #[test]
+    fn skipped_files() {
+        let skipped = FileOutcome::Skipped(SkipReason::Marker);
+        let path = PathBuf::from("fixture.txt");
+
+        let report = json_report(
+            "01ARZ3NDEKTSV4RRFFQ69G5FAV",
+            vec![(Some(path.as_path()), &skipped)],
+        );
+        let expected = json!({
+            "run_id": "01ARZ3NDEKTSV4RRFFQ69G5FAV",
+            "files": [{
+                "path": "fixture.txt",
+                "status": "skipped",
+                "skipped": "marker",
+                "code": "S_MARKER",
+                "retries": 0,
+            }]
+        });
+        assert_eq!(expected, report);
+
+        let report = porcelain_report(vec![(Some(path.as_path()), &skipped)]);
+        assert_eq!("skipped\t0\t0\tmarker\tfixture.txt\n", report);
+    }
+
+    #[test]
+    fn unchanged_files() {
+        let unchanged = FileOutcome::Unchanged(Vec::new());
+        let path = PathBuf::from("clean.txt");
+
+        let report = json_report(
+            "01ARZ3NDEKTSV4RRFFQ69G5FAV",
+            vec![(Some(path.as_path()), &unchanged)],
+        );
+        assert_eq!(json!("unchanged"), report["files"][0]["status"]);
+        assert_eq!(json!(0), report["files"][0]["bytes_saved"]);
+
+        let report = porcelain_report(vec![(Some(path.as_path()), &unchanged)]);
+        assert_eq!("ok\t0\t0\t-\tclean.txt\n", report);
+    }
+
+    #[test]
+    fn html_report_highlights_trimmed() {
+        let ok = FileOutcome::Modified(TrimResult {
+            bytes_saved: 2,
+            retries: 0,
+            findings: vec![
+                Finding {
+                    line_number: 3,
+                    trimmed_line: "<a>".to_string(),
+                    bytes_trimmed: 2,
+                    width_trimmed: Some(2),
+                },
+                Finding {
+                    line_number: 4,
+                    trimmed_line: "b".to_string(),
+                    bytes_trimmed: 2,
+                    width_trimmed: None,
+                },
+            ],
+            skipped: None,
+            eof_blank_lines: 0,
+            warnings: Vec::new(),
+            whitespace_only: None,
+            ansi_bytes_stripped: 0,
+        });
+        let path = PathBuf::from("a&b");
+
+        let report = html_report(
+            "01ARZ3NDEKTSV4RRFFQ69G5FAV",
+            vec![(Some(path.as_path()), &ok)],
+        );
+        assert!(report.starts_with("<!DOCTYPE html>"));
+        assert!(report.contains("<h2>a&amp;b</h2>"));
+        assert!(report.contains("run 01ARZ3NDEKTSV4RRFFQ69G5FAV"));
+        assert!(report.contains("&lt;a&gt;<span class=\"trimmed\">__</span>\n"));
+        assert!(report.contains("b<span class=\"trimmed\">__</span> (2 bytes)\n"));
+    }
+
+    #[test]
+    fn format_round_trip() {
+        for name in Format::VARIANTS {
+            assert_eq!(*name, name.parse::<Format>().unwrap().to_string());
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Files already trimmed by an interrupted run, so that a restarted run can skip them; for huge
+//! trees.
+
+use serde_json::json;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs::read_to_string;
+use std::fs::remove_file;
+use std::fs::rename;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::fs::TryLockError;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::util::mktemp_sibling;
+
+/// Version of the format written by `ResumeState::save`.
+const VERSION: u64 = 1;
+
+/// Number of files completed between saves; at most this many are trimmed again after an
+/// interruption, which is harmless, as trimming is idempotent.
+const SAVE_EVERY: usize = 64;
+
+/// Completed files of a run, kept in a state file.
+///
+/// The state file is locked for as long as this lives, so that concurrent runs cannot overwrite
+/// each other's progress. The lock is released by the OS if the run is killed.
+#[derive(Debug)]
+pub struct ResumeState {
+    /// where the state is saved
+    path: PathBuf,
+    /// held for the lifetime of this state; see `lock_path`
+    lock: File,
+    /// completed files, and the number of them completed since the last save
+    completed: Mutex<(HashSet<String>, usize)>,
+}
+
+impl ResumeState {
+    /// Lock the state file under `path` and load it; it need not exist.
+    ///
+    /// # Returns
+    ///
+    /// The state, or an error of kind `WouldBlock` if another run is using `path`.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let lock = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(lock_path(path))?;
+        match lock.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    format!("{:?} is in use by another run", path),
+                ))
+            }
+            Err(TryLockError::Error(err)) => return Err(err),
+        }
+
+        let completed = match read_to_string(path) {
+            Ok(content) => parse(path, &content)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => HashSet::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(ResumeState {
+            path: path.to_path_buf(),
+            lock,
+            completed: Mutex::new((completed, 0)),
+        })
+    }
+
+    /// # Returns
+    ///
+    /// `true` if `file` was completed by this run or an earlier one, `false` otherwise.
+    pub fn is_completed(&self, file: &Path) -> bool {
+        let (completed, _) = &*self.completed.lock().unwrap();
+        completed.contains(file.to_string_lossy().as_ref())
+    }
+
+    /// Record `file` as completed.
+    ///
+    /// # Side Effects
+    ///
+    /// Saves the state every `SAVE_EVERY` files.
+    pub fn complete(&self, file: &Path) -> io::Result<()> {
+        let mut guard = self.completed.lock().unwrap();
+        let (completed, unsaved) = &mut *guard;
+        if completed.insert(file.to_string_lossy().into_owned()) {
+            *unsaved += 1;
+        }
+        match *unsaved >= SAVE_EVERY {
+            true => {
+                save(&self.path, completed)?;
+                *unsaved = 0;
+                Ok(())
+            }
+            false => Ok(()),
+        }
+    }
+
+    /// Save the state under its path, atomically.
+    pub fn save(&self) -> io::Result<()> {
+        let mut guard = self.completed.lock().unwrap();
+        let (completed, unsaved) = &mut *guard;
+        save(&self.path, completed)?;
+        *unsaved = 0;
+        Ok(())
+    }
+
+    /// Remove the state file, e.g. once the run has completed, and release the lock.
+    pub fn remove(self) -> io::Result<()> {
+        match remove_file(&self.path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        // still locked, so no other run can be holding it
+        let result = remove_file(lock_path(&self.path));
+        drop(self.lock);
+        result
+    }
+}
+
+/// # Returns
+///
+/// Path of the file that is locked while the state under `path` is in use.
+fn lock_path(path: &Path) -> PathBuf {
+    let mut lock_path = OsString::from(path.as_os_str());
+    lock_path.push(".lock");
+    PathBuf::from(lock_path)
+}
+
+/// # Returns
+///
+/// The completed files in `content`, read from the state file under `path`.
+fn parse(path: &Path, content: &str) -> io::Result<HashSet<String>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let value: Value = serde_json::from_str(content)
+        .map_err(|err| invalid(format!("{:?} is not a resume state: {}", path, err)))?;
+
+    match value.get("version").and_then(Value::as_u64) {
+        Some(VERSION) => {}
+        version => {
+            let message = format!("unsupported state version {:?} in {:?}", version, path);
+            return Err(invalid(message));
+        }
+    }
+
+    value
+        .get("completed")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .map(|file| match file.as_str() {
+            Some(file) => Ok(file.to_string()),
+            None => Err(invalid(format!("malformed file in {:?}", path))),
+        })
+        .collect()
+}
+
+/// Save `completed` under `path`, atomically.
+fn save(path: &Path, completed: &HashSet<String>) -> io::Result<()> {
+    let mut completed: Vec<_> = completed.iter().collect();
+    completed.sort();
+    let value = json!({ "version": VERSION, "completed": completed });
+
+    let (temp_path, mut temp_file) = mktemp_sibling(path)?;
+    let result = writeln!(temp_file, "{:#}", value).and_then(|_| rename(&temp_path, path));
+    if result.is_err() {
+        let _ = remove_file(&temp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::mktemp;
+
+    #[test]
+    fn complete_then_resume() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let path = mktemp(&prefix, &"").unwrap();
+        remove_file(&path).unwrap();
+
+        let state = ResumeState::open(&path).unwrap();
+        assert!(!state.is_completed(Path::new("a")));
+        state.complete(Path::new("a")).unwrap();
+        assert!(state.is_completed(Path::new("a")));
+        state.save().unwrap();
+        drop(state);
+
+        let state = ResumeState::open(&path).unwrap();
+        assert!(state.is_completed(Path::new("a")));
+        assert!(!state.is_completed(Path::new("b")));
+        state.remove().unwrap();
+        assert!(!path.exists());
+        assert!(!lock_path(&path).exists());
+    }
+
+    #[test]
+    fn one_run_at_a_time() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let path = mktemp(&prefix, &"").unwrap();
+        remove_file(&path).unwrap();
+
+        let state = ResumeState::open(&path).unwrap();
+        let err = ResumeState::open(&path).unwrap_err();
+        assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+
+        drop(state);
+        ResumeState::open(&path).unwrap().remove().unwrap();
+    }
+
+    #[test]
+    fn open_rejects_other_versions() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let path = mktemp(&prefix, &r#"{ "version": 99, "completed": [] }"#).unwrap();
+
+        let err = ResumeState::open(&path).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+}
@@ -0,0 +1,124 @@
+use std::borrow::Cow;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::line::trim_end_with;
+use crate::options::TrimOptions;
+
+/// A per-line cleanup, applied in the same streaming pass as the trailing whitespace trim.
+///
+/// Every line is first trimmed by `TrailingWhitespace`, then passed through each rule in
+/// `TrimOptions::rules` in order. Bytes removed by any rule count toward the bytes saved, and a
+/// line that ends up empty is treated like any other blank line.
+pub trait LineRule: Debug + Send + Sync {
+    /// Name used to select this rule, e.g. with `--rule`.
+    fn name(&self) -> &'static str;
+
+    /// # Returns
+    ///
+    /// `line`, which has no line terminator, after applying this rule. Borrowed if the rule
+    /// leaves `line` as is, so that unchanged lines are never copied.
+    fn apply<'a>(&self, line: &'a str, options: &TrimOptions) -> Cow<'a, str>;
+}
+
+/// The built-in rule; removes whitespace at the end of the line, as defined by `options`.
+#[derive(Debug)]
+pub struct TrailingWhitespace;
+
+impl LineRule for TrailingWhitespace {
+    fn name(&self) -> &'static str {
+        "trailing-whitespace"
+    }
+
+    fn apply<'a>(&self, line: &'a str, options: &TrimOptions) -> Cow<'a, str> {
+        trim_end_with(
+            line,
+            options.whitespace,
+            options.min_run,
+            options.keep_form_feed,
+            options.engine,
+        )
+    }
+}
+
+/// Collapses a run of whitespace between code and a trailing `//` comment into a single space,
+/// e.g. `let x = 1;    // one` becomes `let x = 1; // one`.
+///
+/// Indentation before a comment that starts the line is left alone.
+#[derive(Debug)]
+pub struct CollapseCommentSpace;
+
+impl LineRule for CollapseCommentSpace {
+    fn name(&self) -> &'static str {
+        "collapse-comment-space"
+    }
+
+    fn apply<'a>(&self, line: &'a str, options: &TrimOptions) -> Cow<'a, str> {
+        let comment_start = match line.find("//") {
+            Some(index) => index,
+            None => return Cow::Borrowed(line),
+        };
+        let code = line[..comment_start].trim_end_matches(|c| options.whitespace.is_whitespace(c));
+
+        match code.trim_start().is_empty() || comment_start - code.len() < 2 {
+            // only indentation, or nothing to collapse
+            true => Cow::Borrowed(line),
+            false => Cow::Owned(format!("{} {}", code, &line[comment_start..])),
+        }
+    }
+}
+
+/// Names of the rules that can be looked up with `builtin`.
+pub const BUILTIN_NAMES: &[&str] = &["collapse-comment-space"];
+
+/// # Returns
+///
+/// The built-in rule called `name`, if any. `TrailingWhitespace` is not included, as it always
+/// runs.
+pub fn builtin(name: &str) -> Option<Arc<dyn LineRule>> {
+    match name {
+        "collapse-comment-space" => Some(Arc::new(CollapseCommentSpace)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod collapse_comment_space {
+        use super::*;
+
+        fn test_data() -> Vec<(&'static str, &'static str)> {
+            vec![
+                ("", ""),
+                ("abc", "abc"),
+                ("abc // def", "abc // def"),
+                ("abc  // def", "abc // def"),
+                ("abc \t // def  ", "abc // def  "),
+                ("abc// def", "abc// def"),
+                // indentation is not collapsed
+                ("    // def", "    // def"),
+                ("// def", "// def"),
+                // only the first `//` counts
+                ("abc  // def  // ghi", "abc // def  // ghi"),
+            ]
+        }
+
+        #[test]
+        fn parametrized_collapse_comment_space() {
+            let options = TrimOptions::default();
+            for (input, expected) in test_data() {
+                assert_eq!(expected, CollapseCommentSpace.apply(input, &options));
+            }
+        }
+    }
+
+    #[test]
+    fn builtin_names() {
+        for name in BUILTIN_NAMES {
+            assert_eq!(Some(*name), builtin(name).map(|rule| rule.name()));
+        }
+        assert!(builtin("trailing-whitespace").is_none());
+    }
+}
@@ -0,0 +1,197 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::options::TrimOptions;
+use crate::rules::LineRule;
+use crate::rules::TrailingWhitespace;
+use crate::trim::suppression;
+use crate::util::*;
+
+/// Like `has_trailing_whitespace_in`, but for the file under `path`.
+#[inline]
+pub fn has_trailing_whitespace(path: &Path, options: &TrimOptions) -> io::Result<bool> {
+    File::open(path)
+        .map(BufReader::new)
+        .and_then(|reader| has_trailing_whitespace_in(reader, options))
+}
+
+/// Check for trailing whitespace without trimming anything, stopping at the first finding.
+///
+/// # Returns
+///
+/// `true` if any line of `reader` has trailing whitespace that would be trimmed under `options`,
+/// or if `reader` ends with blank lines, `false` otherwise.
+///
+/// Reading stops as soon as a line with trailing whitespace is found, so the cost is bounded by
+/// the position of the first finding rather than by the size of the input. Trailing blank lines
+/// can only be known at the end of the input, though.
+pub fn has_trailing_whitespace_in<R>(reader: R, options: &TrimOptions) -> io::Result<bool>
+where
+    R: BufRead,
+{
+    // number of blank lines since the last non-empty line
+    let mut blank_run = 0;
+    let mut seen_content = false;
+    let mut in_block = false;
+
+    for line in split_records(reader, options.separator()) {
+        let Line { content, .. } = line?;
+        let (untouched, next_in_block) = suppression(&content, in_block, options);
+        in_block = next_in_block;
+        let trimmed = match untouched {
+            true => Cow::Borrowed(content.as_str()),
+            false => TrailingWhitespace.apply(&content, options),
+        };
+        if trimmed.len() != content.len() {
+            return Ok(true);
+        }
+        match trimmed.is_empty() {
+            true => blank_run += 1,
+            false => {
+                blank_run = 0;
+                seen_content = true;
+            }
+        }
+    }
+
+    // an input that is a single `\n` is left as is
+    Ok(blank_run > 1 || (seen_content && blank_run > 0))
+}
+
+/// Like `estimated_savings_in`, but for the file under `path`.
+#[inline]
+pub fn estimated_savings(path: &Path, options: &TrimOptions) -> io::Result<usize> {
+    File::open(path)
+        .map(BufReader::new)
+        .and_then(|reader| estimated_savings_in(reader, options))
+}
+
+/// Estimate how much trimming would save without trimming anything, e.g. to plan a large run.
+///
+/// # Returns
+///
+/// Number of bytes of trailing whitespace and of trailing blank lines in `reader`; line endings
+/// that would be rewritten, and the final linebreak, are not counted.
+pub fn estimated_savings_in<R>(reader: R, options: &TrimOptions) -> io::Result<usize>
+where
+    R: BufRead,
+{
+    let mut saved = 0;
+    // bytes of the terminators of the blank lines since the last non-empty line
+    let mut blank_len = 0;
+    let mut seen_content = false;
+    let mut in_block = false;
+    for line in split_records(reader, options.separator()) {
+        let Line { content, ending } = line?;
+        let (untouched, next_in_block) = suppression(&content, in_block, options);
+        in_block = next_in_block;
+        let trimmed = match untouched {
+            true => Cow::Borrowed(content.as_str()),
+            false => TrailingWhitespace.apply(&content, options),
+        };
+        saved += content.len() - trimmed.len();
+        match trimmed.is_empty() {
+            true => blank_len += ending.len(),
+            false => {
+                blank_len = 0;
+                seen_content = true;
+            }
+        }
+    }
+
+    // the last non-empty line keeps its terminator; an input of blank lines keeps one
+    Ok(match seen_content {
+        true => saved + blank_len,
+        false => saved + blank_len.saturating_sub(1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parallel::*;
+
+    mod has_trailing_whitespace {
+        use super::*;
+
+        fn test_data() -> Vec<(&'static str, bool)> {
+            vec![
+                ("", false),
+                ("abc", false),
+                ("abc\n", false),
+                ("abc\r\n", false),
+                ("\n", false),
+                ("\nabc\n", false),
+                ("abc ", true),
+                ("abc\t\n", true),
+                ("abc\ndef \nghi\n", true),
+                ("abc\n\n", true),
+                ("\n\n", true),
+                (" \n", true),
+            ]
+        }
+
+        #[test]
+        fn parametrized_has_trailing_whitespace() {
+            test_data()
+                .into_par_iter()
+                .enumerate()
+                .for_each(|(index, (input, expected))| {
+                    let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
+                    let path_to_temp = mktemp(&prefix, &input).unwrap();
+                    let options = TrimOptions::default();
+
+                    assert_eq!(
+                        expected,
+                        has_trailing_whitespace(&path_to_temp, &options).unwrap()
+                    );
+                });
+        }
+
+        #[test]
+        fn ignore_marker() {
+            let options = TrimOptions {
+                ignore_marker: Some("trim:ignore".to_string()),
+                ..TrimOptions::default()
+            };
+            let clean = "a # trim:ignore \n# trim:off\nb \n# trim:on\nc\n";
+            assert!(!has_trailing_whitespace_in(clean.as_bytes(), &options).unwrap());
+            assert!(has_trailing_whitespace_in(clean.as_bytes(), &TrimOptions::default()).unwrap());
+        }
+
+        /// nothing past the first finding is read
+        #[test]
+        fn stops_at_first_finding() {
+            let options = TrimOptions::default();
+            let mut input = b"abc\ndef \n".to_vec();
+            input.extend_from_slice(&[0xff; 1024]); // invalid UTF-8, an error if read
+
+            assert!(has_trailing_whitespace_in(&input[..], &options).unwrap());
+        }
+    }
+
+    #[test]
+    fn parametrized_estimated_savings() {
+        let test_data = [
+            ("", 0),
+            ("abc\n", 0),
+            ("abc  \n", 2),
+            ("abc \ndef\t\n\n\n", 4),
+            ("\n\n\n", 2),
+            (" \n", 1),
+        ];
+        for (input, expected) in test_data {
+            let options = TrimOptions::default();
+            assert_eq!(
+                expected,
+                estimated_savings_in(input.as_bytes(), &options).unwrap(),
+                "{:?}",
+                input
+            );
+        }
+    }
+}
@@ -0,0 +1,415 @@
+//! JSON Schemas of the JSON reports, so that consumers can validate them, or generate types from
+//! them, without reverse-engineering them from samples; see `trim schema`.
+//!
+//! Every name and code the reports may hold is listed from the types that produce it, e.g.
+//! `Warning::ALL` and `warning_code`, so that the schemas cannot drift from the reports.
+
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+use std::fmt;
+use std::io;
+use std::iter;
+use std::str::FromStr;
+
+use crate::diagnostics::error_code;
+use crate::diagnostics::skip_code;
+use crate::diagnostics::warning_code;
+use crate::error::error_kind;
+use crate::error::KNOWN_KINDS;
+use crate::options::EmptyFile;
+use crate::stats::StatsSnapshot;
+use crate::trim::SkipReason;
+use crate::trim::Warning;
+
+/// Version of JSON Schema the schemas are written in.
+pub const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// A JSON document written by trim that has a schema.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Schema {
+    /// the summary of a run with `--format json`; see `report::json_report`
+    #[default]
+    Report,
+    /// the output of `trim merge-reports`; see `report::merge_json_reports`
+    MergedReport,
+}
+
+impl Schema {
+    /// Values accepted by `FromStr`, in the order they should be listed to the user.
+    pub const VARIANTS: &'static [&'static str] = &["report", "merged-report"];
+
+    /// # Returns
+    ///
+    /// The JSON Schema of this document.
+    pub fn to_json(self) -> Value {
+        let (title, properties, required) = match self {
+            Schema::Report => (
+                "trim report",
+                json!({
+                    "run_id": { "type": "string" },
+                    "files": { "type": "array", "items": { "$ref": "#/$defs/file" } },
+                    "audit": { "$ref": "#/$defs/audit" },
+                }),
+                json!(["run_id", "files"]),
+            ),
+            Schema::MergedReport => (
+                "trim merged report",
+                json!({
+                    "run_id": { "type": "string" },
+                    "merged_run_ids": {
+                        "type": "array",
+                        "items": { "type": ["string", "null"] },
+                    },
+                    "stats": stats(),
+                    "files": { "type": "array", "items": { "$ref": "#/$defs/file" } },
+                }),
+                json!(["run_id", "merged_run_ids", "stats", "files"]),
+            ),
+        };
+        json!({
+            "$schema": DIALECT,
+            "title": title,
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+            "$defs": {
+                "file": {
+                    "oneOf": [
+                        trimmed_file("modified"),
+                        trimmed_file("unchanged"),
+                        skipped_file(),
+                        failed_file(),
+                    ],
+                },
+                "audit": audit(),
+            },
+        })
+    }
+}
+
+impl FromStr for Schema {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "report" => Ok(Schema::Report),
+            "merged-report" => Ok(Schema::MergedReport),
+            _ => Err(format!(
+                "expected one of {:?}, got {:?}",
+                Schema::VARIANTS,
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Schema::Report => write!(f, "report"),
+            Schema::MergedReport => write!(f, "merged-report"),
+        }
+    }
+}
+
+/// # Returns
+///
+/// The schema of an object with exactly `properties`, all of them required.
+fn closed_object(properties: Value) -> Value {
+    let required: Vec<String> = properties
+        .as_object()
+        .into_iter()
+        .flat_map(|fields| fields.keys().cloned())
+        .collect();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+/// # Returns
+///
+/// The schema of the path of a file; `null` for stdin.
+fn path() -> Value {
+    json!({ "type": ["string", "null"] })
+}
+
+/// # Returns
+///
+/// The schema of a file that was modified or unchanged, going by `status`.
+fn trimmed_file(status: &str) -> Value {
+    let warnings: Vec<&str> = Warning::ALL
+        .iter()
+        .map(|warning| warning.as_str())
+        .collect();
+    let warning_codes: Vec<&str> = Warning::ALL
+        .iter()
+        .map(|&warning| warning_code(warning))
+        .collect();
+    let whitespace_only: Vec<Value> = EmptyFile::VARIANTS
+        .iter()
+        .filter_map(|variant| variant.parse::<EmptyFile>().ok())
+        .map(|empty_file| json!(empty_file.done()))
+        .chain(iter::once(Value::Null))
+        .collect();
+    closed_object(json!({
+        "path": path(),
+        "status": { "const": status },
+        "bytes_saved": { "type": "integer" },
+        "retries": { "type": "integer", "minimum": 0 },
+        "eof_blank_lines": { "type": "integer", "minimum": 0 },
+        "ansi_bytes_stripped": { "type": "integer", "minimum": 0 },
+        "warnings": { "type": "array", "items": { "enum": warnings } },
+        "warning_codes": { "type": "array", "items": { "enum": warning_codes } },
+        "whitespace_only": { "enum": whitespace_only },
+    }))
+}
+
+/// # Returns
+///
+/// The schema of a file that was skipped.
+fn skipped_file() -> Value {
+    let reasons: Vec<&str> = SkipReason::ALL
+        .iter()
+        .map(|reason| reason.as_str())
+        .collect();
+    let codes: Vec<&str> = SkipReason::ALL
+        .iter()
+        .map(|&reason| skip_code(reason))
+        .collect();
+    closed_object(json!({
+        "path": path(),
+        "status": { "const": "skipped" },
+        "skipped": { "enum": reasons },
+        "code": { "enum": codes },
+        "retries": { "type": "integer", "minimum": 0 },
+    }))
+}
+
+/// # Returns
+///
+/// The schema of a file that failed.
+fn failed_file() -> Value {
+    let errors: Vec<io::Error> = KNOWN_KINDS
+        .iter()
+        .chain(iter::once(&io::ErrorKind::Other))
+        .map(|&kind| io::Error::from(kind))
+        .collect();
+    let kinds: Vec<&str> = errors.iter().map(error_kind).collect();
+    let codes: Vec<&str> = errors.iter().map(error_code).collect();
+    closed_object(json!({
+        "path": path(),
+        "status": { "const": "failed" },
+        "error": closed_object(json!({
+            "kind": { "enum": kinds },
+            "code": { "enum": codes },
+            "message": { "type": "string" },
+        })),
+    }))
+}
+
+/// # Returns
+///
+/// The schema of the totals of a merged report, with the fields of `StatsSnapshot`.
+fn stats() -> Value {
+    let properties: Map<String, Value> = match serde_json::to_value(StatsSnapshot::default()) {
+        Ok(Value::Object(fields)) => fields
+            .into_iter()
+            .map(|(field, _)| (field, json!({ "type": "integer" })))
+            .collect(),
+        _ => Map::new(),
+    };
+    closed_object(Value::Object(properties))
+}
+
+/// # Returns
+///
+/// The schema of the audit of a run with `--audit-sample`.
+fn audit() -> Value {
+    closed_object(json!({
+        "audited": { "type": "integer", "minimum": 0 },
+        "modified": { "type": "integer", "minimum": 0 },
+        "failures": {
+            "type": "array",
+            "items": closed_object(json!({
+                "path": { "type": "string" },
+                "message": { "type": "string" },
+            })),
+        },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::json_report;
+    use crate::report::merge_json_reports;
+    use crate::trim::FileOutcome;
+    use crate::trim::TrimResult;
+    use std::path::PathBuf;
+
+    /// # Returns
+    ///
+    /// Why `value` does not match `schema`, as far as the keywords the schemas use go; `root` is
+    /// the schema `$ref`s are resolved against.
+    fn violation(root: &Value, schema: &Value, value: &Value) -> Option<String> {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/$defs/");
+            return violation(root, &root["$defs"][name], value);
+        }
+        if let Some(schemas) = schema["oneOf"].as_array() {
+            let matches = schemas
+                .iter()
+                .filter(|schema| violation(root, schema, value).is_none())
+                .count();
+            return (matches != 1).then(|| format!("{} matches {} of oneOf", value, matches));
+        }
+        let type_of = match value {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(number) if number.is_i64() || number.is_u64() => "integer",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        };
+        let types: Vec<&Value> = match &schema["type"] {
+            Value::Array(types) => types.iter().collect(),
+            Value::Null => Vec::new(),
+            single => vec![single],
+        };
+        if !types.is_empty() && !types.iter().any(|t| t.as_str() == Some(type_of)) {
+            return Some(format!("{} is not {:?}", value, types));
+        }
+        if schema
+            .get("const")
+            .is_some_and(|constant| constant != value)
+        {
+            return Some(format!("{} is not {}", value, schema["const"]));
+        }
+        if let Some(values) = schema["enum"].as_array() {
+            if !values.contains(value) {
+                return Some(format!("{} is not in {:?}", value, values));
+            }
+        }
+        if let (Some(minimum), Some(number)) = (schema["minimum"].as_i64(), value.as_i64()) {
+            if number < minimum {
+                return Some(format!("{} is under {}", value, minimum));
+            }
+        }
+        if let Value::Array(items) = value {
+            if let Some(items_schema) = schema.get("items") {
+                return items
+                    .iter()
+                    .find_map(|item| violation(root, items_schema, item));
+            }
+        }
+        if let Value::Object(fields) = value {
+            let properties = &schema["properties"];
+            for required in schema["required"].as_array().into_iter().flatten() {
+                let required = required.as_str().unwrap_or_default();
+                if !fields.contains_key(required) {
+                    return Some(format!("{} lacks {}", value, required));
+                }
+            }
+            for (field, field_value) in fields {
+                match properties.get(field) {
+                    Some(field_schema) => {
+                        if let Some(violation) = violation(root, field_schema, field_value) {
+                            return Some(violation);
+                        }
+                    }
+                    None if schema["additionalProperties"] == json!(false) => {
+                        return Some(format!("{} has {}", value, field))
+                    }
+                    None => (),
+                }
+            }
+        }
+        None
+    }
+
+    fn outcomes() -> Vec<(Option<PathBuf>, FileOutcome)> {
+        let modified = |warnings: Vec<Warning>, whitespace_only| {
+            FileOutcome::Modified(TrimResult {
+                bytes_saved: 3,
+                retries: 1,
+                findings: Vec::new(),
+                skipped: None,
+                eof_blank_lines: 2,
+                warnings,
+                whitespace_only,
+                ansi_bytes_stripped: 0,
+            })
+        };
+        let errors = KNOWN_KINDS
+            .iter()
+            .chain(iter::once(&io::ErrorKind::Other))
+            .map(|&kind| FileOutcome::Failed(io::Error::new(kind, "nope")));
+        iter::once(modified(Warning::ALL.to_vec(), None))
+            .chain(iter::once(modified(Vec::new(), Some(EmptyFile::Delete))))
+            .chain(iter::once(FileOutcome::Unchanged(Warning::ALL.to_vec())))
+            .chain(
+                SkipReason::ALL
+                    .iter()
+                    .map(|&reason| FileOutcome::Skipped(reason)),
+            )
+            .chain(errors)
+            .enumerate()
+            .map(|(index, outcome)| (Some(PathBuf::from(index.to_string())), outcome))
+            .chain(iter::once((None, FileOutcome::Unchanged(Vec::new()))))
+            .collect()
+    }
+
+    #[test]
+    fn report_matches_schema() {
+        let schema = Schema::Report.to_json();
+        let outcomes = outcomes();
+        let mut report = json_report(
+            "01ARZ3NDEKTSV4RRFFQ69G5FAV",
+            outcomes
+                .iter()
+                .map(|(path, outcome)| (path.as_deref(), outcome)),
+        );
+        assert_eq!(None, violation(&schema, &schema, &report));
+
+        report["audit"] = json!({
+            "audited": 1,
+            "modified": 3,
+            "failures": [{ "path": "a", "message": "nope" }],
+        });
+        assert_eq!(None, violation(&schema, &schema, &report));
+
+        report["files"][0]["status"] = json!("trimmed");
+        assert!(violation(&schema, &schema, &report).is_some());
+    }
+
+    #[test]
+    fn merged_report_matches_schema() {
+        let schema = Schema::MergedReport.to_json();
+        let outcomes = outcomes();
+        let report = json_report(
+            "1",
+            outcomes
+                .iter()
+                .map(|(path, outcome)| (path.as_deref(), outcome)),
+        );
+        let merged = merge_json_reports("2", vec![report.clone(), report]).unwrap();
+        assert_eq!(None, violation(&schema, &schema, &merged));
+        assert!(violation(&schema, &schema, &json!({ "run_id": "2" })).is_some());
+    }
+
+    #[test]
+    fn parametrized_schema() {
+        for name in Schema::VARIANTS {
+            let schema = name.parse::<Schema>().unwrap();
+            assert_eq!(*name, schema.to_string());
+            assert_eq!(DIALECT, schema.to_json()["$schema"]);
+        }
+        assert!("events".parse::<Schema>().is_err());
+    }
+}
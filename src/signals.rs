@@ -0,0 +1,175 @@
+//! Requests for the status of a run, by signal: `SIGINFO`, sent by Ctrl-T on BSD and macOS, or
+//! `SIGUSR1` anywhere, as `dd` does; so that a long unattended run can be checked on without
+//! being interrupted.
+//!
+//! The signal handler only writes a byte to a pipe, which is all that is safe to do in one; the
+//! status is printed by whoever reads the pipe, see `StatusRequests::wait`.
+
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::mem;
+use std::os::unix::io::FromRawFd;
+use std::ptr;
+use std::sync::atomic::AtomicI32;
+use std::sync::atomic::Ordering;
+
+/// The write end of the pipe of `StatusRequests`, for the signal handler; -1 until installed.
+static WRITE_FD: AtomicI32 = AtomicI32::new(-1);
+
+/// Signals that request the status of a run.
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+const SIGNALS: &[libc::c_int] = &[libc::SIGINFO, libc::SIGUSR1];
+#[cfg(not(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+const SIGNALS: &[libc::c_int] = &[libc::SIGUSR1];
+
+/// Requests for the status of a run, received by signal; see `status_requests`.
+#[derive(Debug)]
+pub struct StatusRequests {
+    pipe: File,
+}
+
+impl StatusRequests {
+    /// Block until the status is requested.
+    ///
+    /// # Returns
+    ///
+    /// `true` once it is, `false` if it never can be anymore.
+    pub fn wait(&mut self) -> bool {
+        let mut byte = [0];
+        loop {
+            match self.pipe.read(&mut byte) {
+                Ok(1) => return true,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                _ => return false,
+            }
+        }
+    }
+}
+
+/// Handle `SIGINFO` and `SIGUSR1`, which otherwise terminate the process, as requests for the
+/// status of the run, for as long as the process runs.
+///
+/// # Returns
+///
+/// The requests; an error if the handlers cannot be installed, or already are.
+pub fn status_requests() -> io::Result<StatusRequests> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let [read_fd, write_fd] = fds;
+    // `File` closes them on drop, even on error below
+    let pipe = unsafe { File::from_raw_fd(read_fd) };
+    let write_end = unsafe { File::from_raw_fd(write_fd) };
+
+    for &fd in &fds {
+        // not inherited by filter commands; and a full pipe already has a request pending, so the
+        // handler never needs to block on it
+        let flags = match fd == write_fd {
+            true => libc::O_NONBLOCK,
+            false => 0,
+        };
+        let failed = unsafe {
+            libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) != 0
+                || libc::fcntl(fd, libc::F_SETFL, flags) != 0
+        };
+        if failed {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    if WRITE_FD
+        .compare_exchange(-1, write_fd, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "status requests are already handled",
+        ));
+    }
+    // the handler writes to it until the process exits
+    mem::forget(write_end);
+
+    let handler = on_signal as extern "C" fn(libc::c_int);
+    for &signal in SIGNALS {
+        let mut action: libc::sigaction = unsafe { mem::zeroed() };
+        action.sa_sigaction = handler as libc::sighandler_t;
+        // reads and writes of the run carry on after the handler returns
+        action.sa_flags = libc::SA_RESTART;
+        let failed = unsafe {
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(signal, &action, ptr::null_mut()) != 0
+        };
+        if failed {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(StatusRequests { pipe })
+}
+
+extern "C" fn on_signal(_: libc::c_int) {
+    let fd = WRITE_FD.load(Ordering::Relaxed);
+    let errno = io::Error::last_os_error().raw_os_error();
+    // a failed write means a request is pending already
+    unsafe {
+        libc::write(fd, [0u8].as_ptr().cast(), 1);
+    }
+    // the code that was interrupted may be about to read `errno`
+    if let Some(errno) = errno {
+        set_errno(errno);
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn set_errno(errno: libc::c_int) {
+    unsafe { *libc::__errno_location() = errno }
+}
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly"
+))]
+fn set_errno(errno: libc::c_int) {
+    unsafe { *libc::__error() = errno }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly"
+)))]
+fn set_errno(_: libc::c_int) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_requested_by_signal() {
+        let mut requests = status_requests().unwrap();
+        assert!(status_requests().is_err());
+        for &signal in SIGNALS {
+            assert_eq!(0, unsafe { libc::raise(signal) });
+            assert!(requests.wait());
+        }
+    }
+}
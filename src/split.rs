@@ -0,0 +1,133 @@
+//! Splitting one trimmed stream into several files, at lines that match a pattern, e.g. a log into
+//! a file per day; see `--split-by`.
+
+use std::fs::create_dir_all;
+use std::io;
+use std::io::Sink;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::pattern::Pattern;
+use crate::util::Tee;
+
+/// Writes what is written to it to numbered files in a directory, `part-00000`, `part-00001` and so
+/// on, starting the next file at each line that matches a boundary. Each file only appears, as a
+/// whole, once the next one starts, or on `finish`.
+pub struct SplitWriter {
+    dir: PathBuf,
+    boundary: Pattern,
+    /// the line being written, up to its `\n`
+    line: Vec<u8>,
+    /// the file being written, if any line was
+    part: Option<Tee<Sink>>,
+    /// number of files started
+    parts: usize,
+}
+
+impl SplitWriter {
+    /// # Parameters
+    ///
+    /// 1. `dir` -- where the files go; created if need be, and files in it are replaced
+    /// 1. `boundary` -- lines that match it, without their terminator, start the next file
+    pub fn new(dir: &Path, boundary: Pattern) -> io::Result<Self> {
+        create_dir_all(dir)?;
+        Ok(SplitWriter {
+            dir: dir.to_path_buf(),
+            boundary,
+            line: Vec::new(),
+            part: None,
+            parts: 0,
+        })
+    }
+
+    /// Write the line so far to the file it belongs in, starting the next file if it is a boundary.
+    fn end_line(&mut self) -> io::Result<()> {
+        let content = match self.line.strip_suffix(b"\n") {
+            Some(content) => content.strip_suffix(b"\r").unwrap_or(content),
+            None => &self.line,
+        };
+        if self.part.is_none() || self.boundary.is_match(&String::from_utf8_lossy(content)) {
+            self.commit()?;
+            let path = self.dir.join(format!("part-{:05}", self.parts));
+            self.part = Some(Tee::new(io::sink(), Some(&path))?);
+            self.parts += 1;
+        }
+        if let Some(part) = &mut self.part {
+            part.write_all(&self.line)?;
+        }
+        self.line.clear();
+        Ok(())
+    }
+
+    /// Move the file being written, if any, to its path.
+    fn commit(&mut self) -> io::Result<()> {
+        match self.part.take() {
+            Some(mut part) => part.commit(),
+            None => Ok(()),
+        }
+    }
+
+    /// Write out the last file.
+    ///
+    /// # Returns
+    ///
+    /// Number of files written; none if nothing was written.
+    pub fn finish(mut self) -> io::Result<usize> {
+        if !self.line.is_empty() {
+            self.end_line()?;
+        }
+        self.commit()?;
+        Ok(self.parts)
+    }
+}
+
+impl Write for SplitWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for line in buf.split_inclusive(|&byte| byte == b'\n') {
+            self.line.extend_from_slice(line);
+            if line.ends_with(b"\n") {
+                self.end_line()?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    /// Nothing to do; each file is written out as a whole, once it is complete.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::read_to_string;
+    use std::fs::remove_dir_all;
+
+    #[test]
+    fn parametrized_split() {
+        let test_data = [
+            ("", vec![]),
+            ("a\nb", vec!["a\nb"]),
+            ("# 1\na\n# 2\nb\n", vec!["# 1\na\n", "# 2\nb\n"]),
+            ("a\n# 1\r\nb\n# 2\n", vec!["a\n", "# 1\r\nb\n", "# 2\n"]),
+        ];
+        for (i, (input, expected)) in test_data.iter().enumerate() {
+            let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), i);
+            let dir = temp_dir().join(prefix);
+            let mut split = SplitWriter::new(&dir, "^# \\d$".parse().unwrap()).unwrap();
+            // written in pieces that do not end at lines
+            for piece in input.as_bytes().chunks(3) {
+                split.write_all(piece).unwrap();
+            }
+            assert_eq!(expected.len(), split.finish().unwrap(), "{:?}", input);
+            for (part, expected) in expected.iter().enumerate() {
+                let path = dir.join(format!("part-{:05}", part));
+                assert_eq!(*expected, read_to_string(path).unwrap());
+            }
+            let _ = remove_dir_all(&dir);
+        }
+    }
+}
@@ -0,0 +1,340 @@
+//! Totals of a run, updated live by every worker as files are done; e.g. for a progress bar or a
+//! periodic status line during very long runs.
+
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::Add;
+use std::ops::AddAssign;
+use std::str::FromStr;
+use std::sync::atomic::AtomicI64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use crate::messages;
+use crate::trim::FileOutcome;
+
+/// Counters shared by every worker of a run; see `TrimOptions::stats`.
+///
+/// Each counter is updated on its own, so a `snapshot` taken while files are being trimmed may be
+/// a file ahead in one counter than in another, but never loses an update.
+#[derive(Debug, Default)]
+pub struct RunStats {
+    files_total: AtomicUsize,
+    files_modified: AtomicUsize,
+    files_unchanged: AtomicUsize,
+    files_skipped: AtomicUsize,
+    files_failed: AtomicUsize,
+    bytes_saved: AtomicI64,
+    ansi_bytes_stripped: AtomicUsize,
+}
+
+impl RunStats {
+    pub fn new() -> Self {
+        RunStats::default()
+    }
+
+    /// Account for `count` more files to trim.
+    pub fn add_total(&self, count: usize) {
+        self.files_total.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Account for a file that is done, given what became of it.
+    pub fn record(&self, outcome: &FileOutcome) {
+        let counter = match outcome {
+            FileOutcome::Modified(trim_result) => {
+                let bytes_saved = trim_result.bytes_saved as i64;
+                self.bytes_saved.fetch_add(bytes_saved, Ordering::Relaxed);
+                let ansi_bytes_stripped = trim_result.ansi_bytes_stripped;
+                self.ansi_bytes_stripped
+                    .fetch_add(ansi_bytes_stripped, Ordering::Relaxed);
+                &self.files_modified
+            }
+            FileOutcome::Unchanged(_) => &self.files_unchanged,
+            FileOutcome::Skipped(_) => &self.files_skipped,
+            FileOutcome::Failed(_) => &self.files_failed,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// # Returns
+    ///
+    /// The counters as of now.
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            files_total: self.files_total.load(Ordering::Relaxed),
+            files_modified: self.files_modified.load(Ordering::Relaxed),
+            files_unchanged: self.files_unchanged.load(Ordering::Relaxed),
+            files_skipped: self.files_skipped.load(Ordering::Relaxed),
+            files_failed: self.files_failed.load(Ordering::Relaxed),
+            bytes_saved: self.bytes_saved.load(Ordering::Relaxed),
+            ansi_bytes_stripped: self.ansi_bytes_stripped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The counters of a `RunStats` at some point of a run, or totals over any files.
+///
+/// Totals add up: a file's own totals are `From` its `FileOutcome`, and the totals of several files
+/// or runs are their `Sum`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatsSnapshot {
+    /// files to trim, done or not
+    pub files_total: usize,
+    pub files_modified: usize,
+    pub files_unchanged: usize,
+    pub files_skipped: usize,
+    pub files_failed: usize,
+    /// bytes saved ish, across all modified files; see `TrimResult::bytes_saved`
+    pub bytes_saved: i64,
+    /// bytes of terminal escape sequences stripped, across all modified files, which are part of
+    /// `bytes_saved`; see `TrimResult::ansi_bytes_stripped`
+    pub ansi_bytes_stripped: usize,
+}
+
+impl StatsSnapshot {
+    /// # Returns
+    ///
+    /// Number of files done, whatever became of them.
+    pub fn files_done(&self) -> usize {
+        self.files_modified + self.files_unchanged + self.files_skipped + self.files_failed
+    }
+
+    /// # Returns
+    ///
+    /// `true` if the run trimmed more than `threshold` allows, `false` otherwise.
+    pub fn exceeds(&self, threshold: Threshold) -> bool {
+        match threshold {
+            Threshold::Bytes(bytes) => self.bytes_saved > bytes,
+            Threshold::Files(files) => self.files_modified > files,
+        }
+    }
+}
+
+impl From<&FileOutcome> for StatsSnapshot {
+    /// Totals of a single file, given what became of it.
+    fn from(outcome: &FileOutcome) -> Self {
+        let mut stats = StatsSnapshot {
+            files_total: 1,
+            ..StatsSnapshot::default()
+        };
+        match outcome {
+            FileOutcome::Modified(trim_result) => {
+                stats.files_modified = 1;
+                stats.bytes_saved = trim_result.bytes_saved as i64;
+                stats.ansi_bytes_stripped = trim_result.ansi_bytes_stripped;
+            }
+            FileOutcome::Unchanged(_) => stats.files_unchanged = 1,
+            FileOutcome::Skipped(_) => stats.files_skipped = 1,
+            FileOutcome::Failed(_) => stats.files_failed = 1,
+        }
+        stats
+    }
+}
+
+impl Add for StatsSnapshot {
+    type Output = StatsSnapshot;
+
+    fn add(self, other: StatsSnapshot) -> StatsSnapshot {
+        StatsSnapshot {
+            files_total: self.files_total + other.files_total,
+            files_modified: self.files_modified + other.files_modified,
+            files_unchanged: self.files_unchanged + other.files_unchanged,
+            files_skipped: self.files_skipped + other.files_skipped,
+            files_failed: self.files_failed + other.files_failed,
+            bytes_saved: self.bytes_saved + other.bytes_saved,
+            ansi_bytes_stripped: self.ansi_bytes_stripped + other.ansi_bytes_stripped,
+        }
+    }
+}
+
+impl AddAssign for StatsSnapshot {
+    fn add_assign(&mut self, other: StatsSnapshot) {
+        *self = *self + other;
+    }
+}
+
+impl Sum for StatsSnapshot {
+    fn sum<I: Iterator<Item = StatsSnapshot>>(iter: I) -> StatsSnapshot {
+        iter.fold(StatsSnapshot::default(), Add::add)
+    }
+}
+
+impl<'a> Sum<&'a StatsSnapshot> for StatsSnapshot {
+    fn sum<I: Iterator<Item = &'a StatsSnapshot>>(iter: I) -> StatsSnapshot {
+        iter.copied().sum()
+    }
+}
+
+/// Most trimming a run may do before it fails, e.g. so that CI tolerates a little trailing
+/// whitespace; see `StatsSnapshot::exceeds`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Threshold {
+    /// bytes ish saved across all files, e.g. `100bytes`
+    Bytes(i64),
+    /// files modified, e.g. `3files`
+    Files(usize),
+}
+
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let threshold = match (s.strip_suffix("bytes"), s.strip_suffix("files")) {
+            (Some(bytes), _) => bytes.parse().ok().filter(|&b| b >= 0).map(Threshold::Bytes),
+            (_, Some(files)) => files.parse().ok().map(Threshold::Files),
+            _ => None,
+        };
+        threshold.ok_or_else(|| format!("expected <N>bytes or <N>files, got {:?}", s))
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Threshold::Bytes(bytes) => write!(f, "{}bytes", bytes),
+            Threshold::Files(files) => write!(f, "{}files", files),
+        }
+    }
+}
+
+impl fmt::Display for StatsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            concat!(
+                "{}/{} files done, {} modified, {} unchanged, {} skipped, {} failed, ",
+                "{} saved",
+            ),
+            self.files_done(),
+            self.files_total,
+            self.files_modified,
+            self.files_unchanged,
+            self.files_skipped,
+            self.files_failed,
+            messages::bytes(self.bytes_saved)
+        )?;
+        // only with `TrimOptions::strip_ansi`, in effect
+        match self.ansi_bytes_stripped {
+            0 => Ok(()),
+            stripped => write!(f, ", {} bytes of escape sequences stripped", stripped),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parallel::*;
+    use crate::trim::SkipReason;
+    use crate::trim::TrimResult;
+    use std::io;
+
+    #[test]
+    fn record_in_parallel() {
+        let stats = RunStats::new();
+        stats.add_total(400);
+        (0..100).into_par_iter().for_each(|_| {
+            stats.record(&FileOutcome::Modified(TrimResult {
+                bytes_saved: 2,
+                retries: 0,
+                findings: Vec::new(),
+                skipped: None,
+                eof_blank_lines: 0,
+                warnings: Vec::new(),
+                whitespace_only: None,
+                ansi_bytes_stripped: 0,
+            }));
+            stats.record(&FileOutcome::Unchanged(Vec::new()));
+            stats.record(&FileOutcome::Skipped(SkipReason::Marker));
+            stats.record(&FileOutcome::Failed(io::Error::other("nope")));
+        });
+
+        let expected = StatsSnapshot {
+            files_total: 400,
+            files_modified: 100,
+            files_unchanged: 100,
+            files_skipped: 100,
+            files_failed: 100,
+            bytes_saved: 200,
+            ansi_bytes_stripped: 0,
+        };
+        assert_eq!(expected, stats.snapshot());
+        assert_eq!(400, expected.files_done());
+    }
+
+    #[test]
+    fn sum_of_outcomes() {
+        let outcomes = [
+            FileOutcome::Modified(TrimResult {
+                bytes_saved: 3,
+                retries: 0,
+                findings: Vec::new(),
+                skipped: None,
+                eof_blank_lines: 0,
+                warnings: Vec::new(),
+                whitespace_only: None,
+                ansi_bytes_stripped: 1,
+            }),
+            FileOutcome::Unchanged(Vec::new()),
+            FileOutcome::Unchanged(Vec::new()),
+            FileOutcome::Skipped(SkipReason::Marker),
+            FileOutcome::Failed(io::Error::other("nope")),
+        ];
+        let per_file: Vec<StatsSnapshot> = outcomes.iter().map(StatsSnapshot::from).collect();
+        let expected = StatsSnapshot {
+            files_total: 5,
+            files_modified: 1,
+            files_unchanged: 2,
+            files_skipped: 1,
+            files_failed: 1,
+            bytes_saved: 3,
+            ansi_bytes_stripped: 1,
+        };
+        assert_eq!(expected, per_file.iter().sum());
+        assert_eq!(
+            expected + expected,
+            per_file.into_iter().chain([expected]).sum()
+        );
+        assert_eq!(
+            StatsSnapshot::default(),
+            std::iter::empty::<StatsSnapshot>().sum()
+        );
+
+        // the same totals as recorded live
+        let stats = RunStats::new();
+        stats.add_total(outcomes.len());
+        outcomes.iter().for_each(|outcome| stats.record(outcome));
+        assert_eq!(expected, stats.snapshot());
+
+        let json = serde_json::to_string(&expected).unwrap();
+        assert_eq!(expected, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn parametrized_threshold() {
+        let snapshot = StatsSnapshot {
+            files_modified: 3,
+            bytes_saved: 100,
+            ..StatsSnapshot::default()
+        };
+        let test_data = vec![
+            ("99bytes", Some(true)),
+            ("100bytes", Some(false)),
+            ("2files", Some(true)),
+            ("3files", Some(false)),
+            ("0files", Some(true)),
+            ("-1bytes", None),
+            ("3", None),
+            ("files", None),
+        ];
+        for (s, expected) in test_data {
+            let threshold = s.parse::<Threshold>().ok();
+            assert_eq!(expected, threshold.map(|t| snapshot.exceeds(t)), "{}", s);
+            if let Some(threshold) = threshold {
+                assert_eq!(s, threshold.to_string());
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Caps on the rate at which files are read, so that trimming on a busy host does not compete
+//! with the rest of its IO.
+
+use std::io;
+use std::io::Read;
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::Duration;
+use std::time::Instant;
+
+/// A budget of bytes per second, shared by every reader of a run.
+///
+/// Readers sleep whenever the bytes read so far are ahead of the budget since the throttle was
+/// created, so the cap holds across all workers together, not per worker.
+#[derive(Debug)]
+pub struct Throttle {
+    /// bytes that may be read per second; at least 1
+    bytes_per_sec: u64,
+    /// when the budget started
+    start: Instant,
+    /// bytes read so far
+    consumed: Mutex<u64>,
+}
+
+impl Throttle {
+    /// # Parameters
+    ///
+    /// 1. `bytes_per_sec` -- bytes that may be read per second; 0 is treated as 1
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Throttle {
+            bytes_per_sec: bytes_per_sec.max(1),
+            start: Instant::now(),
+            consumed: Mutex::new(0),
+        }
+    }
+
+    /// Account for `len` bytes having been read.
+    ///
+    /// # Side Effects
+    ///
+    /// Sleeps until reading them is within the budget.
+    pub fn consume(&self, len: usize) {
+        let due = {
+            let mut consumed = self.consumed.lock().unwrap();
+            *consumed += len as u64;
+            Duration::from_secs_f64(*consumed as f64 / self.bytes_per_sec as f64)
+        };
+        if let Some(ahead) = due.checked_sub(self.start.elapsed()) {
+            sleep(ahead);
+        }
+    }
+}
+
+/// Reads from `inner` within the budget of a `Throttle`, if any.
+#[derive(Debug)]
+pub struct Throttled<'a, R> {
+    inner: R,
+    throttle: Option<&'a Throttle>,
+}
+
+/// # Returns
+///
+/// `reader`, limited to the budget of `throttle`; not limited at all if `throttle` is `None`.
+#[inline]
+pub fn throttled<R>(reader: R, throttle: Option<&Throttle>) -> Throttled<'_, R>
+where
+    R: Read,
+{
+    Throttled {
+        inner: reader,
+        throttle,
+    }
+}
+
+impl<R> Read for Throttled<'_, R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        if let Some(throttle) = self.throttle {
+            throttle.consume(len);
+        }
+        Ok(len)
+    }
+}
+
+/// Parse a rate in megabytes per second, e.g. for `--throttle`; fractions such as `0.5` are
+/// allowed.
+///
+/// # Returns
+///
+/// The rate in bytes per second, or a description of why `s` is not a rate.
+pub fn parse_rate(s: &str) -> Result<u64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(mb_per_sec) if mb_per_sec.is_finite() && mb_per_sec > 0.0 => {
+            Ok(((mb_per_sec * (1 << 20) as f64) as u64).max(1))
+        }
+        _ => Err(format!(
+            "expected a positive rate in MB/s such as 20 or 0.5, got {:?}",
+            s
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parametrized_parse_rate() {
+        let test_data = vec![
+            ("1", Some(1 << 20)),
+            ("0.5", Some(1 << 19)),
+            (" 20 ", Some(20 << 20)),
+            ("0", None),
+            ("-1", None),
+            ("fast", None),
+            ("inf", None),
+        ];
+        for (input, expected) in test_data {
+            assert_eq!(expected, parse_rate(input).ok());
+        }
+    }
+
+    #[test]
+    fn reads_within_budget() {
+        let throttle = Throttle::new(1000);
+        let mut content = Vec::new();
+        let start = Instant::now();
+        throttled(&[0u8; 100][..], Some(&throttle))
+            .read_to_end(&mut content)
+            .unwrap();
+
+        assert_eq!(100, content.len());
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+}
@@ -1,23 +1,383 @@
-use rayon::prelude::*;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Reverse;
 use std::collections::HashMap;
-use std::env;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fmt;
 use std::fs::copy;
+use std::fs::create_dir_all;
+use std::fs::metadata;
 use std::fs::remove_file;
 use std::fs::rename;
+use std::fs::symlink_metadata;
 use std::fs::File;
-use std::fs::OpenOptions;
 use std::io;
-use std::io::stderr;
 use std::io::stdout;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Cursor;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
 use std::io::Write;
+use std::iter;
 use std::path::Path;
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::str;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::sync_channel;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::thread::sleep;
+use std::thread::spawn;
+use std::time::Duration;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
 
+use crate::cancel::cancellable;
+use crate::cancel::CancellationToken;
+#[cfg(all(target_os = "linux", feature = "copy-file-range"))]
+use crate::copy_range::CopyRangeWriter;
+use crate::error::is_transient;
+use crate::error::with_remediation;
+use crate::filter::FilterFailure;
+use crate::invariants::check_deletions;
+use crate::line::strip_ansi;
+use crate::line::Tail;
+use crate::options::ControlChars;
+use crate::options::EmptyFile;
+use crate::options::MetadataPolicy;
+use crate::options::Schedule;
+use crate::options::TrimOptions;
+use crate::parallel::*;
+use crate::rules::LineRule;
+use crate::rules::TrailingWhitespace;
+use crate::throttle::throttled;
+use crate::throttle::Throttle;
 use crate::util::*;
 
+/// Approximate size of the chunks that `trim_chunked` trims in parallel.
+const CHUNK_SIZE: usize = 1 << 22;
+
+/// Size of the buffers used to read and write files, unless `TrimOptions::max_memory` calls for
+/// smaller ones.
+const BUFFER_SIZE: usize = 1 << 13;
+
+/// How long `trim_reader_line_buffered` waits for the next line, before it writes the blank lines
+/// it has deferred.
+const LINE_BUFFERED_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Number of times a file is tried with `TrimOptions::net_safe`, before giving up.
+const NET_SAFE_ATTEMPTS: u32 = 5;
+
+/// Wait before the first retry with `TrimOptions::net_safe`, doubled on each retry after that.
+const NET_SAFE_BACKOFF: Duration = Duration::from_millis(50);
+
 /// Summary of everything that happened during the trim.
 pub struct TrimResult {
     pub bytes_saved: i32,
+    /// number of times the trim was retried after a transient error; see `TrimOptions::net_safe`
+    pub retries: u32,
+    /// lines that were trimmed, in order; only collected if `TrimOptions::collect_findings`
+    pub findings: Vec<Finding>,
+    /// why the input was left untouched as a whole, if it was
+    pub skipped: Option<SkipReason>,
+    /// number of blank lines the input ended with, which were dropped; a finding of its own,
+    /// found whether or not any line had trailing whitespace
+    pub eof_blank_lines: usize,
+    /// what is worth knowing about the input, but did not keep it from being trimmed
+    pub warnings: Vec<Warning>,
+    /// what became of the input if it held nothing but whitespace; see `TrimOptions::empty_file`
+    pub whitespace_only: Option<EmptyFile>,
+    /// number of bytes of terminal escape sequences dropped, which are part of `bytes_saved`; see
+    /// `TrimOptions::strip_ansi`
+    pub ansi_bytes_stripped: usize,
+}
+
+impl TrimResult {
+    /// # Returns
+    ///
+    /// The result for an input that was skipped as a whole, for `reason`.
+    #[inline]
+    fn skipped(reason: SkipReason) -> Self {
+        TrimResult {
+            bytes_saved: 0,
+            retries: 0,
+            findings: Vec::new(),
+            skipped: Some(reason),
+            eof_blank_lines: 0,
+            warnings: Vec::new(),
+            whitespace_only: None,
+            ansi_bytes_stripped: 0,
+        }
+    }
+}
+
+/// What became of a file trimmed by `trim_files`.
+pub enum FileOutcome {
+    /// its trimmed content differs from the original content
+    Modified(TrimResult),
+    /// it was already clean; its trimmed content is the original content
+    Unchanged(Vec<Warning>),
+    /// it was left untouched as a whole
+    Skipped(SkipReason),
+    /// it could not be trimmed
+    Failed(io::Error),
+}
+
+impl FileOutcome {
+    /// # Returns
+    ///
+    /// The result of trimming the file, if it was modified, `None` otherwise.
+    pub fn trim_result(&self) -> Option<&TrimResult> {
+        match self {
+            FileOutcome::Modified(trim_result) => Some(trim_result),
+            _ => None,
+        }
+    }
+
+    /// # Returns
+    ///
+    /// What is worth knowing about the file, if it was trimmed, whether or not it was modified, or
+    /// if it was skipped for looking like something other than text, or for its filter failing.
+    pub fn warnings(&self) -> &[Warning] {
+        match self {
+            FileOutcome::Modified(trim_result) => &trim_result.warnings,
+            FileOutcome::Unchanged(warnings) => warnings,
+            FileOutcome::Skipped(SkipReason::Shrinks) => &[Warning::Shrinks],
+            FileOutcome::Skipped(SkipReason::FilterFailed) => &[Warning::FilterFailed],
+            _ => &[],
+        }
+    }
+
+    /// # Returns
+    ///
+    /// The error the file could not be trimmed with, if any.
+    pub fn error(&self) -> Option<&io::Error> {
+        match self {
+            FileOutcome::Failed(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// # Returns
+    ///
+    /// `true` if the file could not be trimmed, `false` otherwise.
+    pub fn is_failed(&self) -> bool {
+        self.error().is_some()
+    }
+
+    /// # Returns
+    ///
+    /// `true` if the file needs no trimming again, e.g. by a resumed run, `false` if it failed or
+    /// the run was cancelled before it was trimmed.
+    pub fn is_completed(&self) -> bool {
+        !matches!(
+            self,
+            FileOutcome::Failed(_) | FileOutcome::Skipped(SkipReason::Cancelled)
+        )
+    }
+}
+
+/// For inputs that are not trimmed in-place, e.g. stdin; their trimmed content is always written
+/// out, so they count as modified unless skipped.
+impl From<io::Result<TrimResult>> for FileOutcome {
+    fn from(result: io::Result<TrimResult>) -> Self {
+        match result {
+            Ok(TrimResult {
+                skipped: Some(reason),
+                ..
+            }) => FileOutcome::Skipped(reason),
+            Ok(trim_result) => FileOutcome::Modified(trim_result),
+            Err(err) => FileOutcome::Failed(err),
+        }
+    }
+}
+
+/// Why an input was left untouched as a whole.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    /// it opted out with `TrimOptions::skip_file_marker`
+    Marker,
+    /// another process had it open for writing; see `TrimOptions::writers`
+    OpenForWriting,
+    /// the run was cancelled before it was trimmed in full; see `TrimOptions::cancel`
+    Cancelled,
+    /// an earlier path of the run is the same file, e.g. `Foo.txt` after `foo.txt` on a
+    /// case-insensitive filesystem; trimming both at once would race
+    Duplicate,
+    /// it was modified too recently, e.g. a log still being written; see `TrimOptions::older_than`
+    Recent,
+    /// it was last modified too long ago; see `TrimOptions::newer_than`
+    Old,
+    /// it is owned by another user or group; see `TrimOptions::owned_by`
+    NotOwned,
+    /// trimming would have shrunk it by more than `TrimOptions::max_shrink_percent`
+    Shrinks,
+    /// `TrimOptions::filter` failed on it; see `FilterFailure::Skip`
+    FilterFailed,
+}
+
+impl SkipReason {
+    /// Every reason, in the order they are declared.
+    pub const ALL: &'static [SkipReason] = &[
+        SkipReason::Marker,
+        SkipReason::OpenForWriting,
+        SkipReason::Cancelled,
+        SkipReason::Duplicate,
+        SkipReason::Recent,
+        SkipReason::Old,
+        SkipReason::NotOwned,
+        SkipReason::Shrinks,
+        SkipReason::FilterFailed,
+    ];
+
+    /// # Returns
+    ///
+    /// A stable, machine-readable name for this reason, e.g. for reports.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SkipReason::Marker => "marker",
+            SkipReason::OpenForWriting => "open-for-writing",
+            SkipReason::Cancelled => "cancelled",
+            SkipReason::Duplicate => "duplicate",
+            SkipReason::Recent => "recent",
+            SkipReason::Old => "old",
+            SkipReason::NotOwned => "not-owned",
+            SkipReason::Shrinks => "shrinks",
+            SkipReason::FilterFailed => "filter-failed",
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SkipReason::Marker => write!(f, "skipped by a marker"),
+            SkipReason::OpenForWriting => write!(f, "skipped, open for writing by another process"),
+            SkipReason::Cancelled => write!(f, "skipped, cancelled"),
+            SkipReason::Duplicate => write!(f, "skipped, the same file as an earlier argument"),
+            SkipReason::Recent => write!(f, "skipped, modified too recently"),
+            SkipReason::Old => write!(f, "skipped, not modified recently enough"),
+            SkipReason::NotOwned => write!(f, "skipped, owned by another user or group"),
+            SkipReason::Shrinks => write!(f, "skipped, trimming would shrink it too much"),
+            SkipReason::FilterFailed => write!(f, "skipped, the filter command failed on it"),
+        }
+    }
+}
+
+/// Something worth knowing about an input, which did not keep it from being trimmed; reported
+/// along with its outcome, and only fails the run if asked to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Warning {
+    /// it has both `\n` and `\r\n` line endings
+    MixedLineEndings,
+    /// it is a symlink, which was replaced by a regular file holding the trimmed content of its
+    /// target, rather than having the target trimmed
+    Symlink,
+    /// it was skipped, as trimming would have shrunk it by more than
+    /// `TrimOptions::max_shrink_percent`; it may not be line-oriented text
+    Shrinks,
+    /// `TrimOptions::filter` failed on it, so it was skipped, or written without the filter; see
+    /// `FilterFailure`
+    FilterFailed,
+    /// its owner, group or permissions could not be given to its trimmed copy, which is owned by
+    /// the current user instead; see `MetadataPolicy::BestEffort`
+    MetadataNotKept,
+    /// it starts with a byte order mark, before its shebang, which keeps it from being run as a
+    /// script; the mark is left as it is
+    BomBeforeShebang,
+}
+
+impl Warning {
+    /// Every warning, in the order they are declared.
+    pub const ALL: &'static [Warning] = &[
+        Warning::MixedLineEndings,
+        Warning::Symlink,
+        Warning::Shrinks,
+        Warning::FilterFailed,
+        Warning::MetadataNotKept,
+        Warning::BomBeforeShebang,
+    ];
+
+    /// # Returns
+    ///
+    /// A stable, machine-readable name for this warning, e.g. for reports.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Warning::MixedLineEndings => "mixed-line-endings",
+            Warning::Symlink => "symlink",
+            Warning::Shrinks => "shrinks",
+            Warning::FilterFailed => "filter-failed",
+            Warning::MetadataNotKept => "metadata-not-kept",
+            Warning::BomBeforeShebang => "bom-before-shebang",
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::MixedLineEndings => write!(f, "mixed line endings"),
+            Warning::Symlink => write!(f, "a symlink, replaced by a regular file"),
+            Warning::Shrinks => write!(f, "left as is, trimming would shrink it too much"),
+            Warning::FilterFailed => write!(f, "the filter command failed on it"),
+            Warning::MetadataNotKept => {
+                write!(f, "its owner, group or permissions could not be kept")
+            }
+            Warning::BomBeforeShebang => {
+                write!(
+                    f,
+                    "a byte order mark before its shebang keeps it from running"
+                )
+            }
+        }
+    }
+}
+
+/// A line that was trimmed; the same information as a line of the visuals.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    /// 1-based line number
+    pub line_number: usize,
+    /// the line after trimming, without its terminator
+    pub trimmed_line: String,
+    /// number of bytes trimmed from the line
+    pub bytes_trimmed: usize,
+    /// number of columns what was trimmed took up on a terminal; `None` if that is unknown, e.g.
+    /// for control characters, or if more than the end of the line changed
+    pub width_trimmed: Option<usize>,
+}
+
+impl Finding {
+    /// # Returns
+    ///
+    /// How many `_` visualize what was trimmed: one per column it took up, along with `true`; or
+    /// one per byte, along with `false`, if its width is unknown.
+    pub fn padding_len(&self) -> (usize, bool) {
+        match self.width_trimmed {
+            Some(width) => (width, true),
+            None => (self.bytes_trimmed, false),
+        }
+    }
+}
+
+/// # Returns
+///
+/// Number of columns `trimmed` takes up on a terminal, counting a tab as one, like the visuals do;
+/// `None` if any other control character is in it, whose width is up to the terminal.
+fn display_width(trimmed: &str) -> Option<usize> {
+    trimmed
+        .chars()
+        .map(|c| match c {
+            '\t' => Some(1),
+            _ => c.width(),
+        })
+        .sum()
 }
 
 /// Trim the lines in `Iterator` and write them to `std::io::Stdout`.
@@ -25,8 +385,9 @@ pub struct TrimResult {
 /// # Parameters
 ///
 /// 1. `lines` -- iterator of lines to trim_iter
-/// 1. `suppress_visual` -- if `false`, write visuals to `std::io::Stderr`, don't otherwise
-/// 1. `suppress_newline` -- if `false`, end the last line with `\n`, don't otherwise
+/// 1. `visual_opt` -- where visualizations of the trim will be written, optional; e.g.
+///    `std::io::Stderr`
+/// 1. `options` -- how to trim each line; see `TrimOptions`
 ///
 /// # Returns
 ///
@@ -35,429 +396,4088 @@ pub struct TrimResult {
 /// # Side Effects
 ///
 /// 1. trimmed lines are written to `std::io::Stdout`
-/// 1. visualizations are written to `std::io::Stderr` if and only if `suppress_visual` is `false`
+/// 1. `if let Some(visual) = visual_opt`, visualizations are written to `visual`
 #[inline]
-pub fn trim_iter<I>(
+pub fn trim_iter<I, E>(
     lines: I,
-    suppress_visual: bool,
-    suppress_newline: bool,
+    visual_opt: &mut Option<E>,
+    options: &TrimOptions,
 ) -> io::Result<TrimResult>
 where
-    I: Iterator<Item = io::Result<String>>,
+    I: Iterator<Item = io::Result<Line>>,
+    E: FindingSink,
 {
-    let err = stderr(); // declare outside the `match` to circumvent the borrow checker
-
-    let bytes_saved = trim_custom(
-        lines,
-        &mut stdout().lock(),
-        &mut match suppress_visual {
-            true => None,
-            false => Some(err.lock()),
-        },
-        suppress_newline,
-    )?;
+    TrimState::default()
+        .trim_lines(lines, (1, 0), &mut stdout().lock(), visual_opt, options)?
+        .finish(&mut stdout().lock(), visual_opt, options)
+}
 
-    Ok(TrimResult { bytes_saved })
+/// Like `trim_iter`, but reads the lines from `reader`; see `trim_custom_reader`.
+#[inline]
+pub fn trim_reader<R, E>(
+    reader: R,
+    visual_opt: &mut Option<E>,
+    options: &TrimOptions,
+) -> io::Result<TrimResult>
+where
+    R: BufRead,
+    E: FindingSink,
+{
+    trim_reader_to(reader, stdout().lock(), visual_opt, options)
 }
 
-/// Trim the lines in each file in `files`, in-place.
+/// Like `trim_reader`, but writes the trimmed lines to `out` instead of `std::io::Stdout`, e.g.
+/// to an inherited file descriptor.
+pub fn trim_reader_to<R, W, E>(
+    reader: R,
+    out: W,
+    visual_opt: &mut Option<E>,
+    options: &TrimOptions,
+) -> io::Result<TrimResult>
+where
+    R: BufRead,
+    W: Write,
+    E: FindingSink,
+{
+    let mut out = BufWriter::with_capacity(options.buffer_size(BUFFER_SIZE, 1), out);
+
+    // the head has been read already, so put it back in front of the rest
+    let mut reader = take_records(
+        reader,
+        options.max_lines.unwrap_or(usize::MAX),
+        options.separator(),
+    );
+    let head = read_head(&mut reader, options)?;
+    let mut reader = Cursor::new(head).chain(reader);
+    if skips_file(reader.get_ref().0.get_ref(), options) {
+        io::copy(&mut reader, &mut out)?;
+        out.flush()?;
+        return Ok(TrimResult::skipped(SkipReason::Marker));
+    }
+
+    TrimState::default()
+        .trim_reader(reader, (1, 0), &mut out, visual_opt, options)?
+        .finish(&mut out, visual_opt, options)
+}
+
+/// Like `trim_reader_to`, but reports what each rule changed in each line to `on_finding`, as
+/// the line is trimmed, instead of visualizing it; for embedders that turn findings into their
+/// own diagnostics.
+#[inline]
+pub fn trim_reader_with<R, W, F>(
+    reader: R,
+    out: W,
+    options: &TrimOptions,
+    on_finding: F,
+) -> io::Result<TrimResult>
+where
+    R: BufRead,
+    W: Write,
+    F: FnMut(LineFinding),
+{
+    trim_reader_to(reader, out, &mut Some(OnFinding(on_finding)), options)
+}
+
+/// Whether the file under `path` is already trimmed; see `is_trimmed_reader`.
+#[inline]
+pub fn is_trimmed<P>(path: P, options: &TrimOptions) -> io::Result<bool>
+where
+    P: AsRef<Path>,
+{
+    is_trimmed_reader(File::open(long_path(path.as_ref()))?, options)
+}
+
+/// Whether the content of `reader` is already trimmed, i.e. trimming it with `options` would
+/// leave it as is, byte for byte; nothing is written, and reading stops at the first byte that
+/// trimming would change.
 ///
 /// # Parameters
 ///
-/// 1. `files` -- files to trim, in-place
-/// 1. `suppress_newline` -- if `false`, end the last line with `\n`, don't otherwise
+/// 1. `reader` -- the content to check
+/// 1. `options` -- how each line would be trimmed; see `TrimOptions`
 ///
 /// # Returns
 ///
-/// Mapping,
-/// - from: a path to the file being trimmed in-place
-/// - to: the result of trimming that file
+/// `true` if the trimmed content is the content of `reader`, including if the input would be
+/// skipped as a whole; `false` if any byte would change, or if `TrimOptions::max_lines` would cut
+/// the content short.
+pub fn is_trimmed_reader<R>(reader: R, options: &TrimOptions) -> io::Result<bool>
+where
+    R: Read,
+{
+    Ok(already_trimmed_reader(reader, options)?.is_some())
+}
+
+/// Like `is_trimmed_reader`, but with what is worth knowing about content that is already
+/// trimmed, e.g. its warnings, or why it would be skipped.
 ///
-/// # Side Effects
+/// # Returns
 ///
-/// The content of each file in `files` is overwritten with its trimmed content, if the trimmed
-/// content differs from the original content. This overwriting happens atomically.
-pub fn trim_files(
-    files: &Vec<PathBuf>,
-    suppress_newline: bool,
-) -> HashMap<PathBuf, io::Result<TrimResult>> {
-    files
-        .into_par_iter()
-        .map(|path_buf| (path_buf.clone(), trim_file(&path_buf, suppress_newline)))
-        .collect()
+/// The result of trimming the content of `reader`, if it would be left as is; `None` if any byte
+/// would change.
+pub fn already_trimmed_reader<R>(reader: R, options: &TrimOptions) -> io::Result<Option<TrimResult>>
+where
+    R: Read,
+{
+    let unmatched = Rc::new(RefCell::new(Unmatched::default()));
+    let reader = BufReader::new(Recording {
+        reader,
+        unmatched: Rc::clone(&unmatched),
+    });
+    let matching = Matching {
+        unmatched: Rc::clone(&unmatched),
+    };
+    let result = trim_reader_to(reader, matching, &mut None::<File>, options);
+    let unmatched = unmatched.borrow();
+    match result {
+        Ok(trim_result) if !unmatched.differs && unmatched.bytes.is_empty() => {
+            Ok(Some(trim_result))
+        }
+        Ok(_) => Ok(None),
+        Err(_) if unmatched.differs => Ok(None),
+        Err(err) => Err(err),
+    }
 }
 
-/// Like `trim_files`, but for a single file.
-fn trim_file(path: &Path, suppress_newline: bool) -> io::Result<TrimResult> {
-    // create a tempfile to hold the trimmed content
-    let basename = path.file_name().unwrap().to_str().unwrap().to_string();
-    let basename = format!("{}.trim", hash_default(&basename));
-    let copy_path = env::temp_dir().as_path().join(basename);
-    if copy_path.exists() {
-        remove_file(&copy_path)?;
+/// What was read by `Recording` but not written to `Matching` yet; see `is_trimmed_reader`.
+#[derive(Default)]
+struct Unmatched {
+    bytes: VecDeque<u8>,
+    /// whether a written byte differed from the byte read in its place
+    differs: bool,
+}
+
+/// Reads from `reader`, and keeps what is read in `unmatched`.
+struct Recording<R> {
+    reader: R,
+    unmatched: Rc<RefCell<Unmatched>>,
+}
+
+impl<R> Read for Recording<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.reader.read(buf)?;
+        self.unmatched.borrow_mut().bytes.extend(&buf[..len]);
+        Ok(len)
     }
-    copy(path, &copy_path)?; // copy contents and permissions
+}
 
-    // open a file with write permission, overwriting its content
-    let mut copy_file = OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .open(&copy_path)?;
+/// Takes what is written out of `unmatched`, failing as soon as it differs from what was read.
+struct Matching {
+    unmatched: Rc<RefCell<Unmatched>>,
+}
+
+impl Write for Matching {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut unmatched = self.unmatched.borrow_mut();
+        // the trimmed content can only run ahead of the content once it has all been read
+        let matches = unmatched.bytes.len() >= buf.len()
+            && unmatched.bytes.drain(..buf.len()).eq(buf.iter().copied());
+        if !matches {
+            unmatched.differs = true;
+            return Err(io::Error::other("the content is not trimmed"));
+        }
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Like `trim_iter`, but for `content` held in memory as a whole, which is trimmed in parallel;
+/// see `trim_chunked`.
+#[inline]
+pub fn trim_slice<E>(
+    content: &[u8],
+    visual_opt: &mut Option<E>,
+    options: &TrimOptions,
+) -> io::Result<TrimResult>
+where
+    E: FindingSink,
+{
+    trim_slice_to(content, stdout().lock(), visual_opt, options)
+}
+
+/// Like `trim_slice`, but writes the trimmed lines to `out` instead of `std::io::Stdout`.
+pub fn trim_slice_to<W, E>(
+    content: &[u8],
+    mut out: W,
+    visual_opt: &mut Option<E>,
+    options: &TrimOptions,
+) -> io::Result<TrimResult>
+where
+    W: Write,
+    E: FindingSink,
+{
+    let content = match options.max_lines {
+        Some(max_lines) => first_records(content, max_lines, options.separator()),
+        None => content,
+    };
+    if skips_file(&read_head(&mut &content[..], options)?, options) {
+        out.write_all(content)?;
+        return Ok(TrimResult::skipped(SkipReason::Marker));
+    }
+
+    let trim_result = trim_chunked_by(content, chunk_size(options), &mut out, visual_opt, options)?;
+    out.flush()?;
+    Ok(trim_result)
+}
+
+/// Like `trim_reader_to`, but for interactive pipelines such as `tail -f log | trim`; each line
+/// is written and flushed as soon as it is read, along with its terminator.
+///
+/// Blank lines are still deferred, in case they turn out to be trailing, but only until no line
+/// arrives for `LINE_BUFFERED_TIMEOUT`. `TrimOptions::suppress_newline`,
+/// `TrimOptions::final_newlines`, `TrimOptions::empty_file` and `TrimOptions::skip_file_marker`
+/// are ignored, as they would hold back output.
+///
+/// `reader` is read on a thread of its own, which is left behind if trimming fails.
+#[inline]
+pub fn trim_reader_line_buffered<R, W, E>(
+    reader: R,
+    out: W,
+    visual_opt: &mut Option<E>,
+    options: &TrimOptions,
+) -> io::Result<TrimResult>
+where
+    R: BufRead + Send + 'static,
+    W: Write,
+    E: FindingSink,
+{
+    trim_reader_streaming(
+        reader,
+        out,
+        visual_opt,
+        options,
+        Some(LINE_BUFFERED_TIMEOUT),
+    )
+}
+
+/// Like `trim_reader_line_buffered`, but for input that never really ends, e.g. a growing log
+/// read through `util::Follow`; blank lines are written as soon as they are read, as there are no
+/// trailing ones without an end.
+#[inline]
+pub fn trim_reader_follow<R, W, E>(
+    reader: R,
+    out: W,
+    visual_opt: &mut Option<E>,
+    options: &TrimOptions,
+) -> io::Result<TrimResult>
+where
+    R: BufRead + Send + 'static,
+    W: Write,
+    E: FindingSink,
+{
+    trim_reader_streaming(reader, out, visual_opt, options, None)
+}
 
-    // actual trimming
-    let bytes_saved = trim_custom(
-        readlines(path)?,
-        &mut copy_file,
-        &mut None::<File>,
-        suppress_newline,
-    )?;
+/// Like `trim_reader_line_buffered`, but blank lines are deferred for `blank_timeout`; they are
+/// not deferred at all if `None`.
+fn trim_reader_streaming<R, W, E>(
+    reader: R,
+    out: W,
+    visual_opt: &mut Option<E>,
+    options: &TrimOptions,
+    blank_timeout: Option<Duration>,
+) -> io::Result<TrimResult>
+where
+    R: BufRead + Send + 'static,
+    W: Write,
+    E: FindingSink,
+{
+    let options = &TrimOptions {
+        suppress_newline: false,
+        final_newlines: None,
+        empty_file: EmptyFile::Keep,
+        ..options.clone()
+    };
+    let mut out = BufWriter::with_capacity(options.buffer_size(BUFFER_SIZE, 1), out);
 
-    rename(copy_path, path)?; // mv --force "$copy_path" "$path"
+    let sep = options.separator();
+    let max_lines = options.max_lines.unwrap_or(usize::MAX);
+    let (sender, receiver) = sync_channel(BUFFER_SIZE);
+    spawn(move || {
+        let mut reader = take_records(reader, max_lines, sep);
+        loop {
+            let mut line = Vec::new();
+            match reader.read_until(sep, &mut line) {
+                Ok(0) => break,
+                // the receiver is gone if trimming failed
+                Ok(_) if sender.send(Ok(line)).is_err() => break,
+                Ok(_) => {}
+                Err(err) => {
+                    let _ = sender.send(Err(err));
+                    break;
+                }
+            }
+        }
+    });
 
-    Ok(TrimResult { bytes_saved })
+    let mut state = TrimState::default();
+    let (mut line_number, mut offset) = (1, 0);
+    loop {
+        let received = match blank_timeout {
+            Some(timeout) => receiver.recv_timeout(timeout),
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(line) => {
+                let line = line?;
+                let (content, ending) = split_record(&line, options.separator());
+                let position = (line_number, offset);
+                state.push(
+                    (utf8(content)?, ending),
+                    position,
+                    &mut out,
+                    visual_opt,
+                    options,
+                )?;
+                // the terminator of a non-empty line, or blank lines that are not deferred
+                if blank_timeout.is_none() || state.eol.is_some() && !state.eol_flushed {
+                    state.flush_deferred(&mut out)?;
+                }
+                line_number += 1;
+                offset += line.len() as u64;
+            }
+            // quiet for a while; the blank lines may well be all there is for now
+            Err(RecvTimeoutError::Timeout) => state.flush_deferred(&mut out)?,
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        out.flush()?;
+        if let Some(visual) = visual_opt {
+            visual.flush_findings()?;
+        }
+    }
+    state.finish(&mut out, visual_opt, options)
 }
 
+/// Trim the lines in each file in `files`, in-place.
+///
 /// # Parameters
 ///
-/// 1. `lines` -- lines to trim, as an iterator
-/// 1. `out` -- where trimmed results will be written
-/// 1. `err_opt` -- where visualizations of the trim will be written, optional
-/// 1. `suppress_newline` -- omit `\n` at the end of the last line if true, put it otherwise
+/// 1. `files` -- files to trim, in-place; those not in `TrimOptions::extensions` are left out
+/// 1. `options` -- how to trim each line; see `TrimOptions`
 ///
 /// # Returns
 ///
-/// Number of bytes trimmed.
+/// Each file that was trimmed, in the order of `files`, and what became of it.
 ///
 /// # Side Effects
 ///
-/// 1. trimmed lines are written to `out`
-/// 1. `if let Some(err) = err_opt`, visualizations of the trimmings are written to `err`
-fn trim_custom<I, W, E>(
-    lines: I,
-    out: &mut W,
-    err_opt: &mut Option<E>,
-    suppress_newline: bool,
-) -> io::Result<i32>
+/// The content of each file in `files` is overwritten with its trimmed content, if the trimmed
+/// content differs from the original content. This overwriting happens atomically.
+///
+/// With `TrimOptions::out_dir`, the files are left untouched instead, and the trimmed content of
+/// each is written to its mirrored path, atomically, along with its permissions and modification
+/// time.
+pub fn trim_files(files: &[PathBuf], options: &TrimOptions) -> Vec<(PathBuf, FileOutcome)> {
+    trim_files_with(files, options, |_| {})
+}
+
+/// How far `trim_files_with` is through its files, as of a file being started or done.
+#[derive(Clone, Copy)]
+pub struct Progress<'a> {
+    /// number of files done so far, including `path` if it is done
+    pub files_done: usize,
+    /// number of files to trim in total; those not in `TrimOptions::extensions` are not counted
+    pub files_total: usize,
+    /// the file that was just started, or just done
+    pub path: &'a Path,
+    /// what became of `path` if it is done, `None` if it was just started
+    pub outcome: Option<&'a FileOutcome>,
+}
+
+/// # Returns
+///
+/// The index in `files` of each file that is the same file as one before it, under a path that
+/// differs only by case, if at all; e.g. `Foo.txt` after `foo.txt` on a case-insensitive
+/// filesystem, where both name one file. Only paths that collide once lowercased are looked up.
+fn duplicates(files: &[&PathBuf]) -> HashSet<usize> {
+    let mut by_lowercase: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, path_buf) in files.iter().enumerate() {
+        let lowercase = path_buf.to_string_lossy().to_lowercase();
+        by_lowercase.entry(lowercase).or_default().push(index);
+    }
+    by_lowercase
+        .values()
+        .filter(|indices| indices.len() > 1)
+        .flat_map(|indices| {
+            indices.iter().enumerate().filter_map(move |(i, &index)| {
+                let earlier = &indices[..i];
+                let duplicate = earlier
+                    .iter()
+                    .any(|&earlier| same_file(files[earlier], files[index]));
+                duplicate.then_some(index)
+            })
+        })
+        .collect()
+}
+
+/// Like `trim_files`, but calls `on_progress` as each file is started, and again as soon as it is
+/// done, e.g. to draw a progress bar or to record completed files.
+///
+/// Files are trimmed in parallel, so calls come from several threads, and `files_done` may not
+/// increase from one call to the next.
+pub fn trim_files_with<F>(
+    files: &[PathBuf],
+    options: &TrimOptions,
+    on_progress: F,
+) -> Vec<(PathBuf, FileOutcome)>
 where
-    I: Iterator<Item = io::Result<String>>,
-    W: Write,
-    E: Write,
+    F: Fn(Progress) + Sync,
 {
-    // `lf_trimmed` = number of linebreaks encountered, but not written yet
-    // `u8_trimmed` = number of bytes trimmed for sure
-    //
-    // contains lots of hacks in order to do the trimming in a streaming style
-    let (lf_trimmed, u8_trimmed) = lines
-        .map(io::Result::unwrap)
+    let files: Vec<&PathBuf> = files
+        .iter()
+        .filter(|path_buf| options.includes(path_buf))
+        .collect();
+    // trimming the same file twice at once, each replacing it, would lose one of the two
+    let duplicates = duplicates(&files);
+    let mut files: Vec<(usize, &PathBuf, u64)> = files
+        .into_iter()
         .enumerate()
-        .map(|(index, line)| (index + 1, line) /* make 1-based */)
-        .map(|(line_number, line)| {
-            let trimmed_line = line.trim_end().to_string();
-            let bytes_saved = line.len() - trimmed_line.len();
-            let visual_opt = Some(bytes_saved)
-                .filter(|x| x > &0)
-                .map(red_padding_with_len)
-                .map(|red_pad| format!("{:>6}|{}{}", line_number, trimmed_line, red_pad));
-            (trimmed_line, visual_opt, bytes_saved) // (String, Option<impl >
-        })
-        .fold(
-            // same type as `(lf_trimmed, u8_trimmed)`
-            io::Result::Ok((0usize, 0usize)),
-            |acc, (trimmed_line, opt_visual, u8_trimmed)| {
-                match &acc {
-                    // empty line encountered; increment the `lf_count` without writing, because if
-                    // this `\n` is one of the trailing newlines in the file, we don't want
-                    // to print it and include it as bytes saved, so defer the printing until later
-                    Ok((lf_count, total)) if trimmed_line.len() == 0 => {
-                        Ok((lf_count + 1, total + u8_trimmed))
-                    }
-                    // most common case; a non-empty line
-                    Ok((lf_count, total)) => {
-                        // print the accumulated newlines, if any
-                        let lfs: String = (0..*lf_count).map(|_| '\n').collect();
-                        write!(out, "{}{}", lfs, trimmed_line)?;
-
-                        // print the visual to err, if applicable
-                        if let Some(err) = err_opt {
-                            if let Some(visual) = opt_visual {
-                                writeln!(err, "{}", visual)?;
-                            }
-                        }
-                        // `\n` may or may not exist at the end of this line, but pretend like it
-                        // exists for now, and defer the printing until later
-                        Ok((1, total + u8_trimmed))
-                    }
-                    // just propagate the err
-                    Err(_) => acc,
-                }
-            },
-        )?;
-
-    // trailing `\n` is not printed in `fold`, so if `\n` is not to be suppressed then print one now
-    if !suppress_newline {
-        write!(out, "\n")?;
+        .map(|(index, path_buf)| (index, path_buf, 0))
+        .collect();
+    if options.schedule == Schedule::Size {
+        // unreadable files fail fast anyway, so they may go last
+        files
+            .par_iter_mut()
+            .for_each(|(_, path_buf, len)| *len = path_buf.metadata().map_or(0, |m| m.len()));
+        files.sort_by_key(|&(_, _, len)| Reverse(len));
     }
 
-    // flush both out and err
-    out.flush()?;
-    if let Some(err) = err_opt {
-        err.flush()?;
-    }
-
-    // total number of bytes saved
-    let bytes_saved = (u8_trimmed + lf_trimmed) as i32
-        // `lf_trimmed` includes an imaginary `\n` that may or may not exist
-        + match lf_trimmed {
-            // this means that the last line is nonempty and may or may not end with `\n`
-            // as mentioned in the tests, `abc\n` and `abc` are treated the same, so just -1 to act
-            // like the newline doesn't exist
-            1 => -1,
-            // this only happens if file is empty, just ignore
-            0 => 0,
-            // `abc\n\n` is trimmed to `abc`
-            _ => 0,
-        }
-        + match suppress_newline {
-            true => 1,
-            false => 0, // compensate for the `\n` that is printed above
-        };
-    //
-    Ok(bytes_saved)
+    let files_total = files.len();
+    let files_done = AtomicUsize::new(0);
+    if let Some(stats) = &options.stats {
+        stats.add_total(files_total);
+    }
+    // one file per task, so that idle workers pick up the next file in order
+    let mut outcomes: Vec<_> = files
+        .into_par_iter()
+        .with_max_len(1)
+        .map(|(index, path_buf, _)| {
+            on_progress(Progress {
+                files_done: files_done.load(Ordering::Relaxed),
+                files_total,
+                path: path_buf,
+                outcome: None,
+            });
+            let outcome = match duplicates.contains(&index) {
+                true => FileOutcome::Skipped(SkipReason::Duplicate),
+                false => trim_file_retrying(path_buf, options),
+            };
+            if let Some(stats) = &options.stats {
+                stats.record(&outcome);
+            }
+            on_progress(Progress {
+                files_done: files_done.fetch_add(1, Ordering::Relaxed) + 1,
+                files_total,
+                path: path_buf,
+                outcome: Some(&outcome),
+            });
+            (index, path_buf.clone(), outcome)
+        })
+        .collect();
+    // back in the order of `files`, whatever the schedule
+    outcomes.sort_by_key(|&(index, _, _)| index);
+    outcomes
+        .into_iter()
+        .map(|(_, path_buf, outcome)| (path_buf, outcome))
+        .collect()
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs::read_to_string;
+/// Like `trim_file`, but retries transient errors with exponential backoff if
+/// `TrimOptions::net_safe`. Retrying is safe, as the file is only ever replaced as a whole.
+fn trim_file_retrying(path: &Path, options: &TrimOptions) -> FileOutcome {
+    let attempts = match options.net_safe {
+        true => NET_SAFE_ATTEMPTS,
+        false => 1,
+    };
+    let cancelled = || {
+        options
+            .cancel
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    };
+    let mut retries = 0;
+    loop {
+        // not even opened; whatever it was in the middle of, the file was left untouched
+        if cancelled() {
+            return FileOutcome::Skipped(SkipReason::Cancelled);
+        }
+        match trim_file(path, options) {
+            Err(_) if cancelled() => return FileOutcome::Skipped(SkipReason::Cancelled),
+            Err(ref err) if retries + 1 < attempts && is_transient(err) => {
+                sleep(NET_SAFE_BACKOFF * 2u32.pow(retries));
+                retries += 1;
+            }
+            Ok(FileOutcome::Modified(result)) => {
+                return FileOutcome::Modified(TrimResult { retries, ..result })
+            }
+            Ok(outcome) => return outcome,
+            Err(err) if retries > 0 => {
+                let message = format!("{} (after {} retries)", err, retries);
+                return FileOutcome::Failed(io::Error::new(err.kind(), message));
+            }
+            Err(err) => return FileOutcome::Failed(err),
+        }
+    }
+}
 
-    fn test_data() -> Vec<(&'static str, &'static str, i32)> {
-        vec![
-            // empty
-            ("", "", 1),
-            // nothing to trim
-            ("abc", "abc", 1),
-            ("\nasd fgh\nabc", "\nasd fgh\nabc", 1),
-            // `\r` is not used as a line break
-            ("ab \t  \r abc", "ab \t  \r abc", 1),
-            // `\r` is a whitespace though
-            ("ab \t  \r \nabc", "ab\nabc", 7),
-            ("\n \n \n\t\t \t \n \t\r \n\r   \r\r \n     \n \n", "", 33),
-            // trailing newlines are removed
-            ("ab \ncd \n  \n\n  \n", "ab\ncd", 11),
-            // leading newlines are preserved
-            ("  \n\t\r \r \n 123 absoi", "\n\n 123 absoi", 8),
-            // weirdness with `std::io::BufReader`
-            // this is shown in the tests for `crate::util::tests::readlines` but
-            // 1. bufreader sees no difference between `[^\n]+\n` and ``
-            ("abc", "abc", 1),
-            ("abc\n", "abc", 1),
-            ("abc\n\n", "abc", 3),
-            ("abc ", "abc", 2),
-            ("abc \n", "abc", 2),
-        ]
+/// Like `trim_files`, but for a single file; never `FileOutcome::Failed`, but `Err` instead.
+fn trim_file(path: &Path, options: &TrimOptions) -> io::Result<FileOutcome> {
+    // the lines past the limit would be lost
+    if let Some(max_lines) = options.max_lines {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "only the first {} lines of {:?} would be kept; it can only be trimmed to stdout",
+                max_lines, path
+            ),
+        ));
+    }
+    // paths too long for the platform are opened in a form that is not limited
+    let long = long_path(path);
+    // checked before opening, as opening a pipe blocks until there is a writer; directories fail
+    // on their own once read
+    let file_type = metadata(&long)?.file_type();
+    if !file_type.is_file() && !file_type.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{:?} is not a regular file, e.g. a pipe; it can only be trimmed to stdout",
+                path
+            ),
+        ));
+    }
+    let source = File::open(&long)?;
+    let dest = match &options.out_dir {
+        Some(out_dir) => {
+            let dest = long_path(&mirror_path(out_dir, path)?).into_owned();
+            if let Some(parent) = dest.parent() {
+                create_dir_all(parent)?;
+            }
+            dest
+        }
+        None => long.to_path_buf(),
+    };
+    let path: &Path = &long;
+    // renaming over a symlink replaces the link, rather than its target
+    let is_symlink = dest == path && symlink_metadata(path)?.file_type().is_symlink();
+    // replacing it would lose what the other process writes next
+    if let Some(writers) = &options.writers {
+        if writers.has_writer(&source.metadata()?) {
+            return Ok(FileOutcome::Skipped(SkipReason::OpenForWriting));
+        }
+    }
+    // likely still being written to; a clock set back counts as recent
+    if let Some(older_than) = options.older_than {
+        let age = source.metadata()?.modified()?.elapsed().unwrap_or_default();
+        if age < older_than {
+            return Ok(FileOutcome::Skipped(SkipReason::Recent));
+        }
     }
+    // someone else's to look after
+    if let Some(owner) = &options.owned_by {
+        if !owner.owns(&source.metadata()?) {
+            return Ok(FileOutcome::Skipped(SkipReason::NotOwned));
+        }
+    }
+    // likely trimmed already, by an earlier run
+    if let Some(newer_than) = options.newer_than {
+        if source.metadata()?.modified()? < newer_than {
+            return Ok(FileOutcome::Skipped(SkipReason::Old));
+        }
+    }
+    // opted out; leave the file alone, but still mirror it as is
+    if skips_file(&read_head(&mut BufReader::new(&source), options)?, options) {
+        if dest != path {
+            copy(path, &dest)?;
+            copy_modified(&source, &dest)?;
+        }
+        return Ok(FileOutcome::Skipped(SkipReason::Marker));
+    }
+    (&source).seek(SeekFrom::Start(0))?;
 
-    mod stdout {
-        use super::*;
+    // a reader and a writer per file being trimmed in parallel
+    let buffer_size = options.buffer_size(BUFFER_SIZE, 2 * current_num_threads());
+    let throttle = options.throttle.as_deref();
+    let cancel = options.cancel.as_ref();
+    let mut writer = InPlaceWriter::new(path, &dest, &source, buffer_size, throttle)
+        .with_metadata(options.metadata);
 
-        /// trim and output to stdout, with default options
-        #[test]
-        fn parametrized_n_suppress_newline() {
-            test_data().into_par_iter().enumerate().for_each(
-                |(index, (input, expected_raw, savings))| {
-                    // make a "uniq" file
-                    let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
-                    let path_to_temp = mktemp(&prefix, &input).unwrap();
+    let result = (|| {
+        // actual trimming
+        let fits_in_memory = options.fits_in_memory(source.metadata()?.len());
+        // a filter gets the trimmed content as a whole, so it is trimmed in memory
+        let in_memory = options.filter.is_some() || options.intra_file_parallel && fits_in_memory;
+        let trim_result = match in_memory {
+            true => {
+                let mut content = Vec::new();
+                cancellable(throttled(&source, throttle), cancel).read_to_end(&mut content)?;
+                let mut trimmed = Vec::new();
+                let mut trim_result = trim_chunked_by(
+                    &content,
+                    chunk_size(options),
+                    &mut trimmed,
+                    &mut None::<File>,
+                    options,
+                )?;
+                if let Some(filter) = &options.filter {
+                    match (filter.run(path, &trimmed), filter.on_failure) {
+                        (Ok(filtered), _) => trimmed = filtered,
+                        (Err(err), FilterFailure::Error) => return Err(err),
+                        (Err(_), FilterFailure::Skip) => {
+                            writer.discard();
+                            return Ok(FileOutcome::Skipped(SkipReason::FilterFailed));
+                        }
+                        (Err(_), FilterFailure::Unfiltered) => {
+                            trim_result.warnings.push(Warning::FilterFailed)
+                        }
+                    }
+                }
+                match trimmed == content {
+                    true => writer.write_unchanged(&trimmed, 0)?,
+                    false => writer.write_trimmed(&trimmed)?,
+                }
+                trim_result
+            }
+            false => {
+                let source = cancellable(throttled(&source, throttle), cancel);
+                let reader = BufReader::with_capacity(buffer_size, source);
+                TrimState::default()
+                    .trim_reader(reader, (1, 0), &mut writer, &mut None::<File>, options)?
+                    .finish(&mut writer, &mut None::<File>, options)?
+            }
+        };
 
-                    // make sure that its content is written correctly
-                    let content = read_to_string(&path_to_temp).unwrap();
-                    assert_eq!(input, content);
+        // nothing is left worth keeping, not even the file
+        if trim_result.whitespace_only == Some(EmptyFile::Delete) {
+            writer.discard();
+            match remove_file(&dest) {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+                result => result?,
+            }
+            return Ok(FileOutcome::Modified(trim_result));
+        }
 
-                    //
-                    let mut result = Vec::new();
-                    let lines = readlines(&path_to_temp).unwrap();
-                    let tr = trim_custom(lines, &mut result, &mut None::<File>, false).unwrap();
-                    //
-                    let expected = format!("{}\n", expected_raw);
+        // far more is gone than trailing whitespace could account for, unless it was all there was
+        if let Some(max_shrink_percent) = options.max_shrink_percent {
+            let source_len = source.metadata()?.len();
+            let shrunk = source_len.saturating_sub(writer.trimmed_len()?);
+            if trim_result.whitespace_only.is_none()
+                && shrunk as f64 * 100.0 > max_shrink_percent * source_len as f64
+            {
+                writer.discard();
+                return Ok(FileOutcome::Skipped(SkipReason::Shrinks));
+            }
+        }
 
-                    assert_eq!(savings - 1, tr); // `- 1` because not suppressing `\n`
-                    assert_eq!(expected.as_bytes(), &result[..]);
-                },
-            );
+        // a trim that changed more than whitespace is a bug, which must not reach the file
+        if options.paranoid
+            && options.rules.is_empty()
+            && options.filter.is_none()
+            && !options.strip_ansi
+            && options.control_chars == ControlChars::Keep
+        {
+            let mut original = Vec::new();
+            (&source).seek(SeekFrom::Start(0))?;
+            (&source).read_to_end(&mut original)?;
+            if let Err(violation) = check_deletions(&original, &writer.trimmed()?, options) {
+                let message = format!("the trim {}, so the file is left as is", violation);
+                return Err(io::Error::other(message));
+            }
         }
 
-        /// trim and output to stdout, suppressing the trailing newline in the last line
-        #[test]
-        fn parametrized_suppress_newline() {
-            test_data().into_par_iter().enumerate().for_each(
+        let modified = writer.commit(source.metadata()?.len(), options.net_safe)?;
+        if dest != path {
+            copy_modified(&source, &dest)?;
+        }
+        // `source` still reads what was there before, even once replaced
+        if let Some(audit) = options.audit.as_ref().filter(|_| modified) {
+            audit.offer(&dest, trim_result.bytes_saved, || {
+                let mut original = Vec::new();
+                (&source).seek(SeekFrom::Start(0))?;
+                (&source).read_to_end(&mut original)?;
+                Ok(original)
+            });
+        }
+
+        let mut warnings = trim_result.warnings;
+        if modified && is_symlink {
+            warnings.push(Warning::Symlink);
+        }
+        // a clean file copied to `--out-dir` may not have kept them either
+        if !writer.metadata_kept {
+            warnings.push(Warning::MetadataNotKept);
+        }
+        Ok(match modified {
+            true => FileOutcome::Modified(TrimResult {
+                warnings,
+                ..trim_result
+            }),
+            false => FileOutcome::Unchanged(warnings),
+        })
+    })();
+
+    // don't leave the tempfile behind if anything went wrong
+    if result.is_err() {
+        writer.discard();
+    }
+    result.map_err(with_remediation)
+}
+
+/// Give the file under `dest` the modification time of `source`, e.g. for a mirrored copy.
+fn copy_modified(source: &File, dest: &Path) -> io::Result<()> {
+    let modified = source.metadata()?.modified()?;
+    File::options()
+        .write(true)
+        .open(dest)?
+        .set_modified(modified)
+}
+
+/// Writes the trimmed content of a file next to its destination, to replace the destination in
+/// the end; the destination is the file itself, unless it is mirrored to `TrimOptions::out_dir`.
+///
+/// The tempfile is only created once the output first differs from the file, so a file that is
+/// already clean is neither copied nor replaced in-place.
+struct InPlaceWriter<'a> {
+    path: &'a Path,
+    /// where the trimmed content goes; `path` itself when trimming in-place
+    dest: &'a Path,
+    source: &'a File,
+    /// number of bytes written so far, all identical to the start of `source`, while there is no
+    /// tempfile
+    unchanged_len: u64,
+    /// size of the buffer of the tempfile
+    buffer_size: usize,
+    /// budget for reading the unchanged start of `source` again; see `TrimOptions::throttle`
+    throttle: Option<&'a Throttle>,
+    /// the tempfile and what writes to it, once the output has diverged from `source`
+    temp: Option<(PathBuf, File, TempWriter<'a>)>,
+    /// whether the tempfile must get the owner, group and permissions of `source`
+    metadata: MetadataPolicy,
+    /// `false` if the tempfile did not get all of them
+    metadata_kept: bool,
+}
+
+/// Writes to the tempfile of an in-place trim.
+#[cfg(all(target_os = "linux", feature = "copy-file-range"))]
+type TempWriter<'a> = CopyRangeWriter<'a>;
+/// Writes to the tempfile of an in-place trim.
+#[cfg(not(all(target_os = "linux", feature = "copy-file-range")))]
+type TempWriter<'a> = BufWriter<File>;
+
+/// # Returns
+///
+/// A `TempWriter` that writes the trimmed content of `source` to `dest`, buffering up to
+/// `buffer_size` bytes.
+#[cfg(all(target_os = "linux", feature = "copy-file-range"))]
+#[inline]
+fn temp_writer(source: &File, dest: File, buffer_size: usize) -> TempWriter<'_> {
+    CopyRangeWriter::with_batch_size(source, dest, buffer_size)
+}
+
+/// # Returns
+///
+/// A `TempWriter` that writes the trimmed content of `source` to `dest`, buffering up to
+/// `buffer_size` bytes.
+#[cfg(not(all(target_os = "linux", feature = "copy-file-range")))]
+#[inline]
+fn temp_writer(_source: &File, dest: File, buffer_size: usize) -> TempWriter<'_> {
+    BufWriter::with_capacity(buffer_size, dest)
+}
+
+impl<'a> InPlaceWriter<'a> {
+    /// # Parameters
+    ///
+    /// 1. `path` -- the file being trimmed
+    /// 1. `dest` -- where the trimmed content goes; `path` to trim in-place
+    /// 1. `source` -- the file being trimmed, opened for reading
+    /// 1. `buffer_size` -- size of the buffer of the tempfile
+    /// 1. `throttle` -- see `TrimOptions::throttle`
+    fn new(
+        path: &'a Path,
+        dest: &'a Path,
+        source: &'a File,
+        buffer_size: usize,
+        throttle: Option<&'a Throttle>,
+    ) -> Self {
+        InPlaceWriter {
+            path,
+            dest,
+            source,
+            unchanged_len: 0,
+            buffer_size,
+            throttle,
+            temp: None,
+            metadata: MetadataPolicy::default(),
+            metadata_kept: true,
+        }
+    }
+
+    /// # Returns
+    ///
+    /// This writer, giving the tempfile the owner, group and permissions of `source` as far as
+    /// `metadata` requires; see `MetadataPolicy::copy`.
+    fn with_metadata(self, metadata: MetadataPolicy) -> Self {
+        InPlaceWriter { metadata, ..self }
+    }
+
+    /// # Returns
+    ///
+    /// What writes to the tempfile; the tempfile is created next to `path` and filled with the
+    /// unchanged bytes written so far, if it does not exist yet.
+    fn temp(&mut self) -> io::Result<&mut TempWriter<'a>> {
+        if self.temp.is_none() {
+            // create a tempfile next to `dest` to hold the trimmed content
+            let (copy_path, copy_file) = mktemp_sibling(self.dest)?;
+            let writer = temp_writer(self.source, copy_file.try_clone()?, self.buffer_size);
+            self.temp = Some((copy_path, copy_file, writer));
+            let copy_file = match &mut self.temp {
+                Some((_, copy_file, _)) => copy_file,
+                None => unreachable!(),
+            };
+
+            // only the permissions are needed from the original, not its content
+            self.metadata_kept = self.metadata.copy(&self.source.metadata()?, copy_file)?;
+            let mut prefix =
+                throttled(File::open(self.path)?, self.throttle).take(self.unchanged_len);
+            io::copy(&mut prefix, copy_file)?;
+        }
+        match &mut self.temp {
+            Some((_, _, temp)) => Ok(temp),
+            None => unreachable!(),
+        }
+    }
+
+    /// Replace the file with its trimmed content, unless it is already clean.
+    ///
+    /// # Parameters
+    ///
+    /// 1. `source_len` -- size of the file being trimmed, in bytes
+    /// 1. `net_safe` -- see `TrimOptions::net_safe`
+    ///
+    /// # Returns
+    ///
+    /// `true` if the trimmed content differs from the content of the file, `false` otherwise.
+    fn commit(&mut self, source_len: u64, net_safe: bool) -> io::Result<bool> {
+        // the output differs, or is a strict prefix of the file, e.g. if trailing newlines were
+        // dropped
+        let modified = self.temp.is_some() || self.unchanged_len != source_len;
+        // the output goes elsewhere, where even a clean file has to be copied to
+        if self.temp.is_none() && (modified || self.dest != self.path) {
+            self.temp()?;
+        }
+        match &mut self.temp {
+            Some((copy_path, copy_file, temp)) => {
+                temp.flush_trimmed()?;
+                if net_safe {
+                    copy_file.sync_all()?;
+                }
+                let trimmed_len = copy_file.metadata()?.len();
+
+                rename(&*copy_path, self.dest)?; // mv --force "$copy_path" "$dest"
+
+                if net_safe {
+                    sync_parent(self.dest);
+                    // the rename may have silently not happened, or not in full
+                    let renamed_len = metadata(self.dest)?.len();
+                    if renamed_len != trimmed_len {
+                        let message = format!(
+                            "{} bytes after the rename, expected {}",
+                            renamed_len, trimmed_len
+                        );
+                        return Err(io::Error::other(message));
+                    }
+                }
+                Ok(modified)
+            }
+            // nothing to do; the file is already clean
+            None => Ok(false),
+        }
+    }
+
+    /// # Returns
+    ///
+    /// Number of bytes written so far.
+    fn trimmed_len(&mut self) -> io::Result<u64> {
+        match &mut self.temp {
+            Some((_, copy_file, temp)) => {
+                temp.flush_trimmed()?;
+                Ok(copy_file.metadata()?.len())
+            }
+            None => Ok(self.unchanged_len),
+        }
+    }
+
+    /// # Returns
+    ///
+    /// Everything written so far.
+    fn trimmed(&mut self) -> io::Result<Vec<u8>> {
+        let mut trimmed = Vec::new();
+        match &mut self.temp {
+            Some((copy_path, _, temp)) => {
+                temp.flush_trimmed()?;
+                File::open(&*copy_path)?.read_to_end(&mut trimmed)?;
+            }
+            None => {
+                let mut source = self.source;
+                source.seek(SeekFrom::Start(0))?;
+                source.take(self.unchanged_len).read_to_end(&mut trimmed)?;
+            }
+        }
+        Ok(trimmed)
+    }
+
+    /// Remove the tempfile, if any.
+    fn discard(&mut self) {
+        if let Some((copy_path, _, _)) = self.temp.take() {
+            let _ = remove_file(&copy_path);
+        }
+    }
+}
+
+impl<'a> TrimWrite for InPlaceWriter<'a> {
+    fn write_trimmed(&mut self, bytes: &[u8]) -> io::Result<()> {
+        match bytes.is_empty() {
+            true => Ok(()),
+            false => self.temp()?.write_trimmed(bytes),
+        }
+    }
+
+    fn write_unchanged(&mut self, bytes: &[u8], offset: u64) -> io::Result<()> {
+        match &mut self.temp {
+            Some((_, _, temp)) => temp.write_unchanged(bytes, offset),
+            // still identical to the start of `source`
+            None if offset == self.unchanged_len => {
+                self.unchanged_len += bytes.len() as u64;
+                Ok(())
+            }
+            None => self.temp()?.write_unchanged(bytes, offset),
+        }
+    }
+
+    fn flush_trimmed(&mut self) -> io::Result<()> {
+        match &mut self.temp {
+            Some((_, _, temp)) => temp.flush_trimmed(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Read the lines of `reader` that may opt the input out; see `TrimOptions::skip_file_lines`.
+///
+/// # Returns
+///
+/// The lines read, terminators included.
+fn read_head<R>(reader: &mut R, options: &TrimOptions) -> io::Result<Vec<u8>>
+where
+    R: BufRead,
+{
+    let mut head = Vec::new();
+    if options.skip_file_marker.is_some() {
+        for _ in 0..options.skip_file_lines {
+            if reader.read_until(b'\n', &mut head)? == 0 {
+                break;
+            }
+        }
+    }
+    Ok(head)
+}
+
+/// # Returns
+///
+/// `true` if `head`, the first lines of an input, contains `TrimOptions::skip_file_marker`,
+/// `false` otherwise.
+#[inline]
+fn skips_file(head: &[u8], options: &TrimOptions) -> bool {
+    match &options.skip_file_marker {
+        Some(marker) => String::from_utf8_lossy(head).contains(marker.as_str()),
+        None => false,
+    }
+}
+
+/// Lines from one containing this marker up to one containing `SUPPRESS_ON` are left untouched,
+/// if `TrimOptions::ignore_marker` is set.
+pub const SUPPRESS_OFF: &str = "trim:off";
+
+/// Ends a block started by `SUPPRESS_OFF`.
+pub const SUPPRESS_ON: &str = "trim:on";
+
+/// The state machine behind `TrimOptions::ignore_marker`.
+///
+/// # Parameters
+///
+/// 1. `line` -- the line, without its terminator
+/// 1. `in_block` -- whether `line` comes after `SUPPRESS_OFF`, without `SUPPRESS_ON` since
+/// 1. `options` -- how to trim each line; see `TrimOptions`
+///
+/// # Returns
+///
+/// Whether `line` is left untouched, and whether the line after it is in a block.
+pub(crate) fn suppression(line: &str, in_block: bool, options: &TrimOptions) -> (bool, bool) {
+    let marker = match &options.ignore_marker {
+        Some(marker) => marker,
+        None => return (false, false),
+    };
+    // whichever marker comes last on the line wins
+    let next_in_block = match (line.rfind(SUPPRESS_OFF), line.rfind(SUPPRESS_ON)) {
+        (None, None) => in_block,
+        (off, on) => off > on,
+    };
+    match in_block || line.contains(SUPPRESS_OFF) {
+        // the markers themselves are left untouched too
+        true => (true, next_in_block),
+        false => {
+            let content = line.trim_end_matches(|c| options.whitespace.is_whitespace(c));
+            (content.ends_with(marker.as_str()), next_in_block)
+        }
+    }
+}
+
+/// # Returns
+///
+/// `line` without its trailing whitespace, as defined by `options`, after applying each of
+/// `rules` to it; `options.rules`, unless the line is exempt from them.
+fn trim_line<'a>(
+    line: &'a str,
+    rules: &[Arc<dyn LineRule>],
+    options: &TrimOptions,
+) -> Cow<'a, str> {
+    rules.iter().fold(
+        TrailingWhitespace.apply(line, options),
+        |line, rule| match line {
+            Cow::Borrowed(line) => rule.apply(line, options),
+            Cow::Owned(line) => Cow::Owned(rule.apply(&line, options).into_owned()),
+        },
+    )
+}
+
+/// Like `trim_line`, but also collects what each rule changed into `details`, as findings on
+/// line `line_number`.
+fn trim_line_reporting<'a>(
+    line: &'a str,
+    line_number: usize,
+    rules: &[Arc<dyn LineRule>],
+    options: &TrimOptions,
+    details: &mut Vec<LineFinding>,
+) -> Cow<'a, str> {
+    let mut report = |rule: &dyn LineRule, before: &str, after: &str| {
+        if before != after {
+            let unchanged: usize = before
+                .chars()
+                .zip(after.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a.len_utf8())
+                .sum();
+            // a grapheme cluster the rule changed part of counts as changed as a whole
+            let graphemes = before
+                .grapheme_indices(true)
+                .take_while(|(index, grapheme)| index + grapheme.len() <= unchanged)
+                .count();
+            let unchanged = &before[..unchanged];
+            details.push(LineFinding {
+                line_number,
+                column: unchanged.chars().count() + 1,
+                byte_column: unchanged.len() + 1,
+                utf16_column: unchanged.encode_utf16().count() + 1,
+                display_column: graphemes + 1,
+                bytes_removed: before.len().saturating_sub(after.len()),
+                rule: rule.name(),
+            });
+        }
+    };
+    iter::once(&TrailingWhitespace as &dyn LineRule)
+        .chain(rules.iter().map(AsRef::as_ref))
+        .fold(Cow::Borrowed(line), |line, rule| match line {
+            Cow::Borrowed(line) => {
+                let applied = rule.apply(line, options);
+                report(rule, line, &applied);
+                applied
+            }
+            Cow::Owned(line) => {
+                let applied = rule.apply(&line, options);
+                report(rule, &line, &applied);
+                Cow::Owned(applied.into_owned())
+            }
+        })
+}
+
+/// # Returns
+///
+/// The terminator to write after a line that was read with `ending`.
+#[inline]
+fn terminator(ending: LineEnding, options: &TrimOptions) -> &'static str {
+    match (ending, options.record_sep) {
+        (_, Some(sep)) => ascii(sep),
+        (LineEnding::CrLf, None) if options.keep_crlf => "\r\n",
+        _ => "\n",
+    }
+}
+
+/// Every ASCII byte, in order.
+static ASCII: [u8; 128] = {
+    let mut ascii = [0; 128];
+    let mut byte = 0;
+    while byte < ascii.len() {
+        ascii[byte] = byte as u8;
+        byte += 1;
+    }
+    ascii
+};
+
+/// # Returns
+///
+/// `byte`, which must be ASCII, as a `str`.
+#[inline]
+fn ascii(byte: u8) -> &'static str {
+    let index = byte as usize;
+    str::from_utf8(&ASCII[index..=index]).expect("record separators are ASCII")
+}
+
+/// Where trimmed lines are written to.
+///
+/// Implemented for every `Write`. Other implementations can take advantage of knowing which parts
+/// of the output are verbatim copies of the input.
+pub trait TrimWrite {
+    /// Write `bytes`, which are part of the trimmed output.
+    fn write_trimmed(&mut self, bytes: &[u8]) -> io::Result<()>;
+
+    /// Write `bytes`, which are part of the trimmed output and identical to the input starting at
+    /// byte `offset`.
+    #[inline]
+    fn write_unchanged(&mut self, bytes: &[u8], offset: u64) -> io::Result<()> {
+        let _ = offset;
+        self.write_trimmed(bytes)
+    }
+
+    /// Flush everything written so far to its destination.
+    fn flush_trimmed(&mut self) -> io::Result<()>;
+}
+
+impl<W> TrimWrite for W
+where
+    W: Write,
+{
+    #[inline]
+    fn write_trimmed(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.write_all(bytes)
+    }
+
+    #[inline]
+    fn flush_trimmed(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+/// What a single rule changed in a line; see `LineRule`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineFinding {
+    /// 1-based line number
+    pub line_number: usize,
+    /// 1-based column, in chars, of the first char the rule removed or replaced
+    pub column: usize,
+    /// `column`, in bytes instead, e.g. for tools that index lines as UTF-8
+    pub byte_column: usize,
+    /// `column`, in UTF-16 code units instead, as the Language Server Protocol counts them
+    pub utf16_column: usize,
+    /// `column`, in grapheme clusters instead, i.e. as a user perceives the line, with `é` one
+    /// column whether or not it is composed
+    pub display_column: usize,
+    /// number of bytes the rule removed
+    pub bytes_removed: usize,
+    /// name of the rule; see `LineRule::name`
+    pub rule: &'static str,
+}
+
+/// Where findings are reported to, as each line is trimmed.
+///
+/// Implemented for every `Write`, which gets the visuals: each trimmed line, followed by as much
+/// red padding as was trimmed from it. See `OnFinding` to report to a callback instead.
+pub trait FindingSink {
+    /// Report `finding`, a line that was trimmed, along with `details`, what each rule changed in
+    /// it, in the order the rules were applied.
+    fn found(&mut self, finding: &Finding, details: &[LineFinding]) -> io::Result<()>;
+
+    /// Flush everything reported so far to its destination.
+    fn flush_findings(&mut self) -> io::Result<()>;
+}
+
+impl<W> FindingSink for W
+where
+    W: Write,
+{
+    fn found(&mut self, finding: &Finding, _details: &[LineFinding]) -> io::Result<()> {
+        match finding.padding_len() {
+            (width, true) => writeln!(
+                self,
+                "{:>6}|{}{}",
+                finding.line_number,
+                finding.trimmed_line,
+                red_padding_with_len(width)
+            ),
+            // no width to go by, so say what the padding counts instead
+            (bytes, false) => writeln!(
+                self,
+                "{:>6}|{}{} ({} bytes)",
+                finding.line_number,
+                finding.trimmed_line,
+                red_padding_with_len(bytes),
+                bytes
+            ),
+        }
+    }
+
+    #[inline]
+    fn flush_findings(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+/// Reports each `LineFinding` to a callback, e.g. to turn them into the diagnostics of an
+/// embedder; see `trim_reader_with`.
+pub struct OnFinding<F>(pub F);
+
+impl<F> FindingSink for OnFinding<F>
+where
+    F: FnMut(LineFinding),
+{
+    fn found(&mut self, _finding: &Finding, details: &[LineFinding]) -> io::Result<()> {
+        details.iter().cloned().for_each(&mut self.0);
+        Ok(())
+    }
+
+    #[inline]
+    fn flush_findings(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Findings of a chunk trimmed in parallel, held until they can be reported in order.
+#[derive(Default)]
+struct HeldFindings(Vec<(Finding, Vec<LineFinding>)>);
+
+impl FindingSink for HeldFindings {
+    fn found(&mut self, finding: &Finding, details: &[LineFinding]) -> io::Result<()> {
+        self.0.push((finding.clone(), details.to_vec()));
+        Ok(())
+    }
+
+    #[inline]
+    fn flush_findings(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Progress of a trim over a stream of lines.
+///
+/// Contains lots of hacks in order to do the trimming in a streaming style; blank lines are not
+/// written as they are encountered, because if they turn out to be trailing newlines, they should
+/// be dropped and counted as bytes saved instead.
+#[derive(Default)]
+struct TrimState {
+    /// linebreaks encountered, but not written yet
+    lfs: String,
+    /// offset of `lfs` in the input, if `lfs` is identical to the input from there on
+    lfs_offset: Option<u64>,
+    /// number of linebreaks in `lfs`
+    lf_trimmed: usize,
+    /// terminator of the last non-empty line, which is what the final linebreak will be;
+    /// `None` until a non-empty line has been written, in which case it is `\n`
+    eol: Option<&'static str>,
+    /// number of bytes trimmed for sure
+    u8_trimmed: usize,
+    /// number of those bytes that were terminal escape sequences; see `TrimOptions::strip_ansi`
+    ansi_stripped: usize,
+    /// number of bytes added by escaping control characters, which offset those trimmed; see
+    /// `ControlChars::Escape`
+    u8_grown: usize,
+    /// lines trimmed so far, if `TrimOptions::collect_findings`
+    findings: Vec<Finding>,
+    /// whether the next line is in a `SUPPRESS_OFF` block; see `suppression`
+    in_block: bool,
+    /// whether `eol` of the last non-empty line has been written already, by `flush_deferred`
+    eol_flushed: bool,
+    /// whether a line ended with `\n`, or the record separator
+    lf_seen: bool,
+    /// whether a line ended with `\r\n`
+    crlf_seen: bool,
+    /// whether the input starts with a byte order mark and a shebang; see
+    /// `Warning::BomBeforeShebang`
+    bom_shebang: bool,
+    /// whether any byte was read at all; an empty input is left empty, see
+    /// `TrimOptions::empty_newline`
+    nonempty: bool,
+}
+
+impl TrimState {
+    /// Trim a single line on top of this state.
+    ///
+    /// # Parameters
+    ///
+    /// 1. `content` -- the line to trim, without its terminator
+    /// 1. `ending` -- how the line was terminated
+    /// 1. `line_number` -- 1-based line number of the line, for visuals
+    /// 1. `offset` -- byte offset of the line, within the whole input
+    /// 1. `out` -- where trimmed results will be written
+    /// 1. `err_opt` -- where visualizations of the trim will be written, optional
+    /// 1. `options` -- how to trim each line; see `TrimOptions`
+    fn push<W, E>(
+        &mut self,
+        (content, ending): (&str, LineEnding),
+        (line_number, offset): (usize, u64),
+        out: &mut W,
+        err_opt: &mut Option<E>,
+        options: &TrimOptions,
+    ) -> io::Result<()>
+    where
+        W: TrimWrite,
+        E: FindingSink,
+    {
+        self.nonempty |= !content.is_empty() || !ending.is_empty();
+        match ending {
+            LineEnding::Lf => self.lf_seen = true,
+            LineEnding::CrLf => self.crlf_seen = true,
+            LineEnding::Eof => (),
+        }
+
+        // lines suppressed with markers are kept as they are, terminator included
+        let (untouched, in_block) = suppression(content, self.in_block, options);
+        self.in_block = in_block;
+
+        // a shebang is run as it is, so nothing but its trailing whitespace may change
+        let bom_shebang = offset == 0 && content.starts_with("\u{FEFF}#!");
+        let shebang = bom_shebang || (offset == 0 && content.starts_with("#!"));
+        self.bom_shebang |= bom_shebang;
+        let rules: &[Arc<dyn LineRule>] = match shebang {
+            true => &[],
+            false => &options.rules,
+        };
+
+        // escape sequences go first, as they may hide trailing whitespace, e.g. before `\x1b[0m`
+        let stripped = match options.strip_ansi && !untouched && !shebang {
+            true => strip_ansi(content),
+            false => Cow::Borrowed(content),
+        };
+        self.ansi_stripped += content.len() - stripped.len();
+        // then control characters, which may be left of a sequence cut short
+        let cleaned = match untouched || shebang {
+            true => Cow::Borrowed(content),
+            false => options.control_chars.apply(&stripped, options.whitespace),
+        };
+        let u8_grown = cleaned.len().saturating_sub(stripped.len());
+        self.u8_grown += u8_grown;
+
+        // the terminator is never part of `content`, so it is never counted or visualized
+        let mut details = Vec::new();
+        let trimmed_line = match untouched {
+            true => Cow::Borrowed(content),
+            false if err_opt.is_some() => {
+                trim_line_reporting(&cleaned, line_number, rules, options, &mut details)
+            }
+            false => trim_line(&cleaned, rules, options),
+        };
+        let u8_trimmed = content.len() + u8_grown - trimmed_line.len();
+        self.u8_trimmed += u8_trimmed;
+
+        let lf = match (untouched, ending) {
+            (true, LineEnding::CrLf) => "\r\n",
+            _ => terminator(ending, options),
+        };
+        // offset of the terminator, if it is written exactly as it was read
+        let lf_offset = Some(offset + content.len() as u64).filter(|_| lf.len() == ending.len());
+        let unchanged = trimmed_line == content;
+
+        // empty line encountered; increment the `lf_trimmed` without writing, because if this
+        // `\n` is one of the trailing newlines in the file, we don't want to print it and include
+        // it as bytes saved, so defer the printing until later
+        if trimmed_line.is_empty() {
+            self.lfs_offset = match (self.lfs_offset, lf_offset) {
+                (_, Some(lf_offset)) if unchanged && self.lfs.is_empty() => Some(lf_offset),
+                (Some(lfs_offset), Some(lf_offset))
+                    if unchanged && lfs_offset + self.lfs.len() as u64 == lf_offset =>
+                {
+                    Some(lfs_offset)
+                }
+                _ => None,
+            };
+            self.lfs.push_str(lf);
+            self.lf_trimmed += 1;
+            return Ok(());
+        }
+
+        // most common case; a non-empty line
+        // print the accumulated newlines, if any
+        match self.lfs_offset {
+            Some(lfs_offset) => out.write_unchanged(self.lfs.as_bytes(), lfs_offset)?,
+            None => out.write_trimmed(self.lfs.as_bytes())?,
+        }
+        // an unchanged line is written straight from `content`, without a copy
+        match unchanged {
+            true => out.write_unchanged(content.as_bytes(), offset)?,
+            false => out.write_trimmed(trimmed_line.as_bytes())?,
+        }
+
+        // report the finding to err, e.g. as a visual, if applicable
+        if u8_trimmed > 0 && (err_opt.is_some() || options.collect_findings) {
+            let width_trimmed = content.strip_prefix(&*trimmed_line).and_then(display_width);
+            let finding = Finding {
+                line_number,
+                trimmed_line: trimmed_line.into_owned(),
+                bytes_trimmed: u8_trimmed,
+                width_trimmed,
+            };
+            if let Some(err) = err_opt {
+                err.found(&finding, &details)?;
+            }
+            if options.collect_findings {
+                self.findings.push(finding);
+            }
+        }
+        // `\n` may or may not exist at the end of this line, but pretend like it exists for
+        // now, and defer the printing until later
+        self.lfs.clear();
+        self.lfs.push_str(lf);
+        self.lfs_offset = lf_offset;
+        self.lf_trimmed = 1;
+        self.eol = Some(lf);
+        self.eol_flushed = false;
+        Ok(())
+    }
+
+    /// Write the linebreaks deferred so far, instead of waiting for the next non-empty line; e.g.
+    /// because no more input may arrive for a while. Blank lines written this way are no longer
+    /// trailing ones, even if the input ends right after them.
+    fn flush_deferred<W>(&mut self, out: &mut W) -> io::Result<()>
+    where
+        W: TrimWrite,
+    {
+        match self.lfs_offset {
+            Some(offset) => out.write_unchanged(self.lfs.as_bytes(), offset)?,
+            None => out.write_trimmed(self.lfs.as_bytes())?,
+        }
+        self.eol_flushed |= self.eol.is_some();
+        self.lfs.clear();
+        self.lfs_offset = None;
+        self.lf_trimmed = 0;
+        Ok(())
+    }
+
+    /// Trim `lines` on top of this state.
+    ///
+    /// # Parameters
+    ///
+    /// 1. `lines` -- lines to trim, as an iterator
+    /// 1. `first_line_number` -- 1-based line number of the first line in `lines`, for visuals
+    /// 1. `first_offset` -- byte offset of the first line in `lines`, within the whole input
+    /// 1. `out` -- where trimmed results will be written
+    /// 1. `err_opt` -- where visualizations of the trim will be written, optional
+    /// 1. `options` -- how to trim each line; see `TrimOptions`
+    fn trim_lines<I, W, E>(
+        self,
+        lines: I,
+        (first_line_number, first_offset): (usize, u64),
+        out: &mut W,
+        err_opt: &mut Option<E>,
+        options: &TrimOptions,
+    ) -> io::Result<Self>
+    where
+        I: Iterator<Item = io::Result<Line>>,
+        W: TrimWrite,
+        E: FindingSink,
+    {
+        lines
+            .enumerate()
+            .map(|(index, line)| (index + first_line_number, line))
+            .try_fold(
+                (self, first_offset),
+                |(mut state, offset), (line_number, line)| {
+                    let Line { content, ending } = line?;
+                    let position = (line_number, offset);
+                    state.push((&content, ending), position, out, err_opt, options)?;
+                    Ok((state, offset + (content.len() + ending.len()) as u64))
+                },
+            )
+            .map(|(state, _)| state)
+    }
+
+    /// Like `trim_lines`, but reads the lines from `reader`. Lines are trimmed straight from the
+    /// buffer of `reader` where possible, rather than being copied out of it first.
+    fn trim_reader<R, W, E>(
+        mut self,
+        mut reader: R,
+        (first_line_number, first_offset): (usize, u64),
+        out: &mut W,
+        err_opt: &mut Option<E>,
+        options: &TrimOptions,
+    ) -> io::Result<Self>
+    where
+        R: BufRead,
+        W: TrimWrite,
+        E: FindingSink,
+    {
+        let (mut line_number, mut offset) = (first_line_number, first_offset);
+        // a line that did not fit in the buffer of `reader`, and had to be put together
+        let mut carry = Vec::new();
+
+        loop {
+            let buf = reader.fill_buf()?;
+            if buf.is_empty() {
+                break;
+            }
+            let (consumed, complete) =
+                match buf.iter().position(|&byte| byte == options.separator()) {
+                    Some(index) => (index + 1, true),
+                    None => (buf.len(), false),
+                };
+
+            let line_len = match (complete, carry.is_empty()) {
+                // most common case; the whole line is in the buffer
+                (true, true) => {
+                    let (content, ending) = split_record(&buf[..consumed], options.separator());
+                    let content = utf8(content)?;
+                    self.push(
+                        (content, ending),
+                        (line_number, offset),
+                        out,
+                        err_opt,
+                        options,
+                    )?;
+                    Some(consumed)
+                }
+                (true, false) => {
+                    carry.extend_from_slice(&buf[..consumed]);
+                    let (content, ending) = split_record(&carry, options.separator());
+                    let content = utf8(content)?;
+                    self.push(
+                        (content, ending),
+                        (line_number, offset),
+                        out,
+                        err_opt,
+                        options,
+                    )?;
+                    Some(carry.len())
+                }
+                (false, _) => {
+                    carry.extend_from_slice(buf);
+                    None
+                }
+            };
+            reader.consume(consumed);
+
+            if let Some(line_len) = line_len {
+                carry.clear();
+                line_number += 1;
+                offset += line_len as u64;
+            }
+        }
+
+        // the last line, which has no terminator
+        if !carry.is_empty() {
+            let (content, ending) = split_record(&carry, options.separator());
+            let content = utf8(content)?;
+            self.push(
+                (content, ending),
+                (line_number, offset),
+                out,
+                err_opt,
+                options,
+            )?;
+        }
+        Ok(self)
+    }
+
+    /// Continue this state with `next`, the state of trimming the lines that follow, from a
+    /// default state, into `next_out`.
+    ///
+    /// # Side Effects
+    ///
+    /// Whatever is written to `out` in this call and in `next_out` is equivalent to what would
+    /// have been written to `out` had the lines been trimmed in one go.
+    fn chain<W>(mut self, mut next: TrimState, next_out: &[u8], out: &mut W) -> io::Result<Self>
+    where
+        W: TrimWrite,
+    {
+        self.u8_trimmed += next.u8_trimmed;
+        self.ansi_stripped += next.ansi_stripped;
+        self.u8_grown += next.u8_grown;
+        self.findings.append(&mut next.findings);
+        self.in_block = next.in_block;
+        self.lf_seen |= next.lf_seen;
+        self.crlf_seen |= next.crlf_seen;
+        self.bom_shebang |= next.bom_shebang;
+        self.nonempty |= next.nonempty;
+        match next.eol {
+            // the linebreaks deferred so far come before whatever `next` wrote
+            Some(_) => {
+                match self.lfs_offset {
+                    Some(offset) => out.write_unchanged(self.lfs.as_bytes(), offset)?,
+                    None => out.write_trimmed(self.lfs.as_bytes())?,
+                }
+                out.write_trimmed(next_out)?;
+                self.lfs = next.lfs;
+                self.lfs_offset = next.lfs_offset;
+                self.lf_trimmed = next.lf_trimmed;
+                self.eol = next.eol;
+            }
+            // `next` only had blank lines, which are all still deferred
+            None => {
+                self.lfs_offset = match (self.lfs_offset, next.lfs_offset) {
+                    _ if next.lfs.is_empty() => self.lfs_offset,
+                    (_, next_offset) if self.lfs.is_empty() => next_offset,
+                    (Some(offset), Some(next_offset))
+                        if offset + self.lfs.len() as u64 == next_offset =>
+                    {
+                        Some(offset)
+                    }
+                    _ => None,
+                };
+                self.lfs.push_str(&next.lfs);
+                self.lf_trimmed += next.lf_trimmed;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Write the final linebreak, if any, and flush.
+    ///
+    /// # Returns
+    ///
+    /// See `TrimResult`.
+    fn finish<W, E>(
+        self,
+        out: &mut W,
+        err_opt: &mut Option<E>,
+        options: &TrimOptions,
+    ) -> io::Result<TrimResult>
+    where
+        W: TrimWrite,
+        E: FindingSink,
+    {
+        let TrimState {
+            lfs,
+            lfs_offset,
+            lf_trimmed,
+            eol,
+            u8_trimmed,
+            ansi_stripped,
+            u8_grown,
+            findings,
+            eol_flushed,
+            lf_seen,
+            crlf_seen,
+            bom_shebang,
+            nonempty,
+            ..
+        } = self;
+        let eol_held = eol.is_some();
+        let eol = eol.unwrap_or_else(|| terminator(LineEnding::Lf, options));
+        // count as if the flushed `eol` were still deferred, as it would be without flushing
+        let (lfs_len, lf_trimmed) = match eol_flushed {
+            true => (lfs.len() + eol.len(), lf_trimmed + 1),
+            false => (lfs.len(), lf_trimmed),
+        };
+        let tail = Tail {
+            linebreaks: lf_trimmed,
+            len: lfs_len,
+            eol_len: eol.len(),
+            eol_held,
+        };
+
+        // trailing `\n` is not printed in `fold`, so if `\n` is not to be suppressed then print
+        // one now
+        // no line but blank ones
+        let whitespace_only = Some(options.empty_file).filter(|_| nonempty && !eol_held);
+        let final_newlines = match (nonempty || options.empty_newline, whitespace_only) {
+            (false, _) | (_, Some(EmptyFile::Truncate)) | (_, Some(EmptyFile::Delete)) => 0,
+            _ => options.final_newline_count(),
+        };
+        if !eol_flushed {
+            for index in 0..final_newlines {
+                match (index, lf_trimmed, lfs_offset) {
+                    // `lfs` is exactly the terminator of the last line, as it was read
+                    (0, 1, Some(offset)) => out.write_unchanged(eol.as_bytes(), offset)?,
+                    _ => out.write_trimmed(eol.as_bytes())?,
+                }
+            }
+        }
+
+        // flush both out and err
+        out.flush_trimmed()?;
+        if let Some(err) = err_opt {
+            err.flush_findings()?;
+        }
+
+        Ok(TrimResult {
+            // nothing in, nothing out
+            bytes_saved: match nonempty || options.empty_newline {
+                true => tail.bytes_saved(u8_trimmed, final_newlines) - u8_grown as i32,
+                false => 0,
+            },
+            retries: 0,
+            findings,
+            skipped: None,
+            eof_blank_lines: tail.blank_lines(),
+            warnings: [
+                (lf_seen && crlf_seen, Warning::MixedLineEndings),
+                (bom_shebang, Warning::BomBeforeShebang),
+            ]
+            .iter()
+            .filter_map(|&(seen, warning)| seen.then_some(warning))
+            .collect(),
+            whitespace_only,
+            ansi_bytes_stripped: ansi_stripped,
+        })
+    }
+}
+
+/// # Parameters
+///
+/// 1. `lines` -- lines to trim, as an iterator
+/// 1. `out` -- where trimmed results will be written
+/// 1. `err_opt` -- where visualizations of the trim will be written, optional
+/// 1. `options` -- how to trim each line; see `TrimOptions`
+///
+/// # Returns
+///
+/// Number of bytes trimmed.
+///
+/// # Side Effects
+///
+/// 1. trimmed lines are written to `out`
+/// 1. `if let Some(err) = err_opt`, visualizations of the trimmings are written to `err`
+pub fn trim_custom<I, W, E>(
+    lines: I,
+    out: &mut W,
+    err_opt: &mut Option<E>,
+    options: &TrimOptions,
+) -> io::Result<i32>
+where
+    I: Iterator<Item = io::Result<Line>>,
+    W: TrimWrite,
+    E: FindingSink,
+{
+    TrimState::default()
+        .trim_lines(lines, (1, 0), out, err_opt, options)?
+        .finish(out, err_opt, options)
+        .map(|trim_result| trim_result.bytes_saved)
+}
+
+/// Like `trim_custom`, but reads the lines from `reader`.
+///
+/// Lines are trimmed straight from the buffer of `reader`, so lines that are left unchanged are
+/// never copied before being written to `out`.
+pub fn trim_custom_reader<R, W, E>(
+    reader: R,
+    out: &mut W,
+    err_opt: &mut Option<E>,
+    options: &TrimOptions,
+) -> io::Result<i32>
+where
+    R: BufRead,
+    W: TrimWrite,
+    E: FindingSink,
+{
+    TrimState::default()
+        .trim_reader(reader, (1, 0), out, err_opt, options)?
+        .finish(out, err_opt, options)
+        .map(|trim_result| trim_result.bytes_saved)
+}
+
+/// Like `trim_custom`, but for `content` held in memory as a whole, which is split into
+/// line-aligned chunks of about `CHUNK_SIZE` bytes that are trimmed in parallel.
+///
+/// With `TrimOptions::max_memory`, chunks are smaller and only as many are trimmed at a time as
+/// there are threads.
+///
+/// # Returns
+///
+/// Number of bytes trimmed; identical to what `trim_custom` would return for the same lines.
+///
+/// # Side Effects
+///
+/// Identical to those of `trim_custom` for the same lines.
+#[inline]
+pub fn trim_chunked<W, E>(
+    content: &[u8],
+    out: &mut W,
+    err_opt: &mut Option<E>,
+    options: &TrimOptions,
+) -> io::Result<i32>
+where
+    W: TrimWrite,
+    E: FindingSink,
+{
+    trim_chunked_by(content, chunk_size(options), out, err_opt, options)
+        .map(|trim_result| trim_result.bytes_saved)
+}
+
+/// # Returns
+///
+/// Size of the chunks that `trim_chunked` should split content into under `options`.
+#[inline]
+fn chunk_size(options: &TrimOptions) -> usize {
+    // trimmed content and visuals of the chunks in flight
+    options.buffer_size(CHUNK_SIZE, 2 * current_num_threads())
+}
+
+/// Like `trim_chunked`, but with chunks of about `chunk_size` bytes.
+///
+/// # Returns
+///
+/// See `TrimResult`.
+fn trim_chunked_by<W, E>(
+    content: &[u8],
+    chunk_size: usize,
+    out: &mut W,
+    err_opt: &mut Option<E>,
+    options: &TrimOptions,
+) -> io::Result<TrimResult>
+where
+    W: TrimWrite,
+    E: FindingSink,
+{
+    let chunks = line_aligned_chunks(content, chunk_size, options.separator());
+
+    // 1-based line number and byte offset of the first line in each chunk
+    let first_positions: Vec<(usize, u64)> = chunks
+        .iter()
+        .scan((1, 0), |(line_number, offset), chunk| {
+            let first = (*line_number, *offset);
+            *line_number += chunk
+                .iter()
+                .filter(|&&byte| byte == options.separator())
+                .count();
+            *offset += chunk.len() as u64;
+            Some(first)
+        })
+        .collect();
+
+    // whether each chunk starts in a `SUPPRESS_OFF` block, which depends on all chunks before it
+    let first_in_blocks: Vec<bool> = chunks
+        .iter()
+        .scan(false, |in_block, chunk| {
+            let first = *in_block;
+            if options.ignore_marker.is_some() {
+                *in_block = chunk
+                    .split(|&byte| byte == options.separator())
+                    .map(String::from_utf8_lossy)
+                    .fold(*in_block, |in_block, line| {
+                        suppression(&line, in_block, options).1
+                    });
+            }
+            Some(first)
+        })
+        .collect();
+
+    // number of chunks trimmed before their results are written out; with bounded memory, the
+    // next chunks are not trimmed until the previous ones are written
+    let window = match options.max_memory {
+        Some(_) => current_num_threads(),
+        None => chunks.len(),
+    }
+    .max(1);
+
+    // (state, trimmed content, findings to report) of each chunk
+    let visualize = err_opt.is_some();
+    let positioned: Vec<_> = chunks
+        .into_iter()
+        .zip(first_positions)
+        .zip(first_in_blocks)
+        .collect();
+    positioned
+        .chunks(window)
+        .try_fold(TrimState::default(), |state, window| {
+            let trimmed: Vec<(TrimState, Vec<u8>, HeldFindings)> = window
+                .par_iter()
+                .map(|&((chunk, first_position), in_block)| {
+                    let mut chunk_out = Vec::new();
+                    let mut chunk_err = match visualize {
+                        true => Some(HeldFindings::default()),
+                        false => None,
+                    };
+                    let state = TrimState {
+                        in_block,
+                        ..TrimState::default()
+                    };
+                    let state = state.trim_reader(
+                        chunk,
+                        first_position,
+                        &mut chunk_out,
+                        &mut chunk_err,
+                        options,
+                    )?;
+                    Ok((state, chunk_out, chunk_err.unwrap_or_default()))
+                })
+                .collect::<io::Result<_>>()?;
+
+            // stitch the chunks back together, in order
+            trimmed
+                .into_iter()
+                .try_fold(state, |state, (chunk_state, chunk_out, chunk_err)| {
+                    if let Some(err) = err_opt {
+                        for (finding, details) in &chunk_err.0 {
+                            err.found(finding, details)?;
+                        }
+                    }
+                    state.chain(chunk_state, &chunk_out, out)
+                })
+        })?
+        .finish(out, err_opt, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::read_fixtures;
+    use crate::options::Whitespace;
+    use crate::stats::RunStats;
+    use std::collections::HashMap;
+    use std::fs::read_to_string;
+    use std::sync::Arc;
+    use std::time::SystemTime;
+
+    /// Assert that `outcome` is what trimming the file holding `input` to `trimmed` should come
+    /// to: `FileOutcome::Modified`, saving `savings` bytes ish, if the two differ, and
+    /// `FileOutcome::Unchanged` if they do not.
+    fn assert_outcome(outcome: &FileOutcome, input: &str, trimmed: &str, savings: i32) {
+        match outcome {
+            FileOutcome::Modified(trim_result) if input != trimmed => {
+                assert_eq!(savings, trim_result.bytes_saved, "{:?}", input)
+            }
+            FileOutcome::Unchanged(_) if input == trimmed => {}
+            FileOutcome::Failed(err) => panic!("{:?} failed: {}", input, err),
+            _ => panic!("{:?} did not come to {:?} as expected", input, trimmed),
+        }
+    }
+
+    /// Inputs, what they trim to with `TrimOptions::suppress_newline`, and the bytes ish that
+    /// saves; from the fixtures under `tests/fixtures`, see `crate::fixture`, which are trimmed
+    /// with the default options instead. Empty inputs are left out, as they stay empty with or
+    /// without the final `\n`; see `empty_input`.
+    fn test_data() -> Vec<(String, String, i32)> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        read_fixtures(&dir)
+            .unwrap()
+            .into_iter()
+            .filter(|fixture| !fixture.input.is_empty())
+            .map(|fixture| {
+                let input = String::from_utf8(fixture.input).unwrap();
+                let expected = String::from_utf8(fixture.expected).unwrap();
+                // the final `\n` is suppressed, saving a byte more
+                let expected = expected.strip_suffix('\n').unwrap_or(&expected).to_string();
+                (input, expected, fixture.bytes_saved + 1)
+            })
+            .collect()
+    }
+
+    mod stdout {
+        use super::*;
+
+        /// trim and output to stdout, with default options
+        #[test]
+        fn parametrized_n_suppress_newline() {
+            test_data().into_par_iter().enumerate().for_each(
                 |(index, (input, expected_raw, savings))| {
                     // make a "uniq" file
                     let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
                     let path_to_temp = mktemp(&prefix, &input).unwrap();
 
-                    // make sure that its content is written correctly
-                    let content = read_to_string(&path_to_temp).unwrap();
-                    assert_eq!(input, content);
+                    // make sure that its content is written correctly
+                    let content = read_to_string(&path_to_temp).unwrap();
+                    assert_eq!(input, content);
+
+                    //
+                    let mut result = Vec::new();
+                    let lines = readlines(&path_to_temp).unwrap();
+                    let options = TrimOptions::default();
+                    let tr = trim_custom(lines, &mut result, &mut None::<File>, &options).unwrap();
+                    //
+                    let expected = format!("{}\n", expected_raw);
+
+                    assert_eq!(savings - 1, tr); // `- 1` because not suppressing `\n`
+                    assert_eq!(expected.as_bytes(), &result[..]);
+                },
+            );
+        }
+
+        /// trim and output to stdout, suppressing the trailing newline in the last line
+        #[test]
+        fn parametrized_suppress_newline() {
+            test_data().into_par_iter().enumerate().for_each(
+                |(index, (input, expected_raw, savings))| {
+                    // make a "uniq" file
+                    let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
+                    let path_to_temp = mktemp(&prefix, &input).unwrap();
+
+                    // make sure that its content is written correctly
+                    let content = read_to_string(&path_to_temp).unwrap();
+                    assert_eq!(input, content);
+
+                    //
+                    let mut result = Vec::new();
+                    let lines = readlines(&path_to_temp).unwrap();
+                    let options = TrimOptions {
+                        suppress_newline: true,
+                        ..TrimOptions::default()
+                    };
+                    let tr = trim_custom(lines, &mut result, &mut None::<File>, &options).unwrap();
+                    //
+                    let expected = expected_raw.to_string();
+
+                    assert_eq!(savings, tr);
+                    assert_eq!(expected.as_bytes(), &result[..]);
+                },
+            );
+        }
+    }
+
+    mod in_place_sequential {
+        use super::*;
+
+        /// trim a file in place, with default options
+        #[test]
+        fn parametrized_inplace_n_suppress_newline() {
+            test_data().into_par_iter().enumerate().for_each(
+                |(index, (input, expected_raw, savings))| {
+                    // make a "uniq" file
+                    let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
+                    let path_to_temp = mktemp(&prefix, &input).unwrap();
+
+                    // make sure that its content is written correctly
+                    let content = read_to_string(&path_to_temp).unwrap();
+                    assert_eq!(input, content);
+
+                    // trim the file in-place, sequentially
+                    let expected = format!("{}\n", expected_raw);
+                    trim_files(std::slice::from_ref(&path_to_temp), &TrimOptions::default())
+                        .into_par_iter()
+                        .for_each(|(file_opt, outcome)| {
+                            assert!(file_opt.exists());
+                            // `- 1` because not suppressing `\n`
+                            assert_outcome(&outcome, &input, &expected, savings - 1);
+                        });
+
+                    let result = read_to_string(&path_to_temp).unwrap();
+                    assert_eq!(expected, result);
+                },
+            );
+        }
+
+        /// trim a file in place, suppressing the trailing newline in the last line
+        #[test]
+        fn parametrized_inplace_suppress_newline() {
+            test_data().into_par_iter().enumerate().for_each(
+                |(index, (input, expected_raw, savings))| {
+                    // make a "uniq" file
+                    let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
+                    let path_to_temp = mktemp(&prefix, &input).unwrap();
+
+                    // make sure that its content is written correctly
+                    let content = read_to_string(&path_to_temp).unwrap();
+                    assert_eq!(input, content);
+
+                    // trim the file in-place, sequentially
+                    let options = TrimOptions {
+                        suppress_newline: true,
+                        ..TrimOptions::default()
+                    };
+                    let expected = expected_raw.to_string();
+                    trim_files(std::slice::from_ref(&path_to_temp), &options)
+                        .into_par_iter()
+                        .for_each(|(file_opt, outcome)| {
+                            assert!(file_opt.exists());
+                            assert_outcome(&outcome, &input, &expected, savings);
+                        });
+
+                    let result = read_to_string(&path_to_temp).unwrap();
+                    assert_eq!(expected, result);
+                },
+            );
+        }
+    }
+
+    mod in_place_parallel {
+        use super::*;
+
+        /// trim a file in place, with default options
+        #[test]
+        fn parametrized_inplace_n_suppress_newline() {
+            // path to file containing untrimmed content -> expected trimmed content
+            let path_to_expected: HashMap<_, _> = test_data()
+                .into_par_iter()
+                .enumerate()
+                .map(|(index, (input, expected_raw, savings))| {
+                    // make a "uniq" file
+                    let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
+                    let path_to_temp = mktemp(&prefix, &input).unwrap();
+
+                    // make sure that its content is written correctly
+                    let content = read_to_string(&path_to_temp).unwrap();
+                    assert_eq!(input, content);
+
+                    //
+                    let expected = format!("{}\n", expected_raw);
+                    (path_to_temp, (input, expected, savings))
+                })
+                .collect();
+
+            // collect all the paths and trim them all in one go
+            let paths: Vec<_> = path_to_expected.keys().cloned().collect();
+            let path_to_result: HashMap<_, _> = trim_files(&paths, &TrimOptions::default())
+                .into_iter()
+                .collect();
+
+            // check the results
+            path_to_expected.into_par_iter().for_each(
+                |(path_to_temp, (input, expected, savings))| {
+                    let outcome = path_to_result.get(&path_to_temp).unwrap();
+                    let result = read_to_string(&path_to_temp).unwrap();
+                    assert!(path_to_temp.exists());
+                    // `- 1` because not suppressing `\n`
+                    assert_outcome(outcome, &input, &expected, savings - 1);
+                    assert_eq!(expected, result);
+                },
+            );
+        }
+
+        /// trim a file in place, suppressing the trailing newline in the last line
+        #[test]
+        fn parametrized_inplace_suppress_newline() {
+            // path to file containing untrimmed content -> expected trimmed content
+            let path_to_expected: HashMap<_, _> = test_data()
+                .into_par_iter()
+                .enumerate()
+                .map(|(index, (input, expected_raw, savings))| {
+                    // make a "uniq" file
+                    let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
+                    let path_to_temp = mktemp(&prefix, &input).unwrap();
+
+                    // make sure that its content is written correctly
+                    let content = read_to_string(&path_to_temp).unwrap();
+                    assert_eq!(input, content);
+
+                    //
+                    let expected = expected_raw.to_string();
+                    (path_to_temp, (input, expected, savings))
+                })
+                .collect();
+
+            // collect all the paths and trim them all in one go
+            let paths: Vec<_> = path_to_expected.keys().cloned().collect();
+            let options = TrimOptions {
+                suppress_newline: true,
+                ..TrimOptions::default()
+            };
+            let path_to_result: HashMap<_, _> = trim_files(&paths, &options).into_iter().collect();
+
+            // check the results
+            path_to_expected.into_par_iter().for_each(
+                |(path_to_temp, (input, expected, savings))| {
+                    let outcome = path_to_result.get(&path_to_temp).unwrap();
+                    let result = read_to_string(&path_to_temp).unwrap();
+                    assert!(path_to_temp.exists());
+                    assert_outcome(outcome, &input, &expected, savings);
+                    assert_eq!(expected, result);
+                },
+            );
+        }
+    }
+
+    mod whitespace {
+        use super::*;
+
+        /// (input, expected with `Whitespace::Ascii`, expected with `Whitespace::Unicode`)
+        fn test_data() -> Vec<(&'static str, &'static str, &'static str)> {
+            vec![
+                ("abc \t", "abc", "abc"),
+                // NBSP
+                ("abc\u{a0}", "abc\u{a0}", "abc"),
+                ("abc \u{a0} ", "abc \u{a0}", "abc"),
+                // ideographic space
+                (
+                    "abc\u{3000}\n\u{3000}\nabc",
+                    "abc\u{3000}\n\u{3000}\nabc",
+                    "abc\n\nabc",
+                ),
+                // vertical tab is not ASCII whitespace as defined by `char::is_ascii_whitespace`
+                ("abc\x0b", "abc\x0b", "abc"),
+            ]
+        }
+
+        #[test]
+        fn parametrized_whitespace() {
+            test_data().into_par_iter().enumerate().for_each(
+                |(index, (input, expected_ascii, expected_unicode))| {
+                    for (whitespace, expected) in [
+                        (Whitespace::Ascii, expected_ascii),
+                        (Whitespace::Unicode, expected_unicode),
+                    ] {
+                        let prefix = format!(
+                            "{}_{}_{}_{}_{}",
+                            module_path!(),
+                            line!(),
+                            column!(),
+                            index,
+                            whitespace
+                        );
+                        let path_to_temp = mktemp(&prefix, &input).unwrap();
+
+                        let mut result = Vec::new();
+                        let lines = readlines(&path_to_temp).unwrap();
+                        let options = TrimOptions {
+                            whitespace,
+                            ..TrimOptions::default()
+                        };
+                        let tr =
+                            trim_custom(lines, &mut result, &mut None::<File>, &options).unwrap();
+                        let expected = format!("{}\n", expected);
+
+                        // the appended `\n` is not counted, as none of the inputs end with `\n`
+                        assert_eq!((input.len() + 1 - expected.len()) as i32, tr);
+                        assert_eq!(expected.as_bytes(), &result[..]);
+                    }
+                },
+            );
+        }
+    }
+
+    mod keep_form_feed {
+        use super::*;
+
+        /// (input, expected with `keep_form_feed`)
+        fn test_data() -> Vec<(&'static str, &'static str)> {
+            vec![
+                ("abc", "abc"),
+                ("abc \t", "abc"),
+                ("abc\x0C", "abc\x0C"),
+                ("abc \x0C \t", "abc\x0C"),
+                ("abc\x0C\x0C", "abc\x0C"),
+                // a form feed on its own line is not a blank line
+                ("abc\n\x0C\nabc", "abc\n\x0C\nabc"),
+                ("abc\n \x0C \n\n", "abc\n\x0C"),
+            ]
+        }
+
+        #[test]
+        fn parametrized_keep_form_feed() {
+            test_data()
+                .into_par_iter()
+                .enumerate()
+                .for_each(|(index, (input, expected))| {
+                    let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
+                    let path_to_temp = mktemp(&prefix, &input).unwrap();
+
+                    let mut result = Vec::new();
+                    let lines = readlines(&path_to_temp).unwrap();
+                    let options = TrimOptions {
+                        suppress_newline: true,
+                        keep_form_feed: true,
+                        ..TrimOptions::default()
+                    };
+                    trim_custom(lines, &mut result, &mut None::<File>, &options).unwrap();
+
+                    assert_eq!(expected.as_bytes(), &result[..]);
+                });
+        }
+    }
+
+    mod keep_crlf {
+        use super::*;
+
+        /// (input, expected with `keep_crlf`, bytes saved)
+        fn test_data() -> Vec<(&'static str, &'static str, i32)> {
+            vec![
+                ("abc\r\n", "abc\r\n", 0),
+                // `\r` of the terminator is neither trimmed nor counted
+                ("abc \r\n", "abc\r\n", 1),
+                ("abc \r\ndef\n", "abc\r\ndef\n", 1),
+                ("abc\r\n \r\n\r\ndef \t\r\n", "abc\r\n\r\n\r\ndef\r\n", 3),
+                // a `\r` that is not part of a terminator is still whitespace
+                ("abc \r \r\n", "abc\r\n", 3),
+                // leading newlines keep their terminators
+                (" \r\n\nabc\r\n", "\r\n\nabc\r\n", 1),
+            ]
+        }
+
+        #[test]
+        fn parametrized_keep_crlf() {
+            test_data().into_par_iter().enumerate().for_each(
+                |(index, (input, expected, savings))| {
+                    let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
+                    let path_to_temp = mktemp(&prefix, &input).unwrap();
+
+                    let mut result = Vec::new();
+                    let mut visual = Vec::new();
+                    let lines = readlines(&path_to_temp).unwrap();
+                    let options = TrimOptions {
+                        keep_crlf: true,
+                        ..TrimOptions::default()
+                    };
+                    let tr =
+                        trim_custom(lines, &mut result, &mut Some(&mut visual), &options).unwrap();
+
+                    assert_eq!(savings, tr);
+                    assert_eq!(expected.as_bytes(), &result[..]);
+                    assert!(!visual.contains(&b'\r'));
+                },
+            );
+        }
+    }
+
+    mod min_run {
+        use super::*;
+
+        /// (input, `min_run`, expected)
+        fn test_data() -> Vec<(&'static str, usize, &'static str)> {
+            vec![
+                ("abc ", 0, "abc"),
+                ("abc ", 1, "abc"),
+                ("abc ", 2, "abc "),
+                ("abc  ", 2, "abc"),
+                ("abc \nabc   \n", 3, "abc \nabc"),
+                // counted in chars, not bytes
+                ("abc\u{3000}", 2, "abc\u{3000}"),
+                // a short whitespace-only line is not blank, so it is not collapsed either
+                ("abc\n \n", 2, "abc\n "),
+                ("abc\n  \n", 2, "abc"),
+            ]
+        }
+
+        #[test]
+        fn parametrized_min_run() {
+            test_data().into_par_iter().enumerate().for_each(
+                |(index, (input, min_run, expected))| {
+                    let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
+                    let path_to_temp = mktemp(&prefix, &input).unwrap();
+
+                    let mut result = Vec::new();
+                    let lines = readlines(&path_to_temp).unwrap();
+                    let options = TrimOptions {
+                        suppress_newline: true,
+                        min_run,
+                        ..TrimOptions::default()
+                    };
+                    trim_custom(lines, &mut result, &mut None::<File>, &options).unwrap();
+
+                    assert_eq!(expected.as_bytes(), &result[..]);
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn parametrized_strip_ansi() {
+        // (input, expected with `strip_ansi`, bytes saved, bytes of escape sequences)
+        let test_data = [
+            ("abc\n", "abc\n", 0, 0),
+            ("\x1b[32mok\x1b[0m\n", "ok\n", 9, 9),
+            // whitespace hidden behind a reset is trimmed too
+            ("abc  \x1b[0m\n\x1b[1m\x1b[0m\n", "abc\n", 16, 12),
+            // suppressed lines are left as they are
+            (
+                "\x1b[1mabc\x1b[0m # trim:ignore\n",
+                "\x1b[1mabc\x1b[0m # trim:ignore\n",
+                0,
+                0,
+            ),
+        ];
+        for (input, expected, savings, stripped) in test_data {
+            let options = TrimOptions {
+                strip_ansi: true,
+                ignore_marker: Some("trim:ignore".to_string()),
+                ..TrimOptions::default()
+            };
+            let mut result = Vec::new();
+            let tr =
+                trim_reader_to(input.as_bytes(), &mut result, &mut None::<File>, &options).unwrap();
+            assert_eq!(expected.as_bytes(), &result[..], "{:?}", input);
+            assert_eq!(savings, tr.bytes_saved, "{:?}", input);
+            assert_eq!(stripped, tr.ansi_bytes_stripped, "{:?}", input);
+
+            // in chunks, whose counts add up
+            let mut result = Vec::new();
+            let tr = trim_chunked_by(
+                input.as_bytes(),
+                4,
+                &mut result,
+                &mut None::<File>,
+                &options,
+            )
+            .unwrap();
+            assert_eq!(expected.as_bytes(), &result[..], "{:?}", input);
+            assert_eq!(stripped, tr.ansi_bytes_stripped, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn parametrized_control_chars() {
+        // (input, policy, expected, bytes saved)
+        let test_data = [
+            ("a\x07b \n", ControlChars::Keep, "a\x07b\n", 1),
+            ("a\x07b \n", ControlChars::Strip, "ab\n", 2),
+            // escapes are not saved, but spent
+            ("a\x07b \n", ControlChars::Escape, "a\\u{7}b\n", -3),
+            // bells hiding whitespace, and lines of nothing but them
+            (
+                "abc \x08\n\x07\ndef\n",
+                ControlChars::Strip,
+                "abc\n\ndef\n",
+                3,
+            ),
+            ("\x1b[1mabc\n", ControlChars::Escape, "\\u{1b}[1mabc\n", -5),
+        ];
+        for (input, control_chars, expected, savings) in test_data {
+            let options = TrimOptions {
+                control_chars,
+                ..TrimOptions::default()
+            };
+            let mut result = Vec::new();
+            let tr =
+                trim_reader_to(input.as_bytes(), &mut result, &mut None::<File>, &options).unwrap();
+            assert_eq!(expected.as_bytes(), &result[..], "{:?}", input);
+            assert_eq!(savings, tr.bytes_saved, "{:?}", input);
+        }
+
+        // escape sequences are stripped first, so none are left to escape
+        let options = TrimOptions {
+            strip_ansi: true,
+            control_chars: ControlChars::Escape,
+            ..TrimOptions::default()
+        };
+        let mut result = Vec::new();
+        trim_reader_to(
+            &b"\x1b[1mab\x07c\n"[..],
+            &mut result,
+            &mut None::<File>,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(&b"ab\\u{7}c\n"[..], &result[..]);
+    }
+
+    #[test]
+    fn duplicates_are_skipped() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let path = mktemp(&prefix, &"abc  \n").unwrap();
+        let name = path.file_name().unwrap().to_string_lossy().to_uppercase();
+        // the same file on a case-insensitive filesystem, another one, not there, otherwise
+        let by_case = path.with_file_name(name);
+        let files = vec![path.clone(), by_case.clone(), path.clone()];
+
+        let outcomes = trim_files(&files, &TrimOptions::default());
+        assert!(matches!(outcomes[0].1, FileOutcome::Modified(_)));
+        match same_file(&path, &by_case) {
+            true => assert!(matches!(
+                outcomes[1].1,
+                FileOutcome::Skipped(SkipReason::Duplicate)
+            )),
+            false => assert!(outcomes[1].1.is_failed()),
+        }
+        assert!(matches!(
+            outcomes[2].1,
+            FileOutcome::Skipped(SkipReason::Duplicate)
+        ));
+        assert_eq!("abc\n", read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn parametrized_mixed_line_endings() {
+        let test_data = [
+            ("a\nb\r\n", vec![Warning::MixedLineEndings]),
+            ("a\r\nb\n", vec![Warning::MixedLineEndings]),
+            ("a\nb\n", vec![]),
+            ("a\r\nb\r\n", vec![]),
+            // the last line has no ending at all
+            ("a\r\nb", vec![]),
+        ];
+        for (input, expected) in test_data.iter() {
+            for keep_crlf in [false, true] {
+                let options = TrimOptions {
+                    keep_crlf,
+                    ..TrimOptions::default()
+                };
+                let trim_result =
+                    trim_slice_to(input.as_bytes(), io::sink(), &mut None::<File>, &options)
+                        .unwrap();
+                assert_eq!(expected, &trim_result.warnings, "{:?}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn parametrized_shebang() {
+        let options = TrimOptions {
+            strip_ansi: true,
+            control_chars: ControlChars::Strip,
+            rules: vec![crate::rules::builtin("collapse-comment-space").unwrap()],
+            ..TrimOptions::default()
+        };
+        // (input, expected, warnings)
+        let test_data = [
+            // nothing but the trailing whitespace of a shebang changes
+            (
+                "#!/usr/bin/awk -f  // \x1b[0m\x07 \t\nx  // \x1b[0m\x07 \n",
+                "#!/usr/bin/awk -f  // \x1b[0m\x07\nx //\n",
+                vec![],
+            ),
+            // only on the first line
+            ("\n#!/bin/sh  // \x07\n", "\n#!/bin/sh //\n", vec![]),
+            (
+                "\u{FEFF}#!/usr/bin/awk -f  // \x1b[0m\x07 \t\nx  // \x07 \n",
+                "\u{FEFF}#!/usr/bin/awk -f  // \x1b[0m\x07\nx //\n",
+                vec![Warning::BomBeforeShebang],
+            ),
+            ("\u{FEFF}abc \n", "\u{FEFF}abc\n", vec![]),
+        ];
+        for (input, expected, warnings) in test_data {
+            let mut result = Vec::new();
+            let tr =
+                trim_reader_to(input.as_bytes(), &mut result, &mut None::<File>, &options).unwrap();
+            assert_eq!(expected.as_bytes(), &result[..], "{:?}", input);
+            assert_eq!(warnings, tr.warnings, "{:?}", input);
+
+            let mut result = Vec::new();
+            let tr = trim_chunked_by(
+                input.as_bytes(),
+                4,
+                &mut result,
+                &mut None::<File>,
+                &options,
+            )
+            .unwrap();
+            assert_eq!(expected.as_bytes(), &result[..], "{:?}", input);
+            assert_eq!(warnings, tr.warnings, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn empty_input() {
+        // input, then the output and bytes saved without and with `TrimOptions::empty_newline`
+        let test_data = [
+            ("", ["", "\n"], [0, 0]),
+            // whitespace only is not empty
+            (" ", ["\n", "\n"], [1, 1]),
+            ("\n", ["\n", "\n"], [0, 0]),
+        ];
+        for (input, expected, bytes) in test_data {
+            for (index, empty_newline) in [false, true].iter().enumerate() {
+                let options = TrimOptions {
+                    empty_newline: *empty_newline,
+                    ..TrimOptions::default()
+                };
+                let mut result = Vec::new();
+                let trim_result =
+                    trim_reader_to(input.as_bytes(), &mut result, &mut None::<File>, &options)
+                        .unwrap();
+                assert_eq!(expected[index], String::from_utf8(result).unwrap());
+                assert_eq!(bytes[index], trim_result.bytes_saved, "{:?}", input);
+
+                let mut result = Vec::new();
+                trim_slice_to(input.as_bytes(), &mut result, &mut None::<File>, &options).unwrap();
+                assert_eq!(expected[index], String::from_utf8(result).unwrap());
+            }
+        }
+
+        // nothing to suppress either
+        let options = TrimOptions {
+            suppress_newline: true,
+            ..TrimOptions::default()
+        };
+        let trim_result =
+            trim_reader_to(&b""[..], io::sink(), &mut None::<File>, &options).unwrap();
+        assert_eq!(0, trim_result.bytes_saved);
+
+        // an empty file is left as is
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let files = [mktemp(&prefix, &"").unwrap()];
+        let path_to_result = trim_files(&files, &TrimOptions::default());
+        assert!(matches!(path_to_result[0].1, FileOutcome::Unchanged(_)));
+        assert!(read_to_string(&files[0]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn older_than() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let files = [mktemp(&prefix, &"abc  \n").unwrap()];
+        let options = TrimOptions {
+            older_than: Some(Duration::from_secs(60 * 60)),
+            ..TrimOptions::default()
+        };
+
+        // just written
+        let path_to_result = trim_files(&files, &options);
+        assert!(matches!(
+            path_to_result[0].1,
+            FileOutcome::Skipped(SkipReason::Recent)
+        ));
+        assert_eq!("abc  \n", read_to_string(&files[0]).unwrap());
+
+        // written two hours ago
+        let two_hours_ago = SystemTime::now() - Duration::from_secs(2 * 60 * 60);
+        File::options()
+            .write(true)
+            .open(&files[0])
+            .and_then(|file| file.set_modified(two_hours_ago))
+            .unwrap();
+        let path_to_result = trim_files(&files, &options);
+        assert!(matches!(path_to_result[0].1, FileOutcome::Modified(_)));
+        assert_eq!("abc\n", read_to_string(&files[0]).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn owned_by() {
+        use crate::owner::Owner;
+        use std::os::unix::fs::MetadataExt;
+
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let files = [mktemp(&prefix, &"abc  \n").unwrap()];
+        let uid = metadata(&files[0]).unwrap().uid();
+
+        let options = TrimOptions {
+            owned_by: Some(Owner {
+                uid: Some(uid + 1),
+                gid: None,
+            }),
+            ..TrimOptions::default()
+        };
+        let path_to_result = trim_files(&files, &options);
+        assert!(matches!(
+            path_to_result[0].1,
+            FileOutcome::Skipped(SkipReason::NotOwned)
+        ));
+        assert_eq!("abc  \n", read_to_string(&files[0]).unwrap());
+
+        let options = TrimOptions {
+            owned_by: Some(Owner {
+                uid: Some(uid),
+                gid: None,
+            }),
+            ..TrimOptions::default()
+        };
+        let path_to_result = trim_files(&files, &options);
+        assert!(matches!(path_to_result[0].1, FileOutcome::Modified(_)));
+        assert_eq!("abc\n", read_to_string(&files[0]).unwrap());
+    }
+
+    #[test]
+    fn max_shrink_percent() {
+        let content = format!("abc{}\n", " ".repeat(100));
+        let test_data = [
+            (&content[..], 50.0, false),
+            (&content[..], 99.0, true),
+            ("abc \n", 50.0, true),
+            // it is only whitespace; see `empty_file`
+            ("     \n", 10.0, true),
+        ];
+        for (index, (input, max_shrink_percent, trimmed)) in test_data.iter().enumerate() {
+            let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
+            let files = [mktemp(&prefix, input).unwrap()];
+            let options = TrimOptions {
+                max_shrink_percent: Some(*max_shrink_percent),
+                ..TrimOptions::default()
+            };
+
+            let path_to_result = trim_files(&files, &options);
+            let outcome = &path_to_result[0].1;
+            match trimmed {
+                true => assert!(matches!(outcome, FileOutcome::Modified(_)), "{:?}", input),
+                false => {
+                    assert!(matches!(outcome, FileOutcome::Skipped(SkipReason::Shrinks)));
+                    assert_eq!(&[Warning::Shrinks], outcome.warnings());
+                    assert_eq!(*input, read_to_string(&files[0]).unwrap());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn paranoid() {
+        let options = TrimOptions {
+            paranoid: true,
+            suppress_newline: true,
+            ..TrimOptions::default()
+        };
+        for (index, (input, expected, _)) in test_data().into_iter().enumerate() {
+            let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
+            let files = [mktemp(&prefix, &input).unwrap()];
+
+            let path_to_result = trim_files(&files, &options);
+            assert!(!path_to_result[0].1.is_failed(), "{:?}", input);
+            assert_eq!(expected, read_to_string(&files[0]).unwrap());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn filter() {
+        use crate::filter::Filter;
+
+        let test_data = [
+            ("tr a-z A-Z", FilterFailure::Error, Some("ABC\n"), &[][..]),
+            // already trimmed, and the filter changes nothing
+            ("cat", FilterFailure::Error, None, &[]),
+            ("exit 1", FilterFailure::Error, None, &[]),
+            (
+                "exit 1",
+                FilterFailure::Skip,
+                None,
+                &[Warning::FilterFailed],
+            ),
+            (
+                "exit 1",
+                FilterFailure::Unfiltered,
+                Some("abc\n"),
+                &[Warning::FilterFailed],
+            ),
+        ];
+        for (index, (command, on_failure, expected, warnings)) in test_data.iter().enumerate() {
+            let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
+            let input = match *command {
+                "cat" => "abc\n",
+                _ => "abc  \n",
+            };
+            let files = [mktemp(&prefix, &input).unwrap()];
+            let options = TrimOptions {
+                filter: Some(Filter {
+                    command: command.to_string(),
+                    timeout: None,
+                    on_failure: *on_failure,
+                }),
+                ..TrimOptions::default()
+            };
+
+            let path_to_result = trim_files(&files, &options);
+            let outcome = &path_to_result[0].1;
+            assert_eq!(*warnings, outcome.warnings(), "{} {}", command, on_failure);
+            match (expected, on_failure) {
+                (Some(expected), _) => {
+                    assert!(matches!(outcome, FileOutcome::Modified(_)));
+                    assert_eq!(*expected, read_to_string(&files[0]).unwrap());
+                }
+                (None, FilterFailure::Error) if *command != "cat" => {
+                    assert!(outcome.is_failed());
+                    assert_eq!(input, read_to_string(&files[0]).unwrap());
+                }
+                (None, _) => assert_eq!(input, read_to_string(&files[0]).unwrap()),
+            }
+        }
+    }
+
+    #[test]
+    fn newer_than() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let files = [mktemp(&prefix, &"abc  \n").unwrap()];
+        let two_hours_ago = SystemTime::now() - Duration::from_secs(2 * 60 * 60);
+        File::options()
+            .write(true)
+            .open(&files[0])
+            .and_then(|file| file.set_modified(two_hours_ago))
+            .unwrap();
+
+        // modified before the last hour
+        let options = TrimOptions {
+            newer_than: Some(SystemTime::now() - Duration::from_secs(60 * 60)),
+            ..TrimOptions::default()
+        };
+        let path_to_result = trim_files(&files, &options);
+        assert!(matches!(
+            path_to_result[0].1,
+            FileOutcome::Skipped(SkipReason::Old)
+        ));
+        assert_eq!("abc  \n", read_to_string(&files[0]).unwrap());
+
+        // but within the last three
+        let options = TrimOptions {
+            newer_than: Some(SystemTime::now() - Duration::from_secs(3 * 60 * 60)),
+            ..TrimOptions::default()
+        };
+        let path_to_result = trim_files(&files, &options);
+        assert!(matches!(path_to_result[0].1, FileOutcome::Modified(_)));
+        assert_eq!("abc\n", read_to_string(&files[0]).unwrap());
+    }
+
+    #[test]
+    fn parametrized_empty_file() {
+        // input, then the output for `keep`, `truncate` and `delete`, and whether it held nothing
+        // but whitespace
+        let test_data = [
+            (" \t\n\n", ["\n", "", ""], true),
+            ("\r\n", ["\n", "", ""], true),
+            (" ", ["\n", "", ""], true),
+            ("", ["", "", ""], false),
+            ("\na \n\n", ["\na\n", "\na\n", "\na\n"], false),
+        ];
+        let policies = [EmptyFile::Keep, EmptyFile::Truncate, EmptyFile::Delete];
+        for (index, (input, expected, whitespace_only)) in test_data.iter().enumerate() {
+            for (empty_file, expected) in policies.iter().zip(expected) {
+                let options = TrimOptions {
+                    empty_file: *empty_file,
+                    ..TrimOptions::default()
+                };
+                let mut result = Vec::new();
+                let trim_result =
+                    trim_reader_to(input.as_bytes(), &mut result, &mut None::<File>, &options)
+                        .unwrap();
+                assert_eq!(*expected, String::from_utf8(result).unwrap(), "{:?}", input);
+                let expected_policy = Some(*empty_file).filter(|_| *whitespace_only);
+                assert_eq!(expected_policy, trim_result.whitespace_only);
+
+                let mut result = Vec::new();
+                trim_slice_to(input.as_bytes(), &mut result, &mut None::<File>, &options).unwrap();
+                assert_eq!(*expected, String::from_utf8(result).unwrap(), "{:?}", input);
+
+                // files are deleted instead
+                let prefix = format!(
+                    "{}_{}_{}_{}_{}",
+                    module_path!(),
+                    line!(),
+                    column!(),
+                    index,
+                    empty_file
+                );
+                let files = [mktemp(&prefix, input).unwrap()];
+                trim_files(&files, &options);
+                match (empty_file, whitespace_only) {
+                    (EmptyFile::Delete, true) => assert!(!files[0].exists()),
+                    _ => assert_eq!(*expected, read_to_string(&files[0]).unwrap()),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parametrized_is_trimmed() {
+        let test_data = [
+            ("", true),
+            ("abc\n", true),
+            ("abc\n\ndef\n", true),
+            ("abc \n", false),
+            ("abc\n\n", false),
+            // a final linebreak is added
+            ("abc", false),
+            ("abc\r\n", false),
+            ("abc\t\ndef\n", false),
+        ];
+        for (index, (input, expected)) in test_data.iter().enumerate() {
+            let options = TrimOptions::default();
+            assert_eq!(
+                *expected,
+                is_trimmed_reader(input.as_bytes(), &options).unwrap(),
+                "{:?}",
+                input
+            );
+
+            let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
+            let path = mktemp(&prefix, input).unwrap();
+            assert_eq!(
+                *expected,
+                is_trimmed(&path, &options).unwrap(),
+                "{:?}",
+                input
+            );
+
+            let warnings = already_trimmed_reader(input.as_bytes(), &options)
+                .unwrap()
+                .map(|trim_result| trim_result.warnings);
+            assert_eq!(expected.then(Vec::new), warnings, "{:?}", input);
+
+            // whatever trimming leaves is trimmed
+            let mut trimmed = Vec::new();
+            trim_slice_to(input.as_bytes(), &mut trimmed, &mut None::<File>, &options).unwrap();
+            assert!(
+                is_trimmed_reader(&trimmed[..], &options).unwrap(),
+                "{:?}",
+                input
+            );
+        }
+
+        // but not past the lines that would be kept
+        let options = TrimOptions {
+            max_lines: Some(1),
+            ..TrimOptions::default()
+        };
+        assert!(!is_trimmed_reader(&b"abc\ndef\n"[..], &options).unwrap());
+
+        // with the warnings of the content
+        let input = "\u{FEFF}#!/bin/sh\n";
+        let trim_result = already_trimmed_reader(input.as_bytes(), &TrimOptions::default());
+        let warnings = trim_result.unwrap().map(|trim_result| trim_result.warnings);
+        assert_eq!(Some(vec![Warning::BomBeforeShebang]), warnings);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_warned_of() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let target = mktemp(&prefix, &"abc  \n").unwrap();
+        let link = target.with_extension("link");
+        let _ = remove_file(&link);
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let files = [link.clone()];
+        let outcomes = trim_files(&files, &TrimOptions::default());
+        assert_eq!(&[Warning::Symlink], outcomes[0].1.warnings());
+        assert_eq!("abc\n", read_to_string(&link).unwrap());
+        // the link itself was replaced, not its target
+        assert_eq!("abc  \n", read_to_string(&target).unwrap());
+        assert!(!symlink_metadata(&link).unwrap().file_type().is_symlink());
+    }
+
+    #[cfg(windows)]
+    mod long_paths {
+        use super::*;
+        use std::env::temp_dir;
+        use std::fs::create_dir_all;
+
+        #[test]
+        fn trims_past_max_path() {
+            let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+            let root = temp_dir().join(prefix);
+            // well past the 260 characters Windows paths are limited to by default
+            let dir = (0..8).fold(root.clone(), |dir, i| dir.join(format!("{:0>40}", i)));
+            create_dir_all(long_path(&dir)).unwrap();
+            let files = [dir.join("a.txt")];
+            let path = &files[0];
+            std::fs::write(long_path(path), "abc  \n\n").unwrap();
+
+            let options = TrimOptions::default();
+            let (_, outcome) = trim_files(&files, &options).pop().unwrap();
+            assert!(matches!(outcome, FileOutcome::Modified(_)));
+            assert_eq!(b"abc\n", &std::fs::read(long_path(path)).unwrap()[..]);
+
+            let mirrored = TrimOptions {
+                out_dir: Some(root.join("out")),
+                ..TrimOptions::default()
+            };
+            let (_, outcome) = trim_files(&files, &mirrored).pop().unwrap();
+            assert!(!outcome.is_failed());
+            let mirror = long_path(&mirror_path(&root.join("out"), path).unwrap()).into_owned();
+            assert_eq!(b"abc\n", &std::fs::read(mirror).unwrap()[..]);
+        }
+    }
+
+    #[cfg(unix)]
+    mod fifo {
+        use super::*;
+        use std::process::Command;
+        use std::thread;
+
+        fn mkfifo(prefix: &str) -> PathBuf {
+            let path = mktemp(prefix, &"").unwrap();
+            remove_file(&path).unwrap();
+            let status = Command::new("mkfifo").arg(&path).status().unwrap();
+            assert!(status.success());
+            path
+        }
+
+        #[test]
+        fn streams_to_writer() {
+            let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+            let path = mkfifo(&prefix);
+
+            let writer_path = path.clone();
+            let writer = thread::spawn(move || {
+                let mut fifo = File::options().write(true).open(writer_path).unwrap();
+                fifo.write_all(b"abc  \ndef\t\n\n").unwrap();
+            });
+            let reader = BufReader::new(File::open(&path).unwrap());
+            let mut result = Vec::new();
+            let options = TrimOptions::default();
+            trim_reader_to(reader, &mut result, &mut None::<File>, &options).unwrap();
+            writer.join().unwrap();
+
+            assert_eq!("abc\ndef\n", String::from_utf8(result).unwrap());
+        }
+
+        /// rejected right away; opening the pipe would block without a writer
+        #[test]
+        fn rejected_in_place() {
+            let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+            let path = mkfifo(&prefix);
+
+            let path_to_result = trim_files(std::slice::from_ref(&path), &TrimOptions::default());
+            let kind = path_to_result[0].1.error().map(io::Error::kind);
+            assert_eq!(Some(io::ErrorKind::InvalidInput), kind);
+        }
+    }
+
+    #[cfg(unix)]
+    mod permissions {
+        use super::*;
+        use std::fs::metadata;
+        use std::fs::set_permissions;
+        use std::fs::Permissions;
+        use std::os::unix::fs::PermissionsExt;
+
+        #[test]
+        fn inplace_keeps_mode() {
+            vec![0o600, 0o640, 0o755].into_par_iter().for_each(|mode| {
+                let prefix = format!("{}_{}_{}_{:o}", module_path!(), line!(), column!(), mode);
+                let path_to_temp = mktemp(&prefix, &"abc  \n").unwrap();
+                set_permissions(&path_to_temp, Permissions::from_mode(mode)).unwrap();
+
+                let path_to_result =
+                    trim_files(std::slice::from_ref(&path_to_temp), &TrimOptions::default());
+                assert!(!path_to_result[0].1.is_failed());
+
+                let result_mode = metadata(&path_to_temp).unwrap().permissions().mode();
+                assert_eq!(mode, result_mode & 0o777);
+                assert_eq!("abc\n", read_to_string(&path_to_temp).unwrap());
+            });
+        }
+    }
+
+    mod net_safe {
+        use super::*;
+
+        #[test]
+        fn parametrized_inplace_net_safe() {
+            test_data()
+                .into_par_iter()
+                .enumerate()
+                .for_each(|(index, (input, expected, _))| {
+                    let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
+                    let path_to_temp = mktemp(&prefix, &input).unwrap();
+                    let options = TrimOptions {
+                        net_safe: true,
+                        ..TrimOptions::default()
+                    };
+
+                    let path_to_result = trim_files(std::slice::from_ref(&path_to_temp), &options);
+                    let outcome = &path_to_result[0].1;
 
-                    //
-                    let mut result = Vec::new();
-                    let lines = readlines(&path_to_temp).unwrap();
-                    let tr = trim_custom(lines, &mut result, &mut None::<File>, true).unwrap();
-                    //
-                    let expected = format!("{}", expected_raw);
+                    assert!(!outcome.is_failed());
+                    assert_eq!(0, outcome.trim_result().map_or(0, |result| result.retries));
+                    assert_eq!(
+                        format!("{}\n", expected),
+                        read_to_string(&path_to_temp).unwrap()
+                    );
+                });
+        }
+    }
 
-                    assert_eq!(savings, tr);
-                    assert_eq!(expected.as_bytes(), &result[..]);
-                },
-            );
+    #[cfg(unix)]
+    mod clean {
+        use super::*;
+        use std::fs::metadata;
+        use std::os::unix::fs::MetadataExt;
+
+        /// (input, whether it is already clean)
+        fn test_data() -> Vec<(&'static str, bool)> {
+            vec![
+                ("abc\n", true),
+                ("abc\ndef\n\nghi\n", true),
+                // no newline at the end, so one is added
+                ("abc", false),
+                ("abc  \n", false),
+                ("abc\n\n", false),
+            ]
+        }
+
+        /// a file that is already clean is not replaced
+        #[test]
+        fn parametrized_inplace_clean() {
+            test_data()
+                .into_par_iter()
+                .enumerate()
+                .for_each(|(index, (input, clean))| {
+                    for intra_file_parallel in [false, true] {
+                        let prefix = format!(
+                            "{}_{}_{}_{}_{}",
+                            module_path!(),
+                            line!(),
+                            column!(),
+                            index,
+                            intra_file_parallel
+                        );
+                        let path_to_temp = mktemp(&prefix, &input).unwrap();
+                        let inode = metadata(&path_to_temp).unwrap().ino();
+                        let options = TrimOptions {
+                            intra_file_parallel,
+                            ..TrimOptions::default()
+                        };
+
+                        let path_to_result =
+                            trim_files(std::slice::from_ref(&path_to_temp), &options);
+                        assert!(!path_to_result[0].1.is_failed());
+
+                        assert_eq!(clean, inode == metadata(&path_to_temp).unwrap().ino());
+                    }
+                });
         }
     }
 
-    mod in_place_sequential {
+    mod chunked {
         use super::*;
 
-        /// trim a file in place, with default options
+        /// trimming in chunks of any size is the same as trimming in one go
         #[test]
-        fn parametrized_inplace_n_suppress_newline() {
-            test_data().into_par_iter().enumerate().for_each(
-                |(index, (input, expected_raw, savings))| {
-                    // make a "uniq" file
-                    let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
-                    let path_to_temp = mktemp(&prefix, &input).unwrap();
+        fn parametrized_chunked_matches_custom() {
+            let crlf_options = TrimOptions {
+                keep_crlf: true,
+                ..TrimOptions::default()
+            };
+            // one chunk in flight per thread
+            let bounded_options = TrimOptions {
+                max_memory: Some(1),
+                ..TrimOptions::default()
+            };
+            let mut inputs: Vec<_> = test_data().into_iter().map(|(input, _, _)| input).collect();
+            inputs.push("abc \r\n\r\n \n\r\ndef\t\n\n\nghi\n \n".to_string());
 
-                    // make sure that its content is written correctly
-                    let content = read_to_string(&path_to_temp).unwrap();
-                    assert_eq!(input, content);
+            inputs.into_par_iter().for_each(|input| {
+                for options in &[
+                    TrimOptions::default(),
+                    crlf_options.clone(),
+                    bounded_options.clone(),
+                ] {
+                    let mut expected = Vec::new();
+                    let mut expected_visual = Vec::new();
+                    let expected_tr = trim_custom(
+                        split_lines(input.as_bytes()),
+                        &mut expected,
+                        &mut Some(&mut expected_visual),
+                        options,
+                    )
+                    .unwrap();
 
-                    // trim the file in-place, sequentially
-                    trim_files(&vec![path_to_temp.clone()], false)
-                        .into_par_iter()
-                        .for_each(|(file_opt, trim_result_res)| {
-                            assert!(file_opt.exists());
-                            match trim_result_res {
-                                // `- 1` because not suppressing `\n`
-                                Ok(tr) => assert_eq!(savings - 1, tr.bytes_saved),
-                                _ => panic!(),
-                            };
-                        });
+                    for chunk_size in 0..input.len() + 2 {
+                        let mut result = Vec::new();
+                        let mut visual = Vec::new();
+                        let tr = trim_chunked_by(
+                            input.as_bytes(),
+                            chunk_size,
+                            &mut result,
+                            &mut Some(&mut visual),
+                            options,
+                        )
+                        .unwrap();
 
-                    let expected = format!("{}\n", expected_raw);
-                    let result = read_to_string(&path_to_temp).unwrap();
-                    assert_eq!(expected, result);
-                },
-            );
+                        assert_eq!(expected_tr, tr.bytes_saved);
+                        assert_eq!(expected, result);
+                        assert_eq!(expected_visual, visual);
+                    }
+                }
+            });
         }
+    }
 
-        /// trim a file in place, suppressing the trailing newline in the last line
-        #[test]
-        fn parametrized_inplace_suppress_newline() {
-            test_data().into_par_iter().enumerate().for_each(
-                |(index, (input, expected_raw, savings))| {
-                    // make a "uniq" file
-                    let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
-                    let path_to_temp = mktemp(&prefix, &input).unwrap();
+    mod ignore_marker {
+        use super::*;
 
-                    // make sure that its content is written correctly
-                    let content = read_to_string(&path_to_temp).unwrap();
-                    assert_eq!(input, content);
+        fn test_data() -> Vec<(&'static str, &'static str)> {
+            vec![
+                ("abc  # trim:ignore", "abc  # trim:ignore"),
+                // trailing whitespace after the marker is kept too
+                ("abc # trim:ignore  \n", "abc # trim:ignore  "),
+                ("abc # trim:ignore x  ", "abc # trim:ignore x"),
+                (
+                    "a \n# trim:off \nb \n \n# trim:on \nc \n",
+                    "a\n# trim:off \nb \n \n# trim:on \nc",
+                ),
+                // an unterminated block lasts until the end
+                ("a \n# trim:off\nb \n", "a\n# trim:off\nb "),
+                // the last marker on a line wins
+                ("# trim:off trim:on\nb \n", "# trim:off trim:on\nb"),
+                ("# trim:on trim:off\nb \n", "# trim:on trim:off\nb "),
+                // line endings of untouched lines are kept
+                ("a # trim:ignore \r\nb \r\n", "a # trim:ignore \r\nb"),
+            ]
+        }
 
-                    // trim the file in-place, sequentially
-                    trim_files(&vec![path_to_temp.clone()], true)
-                        .into_par_iter()
-                        .for_each(|(file_opt, trim_result_res)| {
-                            assert!(file_opt.exists());
-                            match trim_result_res {
-                                Ok(tr) => assert_eq!(savings, tr.bytes_saved),
-                                _ => panic!(),
-                            };
-                        });
+        #[test]
+        fn parametrized_ignore_marker() {
+            let options = TrimOptions {
+                ignore_marker: Some("trim:ignore".to_string()),
+                suppress_newline: true,
+                ..TrimOptions::default()
+            };
+            for (input, expected) in test_data() {
+                let mut result = Vec::new();
+                trim_custom(
+                    split_lines(input.as_bytes()),
+                    &mut result,
+                    &mut None::<File>,
+                    &options,
+                )
+                .unwrap();
+                assert_eq!(expected, String::from_utf8(result).unwrap());
 
-                    let expected = format!("{}", expected_raw);
-                    let result = read_to_string(&path_to_temp).unwrap();
-                    assert_eq!(expected, result);
-                },
+                // chunks must agree on where blocks start and end
+                for chunk_size in 0..input.len() + 2 {
+                    let mut chunked = Vec::new();
+                    trim_chunked_by(
+                        input.as_bytes(),
+                        chunk_size,
+                        &mut chunked,
+                        &mut None::<File>,
+                        &options,
+                    )
+                    .unwrap();
+                    assert_eq!(expected, String::from_utf8(chunked).unwrap());
+                }
+            }
+        }
+
+        #[test]
+        fn ignored_without_marker() {
+            let mut result = Vec::new();
+            let input = "abc # trim:ignore  \n# trim:off\nb \n";
+            trim_custom(
+                split_lines(input.as_bytes()),
+                &mut result,
+                &mut None::<File>,
+                &TrimOptions::default(),
+            )
+            .unwrap();
+            assert_eq!(
+                "abc # trim:ignore\n# trim:off\nb\n",
+                String::from_utf8(result).unwrap()
             );
         }
     }
 
-    mod in_place_parallel {
+    mod skip_file {
         use super::*;
 
-        /// trim a file in place, with default options
+        fn options() -> TrimOptions {
+            TrimOptions {
+                skip_file_marker: Some("trim: skip-file".to_string()),
+                skip_file_lines: 2,
+                intra_file_parallel: false,
+                ..TrimOptions::default()
+            }
+        }
+
         #[test]
-        fn parametrized_inplace_n_suppress_newline() {
-            // path to file containing untrimmed content -> expected trimmed content
-            let path_to_expected: HashMap<_, _> = test_data()
-                .into_par_iter()
-                .enumerate()
-                .map(|(index, (input, expected_raw, savings))| {
-                    // make a "uniq" file
-                    let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
-                    let path_to_temp = mktemp(&prefix, &input).unwrap();
+        fn parametrized_inplace_skip_file() {
+            let test_data = vec![
+                (
+                    "# trim: skip-file
+abc  
+",
+                    true,
+                ),
+                (
+                    "
+# trim: skip-file  
+abc  
+",
+                    true,
+                ),
+                // only the first lines are searched
+                (
+                    "
 
-                    // make sure that its content is written correctly
-                    let content = read_to_string(&path_to_temp).unwrap();
-                    assert_eq!(input, content);
+# trim: skip-file
+abc  
+",
+                    false,
+                ),
+                (
+                    "abc  
+", false,
+                ),
+            ];
+            [false, true].iter().for_each(|&intra_file_parallel| {
+                test_data
+                    .par_iter()
+                    .enumerate()
+                    .for_each(|(index, (input, skipped))| {
+                        let prefix = format!(
+                            "{}_{}_{}_{}_{}",
+                            module_path!(),
+                            line!(),
+                            column!(),
+                            index,
+                            intra_file_parallel
+                        );
+                        let path_to_temp = mktemp(&prefix, input).unwrap();
+                        let options = TrimOptions {
+                            intra_file_parallel,
+                            ..options()
+                        };
 
-                    //
-                    let expected = format!("{}\n", expected_raw);
-                    (path_to_temp, (expected, savings))
-                })
+                        let path_to_result =
+                            trim_files(std::slice::from_ref(&path_to_temp), &options);
+                        let outcome = &path_to_result[0].1;
+
+                        assert!(!outcome.is_failed());
+                        assert_eq!(
+                            *skipped,
+                            matches!(outcome, FileOutcome::Skipped(SkipReason::Marker))
+                        );
+                        let content = read_to_string(&path_to_temp).unwrap();
+                        assert_eq!(*skipped, content == *input);
+                    });
+            });
+        }
+
+        #[test]
+        fn head_is_kept_for_the_rest() {
+            let options = options();
+            let mut reader = "a\nb\nc\n".as_bytes();
+            assert_eq!(b"a\nb\n", &read_head(&mut reader, &options).unwrap()[..]);
+            assert_eq!(b"c\n", reader);
+
+            let mut reader = "a\nb\nc\n".as_bytes();
+            let disabled = TrimOptions::default();
+            assert!(read_head(&mut reader, &disabled).unwrap().is_empty());
+            assert!(!skips_file(b"# trim: skip-file", &disabled));
+        }
+    }
+
+    #[test]
+    fn trim_files_by_extension() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let path_to_temp = mktemp(&prefix, &"abc  \n").unwrap();
+        let options = TrimOptions {
+            extensions: vec!["rs".to_string()],
+            ..TrimOptions::default()
+        };
+
+        let path_to_result = trim_files(std::slice::from_ref(&path_to_temp), &options);
+        assert!(path_to_result.is_empty());
+        assert_eq!("abc  \n", read_to_string(&path_to_temp).unwrap());
+    }
+
+    #[test]
+    fn trim_files_with_progress() {
+        let paths: Vec<PathBuf> = test_data()
+            .iter()
+            .enumerate()
+            .map(|(index, (input, _, _))| {
+                let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
+                mktemp(&prefix, input).unwrap()
+            })
+            .collect();
+        let updates = std::sync::Mutex::new(Vec::new());
+        let stats = Arc::new(RunStats::new());
+        let options = TrimOptions {
+            stats: Some(Arc::clone(&stats)),
+            ..TrimOptions::default()
+        };
+
+        trim_files_with(&paths, &options, |progress| {
+            assert_eq!(paths.len(), progress.files_total);
+            let path_buf = progress.path.to_path_buf();
+            let done = progress.outcome.is_some();
+            updates
+                .lock()
+                .unwrap()
+                .push((progress.files_done, path_buf, done));
+        });
+
+        // started, then done, once each
+        let updates = updates.into_inner().unwrap();
+        assert_eq!(2 * paths.len(), updates.len());
+        for path in &paths {
+            let calls: Vec<_> = updates
+                .iter()
+                .filter(|(_, path_buf, _)| path_buf == path)
+                .map(|&(_, _, done)| done)
                 .collect();
+            assert_eq!(vec![false, true], calls);
+        }
+        let files_done = updates.iter().map(|&(files_done, _, _)| files_done).max();
+        assert_eq!(Some(paths.len()), files_done);
+        let snapshot = stats.snapshot();
+        assert_eq!(paths.len(), snapshot.files_total);
+        assert_eq!(paths.len(), snapshot.files_done());
+    }
 
-            // collect all the paths and trim them all in one go
-            let paths: Vec<_> = path_to_expected.keys().cloned().collect();
-            let path_to_result: HashMap<_, _> = trim_files(&paths, false);
+    #[test]
+    fn cancelled() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let path_to_temp = mktemp(&prefix, &"abc  \n").unwrap();
+        let token = CancellationToken::new();
+        token.cancel();
 
-            // check the results
-            path_to_expected
-                .into_par_iter()
-                .for_each(|(path_to_temp, (expected, savings))| {
-                    let trim_result = path_to_result.get(&path_to_temp).unwrap().as_ref().unwrap();
-                    let result = read_to_string(&path_to_temp).unwrap();
-                    assert!(path_to_temp.exists());
-                    // `- 1` because not suppressing `\n`
-                    assert_eq!(savings - 1, trim_result.bytes_saved);
-                    assert_eq!(expected, result);
-                });
+        for intra_file_parallel in [false, true] {
+            let options = TrimOptions {
+                cancel: Some(token.clone()),
+                intra_file_parallel,
+                ..TrimOptions::default()
+            };
+
+            // not started
+            let path_to_result = trim_files(std::slice::from_ref(&path_to_temp), &options);
+            let outcome = &path_to_result[0].1;
+            assert!(matches!(
+                outcome,
+                FileOutcome::Skipped(SkipReason::Cancelled)
+            ));
+            assert!(!outcome.is_completed());
+
+            // abandoned mid-file, without a tempfile left behind
+            assert!(trim_file(&path_to_temp, &options).is_err());
+            assert_eq!("abc  \n", read_to_string(&path_to_temp).unwrap());
+            let temp_prefix = format!(".{}.", path_to_temp.file_name().unwrap().to_string_lossy());
+            let parent = path_to_temp.parent().unwrap();
+            let leftover = parent.read_dir().unwrap().any(|entry| {
+                let name = entry.unwrap().file_name();
+                name.to_string_lossy().starts_with(&temp_prefix)
+            });
+            assert!(!leftover);
         }
+    }
+
+    #[test]
+    fn trim_files_by_schedule() {
+        for schedule in [Schedule::Size, Schedule::Input] {
+            let paths: Vec<PathBuf> = test_data()
+                .iter()
+                .enumerate()
+                .map(|(index, (input, _, _))| {
+                    let prefix = format!(
+                        "{}_{}_{}_{}_{}",
+                        module_path!(),
+                        line!(),
+                        column!(),
+                        index,
+                        schedule
+                    );
+                    mktemp(&prefix, input).unwrap()
+                })
+                .collect();
+            let options = TrimOptions {
+                schedule,
+                ..TrimOptions::default()
+            };
+
+            let path_to_result = trim_files(&paths, &options);
+            assert_eq!(paths.len(), path_to_result.len());
+            // in the order of `paths`, whatever the schedule
+            for ((path, (path_buf, outcome)), (input, expected, _)) in
+                paths.iter().zip(&path_to_result).zip(test_data())
+            {
+                assert_eq!(path, path_buf);
+                let expected = format!("{}\n", expected);
+                match outcome {
+                    FileOutcome::Modified(_) => assert_ne!(input, expected),
+                    FileOutcome::Unchanged(_) => assert_eq!(input, expected),
+                    _ => panic!(),
+                }
+                assert_eq!(expected, read_to_string(path).unwrap());
+            }
+        }
+    }
+
+    mod out_dir {
+        use super::*;
+        use std::env::temp_dir;
 
-        /// trim a file in place, suppressing the trailing newline in the last line
         #[test]
-        fn parametrized_inplace_suppress_newline() {
-            // path to file containing untrimmed content -> expected trimmed content
-            let path_to_expected: HashMap<_, _> = test_data()
+        fn parametrized_out_dir() {
+            test_data()
                 .into_par_iter()
                 .enumerate()
-                .map(|(index, (input, expected_raw, savings))| {
-                    // make a "uniq" file
+                .for_each(|(index, (input, expected, _))| {
                     let prefix = format!("{}_{}_{}_{}", module_path!(), line!(), column!(), index);
                     let path_to_temp = mktemp(&prefix, &input).unwrap();
+                    let out_dir = temp_dir().join(format!("{}.out", prefix));
+                    let options = TrimOptions {
+                        out_dir: Some(out_dir.clone()),
+                        ..TrimOptions::default()
+                    };
 
-                    // make sure that its content is written correctly
-                    let content = read_to_string(&path_to_temp).unwrap();
-                    assert_eq!(input, content);
+                    let path_to_result = trim_files(std::slice::from_ref(&path_to_temp), &options);
+                    assert!(!path_to_result[0].1.is_failed());
 
-                    //
-                    let expected = format!("{}", expected_raw);
-                    (path_to_temp, (expected, savings))
-                })
-                .collect();
+                    // the source is untouched, and even clean files are mirrored
+                    let mirrored = mirror_path(&out_dir, &path_to_temp).unwrap();
+                    assert_eq!(input, read_to_string(&path_to_temp).unwrap());
+                    assert_eq!(
+                        format!("{}\n", expected),
+                        read_to_string(&mirrored).unwrap()
+                    );
+                    assert_eq!(
+                        metadata(&path_to_temp).unwrap().modified().unwrap(),
+                        metadata(&mirrored).unwrap().modified().unwrap()
+                    );
+                });
+        }
+    }
 
-            // collect all the paths and trim them all in one go
-            let paths: Vec<_> = path_to_expected.keys().cloned().collect();
-            let path_to_result: HashMap<_, _> = trim_files(&paths, true);
+    #[test]
+    fn canonical() {
+        let options = TrimOptions {
+            suppress_newline: true,
+            keep_crlf: true,
+            ignore_marker: Some("trim:ignore".to_string()),
+            max_memory: Some(1 << 20),
+            ..TrimOptions::default()
+        }
+        .into_canonical();
+        assert_eq!(Some(1 << 20), options.max_memory);
 
-            // check the results
-            path_to_expected
-                .into_par_iter()
-                .for_each(|(path_to_temp, (expected, savings))| {
-                    let trim_result = path_to_result.get(&path_to_temp).unwrap().as_ref().unwrap();
-                    let result = read_to_string(&path_to_temp).unwrap();
-                    assert!(path_to_temp.exists());
-                    assert_eq!(savings, trim_result.bytes_saved);
+        // the canonical form is guaranteed; these must never change
+        let test_data = [
+            ("", "\n"),
+            ("abc", "abc\n"),
+            (
+                "a \t\r\n\r\nb\x0C  # trim:ignore \r\n\n\n",
+                "a\n\nb\x0C  # trim:ignore\n",
+            ),
+            ("a\u{A0}\n", "a\u{A0}\n"),
+            ("a\rb \n", "a\rb\n"),
+        ];
+        for (input, expected) in test_data {
+            let mut result = Vec::new();
+            trim_custom_reader(input.as_bytes(), &mut result, &mut None::<File>, &options).unwrap();
+            assert_eq!(expected, String::from_utf8(result).unwrap());
+        }
+    }
+
+    #[test]
+    fn record_sep() {
+        let options = TrimOptions {
+            record_sep: Some(b';'),
+            keep_crlf: true,
+            ..TrimOptions::default()
+        };
+        let test_data = [
+            ("", ""),
+            ("a ;b\t;", "a;b;"),
+            // `\r\n` and `\n` are whitespace like any other, not terminators
+            ("a \r\n;b\n", "a;b;"),
+            ("a;\n;;", "a;"),
+            ("a ;;;b", "a;;;b;"),
+        ];
+        for (input, expected) in test_data {
+            let mut result = Vec::new();
+            trim_custom_reader(input.as_bytes(), &mut result, &mut None::<File>, &options).unwrap();
+            assert_eq!(expected, String::from_utf8(result).unwrap());
+
+            for chunk_size in 0..input.len() + 1 {
+                let mut result = Vec::new();
+                trim_chunked_by(
+                    input.as_bytes(),
+                    chunk_size,
+                    &mut result,
+                    &mut None::<File>,
+                    &options,
+                )
+                .unwrap();
+                assert_eq!(expected, String::from_utf8(result).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn final_newlines() {
+        // input, then the output and bytes saved for 0 to 3 final newlines; bytes saved are only
+        // exact if the input ends with a single linebreak
+        let test_data = [
+            ("a \n\n\n\n", ["a", "a\n", "a\n\n", "a\n\n\n"], None),
+            (
+                "a \n",
+                ["a", "a\n", "a\n\n", "a\n\n\n"],
+                Some([2, 1, 0, -1]),
+            ),
+            (
+                "a\r\n\r\nb \n\n",
+                ["a\n\nb", "a\n\nb\n", "a\n\nb\n\n", "a\n\nb\n\n\n"],
+                None,
+            ),
+            ("", ["", "", "", ""], Some([0, 0, 0, 0])),
+        ];
+        for (input, expected, bytes) in test_data {
+            for (count, expected) in expected.iter().enumerate() {
+                let options = TrimOptions {
+                    final_newlines: Some(count),
+                    ..TrimOptions::default()
+                };
+                let mut result = Vec::new();
+                let trim_result =
+                    trim_reader_to(input.as_bytes(), &mut result, &mut None::<File>, &options)
+                        .unwrap();
+                assert_eq!(*expected, String::from_utf8(result).unwrap());
+                if let Some(bytes) = bytes {
+                    assert_eq!(bytes[count], trim_result.bytes_saved);
+                }
+
+                for chunk_size in 0..input.len() + 1 {
+                    let mut result = Vec::new();
+                    trim_chunked_by(
+                        input.as_bytes(),
+                        chunk_size,
+                        &mut result,
+                        &mut None::<File>,
+                        &options,
+                    )
+                    .unwrap();
+                    assert_eq!(*expected, String::from_utf8(result).unwrap());
+                }
+            }
+        }
+
+        // overrides `suppress_newline`
+        let options = TrimOptions {
+            suppress_newline: true,
+            final_newlines: Some(2),
+            ..TrimOptions::default()
+        };
+        let mut result = Vec::new();
+        trim_reader_to(&b"a"[..], &mut result, &mut None::<File>, &options).unwrap();
+        assert_eq!(b"a\n\n", &result[..]);
+    }
+
+    #[test]
+    fn eof_blank_lines() {
+        let test_data = [
+            ("", 0),
+            ("abc", 0),
+            ("abc\n", 0),
+            ("abc\n\n", 1),
+            ("abc \r\n \n\t\n", 2),
+            ("\n\n", 2),
+            // not at the end
+            ("\n\nabc\n", 0),
+        ];
+        let options = TrimOptions::default();
+        for (input, expected) in test_data {
+            let trim_result =
+                trim_reader_to(input.as_bytes(), io::sink(), &mut None::<File>, &options).unwrap();
+            assert_eq!(expected, trim_result.eof_blank_lines);
+
+            for chunk_size in 0..input.len() + 1 {
+                let trim_result = trim_chunked_by(
+                    input.as_bytes(),
+                    chunk_size,
+                    &mut io::sink(),
+                    &mut None::<File>,
+                    &options,
+                )
+                .unwrap();
+                assert_eq!(expected, trim_result.eof_blank_lines);
+            }
+        }
+    }
+
+    #[test]
+    fn max_lines() {
+        let options = TrimOptions {
+            max_lines: Some(3),
+            ..TrimOptions::default()
+        };
+        let test_data = [
+            ("", ""),
+            ("a \nb", "a\nb\n"),
+            ("a \n\n\nb \n", "a\n"),
+            ("a \nb \nc \nd \n", "a\nb\nc\n"),
+        ];
+        for (input, expected) in test_data {
+            let mut result = Vec::new();
+            trim_reader_to(input.as_bytes(), &mut result, &mut None::<File>, &options).unwrap();
+            assert_eq!(expected, String::from_utf8(result).unwrap());
+
+            let mut result = Vec::new();
+            trim_slice_to(input.as_bytes(), &mut result, &mut None::<File>, &options).unwrap();
+            assert_eq!(expected, String::from_utf8(result).unwrap());
+        }
+
+        // the rest of the file would be lost
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let path = mktemp(&prefix, &"a \nb \nc \nd \n").unwrap();
+        let result = trim_file(&path, &options);
+        assert_eq!(
+            Some(io::ErrorKind::InvalidInput),
+            result.as_ref().err().map(io::Error::kind)
+        );
+        assert_eq!("a \nb \nc \nd \n", std::fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn trim_reader_with() {
+        let options = TrimOptions {
+            rules: vec![Arc::new(crate::rules::CollapseCommentSpace)],
+            ..TrimOptions::default()
+        };
+        let input = "a  \nb\nc;    // d \t\n";
+        let mut found = Vec::new();
+        let mut result = Vec::new();
+        super::trim_reader_with(input.as_bytes(), &mut result, &options, |finding| {
+            found.push(finding)
+        })
+        .unwrap();
+
+        assert_eq!("a\nb\nc; // d\n", String::from_utf8(result).unwrap());
+        let expected = vec![
+            LineFinding {
+                line_number: 1,
+                column: 2,
+                byte_column: 2,
+                utf16_column: 2,
+                display_column: 2,
+                bytes_removed: 2,
+                rule: "trailing-whitespace",
+            },
+            LineFinding {
+                line_number: 3,
+                column: 11,
+                byte_column: 11,
+                utf16_column: 11,
+                display_column: 11,
+                bytes_removed: 2,
+                rule: "trailing-whitespace",
+            },
+            LineFinding {
+                line_number: 3,
+                column: 4,
+                byte_column: 4,
+                utf16_column: 4,
+                display_column: 4,
+                bytes_removed: 3,
+                rule: "collapse-comment-space",
+            },
+        ];
+        assert_eq!(expected, found);
+
+        // the same findings, in the same order, when trimmed in parallel
+        let mut held = Some(HeldFindings::default());
+        trim_chunked_by(input.as_bytes(), 1, &mut io::sink(), &mut held, &options).unwrap();
+        let details: Vec<_> = held.unwrap().0.into_iter().flat_map(|(_, d)| d).collect();
+        assert_eq!(expected, details);
+    }
+
+    #[test]
+    fn line_finding_columns() {
+        // `e` and a combining acute accent, then an emoji outside the BMP
+        let input = "e\u{301}\u{1f600}  \n";
+        let mut found = Vec::new();
+        super::trim_reader_with(
+            input.as_bytes(),
+            io::sink(),
+            &TrimOptions::default(),
+            |finding| found.push(finding),
+        )
+        .unwrap();
+
+        let expected = vec![LineFinding {
+            line_number: 1,
+            column: 4,
+            byte_column: 8,
+            utf16_column: 5,
+            display_column: 3,
+            bytes_removed: 2,
+            rule: "trailing-whitespace",
+        }];
+        assert_eq!(expected, found);
+    }
+
+    mod findings {
+        use super::*;
+
+        /// the padding is as wide as what was trimmed, or says that it counts bytes instead
+        #[test]
+        fn parametrized_padding() {
+            let test_data = [
+                ("a  \n", Some(2)),
+                ("a\t \n", Some(2)),
+                // an ideographic space is 3 bytes, but 2 columns
+                ("a\u{3000}\n", Some(2)),
+                ("a\u{a0}\n", Some(1)),
+                // a vertical tab has no width of its own
+                ("a\x0b\n", None),
+            ];
+            for (input, expected) in test_data.iter() {
+                let mut held = Some(HeldFindings::default());
+                trim_slice_to(
+                    input.as_bytes(),
+                    io::sink(),
+                    &mut held,
+                    &TrimOptions::default(),
+                )
+                .unwrap();
+                let findings = held.unwrap().0;
+                assert_eq!(*expected, findings[0].0.width_trimmed, "{:?}", input);
+            }
+
+            let mut visual = Vec::new();
+            trim_slice_to(
+                b"a\x0b\x0b\n",
+                io::sink(),
+                &mut Some(&mut visual),
+                &TrimOptions::default(),
+            )
+            .unwrap();
+            let visual = String::from_utf8(visual).unwrap();
+            let padding = match cfg!(feature = "color") {
+                true => "__\u{1b}[0m",
+                false => "__",
+            };
+            let expected = format!("{} (2 bytes)\n", padding);
+            assert!(visual.ends_with(&expected), "{:?}", visual);
+        }
+
+        /// trimmed lines are collected the same way, whether trimmed in chunks or not
+        #[test]
+        fn collected_in_order() {
+            let input = "a \nb\n\nc  \nd\t";
+            let options = TrimOptions {
+                collect_findings: true,
+                ..TrimOptions::default()
+            };
+            let expected = vec![
+                Finding {
+                    line_number: 1,
+                    trimmed_line: "a".to_string(),
+                    bytes_trimmed: 1,
+                    width_trimmed: Some(1),
+                },
+                Finding {
+                    line_number: 4,
+                    trimmed_line: "c".to_string(),
+                    bytes_trimmed: 2,
+                    width_trimmed: Some(2),
+                },
+                Finding {
+                    line_number: 5,
+                    trimmed_line: "d".to_string(),
+                    bytes_trimmed: 1,
+                    width_trimmed: Some(1),
+                },
+            ];
+
+            for chunk_size in 0..input.len() + 2 {
+                let trim_result = trim_chunked_by(
+                    input.as_bytes(),
+                    chunk_size,
+                    &mut Vec::new(),
+                    &mut None::<File>,
+                    &options,
+                )
+                .unwrap();
+                assert_eq!(expected, trim_result.findings);
+            }
+
+            let trim_result = TrimState::default()
+                .trim_reader(
+                    input.as_bytes(),
+                    (1, 0),
+                    &mut Vec::new(),
+                    &mut None::<File>,
+                    &options,
+                )
+                .unwrap()
+                .finish(&mut Vec::new(), &mut None::<File>, &options)
+                .unwrap();
+            assert_eq!(expected, trim_result.findings);
+        }
+
+        #[test]
+        fn not_collected_by_default() {
+            let trim_result = trim_chunked_by(
+                b"a \n",
+                CHUNK_SIZE,
+                &mut Vec::new(),
+                &mut None::<File>,
+                &TrimOptions::default(),
+            )
+            .unwrap();
+            assert!(trim_result.findings.is_empty());
+        }
+    }
+
+    mod reader {
+        use super::*;
+
+        /// trimming straight from a buffer of any size is the same as trimming line by line
+        #[test]
+        fn parametrized_reader_matches_custom() {
+            let mut inputs: Vec<_> = test_data().into_iter().map(|(input, _, _)| input).collect();
+            inputs.push("abc \r\n\r\n \n\r\ndef\t\n\n\nghi\n \n".to_string());
+
+            inputs.into_par_iter().for_each(|input| {
+                let options = TrimOptions::default();
+                let mut expected = Vec::new();
+                let mut expected_visual = Vec::new();
+                let expected_tr = trim_custom(
+                    split_lines(input.as_bytes()),
+                    &mut expected,
+                    &mut Some(&mut expected_visual),
+                    &options,
+                )
+                .unwrap();
+
+                for capacity in 1..input.len() + 2 {
+                    let mut result = Vec::new();
+                    let mut visual = Vec::new();
+                    let tr = trim_custom_reader(
+                        BufReader::with_capacity(capacity, input.as_bytes()),
+                        &mut result,
+                        &mut Some(&mut visual),
+                        &options,
+                    )
+                    .unwrap();
+
+                    assert_eq!(expected_tr, tr);
                     assert_eq!(expected, result);
-                });
+                    assert_eq!(expected_visual, visual);
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn parametrized_to_writer() {
+        let options = TrimOptions::default();
+        for (input, expected, _) in test_data() {
+            let mut from_reader = Vec::new();
+            trim_reader_to(
+                input.as_bytes(),
+                &mut from_reader,
+                &mut None::<File>,
+                &options,
+            )
+            .unwrap();
+            let mut from_slice = Vec::new();
+            trim_slice_to(
+                input.as_bytes(),
+                &mut from_slice,
+                &mut None::<File>,
+                &options,
+            )
+            .unwrap();
+
+            assert_eq!(
+                format!("{}\n", expected),
+                String::from_utf8(from_reader).unwrap()
+            );
+            assert_eq!(
+                format!("{}\n", expected),
+                String::from_utf8(from_slice).unwrap()
+            );
+        }
+    }
+
+    mod line_buffered {
+        use super::*;
+        use std::io::pipe;
+
+        /// the same as trimming in one go, as long as lines arrive without pauses
+        #[test]
+        fn parametrized_line_buffered_matches_reader() {
+            let options = TrimOptions::default();
+            for (input, _, _) in test_data() {
+                let mut expected = Vec::new();
+                let expected_tr =
+                    trim_reader_to(input.as_bytes(), &mut expected, &mut None::<File>, &options)
+                        .unwrap();
+
+                let mut result = Vec::new();
+                let reader = Cursor::new(input.clone().into_bytes());
+                let tr =
+                    trim_reader_line_buffered(reader, &mut result, &mut None::<File>, &options)
+                        .unwrap();
+
+                assert_eq!(expected_tr.bytes_saved, tr.bytes_saved, "{:?}", input);
+                assert_eq!(expected, result, "{:?}", input);
+            }
+        }
+
+        #[test]
+        fn written_as_lines_arrive() {
+            let (input_reader, mut input) = pipe().unwrap();
+            let (mut output, output_writer) = pipe().unwrap();
+            let trimmer = spawn(move || {
+                let options = TrimOptions::default();
+                let reader = BufReader::new(input_reader);
+                trim_reader_line_buffered(reader, output_writer, &mut None::<File>, &options)
+                    .map(|tr| tr.bytes_saved)
+            });
+            let mut read_exactly = |len| {
+                let mut buf = vec![0; len];
+                output.read_exact(&mut buf).unwrap();
+                String::from_utf8(buf).unwrap()
+            };
+
+            // the terminator comes right away, and blank lines once the input is quiet
+            input.write_all(b"abc  \n").unwrap();
+            assert_eq!("abc\n", read_exactly(4));
+            input.write_all(b"\n\n").unwrap();
+            assert_eq!("\n\n", read_exactly(2));
+
+            // the blank line at the end is trailing after all
+            input.write_all(b"def\n\n").unwrap();
+            drop(input);
+            let mut rest = String::new();
+            output.read_to_string(&mut rest).unwrap();
+            assert_eq!("def\n", rest);
+            assert!(trimmer.join().unwrap().is_ok());
+        }
+
+        #[test]
+        fn follow_writes_blank_lines() {
+            let mut result = Vec::new();
+            let reader = Cursor::new(&b"abc \n\n \n"[..]);
+            let options = TrimOptions::default();
+            trim_reader_follow(reader, &mut result, &mut None::<File>, &options).unwrap();
+            assert_eq!("abc\n\n\n", String::from_utf8(result).unwrap());
+        }
+    }
+
+    mod write_unchanged {
+        use super::*;
+
+        /// Asserts that everything claimed to be unchanged really is identical to `source`.
+        struct CheckedWrite<'a> {
+            source: &'a [u8],
+            written: Vec<u8>,
+        }
+
+        impl<'a> TrimWrite for CheckedWrite<'a> {
+            fn write_trimmed(&mut self, bytes: &[u8]) -> io::Result<()> {
+                self.written.extend_from_slice(bytes);
+                Ok(())
+            }
+
+            fn write_unchanged(&mut self, bytes: &[u8], offset: u64) -> io::Result<()> {
+                let offset = offset as usize;
+                assert_eq!(&self.source[offset..offset + bytes.len()], bytes);
+                self.write_trimmed(bytes)
+            }
+
+            fn flush_trimmed(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn parametrized_offsets() {
+            let mut inputs: Vec<_> = test_data().into_iter().map(|(input, _, _)| input).collect();
+            inputs.push("abc \r\n\r\n\n\r\ndef\t\n\n\nghi\n \n".to_string());
+            inputs.push("abc\r\n\r\n\r\ndef\r\n".to_string());
+
+            inputs.into_par_iter().for_each(|input| {
+                for keep_crlf in [false, true] {
+                    let options = TrimOptions {
+                        keep_crlf,
+                        ..TrimOptions::default()
+                    };
+                    for chunk_size in 0..input.len() + 2 {
+                        let mut out = CheckedWrite {
+                            source: input.as_bytes(),
+                            written: Vec::new(),
+                        };
+                        trim_chunked_by(
+                            input.as_bytes(),
+                            chunk_size,
+                            &mut out,
+                            &mut None::<File>,
+                            &options,
+                        )
+                        .unwrap();
+                    }
+                }
+            });
         }
     }
 }
@@ -1,20 +1,53 @@
+#[cfg(feature = "color")]
 use ansi_term::Colour::Green;
+#[cfg(feature = "color")]
 use ansi_term::Colour::Red;
+#[cfg(feature = "color")]
 use ansi_term::Colour::White;
+#[cfg(feature = "color")]
 use ansi_term::Style;
+use std::borrow::Cow;
 use std::collections::hash_map::DefaultHasher;
+use std::collections::hash_map::RandomState;
 use std::env;
+use std::ffi::OsString;
+use std::fmt;
 use std::fmt::Display;
 use std::fs::remove_file;
+use std::fs::rename;
 use std::fs::File;
+use std::fs::Metadata;
+use std::fs::OpenOptions;
+use std::hash::BuildHasher;
 use std::hash::Hash;
 use std::hash::Hasher;
 use std::io;
 use std::io::BufRead;
 use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::LineWriter;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
 use std::io::Write;
+use std::iter;
+use std::path::Component;
 use std::path::Path;
 use std::path::PathBuf;
+use std::process;
+use std::str;
+use std::str::FromStr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread::sleep;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use crate::options::MetadataPolicy;
+
+/// Number of names `mktemp_sibling` tries before giving up.
+const MKTEMP_ATTEMPTS: usize = 16;
 
 /// # Returns
 ///
@@ -47,12 +80,872 @@ where
     Ok(path_to_temp)
 }
 
+/// # Returns
+///
+/// A random, hex-encoded suffix for a temp file name. Suffixes differ between calls and between
+/// processes.
+fn random_suffix() -> String {
+    format!("{:016x}", random_u64())
+}
+
+/// # Returns
+///
+/// Random bits that differ between calls and between processes; not for cryptographic use.
+pub fn random_u64() -> u64 {
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    // `RandomState` is seeded randomly per process
+    let mut hasher = RandomState::new().build_hasher();
+    process::id().hash(&mut hasher);
+    CALLS.fetch_add(1, Ordering::Relaxed).hash(&mut hasher);
+    SystemTime::now().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Alphabet of Crockford's base 32, as used by ULIDs.
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// # Returns
+///
+/// A new ULID to identify a run by, e.g. `01ARZ3NDEKTSV4RRFFQ69G5FAV`; 26 characters that sort by
+/// the time they were created in.
+pub fn new_run_id() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64);
+    // 48 bits of time, then 80 random bits
+    let bits = (u128::from(millis & ((1 << 48) - 1)) << 80)
+        | (u128::from(random_u64()) << 16)
+        | u128::from(random_u64() & 0xFFFF);
+    (0..26)
+        .rev()
+        .map(|digit| CROCKFORD[((bits >> (5 * digit)) & 0x1F) as usize] as char)
+        .collect()
+}
+
+/// Create a new, empty file in the same directory as `path`, so that it can be renamed over
+/// `path` atomically.
+///
+/// # Returns
+///
+/// Path to the new file, named `.{basename of path}.{random suffix}.trim`, and the file itself
+/// opened for writing.
+///
+/// The file is created exclusively (`O_EXCL`), so it never clobbers or shares a file with another
+/// trim process or another input of the same basename; on a collision, a new suffix is tried.
+pub fn mktemp_sibling(path: &Path) -> io::Result<(PathBuf, File)> {
+    let basename = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{:?} is not a file", path),
+        )
+    })?;
+    let parent = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut last_err = None;
+    for _ in 0..MKTEMP_ATTEMPTS {
+        let mut temp_name = OsString::from(".");
+        temp_name.push(basename);
+        temp_name.push(format!(".{}.trim", random_suffix()));
+        let temp_path = parent.join(temp_name);
+
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+        {
+            Ok(file) => return Ok((temp_path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => last_err = Some(err),
+            Err(err) => return Err(err),
+        }
+    }
+    Err(last_err.unwrap())
+}
+
+/// Replace the content of the file under `path` with `content`, atomically, through a tempfile
+/// that is renamed over it; the file keeps its permissions, and its owner where possible.
+///
+/// # Side Effects
+///
+/// The tempfile is removed again if anything goes wrong, leaving the file as it was.
+pub fn replace_atomically(path: &Path, content: &[u8]) -> io::Result<()> {
+    let metadata = path.metadata()?;
+    let (temp_path, mut temp_file) = mktemp_sibling(path)?;
+    let result = MetadataPolicy::BestEffort
+        .copy(&metadata, &temp_file)
+        .and_then(|_| temp_file.write_all(content))
+        .and_then(|_| rename(&temp_path, path));
+    if result.is_err() {
+        let _ = remove_file(&temp_path);
+    }
+    result
+}
+
+/// Writes everything written to it to `out`, and a copy of it to a file; the copy only appears
+/// under its path, as a whole, once committed, so the path never holds a partial copy.
+pub struct Tee<W> {
+    out: W,
+    /// the tempfile the copy is written to, and the path it is renamed to once committed
+    copy: Option<(PathBuf, BufWriter<File>, PathBuf)>,
+}
+
+impl<W> Tee<W>
+where
+    W: Write,
+{
+    /// # Parameters
+    ///
+    /// 1. `out` -- where everything is written
+    /// 1. `path_opt` -- where a copy of everything is committed to, if anywhere; a file there is
+    ///    replaced, but keeps its permissions
+    pub fn new(out: W, path_opt: Option<&Path>) -> io::Result<Self> {
+        let copy = match path_opt {
+            Some(path) => {
+                let (temp_path, temp_file) = mktemp_sibling(path)?;
+                if let Ok(metadata) = path.metadata() {
+                    MetadataPolicy::BestEffort.copy(&metadata, &temp_file)?;
+                }
+                Some((temp_path, BufWriter::new(temp_file), path.to_path_buf()))
+            }
+            None => None,
+        };
+        Ok(Tee { out, copy })
+    }
+
+    /// Flush `out`, and move the copy, if any, to its path.
+    ///
+    /// # Side Effects
+    ///
+    /// The tempfile is removed if anything goes wrong, leaving the path as it was.
+    pub fn commit(&mut self) -> io::Result<()> {
+        self.out.flush()?;
+        if let Some((temp_path, mut temp, path)) = self.copy.take() {
+            let result = temp.flush().and_then(|_| rename(&temp_path, path));
+            if result.is_err() {
+                let _ = remove_file(&temp_path);
+            }
+            result?;
+        }
+        Ok(())
+    }
+}
+
+impl<W> Write for Tee<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // in full to both, so that the two never differ
+        self.out.write_all(buf)?;
+        if let Some((_, temp, _)) = &mut self.copy {
+            temp.write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()?;
+        if let Some((_, temp, _)) = &mut self.copy {
+            temp.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// A copy that is never committed is discarded.
+impl<W> Drop for Tee<W> {
+    fn drop(&mut self) {
+        if let Some((temp_path, _, _)) = self.copy.take() {
+            let _ = remove_file(&temp_path);
+        }
+    }
+}
+
+/// When `Append` syncs what it appended to disk.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Fsync {
+    /// never; left to the OS
+    Never,
+    /// on every flush, e.g. after every line with `--line-buffered`
+    Flush,
+    /// once, when done appending
+    #[default]
+    End,
+}
+
+impl Fsync {
+    /// Values accepted by `FromStr`, in the order they should be listed to the user.
+    pub const VARIANTS: &'static [&'static str] = &["never", "flush", "end"];
+}
+
+impl FromStr for Fsync {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(Fsync::Never),
+            "flush" => Ok(Fsync::Flush),
+            "end" => Ok(Fsync::End),
+            _ => Err(format!(
+                "expected one of {:?}, got {:?}",
+                Fsync::VARIANTS,
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Fsync {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fsync::Never => write!(f, "never"),
+            Fsync::Flush => write!(f, "flush"),
+            Fsync::End => write!(f, "end"),
+        }
+    }
+}
+
+/// Appends to a file that others may be appending to as well, e.g. a shared log; the file is
+/// opened with `O_APPEND`, and never replaced, so nothing another writer appended is lost.
+///
+/// Writes are line-buffered, so that each append ends at the end of a line, and lines of
+/// different writers do not interleave within a line.
+pub struct Append {
+    file: LineWriter<File>,
+    fsync: Fsync,
+}
+
+impl Append {
+    /// Open the file under `path` for appending, creating it if it does not exist.
+    pub fn open(path: &Path, fsync: Fsync) -> io::Result<Self> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        Ok(Append {
+            file: LineWriter::new(file),
+            fsync,
+        })
+    }
+}
+
+impl Write for Append {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        match self.fsync {
+            Fsync::Flush => self.file.get_ref().sync_data(),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Done appending; a partial last line is appended as is.
+impl Drop for Append {
+    fn drop(&mut self) {
+        if self.file.flush().is_ok() && self.fsync == Fsync::End {
+            let _ = self.file.get_ref().sync_data();
+        }
+    }
+}
+
+/// # Returns
+///
+/// `true` if `a` and `b` are paths to the same file, e.g. through a symlink, or by case on a
+/// case-insensitive filesystem; `false` otherwise, or if either cannot be looked up.
+#[cfg(unix)]
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (a.metadata(), b.metadata()) {
+        (Ok(a), Ok(b)) => (a.dev(), a.ino()) == (b.dev(), b.ino()),
+        _ => false,
+    }
+}
+
+/// # Returns
+///
+/// `true` if `a` and `b` are paths to the same file, e.g. through a symlink, or by case on a
+/// case-insensitive filesystem; `false` otherwise, or if either cannot be looked up.
+#[cfg(not(unix))]
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Give `file` the same permission bits as `metadata`, and on Unix, the same owner and group.
+///
+/// Changing the owner usually requires privileges, so this fails for files of other users unless
+/// running as root; see `MetadataPolicy` for whether that matters.
+pub fn copy_permissions(metadata: &Metadata, file: &File) -> io::Result<()> {
+    file.set_permissions(metadata.permissions())?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::fchown;
+        use std::os::unix::fs::MetadataExt;
+        fchown(file, Some(metadata.uid()), Some(metadata.gid()))?;
+    }
+
+    Ok(())
+}
+
+/// # Returns
+///
+/// `path` relative to the directory `base`, climbing out of it with `..` if need be, e.g.
+/// `../b/c.txt` for `/a/b/c.txt` relative to `/a/d`; both are made canonical first. `path` as is
+/// if either cannot be resolved, and canonical if the two have nothing in common, e.g. on Windows,
+/// if they are on different drives.
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let (path, base) = match (path.canonicalize(), base.canonicalize()) {
+        (Ok(path), Ok(base)) => (path, base),
+        _ => return path.to_path_buf(),
+    };
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common == 0 {
+        return path;
+    }
+    let mut relative: PathBuf = base
+        .components()
+        .skip(common)
+        .map(|_| Component::ParentDir)
+        .collect();
+    relative.extend(path.components().skip(common));
+    relative
+}
+
+/// # Returns
+///
+/// Where `path` goes in a tree mirrored under `out_dir`, e.g. `out/src/lib.rs` for `src/lib.rs`;
+/// absolute paths are mirrored relative to the root. Paths that climb out with `..` cannot be
+/// mirrored, and are an error.
+pub fn mirror_path(out_dir: &Path, path: &Path) -> io::Result<PathBuf> {
+    let mut mirrored = out_dir.to_path_buf();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => mirrored.push(name),
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+            Component::ParentDir => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("cannot mirror {:?}, it contains `..`", path),
+                ))
+            }
+        }
+    }
+    Ok(mirrored)
+}
+
+/// Directories of version control, walked by no one but their tools; trimming the files in them,
+/// e.g. the objects of git, would corrupt the repository.
+const VCS_DIRS: &[&str] = &[".git", ".hg", ".svn"];
+
+/// Expand each of `paths` that is a directory into the regular files underneath it, in order of
+/// their paths, for `-r`; other paths are kept as they are, e.g. files, `-`, or paths that are not
+/// there, to fail later. Symbolic links underneath are not followed, nor kept, and neither are
+/// pipes, sockets or devices; directories of version control, see `VCS_DIRS`, are left out.
+///
+/// # Parameters
+///
+/// 1. `paths` -- the paths to expand
+/// 1. `excluded` -- whether a file or directory underneath is left out, given its path relative
+///    to the directory walked, e.g. `TrimOptions::excludes`; the directories it leaves out are
+///    not walked at all, and so it is not asked about anything under them
+///
+/// # Returns
+///
+/// The paths, with directories walked; a directory that cannot be read is kept as it is, so that
+/// trimming it fails with its error.
+pub fn walk_files(paths: &[PathBuf], excluded: &dyn Fn(&Path) -> bool) -> Vec<PathBuf> {
+    fn walk(
+        dir: &Path,
+        relative: &Path,
+        excluded: &dyn Fn(&Path) -> bool,
+        files: &mut Vec<PathBuf>,
+    ) {
+        let mut entries: Vec<_> = match long_path(dir).read_dir() {
+            Ok(entries) => entries.filter_map(Result::ok).collect(),
+            Err(_) => return files.push(dir.to_path_buf()),
+        };
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            // as given, rather than as read; see `long_path`
+            let path = dir.join(entry.file_name());
+            let relative = relative.join(entry.file_name());
+            if excluded(&relative) {
+                continue;
+            }
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_file() => files.push(path),
+                Ok(file_type) if file_type.is_dir() => {
+                    let name = entry.file_name();
+                    if !VCS_DIRS.iter().any(|vcs_dir| name == *vcs_dir) {
+                        walk(&path, &relative, excluded, files);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    for path in paths {
+        match long_path(path).is_dir() {
+            true => walk(path, Path::new(""), excluded, &mut files),
+            false => files.push(path.clone()),
+        }
+    }
+    files
+}
+
+/// Characters that make a path a glob pattern, unless a file by that name is there.
+const GLOB_CHARS: &[char] = &['*', '?', '['];
+
+/// Expand each of `paths` that is a glob pattern, e.g. `**/*.md`, into the paths it matches, in
+/// order, for shells that do not expand them, such as those of Windows. Patterns match as a POSIX
+/// shell would: `*` does not cross a `/` or match a leading `.`, and they match case-insensitively
+/// on Windows, as its filesystems do.
+///
+/// # Returns
+///
+/// The paths, with patterns expanded; a path that is there, or a pattern that is invalid or
+/// matches nothing, is kept as it is, so that trimming it fails with its error.
+pub fn expand_globs(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let options = glob::MatchOptions {
+        case_sensitive: !cfg!(windows),
+        require_literal_separator: true,
+        require_literal_leading_dot: true,
+    };
+    let mut expanded = Vec::new();
+    for path in paths {
+        let matches: Vec<PathBuf> = match path.to_str() {
+            Some(pattern) if pattern.contains(GLOB_CHARS) && !path.exists() => {
+                match glob::glob_with(pattern, options) {
+                    Ok(matches) => matches.filter_map(Result::ok).collect(),
+                    Err(_) => Vec::new(),
+                }
+            }
+            _ => Vec::new(),
+        };
+        match matches.is_empty() {
+            true => expanded.push(path.clone()),
+            false => expanded.extend(matches),
+        }
+    }
+    expanded
+}
+
+/// # Returns
+///
+/// The first of `files` that no in-place run should start from without a second thought: a
+/// filesystem root such as `/` or `C:\`, the home directory, or anything right under a
+/// filesystem root, as a typo such as `trim -i / *` would give; `None` if there is none.
+pub fn dangerous_path(files: &[PathBuf]) -> Option<&PathBuf> {
+    let home = env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })
+        .map(PathBuf::from)
+        .and_then(|home| home.canonicalize().ok());
+    files.iter().find(|path| {
+        // paths that are not there yet are only made absolute, so `nope` is not taken for a root
+        let path = path
+            .canonicalize()
+            .or_else(|_| std::path::absolute(path))
+            .unwrap_or_else(|_| path.to_path_buf());
+        match path.parent() {
+            None => true,
+            Some(parent) => parent.parent().is_none() || Some(&path) == home.as_ref(),
+        }
+    })
+}
+
+/// Ask the user a yes-or-no `question` on stderr, if stdin is a terminal.
+///
+/// # Returns
+///
+/// `true` if the user answered yes, `false` if they answered anything else, or could not be
+/// asked.
+pub fn confirm(question: &str) -> bool {
+    use std::io::IsTerminal;
+
+    if !io::stdin().is_terminal() {
+        return false;
+    }
+    eprint!("{} [y/N] ", question);
+    let mut answer = String::new();
+    match io::stdin().lock().read_line(&mut answer) {
+        Ok(_) => matches!(answer.trim(), "y" | "Y" | "yes" | "Yes"),
+        Err(_) => false,
+    }
+}
+
+/// Length from which Windows paths fail to open unless they are in extended-length form.
+#[cfg(windows)]
+const MAX_PATH: usize = 260;
+
+/// # Returns
+///
+/// `path` in a form that opens whatever its length. On Windows, a path that is `MAX_PATH`
+/// characters or more once absolute is given in extended-length form, e.g. `\\?\C:\src\lib.rs`,
+/// or `\\?\UNC\server\share\lib.rs` on a network share; any other path is returned as is.
+#[cfg(windows)]
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    use std::path::Prefix;
+
+    let absolute = match std::path::absolute(path) {
+        Ok(absolute) if absolute.as_os_str().len() >= MAX_PATH => absolute,
+        _ => return Cow::Borrowed(path),
+    };
+    let long = match absolute.components().next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::Verbatim(_) | Prefix::VerbatimUNC(..) | Prefix::VerbatimDisk(_) => absolute,
+            Prefix::UNC(..) => match absolute.to_str().and_then(|s| s.strip_prefix(r"\\")) {
+                Some(rest) => PathBuf::from(format!(r"\\?\UNC\{}", rest)),
+                None => return Cow::Borrowed(path),
+            },
+            Prefix::Disk(_) => {
+                let mut long = OsString::from(r"\\?\");
+                long.push(absolute.as_os_str());
+                PathBuf::from(long)
+            }
+            // device paths, e.g. `\\.\pipe\name`, have no extended-length form
+            Prefix::DeviceNS(_) => return Cow::Borrowed(path),
+        },
+        _ => return Cow::Borrowed(path),
+    };
+    Cow::Owned(long)
+}
+
+/// Paths are not limited in length on this platform.
+///
+/// # Returns
+///
+/// `path` as is.
+#[cfg(not(windows))]
+#[inline]
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
+/// How long `Follow` waits before it checks for more content again.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Reads a file like `tail -f`; instead of ending, waits for more content to be appended. If the
+/// file is truncated, e.g. by `logrotate --copytruncate`, reading starts over from the start.
+#[derive(Debug)]
+pub struct Follow {
+    file: File,
+    /// bytes read so far since the last truncation
+    position: u64,
+}
+
+impl Follow {
+    /// # Returns
+    ///
+    /// A reader that follows `file` from its current position.
+    pub fn new(mut file: File) -> io::Result<Self> {
+        let position = file.stream_position()?;
+        Ok(Follow { file, position })
+    }
+}
+
+impl Read for Follow {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let len = self.file.read(buf)?;
+            if len > 0 {
+                self.position += len as u64;
+                return Ok(len);
+            }
+            if self.file.metadata()?.len() < self.position {
+                self.position = self.file.seek(SeekFrom::Start(0))?;
+                continue;
+            }
+            sleep(FOLLOW_INTERVAL);
+        }
+    }
+}
+
+/// Flush the directory that contains `path` to disk, so that a rename into it is durable.
+///
+/// Directories cannot be opened as files everywhere, e.g. on Windows, so this is best-effort.
+pub fn sync_parent(path: &Path) {
+    let parent = match path.parent() {
+        Some(parent) if parent.as_os_str().is_empty() => Path::new("."),
+        Some(parent) => parent,
+        None => return,
+    };
+    if let Ok(dir) = File::open(parent) {
+        let _ = dir.sync_all();
+    }
+}
+
+/// Open the file descriptor `fd`, inherited from the parent process, e.g. `3` for `3>summary.txt`.
+///
+/// # Returns
+///
+/// A `File` that writes to a duplicate of `fd`, or an error if `fd` is not open.
+#[cfg(unix)]
+pub fn open_fd(fd: i32) -> io::Result<File> {
+    use std::os::unix::io::BorrowedFd;
+
+    const EBADF: i32 = 9;
+    if fd < 0 {
+        return Err(io::Error::from_raw_os_error(EBADF));
+    }
+    // duplicating an fd that is not open fails with `EBADF`, without touching anything
+    let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+    borrowed.try_clone_to_owned().map(File::from)
+}
+
+/// Inherited file descriptors are a Unix concept.
+///
+/// # Returns
+///
+/// An `io::ErrorKind::Unsupported` error.
+#[cfg(not(unix))]
+pub fn open_fd(fd: i32) -> io::Result<File> {
+    let message = format!("cannot open file descriptor {} on this platform", fd);
+    Err(io::Error::new(io::ErrorKind::Unsupported, message))
+}
+
+/// How a line read by `split_lines` was terminated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`, or the record separator if there is one; see `TrimOptions::record_sep`
+    Lf,
+    /// `\r\n`
+    CrLf,
+    /// the last line of the input, which has no terminator
+    Eof,
+}
+
+impl LineEnding {
+    /// # Returns
+    ///
+    /// Number of bytes in the terminator.
+    #[inline]
+    pub fn len(self) -> usize {
+        match self {
+            LineEnding::Lf => 1,
+            LineEnding::CrLf => 2,
+            LineEnding::Eof => 0,
+        }
+    }
+
+    /// # Returns
+    ///
+    /// `true` if there is no terminator, i.e. for `Eof`.
+    #[inline]
+    pub fn is_empty(self) -> bool {
+        self == LineEnding::Eof
+    }
+}
+
+/// A line with its content and its terminator kept apart.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line {
+    /// the line, without its terminator
+    pub content: String,
+    /// how the line was terminated
+    pub ending: LineEnding,
+}
+
+impl Line {
+    /// # Returns
+    ///
+    /// `bytes`, which is a record possibly ending with `sep`, split into its content and
+    /// terminator; see `split_record`. An `io::ErrorKind::InvalidData` error if the content is not
+    /// valid UTF-8, like `BufRead::lines`.
+    fn from_bytes(mut bytes: Vec<u8>, sep: u8) -> io::Result<Self> {
+        let (content, ending) = split_record(&bytes, sep);
+        bytes.truncate(content.len());
+        let content = String::from_utf8(bytes)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        Ok(Line { content, ending })
+    }
+}
+
+/// # Returns
+///
+/// `line`, which is a line possibly ending with `\n` or `\r\n`, split into its content and
+/// terminator.
+#[inline]
+pub fn split_terminator(line: &[u8]) -> (&[u8], LineEnding) {
+    match line {
+        [content @ .., b'\r', b'\n'] => (content, LineEnding::CrLf),
+        [content @ .., b'\n'] => (content, LineEnding::Lf),
+        _ => (line, LineEnding::Eof),
+    }
+}
+
+/// # Returns
+///
+/// `record`, which possibly ends with `sep`, split into its content and terminator; the same as
+/// `split_terminator` if `sep` is `\n`, with `LineEnding::Lf` standing for `sep` otherwise.
+#[inline]
+pub fn split_record(record: &[u8], sep: u8) -> (&[u8], LineEnding) {
+    match record {
+        _ if sep == b'\n' => split_terminator(record),
+        [content @ .., last] if *last == sep => (content, LineEnding::Lf),
+        _ => (record, LineEnding::Eof),
+    }
+}
+
+/// # Returns
+///
+/// `bytes` as a `str`, or an `io::ErrorKind::InvalidData` error if it is not valid UTF-8, like
+/// `BufRead::lines`.
+#[inline]
+pub fn utf8(bytes: &[u8]) -> io::Result<&str> {
+    str::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// # Returns
+///
+/// An `Iterator` that reads through `reader` line by line, delimited by `\n` or `\r\n`. Unlike
+/// `BufRead::lines`, the terminator of each line is reported rather than discarded.
+#[inline]
+pub fn split_lines<R>(reader: R) -> impl Iterator<Item = io::Result<Line>>
+where
+    R: BufRead,
+{
+    split_records(reader, b'\n')
+}
+
+/// # Returns
+///
+/// An `Iterator` that reads through `reader` record by record, delimited by `sep`, like
+/// `split_lines` does line by line; see `split_record`.
+pub fn split_records<R>(mut reader: R, sep: u8) -> impl Iterator<Item = io::Result<Line>>
+where
+    R: BufRead,
+{
+    iter::from_fn(move || {
+        let mut bytes = Vec::new();
+        match reader.read_until(sep, &mut bytes) {
+            Ok(0) => None,
+            Ok(_) => Some(Line::from_bytes(bytes, sep)),
+            Err(err) => Some(Err(err)),
+        }
+    })
+}
+
+/// # Returns
+///
+/// `content` split into consecutive chunks of at least `chunk_size` bytes each, except for the
+/// last one, such that every chunk but the last ends right after a `sep`, e.g. `\n`.
+pub fn line_aligned_chunks(content: &[u8], chunk_size: usize, sep: u8) -> Vec<&[u8]> {
+    let mut chunks = Vec::new();
+    let mut rest = content;
+    while !rest.is_empty() {
+        let end = match rest.iter().skip(chunk_size).position(|&byte| byte == sep) {
+            Some(index) => chunk_size + index + 1,
+            None => rest.len(),
+        };
+        let (chunk, next) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = next;
+    }
+    chunks
+}
+
+/// # Returns
+///
+/// The first `count` records of `content`, each ending with `sep`, e.g. `\n`, except for the last
+/// record of `content`; all of `content` if it has no more than `count` records.
+pub fn first_records(content: &[u8], count: usize, sep: u8) -> &[u8] {
+    let mut ends = content
+        .iter()
+        .enumerate()
+        .filter(|&(_, &byte)| byte == sep)
+        .map(|(index, _)| index + 1);
+    let end = match count {
+        0 => 0,
+        _ => ends.nth(count - 1).unwrap_or(content.len()),
+    };
+    &content[..end]
+}
+
+/// Reads the first records of `inner`, like `first_records` does of a slice, and then ends.
+#[derive(Debug)]
+pub struct TakeRecords<R> {
+    inner: R,
+    /// records left to read
+    remaining: usize,
+    sep: u8,
+    /// end of the record within the buffer last returned by `fill_buf`, if it ends there
+    record_end: Option<usize>,
+}
+
+/// # Returns
+///
+/// A reader of the first `count` records of `reader`, each ending with `sep`, e.g. `\n`.
+#[inline]
+pub fn take_records<R>(reader: R, count: usize, sep: u8) -> TakeRecords<R>
+where
+    R: BufRead,
+{
+    TakeRecords {
+        inner: reader,
+        remaining: count,
+        sep,
+        record_end: None,
+    }
+}
+
+impl<R> BufRead for TakeRecords<R>
+where
+    R: BufRead,
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.remaining == 0 {
+            return Ok(&[]);
+        }
+        let sep = self.sep;
+        let buf = self.inner.fill_buf()?;
+        self.record_end = buf
+            .iter()
+            .position(|&byte| byte == sep)
+            .map(|index| index + 1);
+        Ok(&buf[..self.record_end.unwrap_or(buf.len())])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if self.record_end.take() == Some(amt) {
+            self.remaining -= 1;
+        }
+        self.inner.consume(amt);
+    }
+}
+
+impl<R> Read for TakeRecords<R>
+where
+    R: BufRead,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
 /// # Returns
 ///
 /// An `Iterator` that reads through the file under `path` line by line, delimited by `\n` or `\r\n`.
 #[inline]
-pub fn readlines(path: &Path) -> io::Result<impl Iterator<Item = io::Result<String>>> {
-    File::open(path).map(BufReader::new).map(BufReader::lines)
+pub fn readlines(path: &Path) -> io::Result<impl Iterator<Item = io::Result<Line>>> {
+    File::open(path).map(BufReader::new).map(split_lines)
 }
 
 /// Used to visualize the trimmed whitespace.
@@ -60,35 +953,45 @@ pub fn readlines(path: &Path) -> io::Result<impl Iterator<Item = io::Result<Stri
 /// # Returns
 ///
 /// Some `impl Display` that results in `length` contiguous chars with a red background and a
-/// white foreground, each with `_` as the text.
+/// white foreground, each with `_` as the text; plain `_` without the `color` feature.
 #[inline]
 pub fn red_padding_with_len(length: usize) -> impl Display {
-    Style::new()
-        .on(Red)
-        .fg(White)
-        .paint((0..length).map(|_| '_').collect::<String>())
+    let padding = (0..length).map(|_| '_').collect::<String>();
+    #[cfg(feature = "color")]
+    let padding = Style::new().on(Red).fg(White).paint(padding);
+    padding
 }
 
 /// # Returns
 ///
-/// Some `impl Display` that results in the original `text` with green font.
+/// Some `impl Display` that results in the original `text` with red font; as is without the
+/// `color` feature.
 #[inline]
 pub fn red(text: &str) -> impl Display {
-    Style::new().fg(Red).paint(String::from(text))
+    #[cfg(feature = "color")]
+    let text = Style::new().fg(Red).paint(String::from(text));
+    #[cfg(not(feature = "color"))]
+    let text = String::from(text);
+    text
 }
 
 /// # Returns
 ///
-/// Some `impl Display` that results in the original `text` with red font.
+/// Some `impl Display` that results in the original `text` with green font; as is without the
+/// `color` feature.
 #[inline]
 pub fn green(text: &str) -> impl Display {
-    Style::new().fg(Green).paint(String::from(text))
+    #[cfg(feature = "color")]
+    let text = Style::new().fg(Green).paint(String::from(text));
+    #[cfg(not(feature = "color"))]
+    let text = String::from(text);
+    text
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rayon::prelude::*;
+    use crate::parallel::*;
 
     mod readlines {
         use super::*;
@@ -118,12 +1021,406 @@ mod tests {
                     let path_to_temp = mktemp(&prefix, &input).unwrap();
                     let result_lines: Vec<_> = readlines(&path_to_temp)
                         .unwrap()
-                        .into_iter()
                         .map(Result::unwrap)
+                        .map(|line| line.content)
                         .collect();
                     let expected_lines: Vec<_> = expected.into_iter().map(String::from).collect();
                     assert_eq!(expected_lines, result_lines);
                 });
         }
     }
+
+    mod split_lines {
+        use super::*;
+
+        fn test_data() -> Vec<(&'static str, Vec<LineEnding>)> {
+            use LineEnding::*;
+            vec![
+                ("", vec![]),
+                ("abc", vec![Eof]),
+                ("abc\n", vec![Lf]),
+                ("abc\r\n", vec![CrLf]),
+                ("abc\r\n\n\r\nabc", vec![CrLf, Lf, CrLf, Eof]),
+                // a lone `\r` is not a terminator
+                ("abc\r", vec![Eof]),
+                ("abc\rabc\r\r\n", vec![CrLf]),
+            ]
+        }
+
+        #[test]
+        fn parametrized_split_lines() {
+            test_data().into_par_iter().for_each(|(input, expected)| {
+                let result: Vec<_> = split_lines(input.as_bytes())
+                    .map(Result::unwrap)
+                    .map(|line| line.ending)
+                    .collect();
+                assert_eq!(expected, result);
+            });
+        }
+
+        #[test]
+        fn invalid_utf8() {
+            let mut lines = split_lines(&b"\xff\n"[..]);
+            let err = lines.next().unwrap().unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        }
+
+        #[test]
+        fn split_records_by_sep() {
+            use LineEnding::*;
+            let result: Vec<_> = split_records(&b"a ;b\r\n;\nc"[..], b';')
+                .map(Result::unwrap)
+                .map(|line| (line.content, line.ending))
+                .collect();
+            let expected = vec![
+                ("a ".to_string(), Lf),
+                ("b\r\n".to_string(), Lf),
+                ("\nc".to_string(), Eof),
+            ];
+            assert_eq!(expected, result);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn new_run_id() {
+        let (a, b) = (super::new_run_id(), super::new_run_id());
+        assert_ne!(a, b);
+        assert_eq!(26, a.len());
+        assert!(a.bytes().all(|c| CROCKFORD.contains(&c)));
+        // the time comes first, so ids of later runs sort after; same millisecond at worst
+        assert!(a[..10] <= b[..10]);
+    }
+
+    #[test]
+    fn long_path() {
+        let short = Path::new("src/lib.rs");
+        assert_eq!(short, super::long_path(short));
+
+        #[cfg(windows)]
+        {
+            let deep = "a\\".repeat(150);
+            let test_data = [
+                (format!(r"C:\{}x", deep), format!(r"\\?\C:\{}x", deep)),
+                (
+                    format!(r"\\server\share\{}x", deep),
+                    format!(r"\\?\UNC\server\share\{}x", deep),
+                ),
+                (format!(r"\\?\C:\{}x", deep), format!(r"\\?\C:\{}x", deep)),
+            ];
+            for (path, expected) in test_data {
+                assert_eq!(Path::new(&expected), super::long_path(Path::new(&path)));
+            }
+        }
+    }
+
+    #[test]
+    fn relative_path() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let root = env::temp_dir().join(prefix);
+        std::fs::create_dir_all(root.join("a").join("b")).unwrap();
+        std::fs::create_dir_all(root.join("c")).unwrap();
+        let path = root.join("a").join("b").join("x.txt");
+        std::fs::write(&path, "").unwrap();
+
+        let test_data = [
+            (root.clone(), "a/b/x.txt"),
+            (root.join("a").join("b"), "x.txt"),
+            (root.join("c"), "../a/b/x.txt"),
+            (root.join("a").join("b").join("..").join(".."), "a/b/x.txt"),
+        ];
+        for (base, expected) in test_data {
+            assert_eq!(Path::new(expected), super::relative_path(&path, &base));
+        }
+        // not there, so as is
+        let gone = root.join("gone.txt");
+        assert_eq!(gone, super::relative_path(&gone, &root));
+    }
+
+    #[test]
+    fn mirror_path() {
+        let out_dir = Path::new("out");
+        let test_data = [
+            ("src/lib.rs", Some("out/src/lib.rs")),
+            ("./a/./b", Some("out/a/b")),
+            ("/tmp/a", Some("out/tmp/a")),
+            ("../a", None),
+        ];
+        for (path, expected) in test_data {
+            let result = super::mirror_path(out_dir, Path::new(path)).ok();
+            assert_eq!(expected.map(PathBuf::from), result);
+        }
+    }
+
+    #[test]
+    fn walk_files() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let root = env::temp_dir().join(prefix);
+        for dir in ["b/c", ".git", "a"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in ["b/c/d.txt", "b/a.txt", ".git/config", "z.txt"] {
+            std::fs::write(root.join(file), "abc  \n").unwrap();
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(root.join("z.txt"), root.join("link.txt")).unwrap();
+
+        let paths = [root.clone(), PathBuf::from("-"), root.join("nope")];
+        let expected: Vec<PathBuf> = ["b/a.txt", "b/c/d.txt", "z.txt"]
+            .iter()
+            .map(|file| root.join(file))
+            .chain([PathBuf::from("-"), root.join("nope")])
+            .collect();
+        assert_eq!(expected, super::walk_files(&paths, &|_| false));
+
+        // left out, and not walked
+        let excluded = |path: &Path| path == Path::new("b") || path == Path::new("z.txt");
+        let expected = vec![PathBuf::from("-"), root.join("nope")];
+        assert_eq!(expected, super::walk_files(&paths, &excluded));
+
+        // matched relative to the directory walked, not the directories above it
+        let options = crate::options::TrimOptions {
+            exclude: vec![glob::Pattern::new("b").unwrap()],
+            ..Default::default()
+        };
+        let paths = [root.join("b/c")];
+        let expected = vec![root.join("b/c/d.txt")];
+        assert_eq!(
+            expected,
+            super::walk_files(&paths, &|path| options.excludes(path))
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn expand_globs() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let root = env::temp_dir().join(prefix);
+        std::fs::create_dir_all(root.join("b/c")).unwrap();
+        for file in ["a.md", ".a.md", "b/c/d.md", "b/e.txt", "[f].md"] {
+            std::fs::write(root.join(file), "abc  \n").unwrap();
+        }
+
+        let pattern = |pattern: &str| PathBuf::from(format!("{}/{}", root.display(), pattern));
+        let paths = [
+            pattern("**/*.md"),
+            pattern("b/*"),
+            pattern("[f].md"),
+            pattern("*.rs"),
+            pattern("[.md"),
+            PathBuf::from("-"),
+        ];
+        // a file named like a pattern is matched by other patterns, and kept as it is otherwise
+        let expected: Vec<PathBuf> = ["[f].md", "a.md", "b/c/d.md", "b/c", "b/e.txt", "[f].md"]
+            .iter()
+            .map(|file| root.join(file))
+            .chain([pattern("*.rs"), pattern("[.md"), PathBuf::from("-")])
+            .collect();
+        assert_eq!(expected, super::expand_globs(&paths));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dangerous_path() {
+        let test_data = [
+            (vec!["/"], Some("/")),
+            (vec!["/tmp/a", "/a"], Some("/a")),
+            (vec!["/tmp/a", "/tmp/b"], None),
+            // not there yet, and relative to the working directory
+            (vec!["no-such-dir-of-trim/a/b"], None),
+            (vec![], None),
+        ];
+        for (files, expected) in test_data {
+            let files: Vec<PathBuf> = files.into_iter().map(PathBuf::from).collect();
+            let result = super::dangerous_path(&files);
+            assert_eq!(expected.map(PathBuf::from).as_ref(), result);
+        }
+
+        if let Some(home) = env::var_os("HOME") {
+            let files = [PathBuf::from("/tmp/a"), PathBuf::from(home)];
+            assert_eq!(Some(&files[1]), super::dangerous_path(&files));
+        }
+    }
+
+    #[test]
+    fn append() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let path = mktemp(&prefix, &"a\n").unwrap();
+        for fsync in [Fsync::Never, Fsync::Flush, Fsync::End] {
+            let mut append = Append::open(&path, fsync).unwrap();
+            append.write_all(b"b\n").unwrap();
+            append.flush().unwrap();
+        }
+        assert_eq!(b"a\nb\nb\nb\n", &std::fs::read(&path).unwrap()[..]);
+
+        for &variant in Fsync::VARIANTS {
+            assert_eq!(variant, variant.parse::<Fsync>().unwrap().to_string());
+        }
+    }
+
+    #[test]
+    fn tee() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let path = std::env::temp_dir().join(prefix);
+        let _ = remove_file(&path);
+
+        let mut out = Vec::new();
+        let mut tee = Tee::new(&mut out, Some(&path)).unwrap();
+        tee.write_all(b"a\n").unwrap();
+        tee.flush().unwrap();
+        // nothing under the path until committed
+        assert!(!path.exists());
+        tee.commit().unwrap();
+        drop(tee);
+        assert_eq!(b"a\n", &out[..]);
+        assert_eq!(b"a\n", &std::fs::read(&path).unwrap()[..]);
+
+        // an uncommitted copy is discarded, leaving the path as it was
+        let mut tee = Tee::new(io::sink(), Some(&path)).unwrap();
+        tee.write_all(b"b\n").unwrap();
+        drop(tee);
+        assert_eq!(b"a\n", &std::fs::read(&path).unwrap()[..]);
+    }
+
+    #[test]
+    fn replace_atomically() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let path = mktemp(&prefix, &"a \n").unwrap();
+        super::replace_atomically(&path, b"a\n").unwrap();
+        assert_eq!(b"a\n", &std::fs::read(&path).unwrap()[..]);
+
+        // no tempfile is left behind next to it
+        let dir = path.parent().unwrap();
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let siblings = std::fs::read_dir(dir)
+            .unwrap()
+            .filter(|entry| {
+                let entry_name = entry.as_ref().unwrap().file_name();
+                entry_name.to_string_lossy().contains(&name)
+            })
+            .count();
+        assert_eq!(1, siblings);
+    }
+
+    #[test]
+    fn follow() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let path = mktemp(&prefix, &"abc\n").unwrap();
+        let mut follow = Follow::new(File::open(&path).unwrap()).unwrap();
+
+        let mut buf = [0; 4];
+        follow.read_exact(&mut buf).unwrap();
+        assert_eq!(b"abc\n", &buf);
+
+        // waits for more, rather than ending
+        let appender_path = path.clone();
+        let appender = std::thread::spawn(move || {
+            sleep(FOLLOW_INTERVAL);
+            let mut file = OpenOptions::new().append(true).open(appender_path).unwrap();
+            file.write_all(b"def\n").unwrap();
+        });
+        follow.read_exact(&mut buf).unwrap();
+        assert_eq!(b"def\n", &buf);
+        appender.join().unwrap();
+
+        // truncated; starts over
+        std::fs::write(&path, b"gh\n").unwrap();
+        let mut buf = [0; 3];
+        follow.read_exact(&mut buf).unwrap();
+        assert_eq!(b"gh\n", &buf);
+    }
+
+    #[cfg(unix)]
+    mod open_fd {
+        use super::*;
+        use std::fs::read_to_string;
+        use std::os::unix::io::AsRawFd;
+
+        #[test]
+        fn writes_to_fd() {
+            let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+            let path = mktemp(&prefix, &"").unwrap();
+            let file = OpenOptions::new().write(true).open(&path).unwrap();
+
+            let mut dup = open_fd(file.as_raw_fd()).unwrap();
+            drop(file); // the duplicate outlives the original
+            dup.write_all(b"abc").unwrap();
+
+            assert_eq!("abc", read_to_string(&path).unwrap());
+        }
+
+        #[test]
+        fn not_open() {
+            for fd in [-1, i32::MAX] {
+                let err = open_fd(fd).unwrap_err();
+                assert_eq!(Some(9), err.raw_os_error()); // EBADF
+            }
+        }
+    }
+
+    mod mktemp_sibling {
+        use super::*;
+        use std::collections::HashSet;
+
+        #[test]
+        fn unique_names_next_to_path() {
+            let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+            let path = mktemp(&prefix, &"abc").unwrap();
+
+            let temps: Vec<_> = (0..64)
+                .into_par_iter()
+                .map(|_| mktemp_sibling(&path).unwrap().0)
+                .collect();
+            let unique: HashSet<_> = temps.iter().collect();
+            assert_eq!(temps.len(), unique.len());
+
+            for temp in &temps {
+                assert_eq!(path.parent(), temp.parent());
+                remove_file(temp).unwrap();
+            }
+        }
+
+        #[test]
+        fn no_basename() {
+            let err = mktemp_sibling(Path::new("/")).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        }
+    }
+
+    #[test]
+    fn first_records() {
+        let content = b"a\n\nbc\nd";
+        for count in 0..6 {
+            let expected = super::first_records(content, count, b'\n');
+            let mut result = Vec::new();
+            take_records(&content[..], count, b'\n')
+                .read_to_end(&mut result)
+                .unwrap();
+            assert_eq!(expected, &result[..]);
+        }
+        assert_eq!(b"", super::first_records(content, 0, b'\n'));
+        assert_eq!(b"a\n\n", super::first_records(content, 2, b'\n'));
+        assert_eq!(content, super::first_records(content, 4, b'\n'));
+        assert_eq!(b"a\n\nb", super::first_records(content, 1, b'b'));
+    }
+
+    mod line_aligned_chunks {
+        use super::*;
+
+        #[test]
+        fn chunks_end_with_lf() {
+            let content = b"a\nbc\n\ndef\ng";
+            for chunk_size in 0..content.len() + 2 {
+                let chunks = line_aligned_chunks(content, chunk_size, b'\n');
+                assert_eq!(&content[..], &chunks.concat()[..]);
+
+                let (last, init) = chunks.split_last().unwrap();
+                assert!(init.iter().all(|chunk| chunk.ends_with(b"\n")));
+                assert!(init.iter().all(|chunk| chunk.len() >= chunk_size));
+                assert!(!last.is_empty());
+            }
+            assert!(line_aligned_chunks(b"", 4, b'\n').is_empty());
+        }
+    }
 }
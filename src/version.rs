@@ -0,0 +1,98 @@
+//! What build of trim is running: its version, and how it was built, e.g. for bug reports, or
+//! for embedders to check what they are linked against; see `build_info`.
+
+use alloc::vec::Vec;
+use core::fmt;
+use serde::Serialize;
+
+/// Every cargo feature of the crate, sorted, and whether this build has it; optional dependencies,
+/// which are features too, are left out.
+const FEATURES: &[(&str, bool)] = &[
+    ("color", cfg!(feature = "color")),
+    ("copy-file-range", cfg!(feature = "copy-file-range")),
+    ("default", cfg!(feature = "default")),
+    ("minimal", cfg!(feature = "minimal")),
+    ("parallel", cfg!(feature = "parallel")),
+    ("simd", cfg!(feature = "simd")),
+    ("std", cfg!(feature = "std")),
+];
+
+/// How a build of trim was built; see `build_info`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BuildInfo {
+    /// version of the crate, e.g. `2.0.1`
+    pub version: &'static str,
+    /// abbreviated hash of the git commit it was built from; `None` if it was not built from a
+    /// git repository, e.g. from crates.io
+    pub git_hash: Option<&'static str>,
+    /// target triple it was built for, e.g. `x86_64-unknown-linux-gnu`
+    pub target: &'static str,
+    /// version of the compiler it was built with, as told by `rustc --version`
+    pub rustc: &'static str,
+    /// cargo features it was built with, sorted, e.g. `["copy-file-range", "default", "std"]`
+    pub features: Vec<&'static str>,
+    /// whether it was built with debug assertions, e.g. without `--release`
+    pub debug_assertions: bool,
+}
+
+/// # Returns
+///
+/// How this build of trim was built, as recorded by its build script.
+pub fn build_info() -> BuildInfo {
+    let git_hash = env!("TRIM_BUILD_GIT_HASH");
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_hash: Some(git_hash).filter(|hash| !hash.is_empty()),
+        target: env!("TRIM_BUILD_TARGET"),
+        rustc: env!("TRIM_BUILD_RUSTC"),
+        features: FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(feature, _)| *feature)
+            .collect(),
+        debug_assertions: cfg!(debug_assertions),
+    }
+}
+
+/// Lists everything, one field per line, after the version, as in `trim --version --verbose`.
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "trim {}", self.version)?;
+        writeln!(f, "commit:   {}", self.git_hash.unwrap_or("unknown"))?;
+        writeln!(f, "target:   {}", self.target)?;
+        writeln!(f, "rustc:    {}", self.rustc)?;
+        writeln!(f, "features: {}", self.features.join(", "))?;
+        write!(
+            f,
+            "profile:  {}",
+            match self.debug_assertions {
+                true => "debug",
+                false => "release",
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use alloc::string::ToString;
+
+    #[test]
+    fn build_info_of_this_build() {
+        let info = build_info();
+        assert_eq!(env!("CARGO_PKG_VERSION"), info.version);
+        assert!(!info.target.is_empty());
+        assert_eq!(cfg!(feature = "std"), info.features.contains(&"std"));
+        for (feature, enabled) in FEATURES {
+            assert_eq!(*enabled, info.features.contains(feature), "{}", feature);
+        }
+        let mut sorted = info.features.clone();
+        sorted.sort();
+        assert_eq!(sorted, info.features);
+        assert!(info
+            .to_string()
+            .starts_with(&format!("trim {}\n", info.version)));
+    }
+}
@@ -0,0 +1,136 @@
+//! Best-effort detection of files that other processes have open for writing; replacing such a
+//! file with its trimmed copy would lose whatever they write to it afterwards.
+
+use std::collections::HashSet;
+use std::fs::Metadata;
+use std::io;
+
+/// Files that other processes had open for writing when `Writers::scan` was called.
+///
+/// Only detectable on Linux, through `/proc`, and only for processes whose file descriptors are
+/// visible to this one; elsewhere, no file is ever found to be open for writing.
+#[derive(Clone, Debug, Default)]
+pub struct Writers {
+    /// device and inode of each file open for writing
+    files: HashSet<(u64, u64)>,
+}
+
+impl Writers {
+    /// # Returns
+    ///
+    /// The files that other processes have open for writing at the moment.
+    #[cfg(target_os = "linux")]
+    pub fn scan() -> io::Result<Self> {
+        use std::fs::metadata;
+        use std::fs::read_dir;
+        use std::fs::read_to_string;
+        use std::os::unix::fs::MetadataExt;
+        use std::process;
+
+        let own_pid = process::id().to_string();
+        let mut files = HashSet::new();
+        for process_entry in read_dir("/proc")?.flatten() {
+            let pid = process_entry.file_name();
+            let pid = pid.to_string_lossy();
+            if !pid.bytes().all(|b| b.is_ascii_digit()) || pid == own_pid {
+                continue;
+            }
+            // processes of other users, or ones that exited since
+            let fds = match read_dir(process_entry.path().join("fd")) {
+                Ok(fds) => fds,
+                Err(_) => continue,
+            };
+            for fd_entry in fds.flatten() {
+                let fdinfo = process_entry
+                    .path()
+                    .join("fdinfo")
+                    .join(fd_entry.file_name());
+                let writable = read_to_string(fdinfo).ok().and_then(|info| {
+                    let flags = info.lines().find_map(|line| line.strip_prefix("flags:"))?;
+                    let flags = u32::from_str_radix(flags.trim(), 8).ok()?;
+                    // O_WRONLY or O_RDWR
+                    Some(flags & 0o3 != 0)
+                });
+                if writable != Some(true) {
+                    continue;
+                }
+                if let Ok(target) = metadata(fd_entry.path()) {
+                    if target.is_file() {
+                        files.insert((target.dev(), target.ino()));
+                    }
+                }
+            }
+        }
+        Ok(Writers { files })
+    }
+
+    /// # Returns
+    ///
+    /// No files; open files cannot be detected on this platform.
+    #[cfg(not(target_os = "linux"))]
+    pub fn scan() -> io::Result<Self> {
+        Ok(Writers::default())
+    }
+
+    /// # Returns
+    ///
+    /// `true` if the file with `metadata` was open for writing by another process during `scan`,
+    /// `false` otherwise.
+    #[cfg(unix)]
+    pub fn has_writer(&self, metadata: &Metadata) -> bool {
+        use std::os::unix::fs::MetadataExt;
+
+        self.files.contains(&(metadata.dev(), metadata.ino()))
+    }
+
+    /// # Returns
+    ///
+    /// `false`; open files cannot be detected on this platform.
+    #[cfg(not(unix))]
+    pub fn has_writer(&self, _metadata: &Metadata) -> bool {
+        false
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::util::mktemp;
+    use std::fs::metadata;
+    use std::fs::File;
+    use std::io::Read;
+    use std::process::Command;
+    use std::process::Stdio;
+
+    #[test]
+    fn finds_other_writers() {
+        let prefix = format!("{}_{}_{}", module_path!(), line!(), column!());
+        let path = mktemp(&prefix, &"abc  \n").unwrap();
+        let path_metadata = metadata(&path).unwrap();
+
+        // open in this process only; not another process
+        let _own = File::options().append(true).open(&path).unwrap();
+        assert!(!Writers::scan().unwrap().has_writer(&path_metadata));
+
+        let mut writer = Command::new("sh")
+            .arg("-c")
+            .arg(format!("exec 3>>{:?}; echo; read _", path))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        // wait until the file is open
+        let mut ready = [0u8; 1];
+        writer
+            .stdout
+            .as_mut()
+            .unwrap()
+            .read_exact(&mut ready)
+            .unwrap();
+
+        let found = Writers::scan().unwrap().has_writer(&path_metadata);
+        drop(writer.stdin.take());
+        writer.wait().unwrap();
+        assert!(found);
+    }
+}